@@ -6,15 +6,18 @@
 /// will be printed in the terminal.
 ///
 fn main() {
-    // prost_build::compile_protos(
-    //     &[
-    //         "proto/signed.proto",
-    //         "proto/package.proto",
-    //         "proto/versions.proto",
-    //     ],
-    //     &["proto/"],
-    // )
-    // .expect("Failed to generate prost code from .proto files");
+    // prost_build::Config::new()
+    //     // Allows the signed payload to share the buffer it was decoded from
+    //     .bytes(["signed.Signed.payload"])
+    //     .compile_protos(
+    //         &[
+    //             "proto/signed.proto",
+    //             "proto/package.proto",
+    //             "proto/versions.proto",
+    //         ],
+    //         &["proto/"],
+    //     )
+    //     .expect("Failed to generate prost code from .proto files");
 
     // println!(
     //     "cargo::warning=Regenerated proto files, which must be manually copied into the `src` directory. Generated files can be found in {}",
@@ -0,0 +1,8 @@
+#![no_main]
+
+use hexpm::version::Range;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    let _ = Range::new(input.to_string());
+});
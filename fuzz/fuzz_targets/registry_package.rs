@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Signatures can't be forged, so the payload is decoded as it would be once
+// verified rather than through `get_package_response`
+fuzz_target!(|payload: &[u8]| {
+    let _ = hexpm::proto::decode_package(payload);
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Signatures can't be forged, so the payload is decoded as it would be once
+// verified rather than through `get_repository_versions_response`
+fuzz_target!(|payload: &[u8]| {
+    if let Ok(versions) = hexpm::proto::decode_versions(payload) {
+        for package in versions.packages {
+            for version in package.versions {
+                let _ = hexpm::version::Version::parse(&version);
+            }
+        }
+    }
+});
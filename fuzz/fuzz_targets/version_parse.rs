@@ -0,0 +1,12 @@
+#![no_main]
+
+use hexpm::version::Version;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    if let Ok(version) = Version::parse(input) {
+        // Any version that parses must survive a round trip through Display
+        let printed = version.to_string();
+        assert_eq!(Version::parse(&printed), Ok(version));
+    }
+});
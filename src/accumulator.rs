@@ -0,0 +1,213 @@
+//! Feeding response bodies to the parsers a chunk at a time.
+//!
+//! The response parsers take the whole body as a `Vec<u8>`. Clients that
+//! receive bodies in chunks, such as from an async stream or across an FFI
+//! boundary, can instead create a [`ResponseAccumulator`] for the endpoint
+//! once the status and headers have arrived, push each chunk to it as it is
+//! received, and finish it to get the parsed result.
+//!
+//! ```
+//! let response = http::Response::builder().status(200).body(()).unwrap();
+//! let (parts, ()) = response.into_parts();
+//! let mut accumulator = hexpm::accumulator::ResponseAccumulator::get_docs_tarball(parts);
+//! for chunk in [&b"first "[..], &b"second"[..]] {
+//!     accumulator.push(chunk).unwrap();
+//! }
+//! assert_eq!(accumulator.finish().unwrap(), b"first second");
+//! ```
+
+use crate::ApiError;
+#[cfg(feature = "registry")]
+use crate::{Package, version::Version};
+#[cfg(feature = "api")]
+use crate::{Release, ReleaseMeta};
+#[cfg(feature = "registry")]
+use std::collections::HashMap;
+
+type Parser<T> = Box<dyn FnOnce(http::Response<Vec<u8>>) -> Result<T, ApiError> + Send>;
+
+/// Collects the body of a response and parses it once complete.
+pub struct ResponseAccumulator<T> {
+    parts: http::response::Parts,
+    body: Vec<u8>,
+    limit: Option<u64>,
+    parse: Parser<T>,
+}
+
+impl<T> ResponseAccumulator<T> {
+    /// Create an accumulator that parses the response with the given
+    /// function, for endpoints that have no constructor of their own.
+    pub fn new(
+        parts: http::response::Parts,
+        parse: impl FnOnce(http::Response<Vec<u8>>) -> Result<T, ApiError> + Send + 'static,
+    ) -> Self {
+        let body = match expected_len(&parts) {
+            // Don't trust the header to allocate more than a modest amount up
+            // front.
+            Some(len) => Vec::with_capacity(len.min(1024 * 1024) as usize),
+            None => Vec::new(),
+        };
+        Self {
+            parts,
+            body,
+            limit: None,
+            parse: Box::new(parse),
+        }
+    }
+
+    /// Reject bodies larger than `limit` bytes. Once a chunk takes the body
+    /// over the limit [`push`](Self::push) returns an error, so the client can
+    /// stop reading the response.
+    pub fn with_limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// The status of the response.
+    pub fn status(&self) -> http::StatusCode {
+        self.parts.status
+    }
+
+    /// The length of the body according to the `content-length` header, if
+    /// the response had one.
+    pub fn expected_len(&self) -> Option<u64> {
+        expected_len(&self.parts)
+    }
+
+    /// The number of bytes of the body received so far.
+    pub fn received(&self) -> u64 {
+        self.body.len() as u64
+    }
+
+    /// Add the next chunk of the body.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), ApiError> {
+        if let Some(limit) = self.limit
+            && self.received() + chunk.len() as u64 > limit
+        {
+            return Err(ApiError::ResponseTooLarge(limit));
+        }
+        self.body.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    /// Parse the response once the whole body has been pushed.
+    pub fn finish(self) -> Result<T, ApiError> {
+        (self.parse)(http::Response::from_parts(self.parts, self.body))
+    }
+}
+
+#[cfg(feature = "registry")]
+impl ResponseAccumulator<Package> {
+    /// See [`get_package_response`](crate::get_package_response).
+    pub fn get_package(parts: http::response::Parts, public_key: &[u8]) -> Self {
+        let public_key = public_key.to_vec();
+        Self::new(parts, move |response| {
+            crate::get_package_response(response, &public_key)
+        })
+    }
+}
+
+#[cfg(feature = "registry")]
+impl ResponseAccumulator<HashMap<String, Vec<Version>>> {
+    /// See [`get_repository_versions_response`](crate::get_repository_versions_response).
+    pub fn get_repository_versions(parts: http::response::Parts, public_key: &[u8]) -> Self {
+        let public_key = public_key.to_vec();
+        Self::new(parts, move |response| {
+            crate::get_repository_versions_response(response, &public_key)
+        })
+    }
+}
+
+impl ResponseAccumulator<Vec<u8>> {
+    /// See [`get_package_tarball_response`](crate::get_package_tarball_response).
+    #[cfg(feature = "registry")]
+    pub fn get_package_tarball(parts: http::response::Parts, checksum: &[u8]) -> Self {
+        let checksum = checksum.to_vec();
+        Self::new(parts, move |response| {
+            crate::get_package_tarball_response(response, &checksum)
+        })
+    }
+
+    /// See [`get_docs_tarball_response`](crate::get_docs_tarball_response).
+    pub fn get_docs_tarball(parts: http::response::Parts) -> Self {
+        Self::new(parts, crate::get_docs_tarball_response)
+    }
+}
+
+#[cfg(feature = "api")]
+impl ResponseAccumulator<Release<ReleaseMeta>> {
+    /// See [`get_package_release_response`](crate::get_package_release_response).
+    pub fn get_package_release(parts: http::response::Parts) -> Self {
+        Self::new(parts, crate::get_package_release_response)
+    }
+}
+
+impl<T> std::fmt::Debug for ResponseAccumulator<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseAccumulator")
+            .field("parts", &self.parts)
+            .field("received", &self.body.len())
+            .field("limit", &self.limit)
+            .finish_non_exhaustive()
+    }
+}
+
+fn expected_len(parts: &http::response::Parts) -> Option<u64> {
+    parts
+        .headers
+        .get(http::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(status: u16, headers: &[(&str, &str)]) -> http::response::Parts {
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn package_tarball_in_chunks() {
+        let tarball = std::include_bytes!("../test/gleam_stdlib-0.14.0.tar");
+        let checksum = ring::digest::digest(&ring::digest::SHA256, tarball);
+        let length = tarball.len().to_string();
+        let mut accumulator = ResponseAccumulator::get_package_tarball(
+            parts(200, &[("content-length", &length)]),
+            checksum.as_ref(),
+        );
+        assert_eq!(accumulator.expected_len(), Some(tarball.len() as u64));
+        for chunk in tarball.chunks(1000) {
+            accumulator.push(chunk).unwrap();
+        }
+        assert_eq!(accumulator.received(), tarball.len() as u64);
+        assert_eq!(accumulator.finish().unwrap(), tarball);
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn error_statuses_are_parsed() {
+        let accumulator = ResponseAccumulator::get_package_tarball(parts(404, &[]), &[0; 32]);
+        assert_eq!(accumulator.status(), http::StatusCode::NOT_FOUND);
+        assert!(matches!(accumulator.finish(), Err(ApiError::NotFound)));
+    }
+
+    #[test]
+    fn limit() {
+        let mut accumulator = ResponseAccumulator::get_docs_tarball(parts(200, &[])).with_limit(10);
+        accumulator.push(b"0123456789").unwrap();
+        assert!(matches!(
+            accumulator.push(b"a"),
+            Err(ApiError::ResponseTooLarge(10))
+        ));
+        assert_eq!(accumulator.finish().unwrap(), b"0123456789");
+    }
+}
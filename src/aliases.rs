@@ -0,0 +1,182 @@
+//! Support for packages that have been renamed.
+//!
+//! When a package is renamed on Hex the old name is retired with the
+//! `renamed` reason and a new package is published. Projects that still refer
+//! to the old name can use an [`Aliases`] map to transparently request the
+//! successor instead.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AliasError {
+    #[error("the package aliases form a cycle: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+
+    #[error("both {alias} and the package it is an alias of, {package}, are required")]
+    Conflict { alias: String, package: String },
+}
+
+/// An alias that was applied, mapping the name a package was requested by to
+/// the name it was resolved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedAlias {
+    pub from: String,
+    pub to: String,
+}
+
+/// A map of old package names to the names of their successors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aliases {
+    renames: HashMap<String, String>,
+}
+
+impl Aliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the package `from` has been renamed to `to`.
+    pub fn insert(&mut self, from: impl Into<String>, to: impl Into<String>) {
+        self.renames.insert(from.into(), to.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    /// The name a package should be requested by, following chains of
+    /// renames. Returns the name unchanged if it has no alias.
+    pub fn resolve<'a>(&'a self, name: &'a str) -> Result<&'a str, AliasError> {
+        let mut seen = vec![name];
+        let mut current = name;
+        while let Some(next) = self.renames.get(current) {
+            if seen.contains(&next.as_str()) {
+                seen.push(next);
+                return Err(AliasError::Cycle(
+                    seen.into_iter().map(String::from).collect(),
+                ));
+            }
+            seen.push(next);
+            current = next;
+        }
+        Ok(current)
+    }
+
+    /// Rename the packages in a map of requirements, such as those from a
+    /// project's manifest, returning the renamed map and a report of the
+    /// aliases that were applied.
+    ///
+    /// It is an error for a map to contain both a package and an alias of it,
+    /// as the two requirements may disagree.
+    pub fn apply<V>(
+        &self,
+        requirements: HashMap<String, V>,
+    ) -> Result<(HashMap<String, V>, Vec<AppliedAlias>), AliasError> {
+        let mut renamed = HashMap::with_capacity(requirements.len());
+        let mut applied = vec![];
+        let mut original_names = HashMap::new();
+
+        for (name, value) in requirements {
+            let resolved = self.resolve(&name)?.to_string();
+            if let Some(other) = original_names.insert(resolved.clone(), name.clone()) {
+                let alias = if other == resolved { name } else { other };
+                return Err(AliasError::Conflict {
+                    alias,
+                    package: resolved,
+                });
+            }
+            if resolved != name {
+                applied.push(AppliedAlias {
+                    from: name,
+                    to: resolved.clone(),
+                });
+            }
+            renamed.insert(resolved, value);
+        }
+
+        applied.sort_by(|a, b| a.from.cmp(&b.from));
+        Ok((renamed, applied))
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Aliases {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut aliases = Self::new();
+        for (from, to) in iter {
+            aliases.insert(from, to);
+        }
+        aliases
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve() {
+        let aliases: Aliases = [("old", "middle"), ("middle", "new")].into_iter().collect();
+        assert_eq!(aliases.resolve("old"), Ok("new"));
+        assert_eq!(aliases.resolve("middle"), Ok("new"));
+        assert_eq!(aliases.resolve("new"), Ok("new"));
+        assert_eq!(aliases.resolve("other"), Ok("other"));
+    }
+
+    #[test]
+    fn resolve_cycle() {
+        let aliases: Aliases = [("a", "b"), ("b", "a")].into_iter().collect();
+        assert_eq!(
+            aliases.resolve("a"),
+            Err(AliasError::Cycle(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "a".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let aliases: Aliases = [("poison", "jason")].into_iter().collect();
+        let requirements = [
+            ("poison".to_string(), "~> 1.0"),
+            ("plug".to_string(), "~> 1.14"),
+        ]
+        .into();
+        let (renamed, applied) = aliases.apply(requirements).unwrap();
+        assert_eq!(
+            renamed,
+            [
+                ("jason".to_string(), "~> 1.0"),
+                ("plug".to_string(), "~> 1.14"),
+            ]
+            .into()
+        );
+        assert_eq!(
+            applied,
+            vec![AppliedAlias {
+                from: "poison".to_string(),
+                to: "jason".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn apply_conflict() {
+        let aliases: Aliases = [("poison", "jason")].into_iter().collect();
+        let requirements = [
+            ("poison".to_string(), "~> 1.0"),
+            ("jason".to_string(), "~> 1.4"),
+        ]
+        .into();
+        assert_eq!(
+            aliases.apply(requirements),
+            Err(AliasError::Conflict {
+                alias: "poison".to_string(),
+                package: "jason".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Ready to post announcements of new and retired releases, for chat bots and
+//! other integrations built on [`watch`](crate::watch).
+//!
+//! The versions index only lists which releases exist, so newly published
+//! releases are found from an [`IndexDiff`], while retirements and their
+//! reasons come from the registry records of the packages that changed.
+//!
+//! ```
+//! use hexpm::announce::{Announcement, Format, format};
+//! use hexpm::version::Version;
+//!
+//! let announcement = Announcement::Published {
+//!     repository: "hexpm".into(),
+//!     package: "lustre".into(),
+//!     version: Version::new(4, 1, 0),
+//!     new_package: false,
+//! };
+//! assert_eq!(announcement.to_text(), "lustre 4.1.0 was published");
+//! assert_eq!(
+//!     format(&[announcement], Format::Markdown),
+//!     "[lustre 4.1.0](https://hex.pm/packages/lustre/4.1.0) was published"
+//! );
+//! ```
+
+#[cfg(feature = "api")]
+use serde_json::json;
+
+use crate::index::{IndexDiff, VersionsIndex};
+use crate::version::Version;
+use crate::{Package, RetirementStatus};
+
+/// Something that happened to a release that is worth telling people about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Announcement {
+    Published {
+        repository: String,
+        package: String,
+        version: Version,
+        /// Whether this is the first release of the package.
+        new_package: bool,
+    },
+    Retired {
+        repository: String,
+        package: String,
+        version: Version,
+        status: RetirementStatus,
+    },
+}
+
+/// How to write a list of announcements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One sentence per line.
+    Text,
+    /// One sentence per line, with the releases linked to their pages on Hex.
+    Markdown,
+    /// A JSON array of objects, for webhooks.
+    #[cfg(feature = "api")]
+    Json,
+}
+
+impl Announcement {
+    /// Announce the releases added to a repository's index, given the index
+    /// after the change. The releases of new packages are looked up in the
+    /// index, so packages missing from it are not announced.
+    ///
+    /// Announcements are sorted by package name and version.
+    pub fn from_diff(repository: &str, diff: &IndexDiff, index: &VersionsIndex) -> Vec<Self> {
+        let new_packages = diff.added_packages.iter().filter_map(|name| {
+            let versions = index.package(name)?;
+            Some((name, versions, true))
+        });
+        let new_versions = diff
+            .added_versions
+            .iter()
+            .map(|(name, versions)| (name, versions.as_slice(), false));
+        let mut announcements: Vec<_> = new_packages
+            .chain(new_versions)
+            .flat_map(|(name, versions, new_package)| {
+                versions.iter().map(move |version| Self::Published {
+                    repository: repository.to_string(),
+                    package: name.clone(),
+                    version: version.clone(),
+                    new_package,
+                })
+            })
+            .collect();
+        announcements.sort_by(|a, b| (a.package(), a.version()).cmp(&(b.package(), b.version())));
+        announcements
+    }
+
+    /// Announce the releases of a package that are retired now but were not
+    /// in its `previous` record, or all retired releases if there is none.
+    pub fn retirements(package: &Package, previous: Option<&Package>) -> Vec<Self> {
+        let was_retired = |version: &Version| {
+            previous.is_some_and(|previous| {
+                previous
+                    .releases
+                    .iter()
+                    .any(|release| &release.version == version && release.is_retired())
+            })
+        };
+        package
+            .releases
+            .iter()
+            .filter(|release| !was_retired(&release.version))
+            .filter_map(|release| {
+                Some(Self::Retired {
+                    repository: package.repository.clone(),
+                    package: package.name.to_string(),
+                    version: release.version.clone(),
+                    status: release.retirement_status.clone()?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn package(&self) -> &str {
+        match self {
+            Self::Published { package, .. } | Self::Retired { package, .. } => package,
+        }
+    }
+
+    pub fn version(&self) -> &Version {
+        match self {
+            Self::Published { version, .. } | Self::Retired { version, .. } => version,
+        }
+    }
+
+    pub fn repository(&self) -> &str {
+        match self {
+            Self::Published { repository, .. } | Self::Retired { repository, .. } => repository,
+        }
+    }
+
+    /// The page of the release on Hex.
+    pub fn url(&self) -> String {
+        let (repository, package, version) = (self.repository(), self.package(), self.version());
+        let base = crate::hexpm_defaults::WEBSITE_BASE;
+        if repository == "hexpm" {
+            format!("{base}packages/{package}/{version}")
+        } else {
+            format!("{base}packages/{repository}/{package}/{version}")
+        }
+    }
+
+    /// The announcement as a plain text sentence.
+    pub fn to_text(&self) -> String {
+        let release = format!("{} {}", self.package(), self.version());
+        self.sentence(&release)
+    }
+
+    /// The announcement as a Markdown sentence, linking to the release.
+    pub fn to_markdown(&self) -> String {
+        let release = format!(
+            "[{} {}]({})",
+            escape_markdown(self.package()),
+            self.version(),
+            self.url()
+        );
+        self.sentence(&release)
+    }
+
+    /// The announcement as a JSON object.
+    #[cfg(feature = "api")]
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut value = json!({
+            "repository": self.repository(),
+            "package": self.package(),
+            "version": self.version().to_string(),
+            "url": self.url(),
+        });
+        match self {
+            Self::Published { new_package, .. } => {
+                value["event"] = json!("published");
+                value["new_package"] = json!(new_package);
+            }
+            Self::Retired { status, .. } => {
+                value["event"] = json!("retired");
+                value["reason"] = json!(status.reason.to_str());
+                value["message"] = json!(status.message);
+            }
+        }
+        value
+    }
+
+    fn sentence(&self, release: &str) -> String {
+        match self {
+            Self::Published {
+                new_package: true, ..
+            } => format!("{release} was published, the first release of a new package"),
+            Self::Published { .. } => format!("{release} was published"),
+            Self::Retired { status, .. } if status.message.is_empty() => {
+                format!("{release} was retired ({})", status.reason.to_str())
+            }
+            Self::Retired { status, .. } => format!(
+                "{release} was retired ({}): {}",
+                status.reason.to_str(),
+                status.message
+            ),
+        }
+    }
+}
+
+/// Write a list of announcements as a single message.
+pub fn format(announcements: &[Announcement], format: Format) -> String {
+    match format {
+        Format::Text => lines(announcements, Announcement::to_text),
+        Format::Markdown => lines(announcements, Announcement::to_markdown),
+        #[cfg(feature = "api")]
+        Format::Json => {
+            let values: Vec<_> = announcements.iter().map(Announcement::to_json).collect();
+            serde_json::Value::Array(values).to_string()
+        }
+    }
+}
+
+fn lines(announcements: &[Announcement], line: fn(&Announcement) -> String) -> String {
+    announcements
+        .iter()
+        .map(line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Package names may contain underscores, which Markdown would otherwise
+/// take as emphasis.
+fn escape_markdown(text: &str) -> String {
+    text.replace('_', "\\_")
+}
+
+#[cfg(all(test, feature = "api"))]
+mod tests {
+    use super::*;
+    use crate::RetirementReason;
+    use crate::fixtures::{package, release};
+    use std::collections::HashMap;
+    use std::time::SystemTime;
+
+    #[test]
+    fn published() {
+        let mut index = VersionsIndex::new(
+            HashMap::from([("lustre".to_string(), vec![Version::new(4, 0, 0)])]),
+            SystemTime::UNIX_EPOCH,
+        );
+        let diff = index.update(
+            HashMap::from([
+                (
+                    "lustre".to_string(),
+                    vec![Version::new(4, 0, 0), Version::new(4, 1, 0)],
+                ),
+                ("gleam_json".to_string(), vec![Version::new(1, 0, 0)]),
+            ]),
+            SystemTime::UNIX_EPOCH,
+        );
+        let announcements = Announcement::from_diff("hexpm", &diff, &index);
+        assert_eq!(
+            format(&announcements, Format::Text),
+            "gleam_json 1.0.0 was published, the first release of a new package\n\
+             lustre 4.1.0 was published"
+        );
+        assert_eq!(
+            format(&announcements, Format::Markdown),
+            "[gleam\\_json 1.0.0](https://hex.pm/packages/gleam_json/1.0.0) was published, \
+             the first release of a new package\n\
+             [lustre 4.1.0](https://hex.pm/packages/lustre/4.1.0) was published"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&format(&announcements, Format::Json)).unwrap();
+        assert_eq!(
+            json[1],
+            json!({
+                "event": "published",
+                "repository": "hexpm",
+                "package": "lustre",
+                "version": "4.1.0",
+                "url": "https://hex.pm/packages/lustre/4.1.0",
+                "new_package": false,
+            })
+        );
+    }
+
+    #[test]
+    fn retired() {
+        let previous = Package {
+            repository: "acme".into(),
+            ..package(
+                "wisp",
+                [
+                    release("1.0.0").retired(RetirementReason::Security, ""),
+                    release("1.1.0"),
+                ],
+            )
+        };
+        let mut package = previous.clone();
+        package.releases[1] = release("1.1.0")
+            .retired(RetirementReason::Security, "Upgrade to 1.1.1")
+            .build();
+
+        assert_eq!(Announcement::retirements(&package, None).len(), 2);
+        let announcements = Announcement::retirements(&package, Some(&previous));
+        assert_eq!(
+            format(&announcements, Format::Text),
+            "wisp 1.1.0 was retired (security): Upgrade to 1.1.1"
+        );
+        assert_eq!(
+            announcements[0].url(),
+            "https://hex.pm/packages/acme/wisp/1.1.0"
+        );
+        assert_eq!(announcements[0].to_json()["reason"], "security");
+        assert_eq!(
+            Announcement::retirements(&previous, None)[0].to_text(),
+            "wisp 1.0.0 was retired (security)"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! The results of operations on many items at once, such as parsing every
+//! requirement of a project or every package of a versions index, where one
+//! bad item should not lose the results of the others.
+//!
+//! A [`BulkResult`] keeps the outcome of each item in order. Callers that
+//! want to carry on with what succeeded use [`BulkResult::partition`], while
+//! those that need every item use [`BulkResult::into_result`] to fail with all
+//! of the errors, or [`BulkResult::first_error`] to fail with the first.
+//!
+//! ```
+//! use hexpm::bulk::BulkResult;
+//!
+//! let results: BulkResult<u32, String> = ["1", "two", "3"]
+//!     .into_iter()
+//!     .map(|input| input.parse().map_err(|_| format!("{input} is not a number")))
+//!     .collect();
+//! assert_eq!(results.summary().to_string(), "2 succeeded, 1 failed");
+//! let (numbers, errors) = results.partition();
+//! assert_eq!(numbers, vec![1, 3]);
+//! assert_eq!(errors, vec!["two is not a number".to_string()]);
+//! ```
+
+use std::fmt;
+
+/// The outcome of each item of a bulk operation, in the order of the items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkResult<T, E> {
+    results: Vec<Result<T, E>>,
+}
+
+/// How many items of a [`BulkResult`] succeeded and failed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BulkSummary {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+impl BulkSummary {
+    pub fn total(&self) -> usize {
+        self.succeeded + self.failed
+    }
+}
+
+impl fmt::Display for BulkSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} succeeded, {} failed", self.succeeded, self.failed)
+    }
+}
+
+/// The errors of a [`BulkResult`] that was required to succeed completely, as
+/// returned by [`BulkResult::into_result`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkError<E> {
+    /// The errors in the order of their items. There is always at least one.
+    pub errors: Vec<E>,
+    /// The number of items that succeeded.
+    pub succeeded: usize,
+}
+
+impl<E: fmt::Display> fmt::Display for BulkError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.errors.len() + self.succeeded;
+        write!(f, "{} of {total} items failed", self.errors.len())?;
+        if let Some(first) = self.errors.first() {
+            write!(f, ", the first with: {first}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for BulkError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.errors
+            .first()
+            .map(|error| error as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl<T, E> BulkResult<T, E> {
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// Whether every item succeeded.
+    pub fn is_complete(&self) -> bool {
+        self.results.iter().all(Result::is_ok)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Result<T, E>> {
+        self.results.iter()
+    }
+
+    pub fn successes(&self) -> impl Iterator<Item = &T> {
+        self.results
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &E> {
+        self.results
+            .iter()
+            .filter_map(|result| result.as_ref().err())
+    }
+
+    pub fn summary(&self) -> BulkSummary {
+        let failed = self.failures().count();
+        BulkSummary {
+            succeeded: self.results.len() - failed,
+            failed,
+        }
+    }
+
+    /// Split into the values of the items that succeeded and the errors of
+    /// those that failed, each in the order of their items.
+    pub fn partition(self) -> (Vec<T>, Vec<E>) {
+        let mut values = vec![];
+        let mut errors = vec![];
+        for result in self.results {
+            match result {
+                Ok(value) => values.push(value),
+                Err(error) => errors.push(error),
+            }
+        }
+        (values, errors)
+    }
+
+    /// The values of every item, or all of the errors if any item failed.
+    pub fn into_result(self) -> Result<Vec<T>, BulkError<E>> {
+        let (values, errors) = self.partition();
+        if errors.is_empty() {
+            Ok(values)
+        } else {
+            Err(BulkError {
+                errors,
+                succeeded: values.len(),
+            })
+        }
+    }
+
+    /// The values of every item, or the error of the first item that failed,
+    /// as collecting the results into a `Result` would give.
+    pub fn first_error(self) -> Result<Vec<T>, E> {
+        self.results.into_iter().collect()
+    }
+
+    /// Apply `f` to the value of every item that succeeded.
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> BulkResult<U, E> {
+        self.results
+            .into_iter()
+            .map(|result| result.map(&mut f))
+            .collect()
+    }
+}
+
+impl<T, E> Default for BulkResult<T, E> {
+    fn default() -> Self {
+        Self { results: vec![] }
+    }
+}
+
+impl<T, E> From<Vec<Result<T, E>>> for BulkResult<T, E> {
+    fn from(results: Vec<Result<T, E>>) -> Self {
+        Self { results }
+    }
+}
+
+impl<T, E> FromIterator<Result<T, E>> for BulkResult<T, E> {
+    fn from_iter<I: IntoIterator<Item = Result<T, E>>>(iter: I) -> Self {
+        Self {
+            results: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T, E> IntoIterator for BulkResult<T, E> {
+    type Item = Result<T, E>;
+    type IntoIter = std::vec::IntoIter<Result<T, E>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.results.into_iter()
+    }
+}
+
+impl<'a, T, E> IntoIterator for &'a BulkResult<T, E> {
+    type Item = &'a Result<T, E>;
+    type IntoIter = std::slice::Iter<'a, Result<T, E>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.results.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> BulkResult<u32, &'static str> {
+        vec![Ok(1), Err("bad"), Ok(3), Err("worse")].into()
+    }
+
+    #[test]
+    fn partial_success() {
+        let results = results();
+        assert_eq!(
+            results.summary(),
+            BulkSummary {
+                succeeded: 2,
+                failed: 2
+            }
+        );
+        assert!(!results.is_complete());
+        assert_eq!(results.successes().collect::<Vec<_>>(), vec![&1, &3]);
+        assert_eq!(
+            results.failures().collect::<Vec<_>>(),
+            vec![&"bad", &"worse"]
+        );
+        assert_eq!(results.clone().map(|n| n * 10).partition().0, vec![10, 30]);
+    }
+
+    #[test]
+    fn strict() {
+        assert_eq!(results().first_error(), Err("bad"));
+        let error = results().into_result().unwrap_err();
+        assert_eq!(
+            error,
+            BulkError {
+                errors: vec!["bad", "worse"],
+                succeeded: 2
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "2 of 4 items failed, the first with: bad"
+        );
+
+        let complete: BulkResult<u32, &str> = vec![Ok(1)].into();
+        assert!(complete.is_complete());
+        assert_eq!(complete.into_result(), Ok(vec![1]));
+        assert_eq!(BulkResult::<u32, &str>::default().into_result(), Ok(vec![]));
+    }
+}
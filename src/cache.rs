@@ -0,0 +1,327 @@
+//! Keeping a local cache of release tarballs, such as on a developer's machine
+//! or in CI, verified and within a size budget.
+//!
+//! Tarballs are stored by their outer checksum, which the package records
+//! give for every release, so a tarball is only ever stored once however many
+//! projects use it and a changed tarball can never be mistaken for a cached
+//! one. The default [`ContentAddressed`] layout places each tarball at
+//!
+//! ```text
+//! <root>/v1/tarballs/<first two hex digits>/<hex checksum>.tar
+//! ```
+//!
+//! so that no directory grows too large. Other layouts can be used by
+//! implementing [`CacheLayout`].
+//!
+//! As with the rest of this crate the caller does the file system work.
+//! [`verify_entries`] re-checks the contents of cached tarballs and
+//! [`plan_gc`] picks which to remove to stay within a [`GcPolicy`], least
+//! recently used first.
+//!
+//! An [`ArtifactStore`] lets downloads use the cache directly:
+//! [`get_package_tarball_cached_request`](crate::get_package_tarball_cached_request)
+//! returns a stored tarball in place of a request when there is one, and
+//! [`get_package_tarball_cached_response`](crate::get_package_tarball_cached_response)
+//! stores the tarballs that are downloaded.
+//!
+//! ```
+//! use hexpm::cache::{CacheEntry, CacheLayout, ContentAddressed, GcPolicy, plan_gc};
+//! use hexpm::checksum::Checksum;
+//! use std::time::{Duration, SystemTime};
+//!
+//! let layout = ContentAddressed::new("/home/lucy/.cache/hex");
+//! let checksum = Checksum::from_bytes(&[0xab; 32]).unwrap();
+//! let path = layout.tarball_path(&checksum);
+//! assert!(path.ends_with(format!("v1/tarballs/ab/{}.tar", checksum.to_hex())));
+//! assert_eq!(layout.checksum_of(&path), Some(checksum));
+//!
+//! let entries = vec![CacheEntry {
+//!     checksum,
+//!     size: 1024,
+//!     last_used: SystemTime::UNIX_EPOCH,
+//! }];
+//! let policy = GcPolicy {
+//!     max_size: Some(512),
+//!     ..GcPolicy::default()
+//! };
+//! let plan = plan_gc(&entries, &policy, SystemTime::now());
+//! assert_eq!(plan.evict.len(), 1);
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::checksum::Checksum;
+
+/// Where cached tarballs are kept.
+pub trait CacheLayout {
+    /// The path of the tarball with the given outer checksum.
+    fn tarball_path(&self, checksum: &Checksum) -> PathBuf;
+
+    /// The checksum of the tarball at a path, or `None` if the path is not
+    /// one of this layout's tarball paths, such as a temporary file.
+    fn checksum_of(&self, path: &Path) -> Option<Checksum>;
+}
+
+/// The default layout, described in the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentAddressed {
+    root: PathBuf,
+}
+
+impl ContentAddressed {
+    /// The version of the layout, which is part of every path so that a
+    /// future layout can share the cache directory.
+    pub const VERSION: &'static str = "v1";
+
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The directory holding the tarballs, for listing the cache contents.
+    pub fn tarballs_dir(&self) -> PathBuf {
+        self.root.join(Self::VERSION).join("tarballs")
+    }
+}
+
+impl CacheLayout for ContentAddressed {
+    fn tarball_path(&self, checksum: &Checksum) -> PathBuf {
+        let hex = checksum.to_hex();
+        self.tarballs_dir()
+            .join(&hex[..2])
+            .join(format!("{hex}.tar"))
+    }
+
+    fn checksum_of(&self, path: &Path) -> Option<Checksum> {
+        let hex = path.file_name()?.to_str()?.strip_suffix(".tar")?;
+        let checksum = Checksum::from_hex(hex).ok()?;
+        (self.tarball_path(&checksum) == path).then_some(checksum)
+    }
+}
+
+/// A tarball in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub checksum: Checksum,
+    /// The size of the tarball in bytes.
+    pub size: u64,
+    /// When the tarball was last used, such as the file's access or
+    /// modification time.
+    pub last_used: SystemTime,
+}
+
+/// Limits on the contents of a cache. Entries are removed when any limit is
+/// exceeded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcPolicy {
+    /// The largest total size of the cached tarballs, in bytes.
+    pub max_size: Option<u64>,
+    /// How long a tarball may go unused before it is removed.
+    pub max_age: Option<Duration>,
+    /// Tarballs that are never removed, such as those of the releases in a
+    /// project's lock file.
+    pub keep: HashSet<Checksum>,
+}
+
+/// The entries to remove from a cache, as found by [`plan_gc`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcPlan {
+    /// The entries to remove, least recently used first.
+    pub evict: Vec<CacheEntry>,
+    /// The total size of the entries to remove, in bytes.
+    pub freed: u64,
+    /// The total size of the entries that remain, in bytes.
+    pub remaining: u64,
+}
+
+/// Pick the entries to remove so that the cache keeps within `policy` at
+/// `now`. Entries unused for longer than the maximum age are removed first,
+/// then the least recently used entries until the cache is small enough.
+///
+/// Kept entries count towards the size of the cache, so the cache may still
+/// be over its size limit if they alone exceed it.
+pub fn plan_gc(entries: &[CacheEntry], policy: &GcPolicy, now: SystemTime) -> GcPlan {
+    let mut candidates: Vec<&CacheEntry> = entries
+        .iter()
+        .filter(|entry| !policy.keep.contains(&entry.checksum))
+        .collect();
+    candidates.sort_by_key(|entry| (entry.last_used, entry.checksum));
+
+    let mut plan = GcPlan {
+        remaining: entries.iter().map(|entry| entry.size).sum(),
+        ..GcPlan::default()
+    };
+    for entry in candidates {
+        let expired = policy.max_age.is_some_and(|max_age| {
+            now.duration_since(entry.last_used)
+                .is_ok_and(|unused| unused > max_age)
+        });
+        let too_large = policy
+            .max_size
+            .is_some_and(|max_size| plan.remaining > max_size);
+        // Candidates are oldest first, so once neither applies neither will
+        // for any later entry.
+        if !expired && !too_large {
+            break;
+        }
+        plan.evict.push(*entry);
+        plan.freed += entry.size;
+        plan.remaining -= entry.size;
+    }
+    plan
+}
+
+/// The result of [`verify_entries`].
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// The number of entries whose contents match their checksum.
+    pub verified: usize,
+    /// Entries whose contents do not match their checksum, which should be
+    /// removed and downloaded again.
+    pub corrupt: Vec<Checksum>,
+    /// Entries that could not be read.
+    pub unreadable: Vec<(Checksum, std::io::Error)>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.corrupt.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+/// Check that the contents of every entry still match its checksum, reading
+/// each with `read`, such as after a crash or on a machine with failing
+/// storage.
+#[cfg(feature = "registry")]
+pub fn verify_entries(
+    entries: &[CacheEntry],
+    mut read: impl FnMut(&CacheEntry) -> std::io::Result<Vec<u8>>,
+) -> VerifyReport {
+    let mut report = VerifyReport::default();
+    for entry in entries {
+        match read(entry) {
+            Ok(contents) if Checksum::of(&contents) == entry.checksum => report.verified += 1,
+            Ok(_) => report.corrupt.push(entry.checksum),
+            Err(error) => report.unreadable.push((entry.checksum, error)),
+        }
+    }
+    report
+}
+
+/// Verified tarballs kept by their outer checksum, such as the files of a
+/// [`CacheLayout`].
+///
+/// Stores are a cache, so failing to read or write one should not fail a
+/// download: `get` returns `None` for a tarball it could not read, and `put`
+/// may drop a tarball it could not write.
+pub trait ArtifactStore {
+    /// The stored tarball with this checksum, if there is one. The contents
+    /// are checked against the checksum before use, so a corrupt entry is
+    /// downloaded again.
+    fn get(&self, checksum: &Checksum) -> Option<Vec<u8>>;
+
+    /// Store a tarball whose contents have been verified to match its
+    /// checksum.
+    fn put(&mut self, checksum: &Checksum, tarball: &[u8]);
+}
+
+impl ArtifactStore for HashMap<Checksum, Vec<u8>> {
+    fn get(&self, checksum: &Checksum) -> Option<Vec<u8>> {
+        HashMap::get(self, checksum).cloned()
+    }
+
+    fn put(&mut self, checksum: &Checksum, tarball: &[u8]) {
+        let _ = self.insert(*checksum, tarball.to_vec());
+    }
+}
+
+/// How to get a tarball, as decided by
+/// [`get_package_tarball_cached_request`](crate::get_package_tarball_cached_request).
+#[derive(Debug)]
+pub enum TarballFetch {
+    /// The tarball was in the store and matches its checksum.
+    Cached(Vec<u8>),
+    /// The tarball must be downloaded with this request, and the response
+    /// given to
+    /// [`get_package_tarball_cached_response`](crate::get_package_tarball_cached_response).
+    Download(Box<http::Request<Vec<u8>>>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(byte: u8, size: u64, last_used: u64) -> CacheEntry {
+        CacheEntry {
+            checksum: Checksum::from_bytes(&[byte; 32]).unwrap(),
+            size,
+            last_used: SystemTime::UNIX_EPOCH + Duration::from_secs(last_used),
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let layout = ContentAddressed::new("/cache");
+        let checksum = Checksum::from_bytes(&[0x0f; 32]).unwrap();
+        let path = layout.tarball_path(&checksum);
+        assert_eq!(
+            path,
+            Path::new("/cache/v1/tarballs/0f").join(format!("{}.tar", checksum.to_hex()))
+        );
+        assert_eq!(layout.checksum_of(&path), Some(checksum));
+        let elsewhere = Path::new("/cache/v1/tarballs/aa").join(path.file_name().unwrap());
+        assert_eq!(layout.checksum_of(&elsewhere), None);
+        assert_eq!(layout.checksum_of(&path.with_extension("tmp")), None);
+    }
+
+    #[test]
+    fn gc() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let entries = [entry(1, 100, 900), entry(2, 100, 100), entry(3, 100, 500)];
+
+        let plan = plan_gc(&entries, &GcPolicy::default(), now);
+        assert!(plan.evict.is_empty());
+        assert_eq!(plan.remaining, 300);
+
+        let by_size = GcPolicy {
+            max_size: Some(150),
+            ..GcPolicy::default()
+        };
+        let plan = plan_gc(&entries, &by_size, now);
+        assert_eq!(plan.evict, vec![entries[1], entries[2]]);
+        assert_eq!((plan.freed, plan.remaining), (200, 100));
+
+        let by_age = GcPolicy {
+            max_age: Some(Duration::from_secs(600)),
+            ..GcPolicy::default()
+        };
+        assert_eq!(plan_gc(&entries, &by_age, now).evict, vec![entries[1]]);
+
+        let keeping = GcPolicy {
+            keep: HashSet::from([entries[1].checksum]),
+            ..by_size
+        };
+        let plan = plan_gc(&entries, &keeping, now);
+        assert_eq!(plan.evict, vec![entries[2], entries[0]]);
+        assert_eq!(plan.remaining, 100);
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn verify() {
+        let good = CacheEntry {
+            checksum: Checksum::of(b"tarball"),
+            ..entry(0, 7, 0)
+        };
+        let entries = [good, entry(1, 7, 0), entry(2, 7, 0)];
+        let report = verify_entries(&entries, |entry| match entry.checksum.as_bytes()[0] {
+            2 => Err(std::io::ErrorKind::NotFound.into()),
+            _ => Ok(b"tarball".to_vec()),
+        });
+        assert_eq!(report.verified, 1);
+        assert_eq!(report.corrupt, vec![entries[1].checksum]);
+        assert_eq!(report.unreadable.len(), 1);
+        assert!(!report.is_ok());
+    }
+}
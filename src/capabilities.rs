@@ -0,0 +1,330 @@
+//! Finding out which endpoints a Hex compatible server supports, so that
+//! tools working with servers other than hex.pm, such as self-hosted
+//! registries and mirrors, can avoid calling endpoints they do not have.
+//!
+//! [`probe_capabilities`] starts a [`CapabilityProbe`], which lists a cheap
+//! request for each capability: `HEAD` requests for repository resources and
+//! small `GET` requests to the API. The caller sends them, in any order and
+//! concurrently if they like, and hands each response back. Probes whose
+//! request could not be sent, or whose response says nothing either way, are
+//! left [`Support::Unknown`].
+//!
+//! ```
+//! use hexpm::capabilities::{Capability, probe_capabilities};
+//! use hexpm::Config;
+//!
+//! let mut probe = probe_capabilities(None);
+//! for (check, request) in probe.requests(None, &Config::new()) {
+//!     // Send the request, here answered as a server without an API would
+//!     let _ = request;
+//!     let response = http::Response::builder().status(404).body(()).unwrap();
+//!     probe.handle_response(check, &response);
+//! }
+//! let capabilities = probe.capabilities();
+//! assert!(!capabilities.supports(Capability::Search));
+//! assert_eq!(capabilities.registry_version, None);
+//! ```
+
+use http::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{Config, Query, ReleaseRef};
+
+/// Whether a server supports a capability.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Support {
+    Supported,
+    Unsupported,
+    /// The capability was not probed, or its probe was inconclusive, such as
+    /// when the server was unavailable.
+    #[default]
+    Unknown,
+}
+
+impl Support {
+    /// Whether the capability may be used, which is the case unless it is
+    /// known to be unsupported. Calls to endpoints of unknown support fail
+    /// with the usual errors if the server does not have them.
+    pub fn allows(self) -> bool {
+        self != Support::Unsupported
+    }
+}
+
+/// The version of the repository protocol a server implements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistryVersion {
+    /// The legacy `registry.ets.gz` registry, which this crate cannot read.
+    V1,
+    /// The signed `names`, `versions` and `packages` resources.
+    V2,
+}
+
+/// A feature of Hex that a server may not have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Documentation tarballs in the repository.
+    Docs,
+    /// Searching packages through the API.
+    Search,
+    /// Organizations and their repositories and keys.
+    Organizations,
+}
+
+/// What a server supports, as found by a [`CapabilityProbe`]. The value can
+/// be saved, such as alongside a project's repository settings, to avoid
+/// probing the server each time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Capabilities {
+    pub docs: Support,
+    pub search: Support,
+    pub organizations: Support,
+    /// The newest version of the repository protocol the server serves, or
+    /// `None` if it serves no registry or was not reached.
+    pub registry_version: Option<RegistryVersion>,
+}
+
+impl Capabilities {
+    /// The capabilities of hex.pm, which supports everything.
+    pub const HEXPM: Capabilities = Capabilities {
+        docs: Support::Supported,
+        search: Support::Supported,
+        organizations: Support::Supported,
+        registry_version: Some(RegistryVersion::V2),
+    };
+
+    pub fn support(&self, capability: Capability) -> Support {
+        match capability {
+            Capability::Docs => self.docs,
+            Capability::Search => self.search,
+            Capability::Organizations => self.organizations,
+        }
+    }
+
+    /// Whether a capability may be used, see [`Support::allows`].
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.support(capability).allows()
+    }
+}
+
+/// A single request of a [`CapabilityProbe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Check {
+    /// A `HEAD` request for the documentation of a release known to have it.
+    Docs,
+    /// A search for packages, limited to a single result.
+    Search,
+    /// A request listing the current user's organizations. Servers with
+    /// organizations reject it without credentials, which is enough to tell
+    /// that they have them.
+    Organizations,
+    /// A `HEAD` request for the versions index.
+    Registry,
+    /// A `HEAD` request for the legacy registry.
+    LegacyRegistry,
+}
+
+/// The state of probing a server, see the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct CapabilityProbe {
+    docs_release: Option<ReleaseRef>,
+    capabilities: Capabilities,
+    registry: Support,
+    legacy_registry: Support,
+}
+
+/// Start probing a server. Whether the repository has documentation can only
+/// be told from a release known to have it, so docs are only probed
+/// when one is given, such as a release the caller depends on.
+pub fn probe_capabilities(docs_release: Option<&ReleaseRef>) -> CapabilityProbe {
+    CapabilityProbe {
+        docs_release: docs_release.cloned(),
+        ..CapabilityProbe::default()
+    }
+}
+
+impl CapabilityProbe {
+    /// The requests to send, each with the check to report its response as.
+    pub fn requests(
+        &self,
+        api_key: Option<&str>,
+        config: &Config,
+    ) -> Vec<(Check, http::Request<Vec<u8>>)> {
+        let mut requests = vec![];
+        if let Some(release) = &self.docs_release {
+            let request = config
+                .repository_request(
+                    Method::HEAD,
+                    &release.repository_path("docs", "tar.gz"),
+                    api_key,
+                )
+                .body(vec![])
+                .expect("docs probe request");
+            requests.push((Check::Docs, request));
+        }
+        let query = Query::new().search("name:hex*").per_page(1);
+        requests.push((
+            Check::Search,
+            crate::get_packages_request(&query, api_key, config),
+        ));
+        let request = config
+            .api_request(Method::GET, "orgs", api_key)
+            .body(vec![])
+            .expect("organizations probe request");
+        requests.push((Check::Organizations, request));
+        for (check, path) in [
+            (Check::Registry, "versions"),
+            (Check::LegacyRegistry, "registry.ets.gz"),
+        ] {
+            let request = config
+                .repository_request(Method::HEAD, path, api_key)
+                .body(vec![])
+                .expect("registry probe request");
+            requests.push((check, request));
+        }
+        requests
+    }
+
+    /// Record the response to the request of a check.
+    ///
+    /// A successful response means the endpoint is supported, as does a
+    /// rejection of the credentials for API endpoints, since the server only
+    /// checks them for routes it has. `404 Not Found`, `405 Method Not
+    /// Allowed` and `501 Not Implemented` mean it is not. Any other response,
+    /// such as a server error, leaves the check unknown.
+    pub fn handle_response<B>(&mut self, check: Check, response: &http::Response<B>) {
+        let status = response.status();
+        let support = match status {
+            _ if status.is_success() => Support::Supported,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+                if matches!(check, Check::Search | Check::Organizations) =>
+            {
+                Support::Supported
+            }
+            StatusCode::NOT_FOUND
+            | StatusCode::METHOD_NOT_ALLOWED
+            | StatusCode::NOT_IMPLEMENTED => Support::Unsupported,
+            _ => Support::Unknown,
+        };
+        match check {
+            Check::Docs => self.capabilities.docs = support,
+            Check::Search => self.capabilities.search = support,
+            Check::Organizations => self.capabilities.organizations = support,
+            Check::Registry => self.registry = support,
+            Check::LegacyRegistry => self.legacy_registry = support,
+        }
+    }
+
+    /// The capabilities found from the responses handled so far.
+    pub fn capabilities(&self) -> Capabilities {
+        let registry_version = if self.registry == Support::Supported {
+            Some(RegistryVersion::V2)
+        } else if self.legacy_registry == Support::Supported {
+            Some(RegistryVersion::V1)
+        } else {
+            None
+        };
+        Capabilities {
+            registry_version,
+            ..self.capabilities
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::Version;
+
+    fn respond(status: u16) -> http::Response<Vec<u8>> {
+        http::Response::builder()
+            .status(status)
+            .body(vec![])
+            .unwrap()
+    }
+
+    #[test]
+    fn requests() {
+        let release = ReleaseRef::new("gleam_stdlib", Version::new(0, 40, 0));
+        let probe = probe_capabilities(Some(&release));
+        let requests: Vec<_> = probe
+            .requests(None, &Config::new())
+            .into_iter()
+            .map(|(check, request)| (check, request.method().clone(), request.uri().to_string()))
+            .collect();
+        assert_eq!(
+            requests,
+            vec![
+                (
+                    Check::Docs,
+                    Method::HEAD,
+                    "https://repo.hex.pm/docs/gleam_stdlib-0.40.0.tar.gz".to_string()
+                ),
+                (
+                    Check::Search,
+                    Method::GET,
+                    "https://hex.pm/api/packages?search=name%3Ahex*&per_page=1".to_string()
+                ),
+                (
+                    Check::Organizations,
+                    Method::GET,
+                    "https://hex.pm/api/orgs".to_string()
+                ),
+                (
+                    Check::Registry,
+                    Method::HEAD,
+                    "https://repo.hex.pm/versions".to_string()
+                ),
+                (
+                    Check::LegacyRegistry,
+                    Method::HEAD,
+                    "https://repo.hex.pm/registry.ets.gz".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            probe_capabilities(None)
+                .requests(None, &Config::new())
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn capabilities() {
+        let release = ReleaseRef::new("gleam_stdlib", Version::new(0, 40, 0));
+        let mut probe = probe_capabilities(Some(&release));
+        assert_eq!(probe.capabilities(), Capabilities::default());
+        assert!(probe.capabilities().supports(Capability::Docs));
+
+        probe.handle_response(Check::Docs, &respond(404));
+        probe.handle_response(Check::Search, &respond(200));
+        probe.handle_response(Check::Organizations, &respond(401));
+        probe.handle_response(Check::Registry, &respond(503));
+        probe.handle_response(Check::LegacyRegistry, &respond(200));
+        let capabilities = probe.capabilities();
+        assert_eq!(
+            capabilities,
+            Capabilities {
+                docs: Support::Unsupported,
+                search: Support::Supported,
+                organizations: Support::Supported,
+                registry_version: Some(RegistryVersion::V1),
+            }
+        );
+        assert!(!capabilities.supports(Capability::Docs));
+        assert!(capabilities.supports(Capability::Organizations));
+
+        probe.handle_response(Check::Registry, &respond(200));
+        assert_eq!(
+            probe.capabilities().registry_version,
+            Some(RegistryVersion::V2)
+        );
+
+        // Only API routes are known to exist when credentials are rejected
+        probe.handle_response(Check::Docs, &respond(403));
+        assert_eq!(probe.capabilities().docs, Support::Unknown);
+    }
+}
@@ -0,0 +1,341 @@
+//! Reading and writing the `CHECKSUM` file found inside package tarballs.
+//!
+//! The file holds the SHA-256 checksum of the tarball's `VERSION`,
+//! `metadata.config` and `contents.tar.gz` files, concatenated in that order,
+//! written as 64 uppercase hexadecimal characters with no trailing newline.
+//!
+//! Elsewhere checksums are written as lowercase hexadecimal in JSON and lock
+//! files, raw bytes in the registry's protobuf resources, and base64 by some
+//! tools. [`Checksum`] converts between these.
+
+use std::fmt;
+
+#[cfg(feature = "registry")]
+use ring::digest::{Context, SHA256};
+use thiserror::Error;
+
+/// The length of a SHA-256 checksum in bytes.
+pub const CHECKSUM_LENGTH: usize = 32;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ChecksumError {
+    #[error("the checksum file must be {} characters long, not {0}", CHECKSUM_LENGTH * 2)]
+    InvalidLength(usize),
+
+    #[error(
+        "the checksum file contains {character:?} at position {position}, which is not an uppercase hexadecimal digit"
+    )]
+    InvalidCharacter { position: usize, character: char },
+
+    #[error("{0:?} is not a hexadecimal or base64 encoded SHA-256 checksum")]
+    InvalidChecksum(String),
+}
+
+/// A SHA-256 checksum, such as the inner or outer checksum of a release.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Checksum([u8; CHECKSUM_LENGTH]);
+
+impl Checksum {
+    /// Parse a checksum written in hexadecimal of either case, or in standard
+    /// or URL safe base64 with or without padding. A `sha256:` prefix is
+    /// ignored.
+    pub fn parse_any(input: &str) -> Result<Self, ChecksumError> {
+        let checksum = input.trim();
+        let checksum = match checksum.split_once(':') {
+            Some((algorithm, rest)) if algorithm.eq_ignore_ascii_case("sha256") => rest,
+            _ => checksum,
+        };
+        Self::from_hex(checksum)
+            .or_else(|_| Self::from_base64(checksum))
+            .map_err(|_| ChecksumError::InvalidChecksum(input.to_string()))
+    }
+
+    /// Parse a checksum written in hexadecimal of either case.
+    pub fn from_hex(input: &str) -> Result<Self, ChecksumError> {
+        if input.len() != CHECKSUM_LENGTH * 2 {
+            return Err(ChecksumError::InvalidChecksum(input.to_string()));
+        }
+        let bytes =
+            base16::decode(input).map_err(|_| ChecksumError::InvalidChecksum(input.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Parse a checksum written in standard or URL safe base64, with or
+    /// without padding.
+    pub fn from_base64(input: &str) -> Result<Self, ChecksumError> {
+        let invalid = || ChecksumError::InvalidChecksum(input.to_string());
+        let digits = input.strip_suffix('=').unwrap_or(input);
+        if digits.len() != 43 {
+            return Err(invalid());
+        }
+        let mut bytes = Vec::with_capacity(CHECKSUM_LENGTH + 1);
+        let mut buffer = 0u32;
+        let mut bits = 0;
+        for c in digits.bytes() {
+            let value = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' | b'-' => 62,
+                b'/' | b'_' => 63,
+                _ => return Err(invalid()),
+            };
+            buffer = (buffer << 6) | u32::from(value);
+            bits += 6;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((buffer >> bits) as u8);
+            }
+        }
+        // The two bits left over must be zero for the encoding to be canonical.
+        if buffer & ((1 << bits) - 1) != 0 {
+            return Err(invalid());
+        }
+        Self::from_bytes(&bytes)
+    }
+
+    /// A checksum from its raw bytes, as found in protobuf resources.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChecksumError> {
+        bytes
+            .try_into()
+            .map(Self)
+            .map_err(|_| ChecksumError::InvalidChecksum(base16::encode_lower(bytes)))
+    }
+
+    /// The SHA-256 checksum of some data, such as the outer checksum of a
+    /// downloaded tarball.
+    #[cfg(feature = "registry")]
+    pub fn of(data: &[u8]) -> Self {
+        let digest = ring::digest::digest(&SHA256, data);
+        Self::from_bytes(digest.as_ref()).expect("SHA-256 digest is 32 bytes")
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The checksum in lowercase hexadecimal, as used in JSON and lock files.
+    pub fn to_hex(&self) -> String {
+        base16::encode_lower(&self.0)
+    }
+
+    /// The checksum in standard base64 with padding.
+    pub fn to_base64(&self) -> String {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut output = String::with_capacity(44);
+        for chunk in self.0.chunks(3) {
+            let mut group = [0; 3];
+            group[..chunk.len()].copy_from_slice(chunk);
+            let n = u32::from_be_bytes([0, group[0], group[1], group[2]]);
+            for i in 0..=chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            }
+        }
+        while !output.len().is_multiple_of(4) {
+            output.push('=');
+        }
+        output
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl fmt::Debug for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Checksum").field(&self.to_hex()).finish()
+    }
+}
+
+impl AsRef<[u8]> for Checksum {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Checksum> for Vec<u8> {
+    fn from(checksum: Checksum) -> Self {
+        checksum.0.to_vec()
+    }
+}
+
+impl std::str::FromStr for Checksum {
+    type Err = ChecksumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_any(s)
+    }
+}
+
+impl serde::Serialize for Checksum {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Checksum {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s: std::borrow::Cow<'de, str> = serde::Deserialize::deserialize(deserializer)?;
+        Self::parse_any(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parse the contents of a `CHECKSUM` file into the checksum it holds.
+///
+/// Lowercase digits and surrounding whitespace are rejected as Hex never
+/// writes them.
+pub fn parse_checksum_file(content: &[u8]) -> Result<Vec<u8>, ChecksumError> {
+    let content = String::from_utf8_lossy(content);
+    if let Some((position, character)) = content
+        .char_indices()
+        .find(|(_, c)| !matches!(c, '0'..='9' | 'A'..='F'))
+    {
+        return Err(ChecksumError::InvalidCharacter {
+            position,
+            character,
+        });
+    }
+    if content.len() != CHECKSUM_LENGTH * 2 {
+        return Err(ChecksumError::InvalidLength(content.len()));
+    }
+    Ok(base16::decode(content.as_bytes()).expect("validated hexadecimal"))
+}
+
+/// The contents of a `CHECKSUM` file for the given checksum.
+pub fn checksum_file(checksum: &[u8]) -> String {
+    base16::encode_upper(checksum)
+}
+
+/// The checksum stored in the `CHECKSUM` file of a package tarball with the
+/// given files.
+#[cfg(feature = "registry")]
+pub fn inner_checksum(version: &[u8], metadata: &[u8], contents: &[u8]) -> Vec<u8> {
+    let mut context = Context::new(&SHA256);
+    context.update(version);
+    context.update(metadata);
+    context.update(contents);
+    context.finish().as_ref().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECKSUM: &str = "A8B7F5EE1D2B3A3C2F1A4E0C8B1A42F3E7D9C6B5A4F3E2D1C0B9A8F7E6D5C4B3";
+
+    #[test]
+    fn round_trip() {
+        let checksum = parse_checksum_file(CHECKSUM.as_bytes()).unwrap();
+        assert_eq!(checksum.len(), CHECKSUM_LENGTH);
+        assert_eq!(checksum_file(&checksum), CHECKSUM);
+    }
+
+    #[test]
+    fn invalid_length() {
+        assert_eq!(
+            parse_checksum_file(&CHECKSUM.as_bytes()[..62]),
+            Err(ChecksumError::InvalidLength(62))
+        );
+        assert_eq!(
+            parse_checksum_file(b""),
+            Err(ChecksumError::InvalidLength(0))
+        );
+    }
+
+    #[test]
+    fn invalid_characters() {
+        let lowercase = CHECKSUM.to_lowercase();
+        assert_eq!(
+            parse_checksum_file(lowercase.as_bytes()),
+            Err(ChecksumError::InvalidCharacter {
+                position: 0,
+                character: 'a'
+            })
+        );
+        let newline = format!("{}\n", CHECKSUM);
+        assert_eq!(
+            parse_checksum_file(newline.as_bytes()),
+            Err(ChecksumError::InvalidCharacter {
+                position: 64,
+                character: '\n'
+            })
+        );
+    }
+
+    #[test]
+    fn checksum_encodings() {
+        let checksum = Checksum::parse_any(CHECKSUM).unwrap();
+        let lower = CHECKSUM.to_lowercase();
+        assert_eq!(checksum.to_hex(), lower);
+        assert_eq!(Checksum::parse_any(&lower), Ok(checksum));
+        assert_eq!(
+            Checksum::parse_any(&format!("sha256:{lower}")),
+            Ok(checksum)
+        );
+
+        let base64 = checksum.to_base64();
+        assert_eq!(base64, "qLf17h0rOjwvGk4MixpC8+fZxrWk8+LRwLmo9+bVxLM=");
+        assert_eq!(Checksum::parse_any(&base64), Ok(checksum));
+        assert_eq!(Checksum::parse_any(&base64[..43]), Ok(checksum));
+        let url_safe = base64.replace('+', "-").replace('/', "_");
+        assert_eq!(Checksum::from_base64(&url_safe), Ok(checksum));
+
+        assert_eq!(
+            Checksum::from_bytes(checksum.as_bytes()).map(Vec::from),
+            Ok(parse_checksum_file(CHECKSUM.as_bytes()).unwrap())
+        );
+        assert_eq!(
+            Checksum::from_bytes(&[1, 2]),
+            Err(ChecksumError::InvalidChecksum("0102".into()))
+        );
+        for invalid in [
+            "",
+            "00",
+            &lower[1..],
+            "qLf17h0rOjwvGk4MixpC8+fZxrWk8+LRwLmo9+bVxLN",
+        ] {
+            assert_eq!(
+                Checksum::parse_any(invalid),
+                Err(ChecksumError::InvalidChecksum(invalid.into()))
+            );
+        }
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn inner_checksum_of_files() {
+        let checksum = inner_checksum(b"3", b"{<<\"name\">>,<<\"x\">>}.\n", b"contents");
+        let concatenated = inner_checksum(b"", b"", b"3{<<\"name\">>,<<\"x\">>}.\ncontents");
+        assert_eq!(checksum, concatenated);
+        assert_eq!(checksum.len(), CHECKSUM_LENGTH);
+    }
+
+    /// The files of an uncompressed tar archive, which is all that is needed
+    /// to read the outer tarball of a package.
+    #[cfg(feature = "registry")]
+    #[test]
+    fn package_tarball_checksum() {
+        let tarball = std::include_bytes!("../test/gleam_stdlib-0.14.0.tar");
+        let file = |name| crate::tarball::read_file(tarball, name).unwrap().unwrap();
+        let checksum = parse_checksum_file(file("CHECKSUM")).unwrap();
+        assert_eq!(
+            checksum,
+            inner_checksum(
+                file("VERSION"),
+                file("metadata.config"),
+                file("contents.tar.gz")
+            )
+        );
+        assert_eq!(checksum_file(&checksum).as_bytes(), file("CHECKSUM"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Suggestions for keeping the release list of a package tidy, for
+//! maintainers automating housekeeping of their own packages.
+//!
+//! Pre-releases are published for testing ahead of a stable release. Once the
+//! stable release is out they are of no further use, but they remain in the
+//! registry and may still be picked by requirements that allow pre-releases.
+//! [`superseded_pre_releases`] finds them so that they can be retired.
+
+use crate::version::Version;
+#[cfg(feature = "api")]
+use crate::{Config, RetirementReason};
+use crate::{Package, ReleaseRef};
+
+/// A pre-release that a later stable release has superseded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetirementSuggestion {
+    pub release: ReleaseRef,
+    /// The earliest stable release after the pre-release.
+    pub superseded_by: Version,
+}
+
+impl RetirementSuggestion {
+    /// The message to retire the release with.
+    pub fn message(&self) -> String {
+        format!("Superseded by {}", self.superseded_by)
+    }
+
+    /// Create a request that retires the release as deprecated.
+    #[cfg(feature = "api")]
+    pub fn retire_request(&self, api_key: &str, config: &Config) -> http::Request<Vec<u8>> {
+        crate::retire_release_request(
+            &self.release,
+            RetirementReason::Deprecated,
+            Some(&self.message()),
+            api_key,
+            config,
+        )
+    }
+}
+
+/// The pre-releases of a package that have a later stable release and have
+/// not been retired, in version order.
+pub fn superseded_pre_releases(package: &Package) -> Vec<RetirementSuggestion> {
+    let mut stable: Vec<&Version> = package
+        .releases
+        .iter()
+        .map(|release| &release.version)
+        .filter(|version| !version.is_pre())
+        .collect();
+    stable.sort();
+
+    let mut suggestions: Vec<RetirementSuggestion> = package
+        .releases
+        .iter()
+        .filter(|release| release.version.is_pre() && !release.is_retired())
+        .filter_map(|release| {
+            let superseded_by = stable.iter().find(|stable| **stable > &release.version)?;
+            Some(RetirementSuggestion {
+                release: ReleaseRef::new(package.name.clone(), release.version.clone())
+                    .in_repository(package.repository.clone()),
+                superseded_by: (*superseded_by).clone(),
+            })
+        })
+        .collect();
+    suggestions.sort_by(|a, b| a.release.version.cmp(&b.release.version));
+    suggestions
+}
+
+#[cfg(all(test, feature = "api"))]
+mod tests {
+    use super::*;
+    use crate::fixtures::{package, release};
+
+    #[test]
+    fn suggestions() {
+        let package = package(
+            "lustre",
+            [
+                release("1.0.0-rc2"),
+                release("1.0.0-rc1"),
+                release("1.0.0"),
+                release("1.1.0-rc1").retired(RetirementReason::Other, ""),
+                release("1.1.0"),
+                release("2.0.0-rc1"),
+            ],
+        );
+        let suggestions = superseded_pre_releases(&package);
+        let versions: Vec<_> = suggestions
+            .iter()
+            .map(|s| (s.release.version.to_string(), s.superseded_by.to_string()))
+            .collect();
+        assert_eq!(
+            versions,
+            vec![
+                ("1.0.0-rc1".to_string(), "1.0.0".to_string()),
+                ("1.0.0-rc2".to_string(), "1.0.0".to_string()),
+            ]
+        );
+
+        let request = suggestions[0].retire_request("key", &Config::new());
+        assert_eq!(
+            request.uri().path(),
+            "/api/packages/lustre/releases/1.0.0-rc1/retire"
+        );
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(request.body()).unwrap(),
+            serde_json::json!({"reason": "deprecated", "message": "Superseded by 1.0.0"})
+        );
+    }
+}
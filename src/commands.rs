@@ -0,0 +1,497 @@
+//! A uniform JSON interface to the request builders and response parsers, for
+//! language bindings and scripts that would rather not wrap every function.
+//!
+//! A command names an operation and its parameters, along with optional
+//! configuration:
+//!
+//! ```json
+//! {"command": "get_package", "params": {"name": "gleam_stdlib", "public_key": "..."},
+//!  "config": {"repository_base": "https://repo.hex.pm/"}}
+//! ```
+//!
+//! [`request`] turns a command into a [`RequestDescriptor`] for the client to
+//! send. [`response`] takes the same command along with a
+//! [`ResponseDescriptor`] of the response received and returns the parsed
+//! result. Both return `{"ok": ...}` on success and `{"error": {"code": ...,
+//! "message": ...}}` on failure. Request and response bodies are base16
+//! encoded.
+//!
+//! ```
+//! let command = r#"{"command": "get_package_tarball",
+//!     "params": {"package": "gleam_stdlib", "version": "0.14.0", "checksum": "9107f6a859cb96945ad9a099085db028ca2bebb3c8ea42eec227b51c614cc2e0"}}"#;
+//! let request: serde_json::Value = serde_json::from_str(&hexpm::commands::request(command)).unwrap();
+//! assert_eq!(
+//!     request["ok"]["url"],
+//!     "https://repo.hex.pm/tarballs/gleam_stdlib-0.14.0.tar"
+//! );
+//!
+//! let response = r#"{"status": 404, "headers": [], "body": ""}"#;
+//! let result: serde_json::Value =
+//!     serde_json::from_str(&hexpm::commands::response(command, response)).unwrap();
+//! assert_eq!(result["error"]["code"], "not_found");
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[cfg(feature = "registry")]
+use crate::checksum::Checksum;
+use crate::{ApiError, Config, ReleaseRef, RetirementReason};
+
+/// An operation and its parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "command", content = "params", rename_all = "snake_case")]
+pub enum Command {
+    #[cfg(feature = "registry")]
+    GetPackage {
+        name: String,
+        api_key: Option<String>,
+        /// The PEM encoded public key of the repository.
+        public_key: String,
+    },
+    #[cfg(feature = "registry")]
+    GetRepositoryVersions {
+        api_key: Option<String>,
+        /// The PEM encoded public key of the repository.
+        public_key: String,
+    },
+    GetPackageRelease {
+        #[serde(flatten)]
+        release: ReleaseParams,
+        api_key: Option<String>,
+    },
+    #[cfg(feature = "registry")]
+    GetPackageTarball {
+        #[serde(flatten)]
+        release: ReleaseParams,
+        api_key: Option<String>,
+        /// The checksum of the tarball, in hexadecimal or base64.
+        checksum: Checksum,
+    },
+    GetDocsTarball {
+        #[serde(flatten)]
+        release: ReleaseParams,
+        api_key: Option<String>,
+    },
+    ReleaseSize {
+        #[serde(flatten)]
+        release: ReleaseParams,
+        api_key: Option<String>,
+    },
+    RetireRelease {
+        #[serde(flatten)]
+        release: ReleaseParams,
+        reason: String,
+        message: Option<String>,
+        api_key: String,
+    },
+    UnretireRelease {
+        #[serde(flatten)]
+        release: ReleaseParams,
+        api_key: String,
+    },
+}
+
+/// The parameters identifying a release, see [`ReleaseRef`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ReleaseParams {
+    pub package: String,
+    pub version: String,
+    pub repository: Option<String>,
+}
+
+/// The configuration of a command. Unset fields take their values from
+/// [`Config::new`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct CommandConfig {
+    pub api_base: Option<String>,
+    pub repository_base: Option<String>,
+}
+
+/// A command along with its configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Invocation {
+    #[serde(flatten)]
+    pub command: Command,
+    #[serde(default)]
+    pub config: CommandConfig,
+}
+
+/// A request for the client to send.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestDescriptor {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    #[serde(
+        serialize_with = "serialize_base16",
+        deserialize_with = "deserialize_base16"
+    )]
+    pub body: Vec<u8>,
+}
+
+impl From<http::Request<Vec<u8>>> for RequestDescriptor {
+    fn from(request: http::Request<Vec<u8>>) -> Self {
+        let (parts, body) = request.into_parts();
+        Self {
+            method: parts.method.to_string(),
+            url: parts.uri.to_string(),
+            headers: headers(&parts.headers),
+            body,
+        }
+    }
+}
+
+/// A response received by the client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResponseDescriptor {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(
+        serialize_with = "serialize_base16",
+        deserialize_with = "deserialize_base16"
+    )]
+    pub body: Vec<u8>,
+}
+
+impl TryFrom<ResponseDescriptor> for http::Response<Vec<u8>> {
+    type Error = http::Error;
+
+    fn try_from(descriptor: ResponseDescriptor) -> Result<Self, Self::Error> {
+        let mut builder = http::Response::builder().status(descriptor.status);
+        for (name, value) in &descriptor.headers {
+            builder = builder.header(name, value);
+        }
+        builder.body(descriptor.body)
+    }
+}
+
+impl Invocation {
+    pub fn from_json(json: &str) -> Result<Self, ApiError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    fn config(&self) -> Result<Config, ApiError> {
+        let mut config = Config::new();
+        if let Some(base) = &self.config.api_base {
+            config.api_base = parse_uri("api_base", base)?;
+        }
+        if let Some(base) = &self.config.repository_base {
+            config.repository_base = parse_uri("repository_base", base)?;
+        }
+        Ok(config)
+    }
+
+    /// Build the request for the command.
+    pub fn request(&self) -> Result<RequestDescriptor, ApiError> {
+        let config = &self.config()?;
+        if let Some(api_key) = self.command.api_key() {
+            let _ = http::HeaderValue::from_str(api_key).map_err(|error| {
+                ApiError::InvalidArgument {
+                    argument: "api_key",
+                    reason: error.to_string(),
+                }
+            })?;
+        }
+        let request = match &self.command {
+            #[cfg(feature = "registry")]
+            Command::GetPackage { name, api_key, .. } => {
+                crate::PackageName::validate(name)
+                    .map_err(|_| ApiError::InvalidPackageNameFormat(name.clone()))?;
+                crate::get_package_request(name, api_key.as_deref(), config)
+            }
+            #[cfg(feature = "registry")]
+            Command::GetRepositoryVersions { api_key, .. } => {
+                crate::get_repository_versions_request(api_key.as_deref(), config)
+            }
+            Command::GetPackageRelease { release, api_key } => {
+                crate::get_package_release_request(&release.parse()?, api_key.as_deref(), config)
+            }
+            #[cfg(feature = "registry")]
+            Command::GetPackageTarball {
+                release, api_key, ..
+            } => crate::get_package_tarball_request(&release.parse()?, api_key.as_deref(), config),
+            Command::GetDocsTarball { release, api_key } => {
+                crate::get_docs_tarball_request(&release.parse()?, api_key.as_deref(), config)?
+            }
+            Command::ReleaseSize { release, api_key } => {
+                crate::release_size_request(&release.parse()?, api_key.as_deref(), config)
+            }
+            Command::RetireRelease {
+                release,
+                reason,
+                message,
+                api_key,
+            } => crate::retire_release_request(
+                &release.parse()?,
+                parse_reason(reason)?,
+                message.as_deref(),
+                api_key,
+                config,
+            ),
+            Command::UnretireRelease { release, api_key } => {
+                crate::unretire_release_request(&release.parse()?, api_key, config)
+            }
+        };
+        Ok(request.into())
+    }
+
+    /// Parse the response to the command's request.
+    pub fn response(&self, response: ResponseDescriptor) -> Result<Value, ApiError> {
+        let response =
+            http::Response::try_from(response).map_err(|error| ApiError::InvalidArgument {
+                argument: "response",
+                reason: error.to_string(),
+            })?;
+        let value = match &self.command {
+            #[cfg(feature = "registry")]
+            Command::GetPackage { public_key, .. } => serde_json::to_value(
+                crate::get_package_response(response, public_key.as_bytes())?,
+            ),
+            #[cfg(feature = "registry")]
+            Command::GetRepositoryVersions { public_key, .. } => serde_json::to_value(
+                crate::get_repository_versions_response(response, public_key.as_bytes())?,
+            ),
+            Command::GetPackageRelease { .. } => {
+                serde_json::to_value(crate::get_package_release_response(response)?)
+            }
+            #[cfg(feature = "registry")]
+            Command::GetPackageTarball { checksum, .. } => Ok(Value::String(base16::encode_lower(
+                &crate::get_package_tarball_response(response, checksum.as_bytes())?,
+            ))),
+            Command::GetDocsTarball { .. } => Ok(Value::String(base16::encode_lower(
+                &crate::get_docs_tarball_response(response)?,
+            ))),
+            Command::ReleaseSize { .. } => {
+                serde_json::to_value(crate::release_size_response(response)?)
+            }
+            Command::RetireRelease { .. } => {
+                serde_json::to_value(crate::retire_release_response(response)?)
+            }
+            Command::UnretireRelease { .. } => {
+                serde_json::to_value(crate::unretire_release_response(response)?)
+            }
+        };
+        Ok(value?)
+    }
+}
+
+impl Command {
+    fn api_key(&self) -> Option<&str> {
+        match self {
+            #[cfg(feature = "registry")]
+            Command::GetPackage { api_key, .. }
+            | Command::GetRepositoryVersions { api_key, .. }
+            | Command::GetPackageTarball { api_key, .. } => api_key.as_deref(),
+            Command::GetPackageRelease { api_key, .. }
+            | Command::GetDocsTarball { api_key, .. }
+            | Command::ReleaseSize { api_key, .. } => api_key.as_deref(),
+            Command::RetireRelease { api_key, .. } | Command::UnretireRelease { api_key, .. } => {
+                Some(api_key)
+            }
+        }
+    }
+}
+
+impl ReleaseParams {
+    fn parse(&self) -> Result<ReleaseRef, ApiError> {
+        let release = ReleaseRef::parse(&self.package, &self.version)?;
+        Ok(match &self.repository {
+            Some(repository) => release.try_in_repository(repository)?,
+            None => release,
+        })
+    }
+}
+
+/// Build the request for a JSON encoded [`Invocation`], returning the JSON
+/// encoded [`RequestDescriptor`] or error.
+pub fn request(invocation: &str) -> String {
+    outcome(Invocation::from_json(invocation).and_then(|invocation| invocation.request()))
+}
+
+/// Parse a JSON encoded [`ResponseDescriptor`] received for the request of a
+/// JSON encoded [`Invocation`], returning the JSON encoded result or error.
+pub fn response(invocation: &str, response: &str) -> String {
+    let result = Invocation::from_json(invocation).and_then(|invocation| {
+        let response = serde_json::from_str(response)?;
+        invocation.response(response)
+    });
+    outcome(result)
+}
+
+fn outcome(result: Result<impl Serialize, ApiError>) -> String {
+    let outcome = match result {
+        Ok(value) => serde_json::json!({ "ok": value }),
+        Err(error) => serde_json::json!({ "error": error }),
+    };
+    outcome.to_string()
+}
+
+/// A base URL, which must be absolute so that requests can be made under it.
+fn parse_uri(argument: &'static str, uri: &str) -> Result<http::Uri, ApiError> {
+    crate::base_url(uri).ok_or_else(|| ApiError::InvalidArgument {
+        argument,
+        reason: format!("{uri:?} is not an HTTP or HTTPS URL"),
+    })
+}
+
+fn parse_reason(reason: &str) -> Result<RetirementReason, ApiError> {
+    Ok(serde_json::from_value(Value::String(reason.to_string()))?)
+}
+
+fn headers(headers: &http::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+fn serialize_base16<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&base16::encode_lower(bytes))
+}
+
+fn deserialize_base16<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: std::borrow::Cow<'de, str> = Deserialize::deserialize(deserializer)?;
+    base16::decode(s.as_bytes()).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(invocation: Value) -> Value {
+        serde_json::from_str(&request(&invocation.to_string())).unwrap()
+    }
+
+    fn parse(invocation: Value, response: Value) -> Value {
+        serde_json::from_str(&super::response(
+            &invocation.to_string(),
+            &response.to_string(),
+        ))
+        .unwrap()
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn get_package() {
+        let public_key = std::fs::read_to_string("test/public_key").unwrap();
+        let invocation = json!({
+            "command": "get_package",
+            "params": {"name": "exfmt", "public_key": public_key},
+            "config": {"repository_base": "https://mirror.example.com/"},
+        });
+        assert_eq!(
+            run(invocation.clone()),
+            json!({"ok": {
+                "method": "GET",
+                "url": "https://mirror.example.com/packages/exfmt",
+                "headers": [["user-agent", crate::USER_AGENT], ["accept", "application/json"]],
+                "body": "",
+            }})
+        );
+
+        let body = std::fs::read("test/package_exfmt").unwrap();
+        let result = parse(
+            invocation,
+            json!({"status": 200, "body": base16::encode_lower(&body)}),
+        );
+        assert_eq!(result["ok"]["name"], "exfmt");
+    }
+
+    #[test]
+    fn retire_release() {
+        let invocation = json!({
+            "command": "retire_release",
+            "params": {
+                "package": "gleam_stdlib",
+                "version": "0.14.0",
+                "reason": "security",
+                "message": null,
+                "api_key": "my-key",
+            },
+        });
+        let request = run(invocation.clone());
+        assert_eq!(request["ok"]["method"], "POST");
+        assert_eq!(
+            request["ok"]["url"],
+            "https://hex.pm/api/packages/gleam_stdlib/releases/0.14.0/retire"
+        );
+        let body = base16::decode(request["ok"]["body"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!({"reason": "security", "message": null})
+        );
+        assert_eq!(
+            parse(invocation, json!({"status": 204, "body": ""})),
+            json!({"ok": null})
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = run(json!({"command": "no_such_command"}));
+        assert_eq!(error["error"]["code"], "invalid_json");
+
+        let error = run(json!({
+            "command": "get_docs_tarball",
+            "params": {"package": "Invalid Name", "version": "1.0.0"},
+        }));
+        assert_eq!(error["error"]["code"], "invalid_package_name");
+
+        let error = run(json!({
+            "command": "retire_release",
+            "params": {
+                "package": "name",
+                "version": "1.0.0",
+                "reason": "bored",
+                "api_key": "key",
+            },
+        }));
+        assert_eq!(error["error"]["code"], "invalid_json");
+
+        #[cfg(feature = "registry")]
+        {
+            let error = run(json!({
+                "command": "get_package",
+                "params": {"name": "a b", "public_key": ""},
+            }));
+            assert_eq!(error["error"]["code"], "invalid_package_name");
+        }
+
+        let error = run(json!({
+            "command": "release_size",
+            "params": {"package": "jason", "version": "1.0.0", "repository": "a b"},
+        }));
+        assert_eq!(error["error"]["code"], "invalid_repository_name");
+
+        let error = run(json!({
+            "command": "release_size",
+            "params": {"package": "name", "version": "1.0.0", "api_key": "a\nb"},
+        }));
+        assert_eq!(error["error"]["code"], "invalid_argument");
+
+        let error = run(json!({
+            "command": "release_size",
+            "params": {"package": "name", "version": "1.0.0"},
+            "config": {"repository_base": "not a url"},
+        }));
+        assert_eq!(
+            error["error"],
+            json!({
+                "code": "invalid_argument",
+                "message": "the repository_base argument is not valid: \"not a url\" is not an HTTP or HTTPS URL",
+            })
+        );
+    }
+}
@@ -0,0 +1,418 @@
+//! Checking a documentation tarball before publishing it with
+//! [`publish_docs_request`](crate::publish_docs_request).
+//!
+//! Hex rejects some broken documentation bundles, such as those that are too
+//! large, but accepts others that then fail to display on HexDocs, such as
+//! those without an `index.html` page. [`validate_docs_tarball`] checks for
+//! both so that problems are caught before publishing.
+//!
+//! Documentation generated by ExDoc also lists its modules and the other
+//! published versions in small JavaScript files next to the HTML pages.
+//! [`read_docs_index`] parses these so that editors and other tools can offer
+//! documentation search without scraping the HTML. It needs the `api`
+//! feature, which brings in the JSON parser.
+
+use std::fmt;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::ApiError;
+use crate::tarball;
+
+/// The largest compressed documentation tarball Hex accepts.
+pub const MAX_COMPRESSED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The largest size a documentation tarball may decompress to.
+pub const MAX_UNCOMPRESSED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The result of checking a documentation tarball.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocsReport {
+    /// The number of files in the tarball.
+    pub files: usize,
+    /// The total size of the files in the tarball, in bytes.
+    pub uncompressed_size: u64,
+    pub issues: Vec<DocsIssue>,
+}
+
+impl DocsReport {
+    /// Whether the tarball can be published.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// A problem with a documentation tarball.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocsIssue {
+    /// The tarball is larger than [`MAX_COMPRESSED_SIZE`].
+    CompressedTooLarge(u64),
+    /// The tarball decompresses to more than [`MAX_UNCOMPRESSED_SIZE`].
+    UncompressedTooLarge,
+    /// The tarball is not gzip compressed.
+    NotGzipped,
+    /// The tarball is not a valid tar archive.
+    InvalidArchive,
+    /// There is no `index.html` at the root of the tarball.
+    MissingIndex,
+    /// An entry is a link, which HexDocs does not serve.
+    Link(String),
+    /// An entry is neither a file nor a directory.
+    UnsupportedEntry(String),
+    /// An entry's path is absolute or leaves the tarball's root directory.
+    UnsafePath(String),
+}
+
+impl fmt::Display for DocsIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocsIssue::CompressedTooLarge(size) => write!(
+                f,
+                "the tarball is {size} bytes, more than the limit of {MAX_COMPRESSED_SIZE}"
+            ),
+            DocsIssue::UncompressedTooLarge => write!(
+                f,
+                "the tarball decompresses to more than {MAX_UNCOMPRESSED_SIZE} bytes"
+            ),
+            DocsIssue::NotGzipped => write!(f, "the tarball is not gzip compressed"),
+            DocsIssue::InvalidArchive => write!(f, "the tarball is not a valid tar archive"),
+            DocsIssue::MissingIndex => write!(f, "the tarball has no index.html"),
+            DocsIssue::Link(path) => write!(f, "{path} is a link"),
+            DocsIssue::UnsupportedEntry(path) => {
+                write!(f, "{path} is neither a file nor a directory")
+            }
+            DocsIssue::UnsafePath(path) => {
+                write!(f, "{path} is outside of the documentation directory")
+            }
+        }
+    }
+}
+
+/// Check a gzipped documentation tarball against the requirements of Hex and
+/// HexDocs.
+pub fn validate_docs_tarball(gzipped_tarball: &[u8]) -> DocsReport {
+    let mut report = DocsReport::default();
+    let compressed_size = gzipped_tarball.len() as u64;
+    if compressed_size > MAX_COMPRESSED_SIZE {
+        report
+            .issues
+            .push(DocsIssue::CompressedTooLarge(compressed_size));
+    }
+    let tarball = match crate::inflate(gzipped_tarball, MAX_UNCOMPRESSED_SIZE) {
+        Ok(tarball) => tarball,
+        Err(ApiError::ResponseTooLarge(_)) => {
+            report.issues.push(DocsIssue::UncompressedTooLarge);
+            return report;
+        }
+        Err(_) => {
+            report.issues.push(DocsIssue::NotGzipped);
+            return report;
+        }
+    };
+
+    let mut has_index = false;
+    for entry in tarball::entries(&tarball) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => {
+                report.issues.push(DocsIssue::InvalidArchive);
+                return report;
+            }
+        };
+        let path = String::from_utf8_lossy(&entry.path).into_owned();
+        let normalised = path.trim_start_matches("./");
+        if !is_safe(normalised) {
+            report.issues.push(DocsIssue::UnsafePath(path));
+            continue;
+        }
+        match entry.kind {
+            b'0' | b'\0' | b'7' => {
+                has_index |= normalised == "index.html";
+                report.files += 1;
+                report.uncompressed_size += entry.data.len() as u64;
+            }
+            b'5' => (),
+            b'1' | b'2' => report.issues.push(DocsIssue::Link(path)),
+            _ => report.issues.push(DocsIssue::UnsupportedEntry(path)),
+        }
+    }
+    if !has_index {
+        report.issues.push(DocsIssue::MissingIndex);
+    }
+    report
+}
+
+/// A published version of a package's documentation, as listed in
+/// `docs_config.js`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DocsVersion {
+    /// The version as ExDoc writes it, such as `v1.2.0`.
+    pub version: String,
+    pub url: String,
+}
+
+/// A page in the documentation sidebar, such as a module or a guide.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SidebarNode {
+    /// The name of the page, without the `.html` extension.
+    pub id: String,
+    pub title: String,
+    /// The group the page is listed under, if any.
+    #[serde(default)]
+    pub group: String,
+    /// The documented items of a module, such as its functions and types.
+    #[serde(default, rename = "nodeGroups")]
+    pub node_groups: Vec<SidebarNodeGroup>,
+}
+
+/// The documented items of one kind in a module.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SidebarNodeGroup {
+    /// The kind of item, such as `functions` or `types`.
+    pub key: String,
+    pub name: String,
+    pub nodes: Vec<SidebarItem>,
+}
+
+/// A documented item, such as `get/2`, found at `<module>.html#<anchor>`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SidebarItem {
+    pub id: String,
+    pub anchor: String,
+}
+
+/// The contents of a documentation tarball, as listed by the files ExDoc
+/// writes for its sidebar and version selector.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct DocsIndex {
+    #[serde(default)]
+    pub modules: Vec<SidebarNode>,
+    /// Guides and other pages that are not about a module.
+    #[serde(default)]
+    pub extras: Vec<SidebarNode>,
+    /// Mix tasks.
+    #[serde(default)]
+    pub tasks: Vec<SidebarNode>,
+    /// The published versions of the documentation. Empty if the tarball
+    /// has no `docs_config.js`.
+    #[serde(skip)]
+    pub versions: Vec<DocsVersion>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DocsIndexError {
+    #[error("the documentation tarball could not be read")]
+    InvalidTarball,
+
+    #[error("the documentation has no sidebar items, so was not generated by ExDoc")]
+    MissingSidebar,
+
+    #[error("{file} does not assign {variable} a valid value: {reason}")]
+    InvalidScript {
+        file: String,
+        variable: &'static str,
+        reason: String,
+    },
+}
+
+/// Read the modules, pages and versions listed in a gzipped documentation
+/// tarball generated by ExDoc.
+#[cfg(feature = "api")]
+pub fn read_docs_index(gzipped_tarball: &[u8]) -> Result<DocsIndex, DocsIndexError> {
+    let tarball = crate::inflate(gzipped_tarball, MAX_UNCOMPRESSED_SIZE)
+        .map_err(|_| DocsIndexError::InvalidTarball)?;
+    let mut index = None;
+    let mut versions = vec![];
+    for entry in tarball::entries(&tarball) {
+        let entry = entry.map_err(|_| DocsIndexError::InvalidTarball)?;
+        let path = String::from_utf8_lossy(&entry.path);
+        let path = path.trim_start_matches("./");
+        let source = String::from_utf8_lossy(entry.data);
+        if path == "docs_config.js" {
+            versions = parse_docs_config(path, &source)?;
+        } else if path
+            .strip_prefix("dist/sidebar_items")
+            .is_some_and(|name| name.ends_with(".js"))
+        {
+            index = Some(parse_sidebar_items(path, &source)?);
+        }
+    }
+    let mut index = index.ok_or(DocsIndexError::MissingSidebar)?;
+    index.versions = versions;
+    Ok(index)
+}
+
+/// Parse the `versionNodes` list of a `docs_config.js` file.
+#[cfg(feature = "api")]
+pub fn parse_docs_config(file: &str, source: &str) -> Result<Vec<DocsVersion>, DocsIndexError> {
+    script_value(file, source, "versionNodes")
+}
+
+/// Parse the `sidebarNodes` object of a `dist/sidebar_items-*.js` file. The
+/// versions of the returned index are left empty.
+#[cfg(feature = "api")]
+pub fn parse_sidebar_items(file: &str, source: &str) -> Result<DocsIndex, DocsIndexError> {
+    script_value(file, source, "sidebarNodes")
+}
+
+/// Parse the JSON value assigned to a variable in a generated script, such as
+/// `var versionNodes = [...];`. Anything after the value is ignored.
+#[cfg(feature = "api")]
+fn script_value<T: serde::de::DeserializeOwned>(
+    file: &str,
+    source: &str,
+    variable: &'static str,
+) -> Result<T, DocsIndexError> {
+    let invalid = |reason: String| DocsIndexError::InvalidScript {
+        file: file.to_string(),
+        variable,
+        reason,
+    };
+    let value = source
+        .find(variable)
+        .map(|start| source[start + variable.len()..].trim_start())
+        .and_then(|rest| rest.strip_prefix('='))
+        .ok_or_else(|| invalid("the variable is not assigned".into()))?;
+    serde_json::Deserializer::from_str(value)
+        .into_iter()
+        .next()
+        .ok_or_else(|| invalid("there is no value".into()))?
+        .map_err(|error| invalid(error.to_string()))
+}
+
+fn is_safe(path: &str) -> bool {
+    !path.starts_with('/') && !path.contains('\\') && path.split('/').all(|segment| segment != "..")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    fn header(path: &str, kind: u8, size: usize) -> Vec<u8> {
+        let mut header = vec![0; 512];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header
+    }
+
+    fn tarball(entries: &[(&str, u8, &[u8])]) -> Vec<u8> {
+        let mut tar = vec![];
+        for (path, kind, data) in entries {
+            tar.extend(header(path, *kind, data.len()));
+            tar.extend(*data);
+            tar.resize(tar.len().div_ceil(512) * 512, 0);
+        }
+        tar.extend([0; 1024]);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn valid_docs() {
+        let report = validate_docs_tarball(std::include_bytes!("../test/example.tar.gz"));
+        assert!(report.is_valid(), "{report:?}");
+        assert_eq!(report.files, 1);
+
+        let report = validate_docs_tarball(&tarball(&[
+            ("./", b'5', b""),
+            ("./index.html", b'0', b"<html></html>"),
+            ("./dist/app.js", b'0', b"let x;"),
+        ]));
+        assert!(report.is_valid(), "{report:?}");
+        assert_eq!(report.files, 2);
+        assert_eq!(report.uncompressed_size, 19);
+    }
+
+    #[test]
+    fn invalid_docs() {
+        let report = validate_docs_tarball(&tarball(&[
+            ("docs/index.html", b'0', b""),
+            ("../escape.html", b'0', b""),
+            ("/etc/passwd", b'0', b""),
+            ("latest", b'2', b""),
+            ("fifo", b'6', b""),
+        ]));
+        assert_eq!(
+            report.issues,
+            vec![
+                DocsIssue::UnsafePath("../escape.html".into()),
+                DocsIssue::UnsafePath("/etc/passwd".into()),
+                DocsIssue::Link("latest".into()),
+                DocsIssue::UnsupportedEntry("fifo".into()),
+                DocsIssue::MissingIndex,
+            ]
+        );
+
+        let report = validate_docs_tarball(b"<html></html>");
+        assert_eq!(report.issues, vec![DocsIssue::NotGzipped]);
+        assert_eq!(
+            report.issues[0].to_string(),
+            "the tarball is not gzip compressed"
+        );
+    }
+
+    #[test]
+    fn long_paths() {
+        let path = format!("{}/index.html", "nested".repeat(20));
+        let pax = format!("{} path={path}\n", path.len() + 10);
+        let report = validate_docs_tarball(&tarball(&[
+            ("index.html", b'0', b""),
+            ("PaxHeader", b'x', pax.as_bytes()),
+            ("truncated", b'0', b"abc"),
+            ("././@LongLink", b'L', b"../../outside.html\0"),
+            ("outside.html", b'0', b""),
+        ]));
+        assert_eq!(
+            report.issues,
+            vec![DocsIssue::UnsafePath("../../outside.html".into())]
+        );
+        assert_eq!(report.files, 2);
+    }
+
+    #[cfg(feature = "api")]
+    #[test]
+    fn docs_index() {
+        let config =
+            br#"var versionNodes = [{"version":"v1.1.0","url":"https://hexdocs.pm/wisp/1.1.0"},
+            {"version":"v1.0.0","url":"https://hexdocs.pm/wisp/1.0.0"}];
+            searchNodes = [];"#;
+        let sidebar = br#"sidebarNodes={"extras":[{"id":"readme","title":"README","group":""}],
+            "modules":[{"id":"Wisp","title":"Wisp","group":"","nodeGroups":[{"key":"functions",
+            "name":"Functions","nodes":[{"id":"get/2","anchor":"get/2"}]}]}],"tasks":[]}"#;
+        let index = read_docs_index(&tarball(&[
+            ("./index.html", b'0', b""),
+            ("./docs_config.js", b'0', config),
+            ("./dist/sidebar_items-5A1B.js", b'0', sidebar),
+        ]))
+        .unwrap();
+        assert_eq!(index.versions.len(), 2);
+        assert_eq!(index.versions[0].version, "v1.1.0");
+        assert_eq!(index.modules[0].id, "Wisp");
+        assert_eq!(index.modules[0].node_groups[0].nodes[0].anchor, "get/2");
+        assert_eq!(index.extras[0].title, "README");
+        assert!(index.tasks.is_empty());
+
+        let index = read_docs_index(&tarball(&[("dist/sidebar_items.js", b'0', sidebar)]));
+        assert!(index.unwrap().versions.is_empty());
+        assert_eq!(
+            read_docs_index(&tarball(&[("index.html", b'0', b"")])),
+            Err(DocsIndexError::MissingSidebar)
+        );
+        assert_eq!(
+            parse_docs_config("docs_config.js", "var versionNodes = [{"),
+            Err(DocsIndexError::InvalidScript {
+                file: "docs_config.js".into(),
+                variable: "versionNodes",
+                reason: "EOF while parsing an object at line 1 column 3".into(),
+            })
+        );
+        assert!(parse_docs_config("docs_config.js", "var other = [];").is_err());
+    }
+}
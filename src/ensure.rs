@@ -0,0 +1,703 @@
+//! Working out everything a build tool needs to do to have a project's
+//! dependencies ready, in one call.
+//!
+//! [`ensure_dependencies`] starts from the versions in the project's lock
+//! file and only moves packages whose locked versions no longer satisfy the
+//! project's requirements, as with [`what_if`]. Package records are asked for
+//! as they are needed, so the caller only fetches the packages the project
+//! uses. The resulting [`DependencyPlan`] lists the resolved packages for the
+//! new lock file, how they differ from the old one, and the tarballs that are
+//! missing from the caller's store along with the checksum each must have.
+//!
+//! Front-ends showing progress can use
+//! [`ensure_dependencies_with_progress`] and
+//! [`Download::handle_response`], which report each step as an
+//! [`EnsureEvent`]. Progress bars for the downloads can find the size of each
+//! with [`Download::size_request`] and keep a [`ByteProgress`] up to date
+//! with [`Download::handle_response_with_totals`].
+//!
+//! ```
+//! use hexpm::ensure::ensure_dependencies;
+//! use hexpm::checksum::Checksum;
+//! use hexpm::{Package, Release, version::{Range, Version}};
+//! use std::collections::{HashMap, HashSet};
+//!
+//! let lustre = Package {
+//!     name: "lustre".into(),
+//!     repository: "hexpm".into(),
+//!     releases: vec![Release {
+//!         version: Version::new(4, 1, 0),
+//!         requirements: HashMap::new(),
+//!         retirement_status: None,
+//!         outer_checksum: vec![7; 32],
+//!         meta: (),
+//!     }],
+//! };
+//! let roots = HashMap::from([("lustre".into(), Range::new("~> 4.0".into()).unwrap())]);
+//! let locked = HashMap::new();
+//! let store: HashSet<Checksum> = HashSet::new();
+//! let plan = ensure_dependencies(&roots, &locked, |_name| Some(lustre.clone()), &store).unwrap();
+//! assert_eq!(plan.resolved[0].version, Version::new(4, 1, 0));
+//! assert_eq!(plan.downloads[0].release.package, "lustre");
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::checksum::Checksum;
+use crate::resolution::{
+    Constraint, DependencyCycle, ResolutionError, ResolutionOptions, ResolvedPackage,
+    SelectionChange, WhatIf, resolved_packages, what_if,
+};
+use crate::version::{Range, Version};
+use crate::{Config, Package, PackageName, ReleaseRef};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EnsureError {
+    #[error("no package record could be fetched for {0}")]
+    MissingPackage(PackageName),
+
+    #[error("no release of {package} satisfies all of its requirements")]
+    Unresolvable {
+        package: PackageName,
+        constraints: Vec<Constraint>,
+        cycle: Option<DependencyCycle>,
+    },
+
+    #[error("the release {package} {version} has no valid checksum")]
+    InvalidChecksum {
+        package: PackageName,
+        version: Version,
+    },
+
+    #[error(transparent)]
+    Resolution(#[from] ResolutionError),
+}
+
+/// A step of working out or carrying out a [`DependencyPlan`], for showing
+/// progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnsureEvent {
+    /// Resolution has started.
+    Resolving,
+    /// The registry record of a package was fetched.
+    Fetched { package: PackageName },
+    /// A resolved release's tarball is already in the store, so it is not
+    /// downloaded.
+    Skipped {
+        package: PackageName,
+        version: Version,
+    },
+    /// A downloaded tarball has the expected checksum.
+    Verified {
+        package: PackageName,
+        version: Version,
+    },
+    /// A tarball could not be downloaded or did not have the expected
+    /// checksum.
+    Failed {
+        package: PackageName,
+        reason: String,
+    },
+    /// The byte counts of the downloads after one has finished.
+    Bytes(ByteProgress),
+}
+
+/// The accumulated byte counts of a plan's downloads, for progress bars and
+/// estimating the time remaining.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ByteProgress {
+    /// The total size of the downloads, in bytes. Downloads whose size is
+    /// not known count once they have finished, so until then this is too
+    /// low.
+    pub expected: u64,
+    /// The number of unfinished downloads whose size is not known.
+    pub unknown_sizes: usize,
+    /// The bytes received, including those of downloads that failed.
+    pub downloaded: u64,
+    /// The bytes of downloads that had the expected checksum.
+    pub verified: u64,
+    /// The number of downloads that have not yet been verified.
+    pub remaining: usize,
+}
+
+impl ByteProgress {
+    /// The fraction of the expected bytes that have been verified, from `0.0`
+    /// to `1.0`, or `None` while the size of some downloads is not known.
+    pub fn fraction(&self) -> Option<f64> {
+        if self.unknown_sizes > 0 {
+            return None;
+        }
+        if self.expected == 0 {
+            return Some(if self.remaining == 0 { 1.0 } else { 0.0 });
+        }
+        Some((self.verified as f64 / self.expected as f64).min(1.0))
+    }
+
+    /// An estimate of the time left, given the time spent downloading so far,
+    /// assuming the rest download at the same average rate. `None` while the
+    /// size of some downloads is not known or nothing has been received.
+    pub fn eta(&self, elapsed: Duration) -> Option<Duration> {
+        if self.unknown_sizes > 0 || self.downloaded == 0 {
+            return None;
+        }
+        let left = self.expected.saturating_sub(self.verified);
+        Some(elapsed.mul_f64(left as f64 / self.downloaded as f64))
+    }
+}
+
+/// The release tarballs already downloaded, such as a local cache.
+pub trait TarballStore {
+    /// Whether the tarball with the given outer checksum is in the store.
+    fn contains(&self, checksum: &Checksum) -> bool;
+}
+
+impl TarballStore for HashSet<Checksum> {
+    fn contains(&self, checksum: &Checksum) -> bool {
+        HashSet::contains(self, checksum)
+    }
+}
+
+impl<F: Fn(&Checksum) -> bool> TarballStore for F {
+    fn contains(&self, checksum: &Checksum) -> bool {
+        self(checksum)
+    }
+}
+
+/// A release tarball to download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    pub release: ReleaseRef,
+    /// The outer checksum the downloaded tarball must have.
+    pub checksum: Checksum,
+    /// The size of the tarball in bytes, if known. Registry records do not
+    /// give sizes, so this is found with [`size_request`](Self::size_request).
+    pub size: Option<u64>,
+}
+
+impl Download {
+    pub fn request(&self, api_key: Option<&str>, config: &Config) -> http::Request<Vec<u8>> {
+        crate::get_package_tarball_request(&self.release, api_key, config)
+    }
+
+    /// A `HEAD` request to find the size of the tarball without downloading
+    /// it.
+    pub fn size_request(&self, api_key: Option<&str>, config: &Config) -> http::Request<Vec<u8>> {
+        crate::release_size_request(&self.release, api_key, config)
+    }
+
+    /// Record the size from the response to [`size_request`](Self::size_request),
+    /// returning it.
+    pub fn size_response(
+        &mut self,
+        response: http::Response<Vec<u8>>,
+    ) -> Result<Option<u64>, crate::ApiError> {
+        self.size = crate::release_size_response(response)?;
+        Ok(self.size)
+    }
+
+    /// Parse the response to [`request`](Self::request), checking the
+    /// tarball against the expected checksum.
+    #[cfg(feature = "registry")]
+    pub fn response(&self, response: http::Response<Vec<u8>>) -> Result<Vec<u8>, crate::ApiError> {
+        crate::get_package_tarball_response(response, self.checksum.as_bytes())
+    }
+
+    /// As [`response`](Self::response), reporting the outcome to `progress`
+    /// as [`EnsureEvent::Verified`] or [`EnsureEvent::Failed`].
+    #[cfg(feature = "registry")]
+    pub fn handle_response(
+        &self,
+        response: http::Response<Vec<u8>>,
+        mut progress: impl FnMut(EnsureEvent),
+    ) -> Result<Vec<u8>, crate::ApiError> {
+        let result = self.response(response);
+        let package = self.release.package.clone();
+        progress(match &result {
+            Ok(_) => EnsureEvent::Verified {
+                package,
+                version: self.release.version.clone(),
+            },
+            Err(error) => EnsureEvent::Failed {
+                package,
+                reason: error.to_string(),
+            },
+        });
+        result
+    }
+
+    /// As [`handle_response`](Self::handle_response), also adding the
+    /// response to `totals` and reporting them as [`EnsureEvent::Bytes`].
+    #[cfg(feature = "registry")]
+    pub fn handle_response_with_totals(
+        &self,
+        response: http::Response<Vec<u8>>,
+        totals: &mut ByteProgress,
+        mut progress: impl FnMut(EnsureEvent),
+    ) -> Result<Vec<u8>, crate::ApiError> {
+        let received = response.body().len() as u64;
+        let result = self.handle_response(response, &mut progress);
+        totals.downloaded += received;
+        if let Ok(tarball) = &result {
+            let size = tarball.len() as u64;
+            // The expected total now has the actual size of this download
+            match self.size {
+                Some(expected) => totals.expected = totals.expected.saturating_sub(expected) + size,
+                None => {
+                    totals.unknown_sizes = totals.unknown_sizes.saturating_sub(1);
+                    totals.expected += size;
+                }
+            }
+            totals.verified += size;
+            totals.remaining = totals.remaining.saturating_sub(1);
+        }
+        progress(EnsureEvent::Bytes(*totals));
+        result
+    }
+}
+
+/// Everything needed to bring a project's dependencies up to date, as found
+/// by [`ensure_dependencies`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyPlan {
+    /// The resolved packages to record in the lock file, sorted by name.
+    pub resolved: Vec<ResolvedPackage>,
+    /// The changes from the versions in the lock file, sorted by package.
+    pub changes: Vec<SelectionChange>,
+    /// The tarballs missing from the store, sorted by package.
+    pub downloads: Vec<Download>,
+}
+
+impl DependencyPlan {
+    /// Whether the lock file and the store are already up to date.
+    pub fn is_up_to_date(&self) -> bool {
+        self.changes.is_empty() && self.downloads.is_empty()
+    }
+
+    /// The byte counts before anything has been downloaded, from the sizes
+    /// of the downloads known so far.
+    pub fn byte_progress(&self) -> ByteProgress {
+        ByteProgress {
+            expected: self
+                .downloads
+                .iter()
+                .filter_map(|download| download.size)
+                .sum(),
+            unknown_sizes: self
+                .downloads
+                .iter()
+                .filter(|download| download.size.is_none())
+                .count(),
+            remaining: self.downloads.len(),
+            ..ByteProgress::default()
+        }
+    }
+}
+
+/// Resolve the project's requirements `roots`, keeping the `locked`
+/// versions where they still satisfy them, and find the tarballs missing from
+/// `store`. `fetch` is asked for the registry record of each package the
+/// resolution needs, such as from a cache or with
+/// [`get_package_request`](crate::get_package_request), and each package is
+/// asked for at most once.
+pub fn ensure_dependencies(
+    roots: &HashMap<PackageName, Range>,
+    locked: &HashMap<PackageName, Version>,
+    fetch: impl FnMut(&PackageName) -> Option<Package>,
+    store: &impl TarballStore,
+) -> Result<DependencyPlan, EnsureError> {
+    ensure_dependencies_with_progress(roots, locked, fetch, store, |_| ())
+}
+
+/// As [`ensure_dependencies`], reporting each step to `progress`: the start
+/// of resolution, each fetched package record, each tarball already in the
+/// store, and the package that failed if resolution fails.
+pub fn ensure_dependencies_with_progress(
+    roots: &HashMap<PackageName, Range>,
+    locked: &HashMap<PackageName, Version>,
+    mut fetch: impl FnMut(&PackageName) -> Option<Package>,
+    store: &impl TarballStore,
+    mut progress: impl FnMut(EnsureEvent),
+) -> Result<DependencyPlan, EnsureError> {
+    let result = plan(roots, locked, &mut fetch, store, &mut progress);
+    if let Err(error) = &result {
+        let package = match error {
+            EnsureError::MissingPackage(package)
+            | EnsureError::Unresolvable { package, .. }
+            | EnsureError::InvalidChecksum { package, .. } => Some(package.clone()),
+            EnsureError::Resolution(_) => None,
+        };
+        if let Some(package) = package {
+            progress(EnsureEvent::Failed {
+                package,
+                reason: error.to_string(),
+            });
+        }
+    }
+    result
+}
+
+fn plan(
+    roots: &HashMap<PackageName, Range>,
+    locked: &HashMap<PackageName, Version>,
+    fetch: &mut impl FnMut(&PackageName) -> Option<Package>,
+    store: &impl TarballStore,
+    progress: &mut impl FnMut(EnsureEvent),
+) -> Result<DependencyPlan, EnsureError> {
+    progress(EnsureEvent::Resolving);
+    let options = ResolutionOptions::new();
+    let mut packages = HashMap::new();
+    // A package that cannot be resolved may simply not have been fetched
+    // yet, in which case it is fetched and resolution is tried again.
+    let (selected, changes) = loop {
+        match what_if(roots, locked, &packages, &options) {
+            WhatIf::Resolved { selected, changes } => break (selected, changes),
+            WhatIf::Unresolvable { package, .. } if !packages.contains_key(&package) => {
+                let record =
+                    fetch(&package).ok_or_else(|| EnsureError::MissingPackage(package.clone()))?;
+                progress(EnsureEvent::Fetched {
+                    package: package.clone(),
+                });
+                packages.insert(package, record);
+            }
+            WhatIf::Unresolvable {
+                package,
+                constraints,
+                cycle,
+            } => {
+                return Err(EnsureError::Unresolvable {
+                    package,
+                    constraints,
+                    cycle,
+                });
+            }
+        }
+    };
+
+    let resolved = resolved_packages(&selected, &packages)?;
+    let mut downloads = vec![];
+    for package in &resolved {
+        let checksum = Checksum::from_bytes(&package.outer_checksum).map_err(|_| {
+            EnsureError::InvalidChecksum {
+                package: package.name.clone(),
+                version: package.version.clone(),
+            }
+        })?;
+        if store.contains(&checksum) {
+            progress(EnsureEvent::Skipped {
+                package: package.name.clone(),
+                version: package.version.clone(),
+            });
+        } else {
+            let release = ReleaseRef::new(package.name.clone(), package.version.clone())
+                .in_repository(package.repository.clone());
+            downloads.push(Download {
+                release,
+                checksum,
+                size: None,
+            });
+        }
+    }
+    Ok(DependencyPlan {
+        resolved,
+        changes,
+        downloads,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{package, release};
+
+    fn registry() -> HashMap<PackageName, Package> {
+        [
+            package(
+                "lustre",
+                [
+                    release("4.0.0")
+                        .requires("gleam_stdlib", "~> 0.34")
+                        .checksum(1),
+                    release("4.1.0")
+                        .requires("gleam_stdlib", "~> 0.34")
+                        .checksum(2),
+                ],
+            ),
+            package(
+                "gleam_stdlib",
+                [release("0.34.0").checksum(3), release("0.36.0").checksum(4)],
+            ),
+        ]
+        .into_iter()
+        .map(|package| (package.name.clone(), package))
+        .collect()
+    }
+
+    fn range(spec: &str) -> Range {
+        Range::new(spec.into()).unwrap()
+    }
+
+    #[test]
+    fn keeps_locked_versions() {
+        let registry = registry();
+        let mut fetched = vec![];
+        let fetch = |name: &PackageName| {
+            fetched.push(name.to_string());
+            registry.get(name).cloned()
+        };
+        let roots = HashMap::from([("lustre".into(), range("~> 4.0"))]);
+        let locked = HashMap::from([
+            ("lustre".into(), Version::new(4, 0, 0)),
+            ("gleam_stdlib".into(), Version::new(0, 34, 0)),
+        ]);
+        let stdlib =
+            Checksum::from_bytes(&registry["gleam_stdlib"].releases[0].outer_checksum).unwrap();
+        let store = HashSet::from([stdlib]);
+
+        let plan = ensure_dependencies(&roots, &locked, fetch, &store).unwrap();
+        fetched.sort();
+        assert_eq!(fetched, vec!["gleam_stdlib", "lustre"]);
+        assert!(plan.changes.is_empty());
+        let versions: Vec<_> = plan
+            .resolved
+            .iter()
+            .map(|package| package.version.to_string())
+            .collect();
+        assert_eq!(versions, vec!["0.34.0", "4.0.0"]);
+        assert_eq!(plan.downloads.len(), 1);
+        let download = &plan.downloads[0];
+        assert_eq!(download.release.package, "lustre");
+        assert_eq!(
+            download.request(None, &Config::new()).uri().path(),
+            "/tarballs/lustre-4.0.0.tar"
+        );
+        assert!(!plan.is_up_to_date());
+    }
+
+    #[test]
+    fn updates_changed_requirements() {
+        let registry = registry();
+        let roots = HashMap::from([("lustre".into(), range("~> 4.1"))]);
+        let locked = HashMap::from([("lustre".into(), Version::new(4, 0, 0))]);
+        let everything = |_: &Checksum| true;
+        let plan = ensure_dependencies(
+            &roots,
+            &locked,
+            |name| registry.get(name).cloned(),
+            &everything,
+        )
+        .unwrap();
+        assert_eq!(
+            plan.changes,
+            vec![
+                SelectionChange {
+                    package: "gleam_stdlib".into(),
+                    from: None,
+                    to: Some(Version::new(0, 36, 0)),
+                },
+                SelectionChange {
+                    package: "lustre".into(),
+                    from: Some(Version::new(4, 0, 0)),
+                    to: Some(Version::new(4, 1, 0)),
+                },
+            ]
+        );
+        assert!(plan.downloads.is_empty());
+    }
+
+    #[test]
+    fn progress_events() {
+        let registry = registry();
+        let roots = HashMap::from([("lustre".into(), range("== 4.1.0"))]);
+        let stdlib =
+            Checksum::from_bytes(&registry["gleam_stdlib"].releases[1].outer_checksum).unwrap();
+        let store = HashSet::from([stdlib]);
+        let mut events = vec![];
+        ensure_dependencies_with_progress(
+            &roots,
+            &HashMap::new(),
+            |name| registry.get(name).cloned(),
+            &store,
+            |event| events.push(event),
+        )
+        .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                EnsureEvent::Resolving,
+                EnsureEvent::Fetched {
+                    package: "lustre".into()
+                },
+                EnsureEvent::Fetched {
+                    package: "gleam_stdlib".into()
+                },
+                EnsureEvent::Skipped {
+                    package: "gleam_stdlib".into(),
+                    version: Version::new(0, 36, 0),
+                },
+            ]
+        );
+
+        events.clear();
+        let roots = HashMap::from([("missing".into(), range("~> 1.0"))]);
+        let _ = ensure_dependencies_with_progress(
+            &roots,
+            &HashMap::new(),
+            |_| None,
+            &store,
+            |event| events.push(event),
+        );
+        assert_eq!(
+            events,
+            vec![
+                EnsureEvent::Resolving,
+                EnsureEvent::Failed {
+                    package: "missing".into(),
+                    reason: "no package record could be fetched for missing".into(),
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn download_progress() {
+        let tarball = b"tarball".to_vec();
+        let download = Download {
+            release: ReleaseRef::new("lustre", Version::new(4, 1, 0)),
+            checksum: Checksum::of(&tarball),
+            size: None,
+        };
+        let response = |body: &[u8]| {
+            http::Response::builder()
+                .status(200)
+                .body(body.to_vec())
+                .unwrap()
+        };
+        let mut events = vec![];
+        let downloaded = download.handle_response(response(&tarball), |event| events.push(event));
+        assert_eq!(downloaded.unwrap(), tarball);
+        assert!(
+            download
+                .handle_response(response(b"not the tarball"), |event| events.push(event))
+                .is_err()
+        );
+        assert_eq!(
+            events,
+            vec![
+                EnsureEvent::Verified {
+                    package: "lustre".into(),
+                    version: Version::new(4, 1, 0),
+                },
+                EnsureEvent::Failed {
+                    package: "lustre".into(),
+                    reason: "the downloaded data did not have the expected checksum".into(),
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn byte_progress() {
+        let tarball = b"tarball".to_vec();
+        let download = |package: &str, size| Download {
+            release: ReleaseRef::new(package, Version::new(1, 0, 0)),
+            checksum: Checksum::of(&tarball),
+            size,
+        };
+        let mut plan = DependencyPlan {
+            resolved: vec![],
+            changes: vec![],
+            downloads: vec![download("a", None), download("b", None)],
+        };
+        let head = http::Response::builder()
+            .status(200)
+            .header("content-length", "7")
+            .body(vec![])
+            .unwrap();
+        assert_eq!(plan.downloads[0].size_response(head).unwrap(), Some(7));
+
+        let mut totals = plan.byte_progress();
+        assert_eq!(
+            totals,
+            ByteProgress {
+                expected: 7,
+                unknown_sizes: 1,
+                remaining: 2,
+                ..ByteProgress::default()
+            }
+        );
+        assert_eq!(totals.fraction(), None);
+
+        let response = |body: &[u8]| {
+            http::Response::builder()
+                .status(200)
+                .body(body.to_vec())
+                .unwrap()
+        };
+        let mut events = vec![];
+        let _ = plan.downloads[1].handle_response_with_totals(
+            response(b"corrupt"),
+            &mut totals,
+            |event| events.push(event),
+        );
+        assert_eq!((totals.downloaded, totals.verified), (7, 0));
+        plan.downloads[1]
+            .handle_response_with_totals(response(&tarball), &mut totals, |event| {
+                events.push(event)
+            })
+            .unwrap();
+        assert_eq!(
+            events.last(),
+            Some(&EnsureEvent::Bytes(ByteProgress {
+                expected: 14,
+                unknown_sizes: 0,
+                downloaded: 14,
+                verified: 7,
+                remaining: 1,
+            }))
+        );
+        assert_eq!(totals.fraction(), Some(0.5));
+        assert_eq!(
+            totals.eta(Duration::from_secs(4)),
+            Some(Duration::from_secs(2))
+        );
+
+        plan.downloads[0]
+            .handle_response_with_totals(response(&tarball), &mut totals, |_| ())
+            .unwrap();
+        assert_eq!(totals.fraction(), Some(1.0));
+        assert_eq!(totals.eta(Duration::from_secs(6)), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn errors() {
+        let registry = registry();
+        let store = HashSet::new();
+        let roots = HashMap::from([("missing".into(), range("~> 1.0"))]);
+        assert_eq!(
+            ensure_dependencies(
+                &roots,
+                &HashMap::new(),
+                |name| registry.get(name).cloned(),
+                &store
+            ),
+            Err(EnsureError::MissingPackage("missing".into()))
+        );
+
+        let roots = HashMap::from([("lustre".into(), range("~> 5.0"))]);
+        let result = ensure_dependencies(
+            &roots,
+            &HashMap::new(),
+            |name| registry.get(name).cloned(),
+            &store,
+        );
+        assert!(matches!(
+            result,
+            Err(EnsureError::Unresolvable { package, .. }) if package == "lustre"
+        ));
+    }
+}
@@ -0,0 +1,253 @@
+//! A parser for the subset of Erlang term syntax used by the files found in
+//! package tarballs and rebar3 projects, such as `metadata.config` and
+//! `rebar.lock`.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SyntaxError {
+    pub position: usize,
+    pub message: &'static str,
+}
+
+/// Parse a sequence of terms each followed by a `.`, as read by Erlang's
+/// `file:consult/1`.
+pub(crate) fn parse_terms(input: &str) -> Result<Vec<Spanned>, SyntaxError> {
+    let mut parser = Parser { input, position: 0 };
+    let mut terms = vec![];
+    while !parser.at_end() {
+        terms.push(parser.term()?);
+        parser.expect(".", "expected `.` after the term")?;
+    }
+    Ok(terms)
+}
+
+/// The subset of Erlang terms that appear in the files written by Hex and
+/// rebar3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Term {
+    Atom(String),
+    String(String),
+    Binary(String),
+    Integer(u32),
+    List(Vec<Spanned>),
+    Tuple(Vec<Spanned>),
+}
+
+/// A term along with the byte range of its source text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Spanned {
+    pub value: Term,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Spanned {
+    pub fn atom(&self) -> Option<&str> {
+        match &self.value {
+            Term::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    pub fn tuple(&self) -> Option<&[Spanned]> {
+        match &self.value {
+            Term::Tuple(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) trait Binaries {
+    /// The contents of a fixed number of terms, if they are all binaries.
+    fn binaries<const N: usize>(&self) -> Option<[&str; N]>;
+}
+
+impl Binaries for [Spanned] {
+    fn binaries<const N: usize>(&self) -> Option<[&str; N]> {
+        let terms: &[Spanned; N] = self.try_into().ok()?;
+        let mut binaries = [""; N];
+        for (binary, term) in binaries.iter_mut().zip(terms) {
+            let Term::Binary(value) = &term.value else {
+                return None;
+            };
+            *binary = value;
+        }
+        Some(binaries)
+    }
+}
+
+/// Write a binary as Hex does, marking it as UTF-8 if it contains any
+/// characters outside of ASCII.
+pub(crate) fn write_binary(out: &mut String, value: &str) {
+    out.push_str("<<\"");
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    if !value.is_ascii() {
+        out.push_str("/utf8");
+    }
+    out.push_str(">>");
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, message: &'static str) -> Result<T, SyntaxError> {
+        Err(SyntaxError {
+            position: self.position,
+            message,
+        })
+    }
+
+    fn rest(&self) -> &str {
+        &self.input[self.position..]
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.rest().is_empty()
+    }
+
+    /// Skip whitespace and `%` comments.
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            let whitespace = rest.len() - trimmed.len();
+            let comment = if trimmed.starts_with('%') {
+                trimmed.find('\n').unwrap_or(trimmed.len())
+            } else {
+                0
+            };
+            self.position += whitespace + comment;
+            if comment == 0 {
+                return;
+            }
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<(), SyntaxError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.error(message)
+        }
+    }
+
+    fn term(&mut self) -> Result<Spanned, SyntaxError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let rest = self.rest();
+        let value = if rest.starts_with("<<") {
+            self.position += 2;
+            self.skip_whitespace();
+            let value = if self.rest().starts_with('"') {
+                let value = self.string()?;
+                self.eat("/utf8");
+                value
+            } else {
+                String::new()
+            };
+            self.expect(">>", "expected `>>` at the end of the binary")?;
+            Term::Binary(value)
+        } else if rest.starts_with('{') {
+            self.position += 1;
+            Term::Tuple(self.elements("}")?)
+        } else if rest.starts_with('[') {
+            self.position += 1;
+            Term::List(self.elements("]")?)
+        } else if rest.starts_with('"') {
+            Term::String(self.string()?)
+        } else if rest.starts_with('\'') {
+            Term::Atom(self.quoted('\'')?)
+        } else if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let Ok(integer) = rest[..len].parse() else {
+                return self.error("integer is too large");
+            };
+            self.position += len;
+            Term::Integer(integer)
+        } else if rest.starts_with(|c: char| c.is_ascii_lowercase()) {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '@')))
+                .unwrap_or(rest.len());
+            let atom = rest[..len].to_string();
+            self.position += len;
+            Term::Atom(atom)
+        } else {
+            return self.error("expected a term");
+        };
+        Ok(Spanned {
+            value,
+            start,
+            end: self.position,
+        })
+    }
+
+    fn elements(&mut self, close: &str) -> Result<Vec<Spanned>, SyntaxError> {
+        let mut elements = vec![];
+        if self.eat(close) {
+            return Ok(elements);
+        }
+        loop {
+            elements.push(self.term()?);
+            if !self.eat(",") {
+                self.expect(close, "expected `,` or the end of the collection")?;
+                return Ok(elements);
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, SyntaxError> {
+        self.quoted('"')
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<String, SyntaxError> {
+        self.position += 1;
+        let mut string = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    self.position += i + 1;
+                    return Ok(string);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => string.push('\n'),
+                    Some((_, 'r')) => string.push('\r'),
+                    Some((_, 't')) => string.push('\t'),
+                    Some((_, 's')) => string.push(' '),
+                    Some((_, c)) => string.push(c),
+                    None => break,
+                },
+                c => string.push(c),
+            }
+        }
+        self.position = self.input.len();
+        self.error("unterminated string")
+    }
+}
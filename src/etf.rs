@@ -0,0 +1,287 @@
+//! A decoder for Erlang's external term format, as sent by the Hex API for
+//! the `application/vnd.hex+erlang` content type.
+//!
+//! Terms are converted to JSON values so that the same types can be parsed
+//! from either format: binaries and atoms become strings, except for `true`,
+//! `false` and `nil`, lists and tuples become arrays, and maps become objects.
+//! Binaries that are not UTF-8, such as raw checksums, are written as
+//! lowercase hexadecimal.
+
+use serde_json::{Map, Number, Value};
+
+const VERSION: u8 = 131;
+#[cfg(feature = "registry")]
+const COMPRESSED: u8 = 80;
+const NEW_FLOAT: u8 = 70;
+const SMALL_INTEGER: u8 = 97;
+const INTEGER: u8 = 98;
+const ATOM: u8 = 100;
+const SMALL_TUPLE: u8 = 104;
+const LARGE_TUPLE: u8 = 105;
+const NIL: u8 = 106;
+const STRING: u8 = 107;
+const LIST: u8 = 108;
+const BINARY: u8 = 109;
+const SMALL_BIG: u8 = 110;
+const LARGE_BIG: u8 = 111;
+const SMALL_ATOM: u8 = 115;
+const MAP: u8 = 116;
+const ATOM_UTF8: u8 = 118;
+const SMALL_ATOM_UTF8: u8 = 119;
+
+/// How deeply terms may be nested, so that a hostile response cannot
+/// overflow the stack.
+const MAX_DEPTH: usize = 256;
+
+/// Decode a term written by `erlang:term_to_binary/1`.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Value, &'static str> {
+    let mut decoder = Decoder { bytes };
+    if decoder.u8()? != VERSION {
+        return Err("unsupported external term format version");
+    }
+    #[cfg(feature = "registry")]
+    if decoder.bytes.first() == Some(&COMPRESSED) {
+        use std::io::Read;
+        decoder.bytes = &decoder.bytes[1..];
+        let size = decoder.u32()? as usize;
+        let mut inflated = Vec::with_capacity(
+            size.min(crate::hexpm_defaults::MAX_INFLATED_RESOURCE_SIZE as usize),
+        );
+        flate2::read::ZlibDecoder::new(decoder.bytes)
+            .take(crate::hexpm_defaults::MAX_INFLATED_RESOURCE_SIZE)
+            .read_to_end(&mut inflated)
+            .map_err(|_| "invalid compressed term")?;
+        if inflated.len() != size {
+            return Err("compressed term has the wrong size");
+        }
+        return finish(Decoder { bytes: &inflated });
+    }
+    finish(decoder)
+}
+
+fn finish(mut decoder: Decoder<'_>) -> Result<Value, &'static str> {
+    let value = decoder.term(0)?;
+    if !decoder.bytes.is_empty() {
+        return Err("unexpected data after the term");
+    }
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if len > self.bytes.len() {
+            return Err("the term ended early");
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn term(&mut self, depth: usize) -> Result<Value, &'static str> {
+        if depth > MAX_DEPTH {
+            return Err("the term is nested too deeply");
+        }
+        let depth = depth + 1;
+        match self.u8()? {
+            SMALL_INTEGER => Ok(self.u8()?.into()),
+            INTEGER => Ok((self.u32()? as i32).into()),
+            NEW_FLOAT => {
+                let bytes = self.take(8)?.try_into().expect("8 bytes");
+                Number::from_f64(f64::from_be_bytes(bytes))
+                    .map(Value::Number)
+                    .ok_or("the term holds a float that is not a number")
+            }
+            SMALL_BIG => {
+                let len = self.u8()?.into();
+                self.big(len)
+            }
+            LARGE_BIG => {
+                let len = self.u32()? as usize;
+                self.big(len)
+            }
+            ATOM | ATOM_UTF8 => {
+                let len = self.u16()?.into();
+                self.atom(len)
+            }
+            SMALL_ATOM | SMALL_ATOM_UTF8 => {
+                let len = self.u8()?.into();
+                self.atom(len)
+            }
+            BINARY => {
+                let len = self.u32()? as usize;
+                let bytes = self.take(len)?;
+                Ok(Value::String(match std::str::from_utf8(bytes) {
+                    Ok(string) => string.to_string(),
+                    Err(_) => base16::encode_lower(bytes),
+                }))
+            }
+            SMALL_TUPLE => {
+                let arity = self.u8()?.into();
+                self.elements(arity, depth)
+            }
+            LARGE_TUPLE => {
+                let arity = self.u32()? as usize;
+                self.elements(arity, depth)
+            }
+            NIL => Ok(Value::Array(vec![])),
+            STRING => {
+                let len = self.u16()?.into();
+                Ok(self.take(len)?.iter().copied().map(Value::from).collect())
+            }
+            LIST => {
+                let len = self.u32()? as usize;
+                let elements = self.elements(len, depth)?;
+                if self.u8()? != NIL {
+                    return Err("improper lists are not supported");
+                }
+                Ok(elements)
+            }
+            MAP => {
+                let arity = self.u32()? as usize;
+                let mut map = Map::new();
+                for _ in 0..arity {
+                    let key = match self.term(depth)? {
+                        Value::String(key) => key,
+                        Value::Number(key) => key.to_string(),
+                        _ => return Err("map keys must be binaries, atoms or integers"),
+                    };
+                    let value = self.term(depth)?;
+                    map.insert(key, value);
+                }
+                Ok(Value::Object(map))
+            }
+            _ => Err("the term holds an unsupported type"),
+        }
+    }
+
+    fn atom(&mut self, len: usize) -> Result<Value, &'static str> {
+        let bytes = self.take(len)?;
+        Ok(match bytes {
+            b"true" => Value::Bool(true),
+            b"false" => Value::Bool(false),
+            b"nil" | b"undefined" => Value::Null,
+            // Latin-1 atoms are a subset of UTF-8 for the characters used in
+            // atom names, and anything else is replaced.
+            _ => Value::String(String::from_utf8_lossy(bytes).into_owned()),
+        })
+    }
+
+    fn big(&mut self, len: usize) -> Result<Value, &'static str> {
+        let negative = self.u8()? != 0;
+        let digits = self.take(len)?;
+        if digits.iter().skip(8).any(|&byte| byte != 0) {
+            return Err("the term holds an integer too large to represent");
+        }
+        let magnitude = digits
+            .iter()
+            .take(8)
+            .rev()
+            .fold(0u64, |n, &byte| (n << 8) | u64::from(byte));
+        if !negative {
+            return Ok(magnitude.into());
+        }
+        0i64.checked_sub_unsigned(magnitude)
+            .map(Value::from)
+            .ok_or("the term holds an integer too large to represent")
+    }
+
+    fn elements(&mut self, count: usize, depth: usize) -> Result<Value, &'static str> {
+        // Every element takes at least one byte, which stops a hostile length
+        // from reserving a huge amount of memory.
+        if count > self.bytes.len() {
+            return Err("the term ended early");
+        }
+        let mut elements = Vec::with_capacity(count);
+        for _ in 0..count {
+            elements.push(self.term(depth)?);
+        }
+        Ok(Value::Array(elements))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn decodes_terms() {
+        // term_to_binary(#{<<"version">> => <<"1.0.0">>, <<"retired">> => nil,
+        //   <<"downloads">> => 300, <<"tags">> => [ok, {<<1,255>>, -2}]})
+        let bytes = [
+            131, 116, 0, 0, 0, 4, //
+            109, 0, 0, 0, 7, b'v', b'e', b'r', b's', b'i', b'o', b'n', //
+            109, 0, 0, 0, 5, b'1', b'.', b'0', b'.', b'0', //
+            109, 0, 0, 0, 7, b'r', b'e', b't', b'i', b'r', b'e', b'd', //
+            119, 3, b'n', b'i', b'l', //
+            109, 0, 0, 0, 9, b'd', b'o', b'w', b'n', b'l', b'o', b'a', b'd', b's', //
+            98, 0, 0, 1, 44, //
+            109, 0, 0, 0, 4, b't', b'a', b'g', b's', //
+            108, 0, 0, 0, 2, 119, 2, b'o', b'k', 104, 2, 109, 0, 0, 0, 2, 1, 255, 98, 255, 255,
+            255, 254, 106, //
+        ];
+        assert_eq!(
+            decode(&bytes),
+            Ok(json!({
+                "version": "1.0.0",
+                "retired": null,
+                "downloads": 300,
+                "tags": ["ok", ["01ff", -2]],
+            }))
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(decode(&[131, 97, 7]), Ok(json!(7)));
+        assert_eq!(
+            decode(&[131, 110, 8, 0, 255, 255, 255, 255, 255, 255, 255, 255]),
+            Ok(json!(u64::MAX))
+        );
+        assert_eq!(
+            decode(&[131, 110, 8, 1, 0, 0, 0, 0, 0, 0, 0, 128]),
+            Ok(json!(i64::MIN))
+        );
+        assert!(decode(&[131, 110, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]).is_err());
+        let mut float = vec![131, 70];
+        float.extend_from_slice(&1.5f64.to_be_bytes());
+        assert_eq!(decode(&float), Ok(json!(1.5)));
+    }
+
+    #[test]
+    fn rejects_invalid_terms() {
+        for bytes in [
+            &[][..],
+            &[130, 97, 1],
+            &[131, 109, 0, 0, 0, 5, b'a'],
+            &[131, 97, 1, 2],
+            &[131, 108, 0, 0, 0, 1, 97, 1, 97, 2],
+            &[131, 105, 255, 255, 255, 255],
+            &[131, 116, 0, 0, 0, 1, 106, 106],
+        ] {
+            assert!(decode(bytes).is_err(), "{bytes:?}");
+        }
+        let mut nested = vec![131];
+        nested.extend(std::iter::repeat_n([104, 1], 1000).flatten());
+        nested.push(106);
+        assert_eq!(decode(&nested), Err("the term is nested too deeply"));
+    }
+}
@@ -0,0 +1,542 @@
+//! A C ABI for the request builders and response parsers, so that build tools
+//! written in other languages can reuse this crate's implementation of the
+//! Hex protocol.
+//!
+//! This module is only built with the `ffi` feature. To build a shared
+//! library run:
+//!
+//! ```sh
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! The [`hexpm_command_request`] and [`hexpm_command_response`] functions
+//! accept any of the commands described in the [`commands`](crate::commands)
+//! module. The other functions are shortcuts for the most common commands.
+//!
+//! Request functions write a JSON description of the request to send to
+//! their `out` buffer:
+//!
+//! ```json
+//! {"method": "GET", "url": "https://repo.hex.pm/packages/gleam_stdlib",
+//!  "headers": [["accept", "application/json"]], "body": ""}
+//! ```
+//!
+//! The body is base16 encoded. Response functions take the status and body of
+//! the response and write the parsed result to their `out` buffer, as JSON
+//! unless documented otherwise.
+//!
+//! Every function returns [`HEXPM_OK`] on success. If the API returned an
+//! error [`HEXPM_API_ERROR`] is returned and the `out` buffer holds the error
+//! as a JSON object with `code` and `message` fields. If an argument is not
+//! valid [`HEXPM_INVALID_ARGUMENT`] is returned and the `out` buffer is left
+//! untouched. Package names must be valid Hex package names and API keys
+//! valid HTTP header values. [`HEXPM_INTERNAL_ERROR`] is returned, again
+//! leaving the `out` buffer untouched, should this library panic, so that no
+//! panic unwinds into the caller. Buffers written by this library must be
+//! released with [`hexpm_buffer_free`].
+
+use std::ffi::{CStr, c_char};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use crate::{
+    ApiError, Config, PackageName, ReleaseRef,
+    commands::{Invocation, RequestDescriptor},
+    version::Version,
+};
+
+pub const HEXPM_OK: i32 = 0;
+pub const HEXPM_API_ERROR: i32 = 1;
+pub const HEXPM_INVALID_ARGUMENT: i32 = 2;
+pub const HEXPM_INTERNAL_ERROR: i32 = 3;
+
+/// A buffer of bytes owned by this library.
+#[repr(C)]
+#[derive(Debug)]
+pub struct HexpmBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl HexpmBuffer {
+    fn new(data: Vec<u8>) -> Self {
+        let data = Box::into_raw(data.into_boxed_slice());
+        Self {
+            data: data.cast(),
+            len: data.len(),
+        }
+    }
+}
+
+/// Release a buffer written by this library.
+///
+/// # Safety
+///
+/// The buffer must have been written by this library and not already freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hexpm_buffer_free(buffer: HexpmBuffer) {
+    let _ = catch_unwind(AssertUnwindSafe(|| {
+        if !buffer.data.is_null() {
+            let data = std::ptr::slice_from_raw_parts_mut(buffer.data, buffer.len);
+            drop(unsafe { Box::from_raw(data) });
+        }
+    }));
+}
+
+/// Create a request for the registry record of a package.
+///
+/// `api_key` and `repository_base` may be null. `repository_base` defaults to
+/// `https://repo.hex.pm/`.
+///
+/// # Safety
+///
+/// The strings must be null or valid null terminated strings, and `out` must
+/// be a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hexpm_get_package_request(
+    name: *const c_char,
+    api_key: *const c_char,
+    repository_base: *const c_char,
+    out: *mut HexpmBuffer,
+) -> i32 {
+    guard(|| {
+        let arguments = unsafe {
+            (
+                package_name(name),
+                self::api_key(api_key),
+                config(repository_base),
+            )
+        };
+        let (Some(name), Ok(api_key), Ok(config)) = arguments else {
+            return HEXPM_INVALID_ARGUMENT;
+        };
+        let request = crate::get_package_request(name, api_key, &config);
+        unsafe { write_request(request, out) }
+    })
+}
+
+/// Parse a response to a [`hexpm_get_package_request`], verifying it with the
+/// repository's PEM encoded public key.
+///
+/// # Safety
+///
+/// The pointers must be valid for their lengths, and `out` must be a valid
+/// pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hexpm_get_package_response(
+    status: u16,
+    body: *const u8,
+    body_len: usize,
+    public_key: *const u8,
+    public_key_len: usize,
+    out: *mut HexpmBuffer,
+) -> i32 {
+    guard(|| {
+        let Some(response) = (unsafe { response(status, body, body_len) }) else {
+            return HEXPM_INVALID_ARGUMENT;
+        };
+        let public_key = unsafe { bytes(public_key, public_key_len) };
+        let result = crate::get_package_response(response, public_key);
+        unsafe { write_result(result.map(|package| json(&package)), out) }
+    })
+}
+
+/// Create a request for the names and versions of every package in the
+/// repository.
+///
+/// # Safety
+///
+/// As [`hexpm_get_package_request`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hexpm_get_repository_versions_request(
+    api_key: *const c_char,
+    repository_base: *const c_char,
+    out: *mut HexpmBuffer,
+) -> i32 {
+    guard(|| {
+        let arguments = unsafe { (self::api_key(api_key), config(repository_base)) };
+        let (Ok(api_key), Ok(config)) = arguments else {
+            return HEXPM_INVALID_ARGUMENT;
+        };
+        let request = crate::get_repository_versions_request(api_key, &config);
+        unsafe { write_request(request, out) }
+    })
+}
+
+/// Parse a response to a [`hexpm_get_repository_versions_request`]. The
+/// result is a JSON object of package names to lists of versions.
+///
+/// # Safety
+///
+/// As [`hexpm_get_package_response`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hexpm_get_repository_versions_response(
+    status: u16,
+    body: *const u8,
+    body_len: usize,
+    public_key: *const u8,
+    public_key_len: usize,
+    out: *mut HexpmBuffer,
+) -> i32 {
+    guard(|| {
+        let Some(response) = (unsafe { response(status, body, body_len) }) else {
+            return HEXPM_INVALID_ARGUMENT;
+        };
+        let public_key = unsafe { bytes(public_key, public_key_len) };
+        let result = crate::get_repository_versions_response(response, public_key);
+        unsafe { write_result(result.map(|versions| json(&versions)), out) }
+    })
+}
+
+/// Create a request for the tarball of a release.
+///
+/// # Safety
+///
+/// As [`hexpm_get_package_request`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hexpm_get_package_tarball_request(
+    name: *const c_char,
+    version: *const c_char,
+    api_key: *const c_char,
+    repository_base: *const c_char,
+    out: *mut HexpmBuffer,
+) -> i32 {
+    guard(|| {
+        let arguments = unsafe {
+            (
+                package_name(name),
+                string(version).and_then(|version| Version::parse(version).ok()),
+                self::api_key(api_key),
+                config(repository_base),
+            )
+        };
+        let (Some(name), Some(version), Ok(api_key), Ok(config)) = arguments else {
+            return HEXPM_INVALID_ARGUMENT;
+        };
+        let release = ReleaseRef::new(name, version);
+        let request = crate::get_package_tarball_request(&release, api_key, &config);
+        unsafe { write_request(request, out) }
+    })
+}
+
+/// Parse a response to a [`hexpm_get_package_tarball_request`], verifying it
+/// against the release's checksum. The result is the tarball itself rather
+/// than JSON.
+///
+/// # Safety
+///
+/// As [`hexpm_get_package_response`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hexpm_get_package_tarball_response(
+    status: u16,
+    body: *const u8,
+    body_len: usize,
+    checksum: *const u8,
+    checksum_len: usize,
+    out: *mut HexpmBuffer,
+) -> i32 {
+    guard(|| {
+        let Some(response) = (unsafe { response(status, body, body_len) }) else {
+            return HEXPM_INVALID_ARGUMENT;
+        };
+        let checksum = unsafe { bytes(checksum, checksum_len) };
+        let result = crate::get_package_tarball_response(response, checksum);
+        unsafe { write_result(result, out) }
+    })
+}
+
+/// Build the request for a JSON encoded command, as described in the
+/// [`commands`](crate::commands) module.
+///
+/// # Safety
+///
+/// The invocation must be a valid null terminated string, and `out` must be a
+/// valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hexpm_command_request(
+    invocation: *const c_char,
+    out: *mut HexpmBuffer,
+) -> i32 {
+    guard(|| {
+        let Some(invocation) = (unsafe { string(invocation) }) else {
+            return HEXPM_INVALID_ARGUMENT;
+        };
+        // Every error in building a request is in the invocation
+        match Invocation::from_json(invocation).and_then(|invocation| invocation.request()) {
+            Ok(descriptor) => unsafe { write_result(Ok(json(&descriptor)), out) },
+            Err(_) => HEXPM_INVALID_ARGUMENT,
+        }
+    })
+}
+
+/// Parse the JSON encoded response to the request for a JSON encoded command,
+/// as described in the [`commands`](crate::commands) module.
+///
+/// # Safety
+///
+/// The strings must be valid null terminated strings, and `out` must be a
+/// valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hexpm_command_response(
+    invocation: *const c_char,
+    response: *const c_char,
+    out: *mut HexpmBuffer,
+) -> i32 {
+    guard(|| {
+        let (Some(invocation), Some(response)) =
+            (unsafe { (string(invocation), string(response)) })
+        else {
+            return HEXPM_INVALID_ARGUMENT;
+        };
+        let result = Invocation::from_json(invocation).and_then(|invocation| {
+            let response = serde_json::from_str(response)?;
+            invocation.response(response)
+        });
+        unsafe { write_result(result.map(|value| json(&value)), out) }
+    })
+}
+
+/// Run the body of an exported function, turning a panic into
+/// [`HEXPM_INTERNAL_ERROR`] rather than unwinding into the caller.
+fn guard(body: impl FnOnce() -> i32) -> i32 {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or(HEXPM_INTERNAL_ERROR)
+}
+
+unsafe fn write_request(request: http::Request<Vec<u8>>, out: *mut HexpmBuffer) -> i32 {
+    let descriptor = RequestDescriptor::from(request);
+    unsafe { write_result(Ok(json(&descriptor)), out) }
+}
+
+unsafe fn write_result(result: Result<Vec<u8>, ApiError>, out: *mut HexpmBuffer) -> i32 {
+    if out.is_null() {
+        return HEXPM_INVALID_ARGUMENT;
+    }
+    let (status, data) = match result {
+        Ok(data) => (HEXPM_OK, data),
+        Err(error) => (HEXPM_API_ERROR, json(&error)),
+    };
+    unsafe { out.write(HexpmBuffer::new(data)) };
+    status
+}
+
+fn json(value: &impl serde::Serialize) -> Vec<u8> {
+    serde_json::to_vec(value).expect("JSON serialization")
+}
+
+unsafe fn response(status: u16, body: *const u8, len: usize) -> Option<http::Response<Vec<u8>>> {
+    if body.is_null() && len > 0 {
+        return None;
+    }
+    let body = unsafe { bytes(body, len) }.to_vec();
+    http::Response::builder().status(status).body(body).ok()
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if data.is_null() {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(data, len) }
+    }
+}
+
+unsafe fn string<'a>(string: *const c_char) -> Option<&'a str> {
+    if string.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(string) }.to_str().ok()
+}
+
+/// A string that may be null, but must be valid UTF-8 if it is not.
+unsafe fn optional_string<'a>(string: *const c_char) -> Result<Option<&'a str>, ()> {
+    if string.is_null() {
+        return Ok(None);
+    }
+    unsafe { self::string(string) }.map(Some).ok_or(())
+}
+
+/// A valid package name, which is safe to use in a URL.
+unsafe fn package_name<'a>(name: *const c_char) -> Option<&'a str> {
+    unsafe { string(name) }.filter(|name| PackageName::validate(name).is_ok())
+}
+
+/// An API key that may be null, but must be a valid header value if not.
+unsafe fn api_key<'a>(api_key: *const c_char) -> Result<Option<&'a str>, ()> {
+    let api_key = unsafe { optional_string(api_key) }?;
+    if let Some(key) = api_key {
+        http::HeaderValue::from_str(key).map_err(|_| ())?;
+    }
+    Ok(api_key)
+}
+
+/// The configuration with the given repository base, or the default one if it
+/// is null.
+unsafe fn config(repository_base: *const c_char) -> Result<Config, ()> {
+    let mut config = Config::new();
+    if let Some(base) = unsafe { optional_string(repository_base) }? {
+        config.repository_base = crate::base_url(base).ok_or(())?;
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take(buffer: HexpmBuffer) -> Vec<u8> {
+        let data = unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) }.to_vec();
+        unsafe { hexpm_buffer_free(buffer) };
+        data
+    }
+
+    fn empty() -> HexpmBuffer {
+        HexpmBuffer {
+            data: std::ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    #[test]
+    fn tarball_request() {
+        let mut out = empty();
+        let status = unsafe {
+            hexpm_get_package_tarball_request(
+                c"gleam_stdlib".as_ptr(),
+                c"0.14.0".as_ptr(),
+                std::ptr::null(),
+                c"https://mirror.example.com/hex/".as_ptr(),
+                &mut out,
+            )
+        };
+        assert_eq!(status, HEXPM_OK);
+        let description: serde_json::Value = serde_json::from_slice(&take(out)).unwrap();
+        assert_eq!(description["method"], "GET");
+        assert_eq!(
+            description["url"],
+            "https://mirror.example.com/hex/tarballs/gleam_stdlib-0.14.0.tar"
+        );
+        assert_eq!(description["body"], "");
+    }
+
+    #[test]
+    fn command_request() {
+        let mut out = empty();
+        let invocation = cr#"{"command": "get_package_release",
+            "params": {"package": "gleam_stdlib", "version": "0.14.0"}}"#;
+        let status = unsafe { hexpm_command_request(invocation.as_ptr(), &mut out) };
+        assert_eq!(status, HEXPM_OK);
+        let descriptor: RequestDescriptor = serde_json::from_slice(&take(out)).unwrap();
+        assert_eq!(
+            descriptor.url,
+            "https://hex.pm/api/packages/gleam_stdlib/releases/0.14.0"
+        );
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let mut out = empty();
+        let status = unsafe {
+            hexpm_get_package_tarball_request(
+                c"gleam_stdlib".as_ptr(),
+                c"not a version".as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                &mut out,
+            )
+        };
+        assert_eq!(status, HEXPM_INVALID_ARGUMENT);
+        assert!(out.data.is_null());
+
+        let status = unsafe {
+            hexpm_get_package_request(
+                c"a b".as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                &mut out,
+            )
+        };
+        assert_eq!(status, HEXPM_INVALID_ARGUMENT);
+        let status = unsafe {
+            hexpm_get_package_tarball_request(
+                c"gleam_stdlib".as_ptr(),
+                c"0.14.0".as_ptr(),
+                c"key\nwith a newline".as_ptr(),
+                std::ptr::null(),
+                &mut out,
+            )
+        };
+        assert_eq!(status, HEXPM_INVALID_ARGUMENT);
+        let status = unsafe {
+            hexpm_get_package_request(
+                c"gleam_stdlib".as_ptr(),
+                std::ptr::null(),
+                c"not a url".as_ptr(),
+                &mut out,
+            )
+        };
+        assert_eq!(status, HEXPM_INVALID_ARGUMENT);
+        let invocation =
+            cr#"{"command": "get_package", "params": {"name": "a b", "public_key": ""}}"#;
+        let status = unsafe { hexpm_command_request(invocation.as_ptr(), &mut out) };
+        assert_eq!(status, HEXPM_INVALID_ARGUMENT);
+        assert!(out.data.is_null());
+    }
+
+    #[test]
+    fn panics_do_not_unwind() {
+        assert_eq!(guard(|| panic!("a bug")), HEXPM_INTERNAL_ERROR);
+    }
+
+    #[test]
+    fn tarball_response() {
+        let tarball = std::include_bytes!("../test/gleam_stdlib-0.14.0.tar");
+        let checksum = ring::digest::digest(&ring::digest::SHA256, tarball);
+        let mut out = empty();
+        let status = unsafe {
+            hexpm_get_package_tarball_response(
+                200,
+                tarball.as_ptr(),
+                tarball.len(),
+                checksum.as_ref().as_ptr(),
+                checksum.as_ref().len(),
+                &mut out,
+            )
+        };
+        assert_eq!(status, HEXPM_OK);
+        assert_eq!(take(out), tarball);
+
+        let mut out = empty();
+        let status = unsafe {
+            hexpm_get_package_tarball_response(
+                404,
+                std::ptr::null(),
+                0,
+                std::ptr::null(),
+                0,
+                &mut out,
+            )
+        };
+        assert_eq!(status, HEXPM_API_ERROR);
+        let error: serde_json::Value = serde_json::from_slice(&take(out)).unwrap();
+        assert_eq!(error["code"], "not_found");
+    }
+
+    #[test]
+    fn package_response() {
+        let body = std::fs::read("test/package_exfmt").unwrap();
+        let public_key = std::fs::read("test/public_key").unwrap();
+        let mut out = empty();
+        let status = unsafe {
+            hexpm_get_package_response(
+                200,
+                body.as_ptr(),
+                body.len(),
+                public_key.as_ptr(),
+                public_key.len(),
+                &mut out,
+            )
+        };
+        assert_eq!(status, HEXPM_OK);
+        let package: serde_json::Value = serde_json::from_slice(&take(out)).unwrap();
+        assert_eq!(package["name"], "exfmt");
+        assert_eq!(package["releases"][0]["version"], "0.0.0");
+    }
+}
@@ -0,0 +1,74 @@
+//! Packages and releases shared by the test suites of the modules that work
+//! over registry data.
+
+use crate::{
+    Dependency, Package, Release, RetirementReason, RetirementStatus,
+    version::{Range, Version},
+};
+use std::collections::HashMap;
+
+/// A package in the `hexpm` repository with the given releases.
+pub(crate) fn package(name: &str, releases: impl IntoIterator<Item = ReleaseBuilder>) -> Package {
+    Package {
+        name: name.into(),
+        repository: "hexpm".to_string(),
+        releases: releases.into_iter().map(ReleaseBuilder::build).collect(),
+    }
+}
+
+/// A release with no requirements, no retirement and no checksum.
+pub(crate) fn release(version: &str) -> ReleaseBuilder {
+    ReleaseBuilder {
+        release: Release {
+            version: Version::parse(version).unwrap(),
+            requirements: HashMap::new(),
+            retirement_status: None,
+            outer_checksum: vec![],
+            meta: (),
+        },
+    }
+}
+
+pub(crate) struct ReleaseBuilder {
+    release: Release<()>,
+}
+
+impl ReleaseBuilder {
+    pub(crate) fn requires(self, name: &str, requirement: &str) -> Self {
+        self.dependency(name, requirement, None)
+    }
+
+    pub(crate) fn requires_app(self, name: &str, requirement: &str, app: &str) -> Self {
+        self.dependency(name, requirement, Some(app))
+    }
+
+    fn dependency(mut self, name: &str, requirement: &str, app: Option<&str>) -> Self {
+        let dependency = Dependency {
+            requirement: Range::new(requirement.to_string()).unwrap(),
+            optional: false,
+            app: app.map(str::to_string),
+            repository: None,
+        };
+        self.release.requirements.insert(name.into(), dependency);
+        self
+    }
+
+    pub(crate) fn retired(mut self, reason: RetirementReason, message: &str) -> Self {
+        self.release.retirement_status = Some(RetirementStatus {
+            reason,
+            message: message.to_string(),
+            unknown_reason: None,
+        });
+        self
+    }
+
+    /// Set the outer checksum to 32 copies of `byte`.
+    pub(crate) fn checksum(mut self, byte: u8) -> Self {
+        self.release.outer_checksum = vec![byte; 32];
+        self
+    }
+
+    pub(crate) fn build(self) -> Release<()> {
+        self.release
+    }
+}
@@ -0,0 +1,131 @@
+//! The settings specific to the public hex.pm deployment, gathered into a
+//! [`Profile`] so that self-hosted deployments can replace all of them at
+//! once with [`Config::with_profile`](crate::Config::with_profile).
+//!
+//! ```
+//! use hexpm::{Config, hexpm_defaults::Profile};
+//!
+//! let profile = Profile {
+//!     api_base: http::Uri::from_static("https://hex.acme.dev/api/"),
+//!     repository_base: http::Uri::from_static("https://repo.acme.dev/"),
+//!     website_base: http::Uri::from_static("https://hex.acme.dev/"),
+//!     public_key_fingerprint: None,
+//!     rate_limit: None,
+//!     ..Profile::hexpm()
+//! };
+//! let config = Config::with_profile(profile);
+//! assert_eq!(config.api_base, "https://hex.acme.dev/api/");
+//! ```
+
+use std::time::Duration;
+
+/// The base URL of the hex.pm API.
+pub const API_BASE: &str = "https://hex.pm/api/";
+
+/// The base URL of the hex.pm repository.
+pub const REPOSITORY_BASE: &str = "https://repo.hex.pm/";
+
+/// The base URL of the hex.pm website, where package pages are.
+pub const WEBSITE_BASE: &str = "https://hex.pm/";
+
+/// The fingerprint of the key hex.pm signs its registry resources with, in
+/// the format of [`public_key_fingerprint`](crate::public_key_fingerprint).
+pub const PUBLIC_KEY_FINGERPRINT: &str =
+    "SHA256:f137a9cdf0665cc355cfe330013760e55fbe0544072b6d4ef29169fd4141da73";
+
+/// The largest body in bytes read from a streamed download.
+pub const MAX_DOWNLOAD_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The largest size a registry resource may inflate to. The full hex.pm
+/// versions index is a few megabytes, so this leaves plenty of room while
+/// protecting against gzip bombs from untrusted repositories. This applies
+/// to every repository, as registry resources are decoded without a
+/// [`Config`](crate::Config).
+pub const MAX_INFLATED_RESOURCE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The most packages a versions index may list. hex.pm lists tens of
+/// thousands, so like [`MAX_INFLATED_RESOURCE_SIZE`] this only rejects
+/// resources no real repository would serve.
+pub const MAX_REGISTRY_PACKAGES: usize = 1_000_000;
+
+/// The most releases a package record, or versions a package in the
+/// versions index, may have.
+pub const MAX_REGISTRY_RELEASES: usize = 100_000;
+
+/// The most dependencies a release in a package record may have.
+pub const MAX_REGISTRY_DEPENDENCIES: usize = 10_000;
+
+/// The number of API requests hex.pm allows an unauthenticated client in each
+/// [`RATE_LIMIT_WINDOW`].
+pub const RATE_LIMIT: u32 = 100;
+
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// The settings of a Hex deployment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub api_base: http::Uri,
+    pub repository_base: http::Uri,
+    pub website_base: http::Uri,
+    /// The fingerprint of the key the repository signs its resources with,
+    /// for checking that the right key has been configured. `None` if it is
+    /// not known ahead of time.
+    pub public_key_fingerprint: Option<String>,
+    /// The largest body in bytes read from a streamed download.
+    pub max_download_size: u64,
+    /// The API rate limit to assume before the API has reported one, as the
+    /// number of requests allowed in each window. `None` if requests should
+    /// not be delayed until then.
+    pub rate_limit: Option<(u32, Duration)>,
+}
+
+impl Profile {
+    /// The settings of the public hex.pm deployment.
+    pub fn hexpm() -> Self {
+        Self {
+            api_base: http::Uri::from_static(API_BASE),
+            repository_base: http::Uri::from_static(REPOSITORY_BASE),
+            website_base: http::Uri::from_static(WEBSITE_BASE),
+            public_key_fingerprint: Some(PUBLIC_KEY_FINGERPRINT.to_string()),
+            max_download_size: MAX_DOWNLOAD_SIZE,
+            rate_limit: Some((RATE_LIMIT, RATE_LIMIT_WINDOW)),
+        }
+    }
+
+    /// Whether a PEM encoded public key has the expected fingerprint. Keys
+    /// are always accepted when no fingerprint is configured.
+    #[cfg(feature = "registry")]
+    pub fn is_expected_public_key(&self, pem_public_key: &[u8]) -> bool {
+        match &self.public_key_fingerprint {
+            None => true,
+            Some(expected) => crate::public_key_fingerprint(pem_public_key)
+                .is_ok_and(|fingerprint| &fingerprint == expected),
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::hexpm()
+    }
+}
+
+#[cfg(all(test, feature = "registry"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hexpm_public_key() {
+        let profile = Profile::hexpm();
+        assert!(profile.is_expected_public_key(std::include_bytes!("../test/public_key")));
+        assert!(
+            !profile
+                .is_expected_public_key(std::include_bytes!("../test/release_signing_public_key"))
+        );
+        let unchecked = Profile {
+            public_key_fingerprint: None,
+            ..profile
+        };
+        assert!(unchecked.is_expected_public_key(b"not a key"));
+    }
+}
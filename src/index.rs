@@ -0,0 +1,423 @@
+//! A compact binary encoding of a parsed versions index, so that tools which
+//! fetch the index can store it on disk and load it again without re-parsing
+//! every version string.
+//!
+//! The encoding starts with a magic string and a format number. Data written
+//! with a different format number is rejected with
+//! [`CacheError::UnsupportedFormat`], at which point the caller should fetch
+//! the index again.
+//!
+//! Long running processes can hold the index in a [`VersionsIndex`], which
+//! records when it was fetched and reports what changed when it is refreshed.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use bytes::{Buf, BufMut};
+use thiserror::Error;
+
+use crate::version::{Identifier, Version};
+
+const MAGIC: &[u8] = b"hexpm-versions";
+
+/// The current version of the encoding. This is to be incremented whenever the
+/// layout changes.
+pub const FORMAT: u64 = 1;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CacheError {
+    #[error("the data is not an encoded versions index")]
+    NotAnIndex,
+
+    #[error("the versions index was encoded with unsupported format {0}")]
+    UnsupportedFormat(u64),
+
+    #[error("the encoded versions index is truncated or corrupt")]
+    Corrupt,
+}
+
+/// Encode a parsed versions index, as returned by
+/// [`get_repository_versions_response`](crate::get_repository_versions_response).
+pub fn encode_versions(index: &HashMap<String, Vec<Version>>) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    buffer.put_slice(MAGIC);
+    encode_varint(FORMAT, &mut buffer);
+    encode_varint(index.len() as u64, &mut buffer);
+    for (name, versions) in index {
+        encode_str(name, &mut buffer);
+        encode_varint(versions.len() as u64, &mut buffer);
+        for version in versions {
+            encode_version(version, &mut buffer);
+        }
+    }
+    buffer
+}
+
+/// Decode a versions index previously encoded with [`encode_versions`].
+pub fn decode_versions(mut buffer: &[u8]) -> Result<HashMap<String, Vec<Version>>, CacheError> {
+    if !buffer.starts_with(MAGIC) {
+        return Err(CacheError::NotAnIndex);
+    }
+    buffer.advance(MAGIC.len());
+    let format = read_varint(&mut buffer)?;
+    if format != FORMAT {
+        return Err(CacheError::UnsupportedFormat(format));
+    }
+
+    let count = read_len(&mut buffer)?;
+    let mut index = HashMap::with_capacity(count);
+    for _ in 0..count {
+        let name = decode_str(&mut buffer)?;
+        let count = read_len(&mut buffer)?;
+        let versions = (0..count)
+            .map(|_| decode_version(&mut buffer))
+            .collect::<Result<Vec<_>, _>>()?;
+        index.insert(name, versions);
+    }
+
+    if buffer.has_remaining() {
+        return Err(CacheError::Corrupt);
+    }
+    Ok(index)
+}
+
+/// A versions index along with the time it was fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionsIndex {
+    versions: HashMap<String, Vec<Version>>,
+    fetched_at: SystemTime,
+}
+
+impl VersionsIndex {
+    pub fn new(versions: HashMap<String, Vec<Version>>, fetched_at: SystemTime) -> Self {
+        Self {
+            versions,
+            fetched_at,
+        }
+    }
+
+    pub fn fetched_at(&self) -> SystemTime {
+        self.fetched_at
+    }
+
+    pub fn versions(&self) -> &HashMap<String, Vec<Version>> {
+        &self.versions
+    }
+
+    /// The published versions of a package, if it is in the index.
+    pub fn package(&self, name: &str) -> Option<&[Version]> {
+        self.versions.get(name).map(Vec::as_slice)
+    }
+
+    pub fn into_versions(self) -> HashMap<String, Vec<Version>> {
+        self.versions
+    }
+
+    /// Returns `true` if the index was fetched longer ago than `ttl`.
+    pub fn is_stale(&self, ttl: Duration) -> bool {
+        self.is_stale_at(ttl, SystemTime::now())
+    }
+
+    /// Returns `true` if the index was fetched longer than `ttl` before `now`.
+    /// An index fetched after `now` is never stale.
+    pub fn is_stale_at(&self, ttl: Duration, now: SystemTime) -> bool {
+        now.duration_since(self.fetched_at)
+            .is_ok_and(|age| age > ttl)
+    }
+
+    /// Replace the contents of the index with a newly fetched index, returning
+    /// the changes so that callers can invalidate only what was affected.
+    pub fn update(
+        &mut self,
+        versions: HashMap<String, Vec<Version>>,
+        fetched_at: SystemTime,
+    ) -> IndexDiff {
+        let mut diff = IndexDiff::default();
+        for (name, new) in &versions {
+            match self.versions.get(name) {
+                None => diff.added_packages.push(name.clone()),
+                Some(old) => {
+                    let added: Vec<_> = new.iter().filter(|v| !old.contains(v)).cloned().collect();
+                    let removed: Vec<_> =
+                        old.iter().filter(|v| !new.contains(v)).cloned().collect();
+                    if !added.is_empty() {
+                        diff.added_versions.insert(name.clone(), added);
+                    }
+                    if !removed.is_empty() {
+                        diff.removed_versions.insert(name.clone(), removed);
+                    }
+                }
+            }
+        }
+        diff.removed_packages = self
+            .versions
+            .keys()
+            .filter(|name| !versions.contains_key(*name))
+            .cloned()
+            .collect();
+        diff.added_packages.sort();
+        diff.removed_packages.sort();
+
+        self.versions = versions;
+        self.fetched_at = fetched_at;
+        diff
+    }
+}
+
+/// The changes between two fetches of the versions index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexDiff {
+    /// Packages that were not in the previous index.
+    pub added_packages: Vec<String>,
+    /// Packages that are no longer in the index.
+    pub removed_packages: Vec<String>,
+    /// Newly published versions of packages that were already in the index.
+    pub added_versions: HashMap<String, Vec<Version>>,
+    /// Versions that are no longer in the index, such as reverted releases.
+    pub removed_versions: HashMap<String, Vec<Version>>,
+}
+
+impl IndexDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_packages.is_empty()
+            && self.removed_packages.is_empty()
+            && self.added_versions.is_empty()
+            && self.removed_versions.is_empty()
+    }
+
+    /// Returns `true` if the given package changed in any way.
+    pub fn affects(&self, name: &str) -> bool {
+        self.added_packages.iter().any(|n| n == name)
+            || self.removed_packages.iter().any(|n| n == name)
+            || self.added_versions.contains_key(name)
+            || self.removed_versions.contains_key(name)
+    }
+}
+
+fn encode_version(version: &Version, buffer: &mut Vec<u8>) {
+    encode_varint(version.major.into(), buffer);
+    encode_varint(version.minor.into(), buffer);
+    encode_varint(version.patch.into(), buffer);
+    encode_varint(version.pre.len() as u64, buffer);
+    for identifier in &version.pre {
+        match identifier {
+            Identifier::Numeric(n) => {
+                buffer.put_u8(0);
+                encode_varint((*n).into(), buffer);
+            }
+            Identifier::AlphaNumeric(s) => {
+                buffer.put_u8(1);
+                encode_str(s, buffer);
+            }
+        }
+    }
+    match &version.build {
+        None => buffer.put_u8(0),
+        Some(build) => {
+            buffer.put_u8(1);
+            encode_str(build, buffer);
+        }
+    }
+}
+
+fn decode_version(buffer: &mut &[u8]) -> Result<Version, CacheError> {
+    let major = read_u32(buffer)?;
+    let minor = read_u32(buffer)?;
+    let patch = read_u32(buffer)?;
+    let count = read_len(buffer)?;
+    let pre = (0..count)
+        .map(|_| match read_u8(buffer)? {
+            0 => Ok(Identifier::Numeric(read_u32(buffer)?)),
+            1 => Ok(Identifier::AlphaNumeric(decode_str(buffer)?)),
+            _ => Err(CacheError::Corrupt),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let build = match read_u8(buffer)? {
+        0 => None,
+        1 => Some(decode_str(buffer)?),
+        _ => return Err(CacheError::Corrupt),
+    };
+    Ok(Version {
+        major,
+        minor,
+        patch,
+        pre,
+        build,
+    })
+}
+
+fn encode_str(s: &str, buffer: &mut Vec<u8>) {
+    encode_varint(s.len() as u64, buffer);
+    buffer.put_slice(s.as_bytes());
+}
+
+fn decode_str(buffer: &mut &[u8]) -> Result<String, CacheError> {
+    let len = read_len(buffer)?;
+    let s = std::str::from_utf8(&buffer[..len]).map_err(|_| CacheError::Corrupt)?;
+    let s = s.to_string();
+    buffer.advance(len);
+    Ok(s)
+}
+
+/// Write a number as a protobuf style varint: seven bits at a time, least
+/// significant first, with the high bit set on all but the last byte.
+fn encode_varint(mut value: u64, buffer: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buffer.put_u8(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.put_u8(value as u8);
+}
+
+fn read_varint(buffer: &mut &[u8]) -> Result<u64, CacheError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(buffer)?;
+        // The tenth byte holds the last bit of a 64 bit number.
+        if shift == 63 && byte > 1 {
+            return Err(CacheError::Corrupt);
+        }
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(CacheError::Corrupt)
+}
+
+fn read_u32(buffer: &mut &[u8]) -> Result<u32, CacheError> {
+    u32::try_from(read_varint(buffer)?).map_err(|_| CacheError::Corrupt)
+}
+
+/// Read a length, which can never be larger than the remaining data as every
+/// element takes at least one byte.
+fn read_len(buffer: &mut &[u8]) -> Result<usize, CacheError> {
+    match usize::try_from(read_varint(buffer)?) {
+        Ok(len) if len <= buffer.remaining() => Ok(len),
+        _ => Err(CacheError::Corrupt),
+    }
+}
+
+fn read_u8(buffer: &mut &[u8]) -> Result<u8, CacheError> {
+    if buffer.has_remaining() {
+        Ok(buffer.get_u8())
+    } else {
+        Err(CacheError::Corrupt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> HashMap<String, Vec<Version>> {
+        [
+            (
+                "gleam_stdlib".to_string(),
+                vec![
+                    Version::parse("0.1.0").unwrap(),
+                    Version::parse("1.0.0-rc.1+build.5").unwrap(),
+                    Version::parse("1.0.0").unwrap(),
+                ],
+            ),
+            ("empty".to_string(), vec![]),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let index = index();
+        assert_eq!(decode_versions(&encode_versions(&index)), Ok(index));
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn round_trip_test_index() {
+        let response = http::Response::builder()
+            .status(200)
+            .body(std::include_bytes!("../test/versions").to_vec())
+            .unwrap();
+        let index = crate::get_repository_versions_response(
+            response,
+            std::include_bytes!("../test/public_key"),
+        )
+        .unwrap();
+        assert_eq!(decode_versions(&encode_versions(&index)), Ok(index));
+    }
+
+    #[test]
+    fn not_an_index() {
+        assert_eq!(decode_versions(b"hello"), Err(CacheError::NotAnIndex));
+    }
+
+    #[test]
+    fn unsupported_format() {
+        let mut encoded = encode_versions(&index());
+        encoded[MAGIC.len()] = 99;
+        assert_eq!(
+            decode_versions(&encoded),
+            Err(CacheError::UnsupportedFormat(99))
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let encoded = encode_versions(&index());
+        for len in MAGIC.len() + 1..encoded.len() {
+            assert_eq!(
+                decode_versions(&encoded[..len]),
+                Err(CacheError::Corrupt),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn staleness() {
+        let fetched_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let index = VersionsIndex::new(index(), fetched_at);
+        let ttl = Duration::from_secs(60);
+        assert!(!index.is_stale_at(ttl, fetched_at));
+        assert!(!index.is_stale_at(ttl, fetched_at + ttl));
+        assert!(index.is_stale_at(ttl, fetched_at + ttl + Duration::from_secs(1)));
+        assert!(!index.is_stale_at(ttl, fetched_at - ttl - ttl));
+    }
+
+    #[test]
+    fn update() {
+        let v = |version| Version::parse(version).unwrap();
+        let mut index = VersionsIndex::new(index(), SystemTime::UNIX_EPOCH);
+        let updated: HashMap<_, _> = [
+            (
+                "gleam_stdlib".to_string(),
+                vec![v("0.1.0"), v("1.0.0"), v("1.1.0")],
+            ),
+            ("gleam_json".to_string(), vec![v("0.1.0")]),
+        ]
+        .into_iter()
+        .collect();
+        let fetched_at = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+
+        let diff = index.update(updated.clone(), fetched_at);
+        assert_eq!(
+            diff,
+            IndexDiff {
+                added_packages: vec!["gleam_json".to_string()],
+                removed_packages: vec!["empty".to_string()],
+                added_versions: [("gleam_stdlib".to_string(), vec![v("1.1.0")])].into(),
+                removed_versions: [("gleam_stdlib".to_string(), vec![v("1.0.0-rc.1+build.5")])]
+                    .into(),
+            }
+        );
+        assert!(diff.affects("gleam_stdlib"));
+        assert!(!diff.affects("gleam_otp"));
+        assert_eq!(index.fetched_at(), fetched_at);
+        assert_eq!(index.versions(), &updated);
+
+        assert!(index.update(updated, fetched_at).is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+//! Checking the checksums recorded in a lock file against the registry.
+//!
+//! A lock file records the checksum of every locked release. If the registry
+//! later reports a different checksum the release has been replaced since it
+//! was locked, or either the lock file or the registry has been tampered with.
+//! Either way the lock data is not to be trusted until someone has looked
+//! into it.
+
+use std::collections::HashMap;
+
+use crate::Package;
+use crate::mix_lock::{LockEntry, MixLock};
+use crate::rebar_lock::{RebarLock, RebarSource};
+use crate::version::Version;
+
+/// The checksum recorded for a release in a lock file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedRelease<'a> {
+    pub package: &'a str,
+    pub version: &'a Version,
+    /// The checksum of the release tarball, if the lock file records one.
+    pub outer_checksum: Option<&'a [u8]>,
+}
+
+/// A problem found when checking a lock file against the registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    /// The registry has a different checksum for the release than the lock
+    /// file. The release may have been replaced or tampered with.
+    ChecksumMismatch {
+        package: String,
+        version: Version,
+        locked: Vec<u8>,
+        registry: Vec<u8>,
+    },
+    /// The release is no longer in the registry, for example because it was
+    /// reverted.
+    MissingRelease { package: String, version: Version },
+    /// The lock file has no checksum for the release, so it cannot be
+    /// verified. Lock files written by old versions of Hex and rebar3 lack
+    /// them.
+    NoChecksum { package: String, version: Version },
+}
+
+/// The outcome of checking a lock file against the registry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// The number of locked releases whose checksums match the registry.
+    pub verified: usize,
+    /// Locked releases of packages that were not given, and so were not
+    /// checked.
+    pub unchecked: Vec<String>,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl IntegrityReport {
+    /// Returns `true` if every locked release that was checked matches the
+    /// registry.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Check locked releases against freshly fetched package records.
+pub fn check_locked_releases<'a>(
+    locked: impl IntoIterator<Item = LockedRelease<'a>>,
+    packages: &[Package],
+) -> IntegrityReport {
+    let packages: HashMap<&str, &Package> = packages
+        .iter()
+        .map(|package| (package.name.as_str(), package))
+        .collect();
+    let mut report = IntegrityReport::default();
+
+    for locked in locked {
+        let Some(package) = packages.get(locked.package) else {
+            report.unchecked.push(locked.package.to_string());
+            continue;
+        };
+        let Some(release) = package
+            .releases
+            .iter()
+            .find(|release| &release.version == locked.version)
+        else {
+            report.issues.push(IntegrityIssue::MissingRelease {
+                package: locked.package.to_string(),
+                version: locked.version.clone(),
+            });
+            continue;
+        };
+        match locked.outer_checksum {
+            None => report.issues.push(IntegrityIssue::NoChecksum {
+                package: locked.package.to_string(),
+                version: locked.version.clone(),
+            }),
+            Some(checksum) if checksum == release.outer_checksum => report.verified += 1,
+            Some(checksum) => report.issues.push(IntegrityIssue::ChecksumMismatch {
+                package: locked.package.to_string(),
+                version: locked.version.clone(),
+                locked: checksum.to_vec(),
+                registry: release.outer_checksum.clone(),
+            }),
+        }
+    }
+    report
+}
+
+impl MixLock {
+    /// The Hex releases locked by this file.
+    pub fn locked_releases(&self) -> impl Iterator<Item = LockedRelease<'_>> {
+        self.packages.values().filter_map(|entry| match entry {
+            LockEntry::Hex(lock) => Some(LockedRelease {
+                package: &lock.name,
+                version: &lock.version,
+                outer_checksum: lock.outer_checksum.as_deref(),
+            }),
+            LockEntry::Other(_) => None,
+        })
+    }
+
+    /// Check the releases locked by this file against freshly fetched
+    /// package records.
+    pub fn integrity_report(&self, packages: &[Package]) -> IntegrityReport {
+        check_locked_releases(self.locked_releases(), packages)
+    }
+}
+
+impl RebarLock {
+    /// The Hex releases locked by this file.
+    pub fn locked_releases(&self) -> impl Iterator<Item = LockedRelease<'_>> {
+        self.dependencies
+            .iter()
+            .filter_map(|dependency| match &dependency.source {
+                RebarSource::Package {
+                    name,
+                    version,
+                    outer_checksum,
+                    ..
+                } => Some(LockedRelease {
+                    package: name,
+                    version,
+                    outer_checksum: outer_checksum.as_deref(),
+                }),
+                RebarSource::Other(_) => None,
+            })
+    }
+
+    /// Check the releases locked by this file against freshly fetched
+    /// package records.
+    pub fn integrity_report(&self, packages: &[Package]) -> IntegrityReport {
+        check_locked_releases(self.locked_releases(), packages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{package, release};
+
+    fn lock(name: &str, version: &str, checksum: Option<u8>) -> String {
+        let outer = checksum
+            .map(|c| format!(", \"hexpm\", \"{}\"", base16::encode_lower(&[c; 32])))
+            .unwrap_or_default();
+        format!(
+            "  \"{name}\": {{:hex, :{name}, \"{version}\", \"{}\", [:mix], []{outer}}},\n",
+            base16::encode_lower(&[0; 32])
+        )
+    }
+
+    #[test]
+    fn mix_lock_report() {
+        let lock = MixLock::parse(&format!(
+            "%{{\n{}{}{}{}{}}}\n",
+            lock("verified", "1.0.0", Some(1)),
+            lock("replaced", "1.0.0", Some(1)),
+            lock("reverted", "2.0.0", Some(1)),
+            lock("old", "1.0.0", None),
+            lock("unfetched", "1.0.0", Some(1)),
+        ))
+        .unwrap();
+        let packages = [
+            package("verified", [release("1.0.0").checksum(1)]),
+            package("replaced", [release("1.0.0").checksum(2)]),
+            package("reverted", [release("1.0.0").checksum(1)]),
+            package("old", [release("1.0.0").checksum(1)]),
+        ];
+
+        let report = lock.integrity_report(&packages);
+        assert!(!report.is_ok());
+        assert_eq!(report.verified, 1);
+        assert_eq!(report.unchecked, vec!["unfetched".to_string()]);
+        assert_eq!(
+            report.issues,
+            vec![
+                IntegrityIssue::NoChecksum {
+                    package: "old".to_string(),
+                    version: Version::new(1, 0, 0),
+                },
+                IntegrityIssue::ChecksumMismatch {
+                    package: "replaced".to_string(),
+                    version: Version::new(1, 0, 0),
+                    locked: vec![1; 32],
+                    registry: vec![2; 32],
+                },
+                IntegrityIssue::MissingRelease {
+                    package: "reverted".to_string(),
+                    version: Version::new(2, 0, 0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rebar_lock_report() {
+        let lock = RebarLock::parse(&format!(
+            "{{\"1.2.0\",\n[{{<<\"my_app\">>,{{pkg,<<\"their_package\">>,<<\"1.0.0\">>}},0}}]}}.\n\
+             [{{pkg_hash_ext,[{{<<\"my_app\">>, <<\"{}\">>}}]}}].\n",
+            base16::encode_upper(&[7; 32])
+        ))
+        .unwrap();
+        let report =
+            lock.integrity_report(&[package("their_package", [release("1.0.0").checksum(7)])]);
+        assert!(report.is_ok());
+        assert_eq!(report.verified, 1);
+    }
+}
@@ -1,28 +1,90 @@
-mod proto;
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+mod erlang;
+#[cfg(feature = "api")]
+mod etf;
+#[cfg(feature = "registry")]
+pub mod proto;
 
 #[cfg(test)]
+mod fixtures;
+#[cfg(all(test, feature = "api"))]
 mod tests;
 
+pub mod accumulator;
+pub mod aliases;
+pub mod announce;
+pub mod bulk;
+pub mod cache;
+#[cfg(feature = "api")]
+pub mod capabilities;
+pub mod checksum;
+pub mod cleanup;
+#[cfg(feature = "api")]
+pub mod commands;
+#[cfg(feature = "registry")]
+pub mod docs;
+pub mod ensure;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod hexpm_defaults;
+pub mod index;
+pub mod integrity;
+pub mod links;
+pub mod mirror;
+pub mod mix_lock;
+pub mod osv;
+pub mod ownership;
+#[cfg(feature = "api")]
+pub mod plan;
+pub mod problem;
+pub mod project;
+pub mod rebar_lock;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(feature = "api")]
+pub mod republish;
+pub mod resolution;
+#[cfg(feature = "api")]
+pub mod retirement;
+pub mod schedule;
+pub mod search;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(all(feature = "registry", feature = "api"))]
+pub mod snapshot;
+pub mod snippets;
+pub mod tarball;
+#[cfg(all(feature = "registry", any(test, feature = "test-keys")))]
+pub mod test_keys;
+pub mod upgrade;
 pub mod version;
-
-use crate::proto::{signed::Signed, versions::Versions};
-use bytes::buf::Buf;
-use flate2::read::GzDecoder;
+#[cfg(feature = "registry")]
+pub mod watch;
+
+#[cfg(feature = "registry")]
+use crate::proto::versions::Versions;
+#[cfg(feature = "registry")]
+use bytes::{Bytes, buf::Buf};
+#[cfg(feature = "registry")]
+use flate2::bufread::GzDecoder;
 use http::{Method, StatusCode};
 use lazy_static::lazy_static;
-use prost::Message;
 use regex::Regex;
+#[cfg(feature = "registry")]
 use ring::digest::{Context, SHA256};
 use serde::Deserialize;
-use serde_json::json;
+#[cfg(feature = "registry")]
+use std::io::{BufRead, BufReader};
 use std::{
-    collections::HashMap,
-    convert::{TryFrom, TryInto},
+    collections::{BTreeMap, HashMap},
     fmt::Display,
-    io::{BufReader, Read},
+    io::Read,
+    time::{Duration, SystemTime},
 };
 use thiserror::Error;
-use version::{Range, Version};
+use version::{Channel, Range, Version};
+#[cfg(feature = "registry")]
 use x509_parser::prelude::FromDer;
 
 #[derive(Debug, Clone)]
@@ -31,23 +93,310 @@ pub struct Config {
     pub api_base: http::Uri,
     /// Defaults to https://repo.hex.pm/
     pub repository_base: http::Uri,
+    /// Whether to add an explicit `host` header to every request. Most HTTP
+    /// clients derive this from the request URI, but minimal clients that
+    /// write the request as-is need it set. Defaults to `false`.
+    pub host_header: bool,
+    /// The largest body in bytes read from a streamed download, such as with
+    /// [`get_package_tarball_reader_response`]. Defaults to 256 MiB.
+    pub max_download_size: u64,
+    /// Headers added to every request, such as those needed by a proxy or
+    /// for tracing. They replace any header of the same name the request
+    /// would otherwise have, including `accept` and `user-agent`. Defaults to
+    /// none.
+    pub headers: http::HeaderMap,
+    /// The format to ask for from endpoints that support more than JSON,
+    /// such as [`get_package_release_request`]. Defaults to JSON.
+    pub api_format: ApiFormat,
+    /// The deployment this configuration was made for, whose base URLs and
+    /// download size limit are those of the fields above. See
+    /// [`Config::profile`].
+    profile: hexpm_defaults::Profile,
+    /// The rate limits reported by the API, shared by every clone of this
+    /// configuration so that threads sending requests at the same time can
+    /// coordinate their pacing. See [`RateLimitState`]. Defaults to a new,
+    /// empty state.
+    ///
+    /// [`RateLimitState`]: schedule::RateLimitState
+    pub rate_limits: std::sync::Arc<schedule::RateLimitState>,
+    /// Timeouts to suggest instead of the defaults of
+    /// [`RequestKind::default_timeout`]. Defaults to none.
+    pub timeouts: HashMap<RequestKind, Duration>,
+    /// Rewrites the URI of every repository request, such as the versions
+    /// index, package records, tarballs and documentation, for mirrors that
+    /// need signed URLs. Defaults to none.
+    pub url_signer: Option<UrlSigner>,
+    /// How API keys are given in the `authorization` header. Defaults to
+    /// [`AuthStyle::ApiKey`], as Hex expects.
+    pub auth_style: AuthStyle,
+}
+
+/// How an API key is given in the `authorization` header of a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthStyle {
+    /// The key alone, as Hex and its organization repositories expect.
+    #[default]
+    ApiKey,
+    /// The key as a bearer token, as some private registries and mirrors
+    /// behind authenticating proxies expect.
+    Bearer,
+}
+
+/// Rewrites the URIs of repository requests before they are sent, such as to
+/// add a signature and expiry to the query string for a mirror behind an
+/// authenticating CDN.
+///
+/// ```
+/// use hexpm::{Config, UrlSigner};
+///
+/// let mut config = Config::new();
+/// config.url_signer = Some(UrlSigner::new(|uri: &http::Uri| {
+///     format!("{uri}?expires=1700000000&signature=abc").parse().unwrap()
+/// }));
+/// let request = hexpm::get_repository_versions_request(None, &config);
+/// assert_eq!(request.uri().query(), Some("expires=1700000000&signature=abc"));
+/// ```
+#[derive(Clone)]
+pub struct UrlSigner(std::sync::Arc<dyn Fn(&http::Uri) -> http::Uri + Send + Sync>);
+
+impl UrlSigner {
+    pub fn new(sign: impl Fn(&http::Uri) -> http::Uri + Send + Sync + 'static) -> Self {
+        Self(std::sync::Arc::new(sign))
+    }
+
+    pub fn sign(&self, uri: &http::Uri) -> http::Uri {
+        (self.0)(uri)
+    }
+}
+
+impl std::fmt::Debug for UrlSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("UrlSigner(..)")
+    }
+}
+
+/// The environment variables read by [`Config::from_env`], and the settings
+/// from them that are not part of a [`Config`] for the caller to apply.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct EnvReport {
+    /// The variables that were set, in the order they were read.
+    pub used: Vec<&'static str>,
+    /// The API key from `HEX_API_KEY`, to give to requests that need one.
+    pub api_key: Option<String>,
+    /// Whether `HEX_UNSAFE_REGISTRY` asks for registry resources to be used
+    /// without checking their signatures.
+    pub unsafe_registry: bool,
+    /// Whether `HEX_NO_VERIFY_REPO_ORIGIN` asks for registry resources to be
+    /// used without checking that they name the repository they were fetched
+    /// from.
+    pub no_verify_repo_origin: bool,
+}
+
+impl std::fmt::Debug for EnvReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnvReport")
+            .field("used", &self.used)
+            .field("api_key", &self.api_key.as_ref().map(|_| "[redacted]"))
+            .field("unsafe_registry", &self.unsafe_registry)
+            .field("no_verify_repo_origin", &self.no_verify_repo_origin)
+            .finish()
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EnvError {
+    #[error("{variable} is not a valid URL: {value:?}")]
+    InvalidUrl {
+        variable: &'static str,
+        value: String,
+    },
+}
+
+/// A base URL from an environment variable, which the Hex client accepts with
+/// or without a trailing slash.
+fn env_base_url(variable: &'static str, value: &str) -> Result<http::Uri, EnvError> {
+    base_url(value).ok_or_else(|| EnvError::InvalidUrl {
+        variable,
+        value: value.to_string(),
+    })
+}
+
+/// An HTTP or HTTPS base URL, given with or without a trailing slash. The
+/// slash is added as request paths are appended to it.
+pub(crate) fn base_url(value: &str) -> Option<http::Uri> {
+    let trimmed = value.trim();
+    let url = if trimmed.ends_with('/') {
+        trimmed.to_string()
+    } else {
+        format!("{trimmed}/")
+    };
+    let uri: http::Uri = url.parse().ok()?;
+    match uri.scheme_str() {
+        Some("http" | "https") if uri.authority().is_some() => Some(uri),
+        _ => None,
+    }
+}
+
+/// Whether a flag variable is set, following the Hex client in treating
+/// anything other than an empty value, `0` or `false` as true.
+fn env_flag(value: &str) -> bool {
+    !matches!(value.trim(), "" | "0" | "false")
+}
+
+/// The broad kind of a request, which decides how long a transport should
+/// wait for it to complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    /// A `HEAD` request, which has no body to transfer.
+    Check,
+    /// An API request or registry resource, which is small.
+    Metadata,
+    /// A release or documentation tarball download.
+    Download,
+    /// A release or documentation tarball upload.
+    Upload,
+}
+
+impl RequestKind {
+    pub fn default_timeout(&self) -> Duration {
+        Duration::from_secs(match self {
+            RequestKind::Check => 10,
+            RequestKind::Metadata => 30,
+            RequestKind::Download => 5 * 60,
+            RequestKind::Upload => 10 * 60,
+        })
+    }
+}
+
+/// How long to wait for a request to complete, attached to every request
+/// this crate creates as an [extension](http::Extensions), so that
+/// transports can apply timeouts without knowing about each endpoint.
+///
+/// ```
+/// use hexpm::{Config, ReleaseRef, RequestKind, SuggestedTimeout, version::Version};
+/// use std::time::Duration;
+///
+/// let mut config = Config::new();
+/// config.timeouts.insert(RequestKind::Download, Duration::from_secs(900));
+/// let release = ReleaseRef::new("lustre", Version::new(4, 1, 0));
+/// let request = hexpm::get_package_tarball_request(&release, None, &config);
+/// let timeout = request.extensions().get::<SuggestedTimeout>().unwrap();
+/// assert_eq!(timeout.kind, RequestKind::Download);
+/// assert_eq!(timeout.timeout, Duration::from_secs(900));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuggestedTimeout {
+    pub kind: RequestKind,
+    pub timeout: Duration,
+}
+
+/// The format of API response bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApiFormat {
+    #[default]
+    Json,
+    /// Erlang's external term format, which is more compact than JSON.
+    Erlang,
+}
+
+impl ApiFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ApiFormat::Json => "application/json",
+            ApiFormat::Erlang => "application/vnd.hex+erlang",
+        }
+    }
 }
 
 impl Config {
     pub fn new() -> Self {
+        Self::with_profile(hexpm_defaults::Profile::hexpm())
+    }
+
+    /// Configure requests for a Hex deployment other than hex.pm.
+    pub fn with_profile(profile: hexpm_defaults::Profile) -> Self {
         Self {
-            api_base: http::Uri::from_static("https://hex.pm/api/"),
-            repository_base: http::Uri::from_static("https://repo.hex.pm/"),
+            api_base: profile.api_base.clone(),
+            repository_base: profile.repository_base.clone(),
+            host_header: false,
+            max_download_size: profile.max_download_size,
+            headers: http::HeaderMap::new(),
+            api_format: ApiFormat::Json,
+            profile,
+            rate_limits: Default::default(),
+            timeouts: HashMap::new(),
+            url_signer: None,
+            auth_style: AuthStyle::ApiKey,
+        }
+    }
+
+    /// The settings of the deployment this configuration is for, such as for
+    /// [`Scheduler::with_profile`]. Defaults to [`Profile::hexpm`].
+    ///
+    /// The base URLs and download size limit are always those of this
+    /// configuration, so they follow changes such as those made by
+    /// [`from_env`](Self::from_env). The public key fingerprint is only kept
+    /// while the repository is the one the profile was made for, as another
+    /// repository signs its resources with its own key.
+    ///
+    /// [`Scheduler::with_profile`]: crate::schedule::Scheduler::with_profile
+    /// [`Profile::hexpm`]: hexpm_defaults::Profile::hexpm
+    pub fn profile(&self) -> hexpm_defaults::Profile {
+        let public_key_fingerprint = if self.repository_base == self.profile.repository_base {
+            self.profile.public_key_fingerprint.clone()
+        } else {
+            None
+        };
+        hexpm_defaults::Profile {
+            api_base: self.api_base.clone(),
+            repository_base: self.repository_base.clone(),
+            max_download_size: self.max_download_size,
+            public_key_fingerprint,
+            ..self.profile.clone()
+        }
+    }
+
+    /// Configure requests from the environment variables used by the Hex
+    /// client for Elixir, such as `HEX_API_URL`, so that tools behave the
+    /// same as `mix` in CI. See [`EnvReport`] for the variables read.
+    pub fn from_env() -> Result<(Self, EnvReport), EnvError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// As [`from_env`](Self::from_env), reading variables with `var`.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<(Self, EnvReport), EnvError> {
+        let mut config = Self::new();
+        let mut report = EnvReport::default();
+        let mut lookup = |name: &'static str| {
+            let value = var(name)?;
+            report.used.push(name);
+            Some(value)
+        };
+        if let Some(url) = lookup("HEX_API_URL") {
+            config.api_base = env_base_url("HEX_API_URL", &url)?;
+        }
+        if let Some(url) = lookup("HEX_REPO_URL") {
+            config.repository_base = env_base_url("HEX_REPO_URL", &url)?;
         }
+        let api_key = lookup("HEX_API_KEY");
+        let unsafe_registry = lookup("HEX_UNSAFE_REGISTRY").is_some_and(|value| env_flag(&value));
+        let no_verify_repo_origin =
+            lookup("HEX_NO_VERIFY_REPO_ORIGIN").is_some_and(|value| env_flag(&value));
+        report.api_key = api_key;
+        report.unsafe_registry = unsafe_registry;
+        report.no_verify_repo_origin = no_verify_repo_origin;
+        Ok((config, report))
     }
 
+    #[cfg(feature = "api")]
     fn api_request(
         &self,
         method: http::Method,
         path_suffix: &str,
         api_key: Option<&str>,
-    ) -> http::request::Builder {
-        make_request(self.api_base.clone(), method, path_suffix, api_key)
+    ) -> RequestBuilder<'_> {
+        self.request(&self.api_base, method, path_suffix, api_key)
             .header("content-type", "application/json")
             .header("accept", "application/json")
     }
@@ -57,10 +406,96 @@ impl Config {
         method: http::Method,
         path_suffix: &str,
         api_key: Option<&str>,
-    ) -> http::request::Builder {
-        make_request(self.repository_base.clone(), method, path_suffix, api_key)
+    ) -> RequestBuilder<'_> {
+        let request = self.request(&self.repository_base, method, path_suffix, api_key);
+        match &self.url_signer {
+            Some(signer) => request.sign_uri(signer),
+            None => request,
+        }
+    }
+
+    fn request(
+        &self,
+        base: &http::Uri,
+        method: http::Method,
+        path_suffix: &str,
+        api_key: Option<&str>,
+    ) -> RequestBuilder<'_> {
+        let kind = match method {
+            Method::HEAD => RequestKind::Check,
+            _ => RequestKind::Metadata,
+        };
+        let builder = make_request(base.clone(), method, path_suffix, api_key, self.auth_style);
+        let builder = match base.authority() {
+            Some(authority) if self.host_header => builder.header("host", authority.as_str()),
+            _ => builder,
+        };
+        RequestBuilder {
+            config: self,
+            builder,
+            kind,
+        }
+    }
+}
+
+/// A request being built by [`Config`]. A header set here replaces any
+/// earlier value, so endpoints can override the defaults of
+/// [`Config::api_request`], and the configured [`Config::headers`] are applied
+/// once the body is given so that they take precedence over both.
+struct RequestBuilder<'a> {
+    config: &'a Config,
+    builder: http::request::Builder,
+    kind: RequestKind,
+}
+
+impl RequestBuilder<'_> {
+    fn kind(mut self, kind: RequestKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    fn sign_uri(mut self, signer: &UrlSigner) -> Self {
+        if let Some(uri) = self.builder.uri_ref() {
+            let uri = signer.sign(uri);
+            if self.config.host_header
+                && let Some(authority) = uri.authority()
+            {
+                self = self.header("host", authority.as_str());
+            }
+            self.builder = self.builder.uri(uri);
+        }
+        self
+    }
+
+    fn header(mut self, name: &'static str, value: &str) -> Self {
+        if let Some(headers) = self.builder.headers_mut() {
+            headers.remove(name);
+        }
+        self.builder = self.builder.header(name, value);
+        self
+    }
+
+    fn body(mut self, body: Vec<u8>) -> http::Result<http::Request<Vec<u8>>> {
+        if let Some(headers) = self.builder.headers_mut() {
+            for name in self.config.headers.keys() {
+                headers.remove(name);
+                for value in self.config.headers.get_all(name) {
+                    headers.append(name, value.clone());
+                }
+            }
+        }
+        let timeout = match self.config.timeouts.get(&self.kind) {
+            Some(timeout) => *timeout,
+            None => self.kind.default_timeout(),
+        };
+        let timeout = SuggestedTimeout {
+            kind: self.kind,
+            timeout,
+        };
+        self.builder.extension(timeout).body(body)
     }
 }
+
 impl Default for Config {
     fn default() -> Self {
         Self::new()
@@ -72,6 +507,7 @@ fn make_request(
     method: http::Method,
     path_suffix: &str,
     api_key: Option<&str>,
+    auth_style: AuthStyle,
 ) -> http::request::Builder {
     let mut parts = base.into_parts();
     parts.path_and_query = Some(
@@ -86,12 +522,59 @@ fn make_request(
         .method(method)
         .uri(uri)
         .header("user-agent", USER_AGENT);
-    if let Some(key) = api_key {
-        builder = builder.header("authorization", key);
+    match (api_key, auth_style) {
+        (Some(key), AuthStyle::ApiKey) => builder = builder.header("authorization", key),
+        (Some(key), AuthStyle::Bearer) => {
+            builder = builder.header("authorization", format!("Bearer {key}"))
+        }
+        (None, _) => (),
     }
     builder
 }
 
+/// The casing to use for header names when writing a request to the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderCase {
+    /// `content-type`, as stored by the `http` crate and required by HTTP/2.
+    Lower,
+    /// `Content-Type`, as traditionally written by HTTP/1.1 clients.
+    Title,
+}
+
+/// The headers of a request as they should be written by a minimal HTTP/1.1
+/// client, with the `host` header first and names in the given casing.
+///
+/// Header names in an `http::Request` are always lowercase. Most servers
+/// accept this, but some embedded or legacy servers expect the conventional
+/// casing.
+pub fn wire_headers(headers: &http::HeaderMap, case: HeaderCase) -> Vec<(String, &[u8])> {
+    let mut wire = headers
+        .iter()
+        .map(|(name, value)| {
+            let name = match case {
+                HeaderCase::Lower => name.as_str().to_string(),
+                HeaderCase::Title => title_case(name.as_str()),
+            };
+            (name, value.as_bytes())
+        })
+        .collect::<Vec<_>>();
+    wire.sort_by_key(|(name, _)| !name.eq_ignore_ascii_case("host"));
+    wire
+}
+
+fn title_case(name: &str) -> String {
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
 /// Create a request that creates a Hex API key.
 ///
 /// API Docs:
@@ -99,28 +582,38 @@ fn make_request(
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.ex#L137
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/key.ex#L6
+#[cfg(feature = "api")]
 pub fn create_api_key_request(
     username: &str,
     password: &str,
     key_name: &str,
     config: &Config,
 ) -> http::Request<Vec<u8>> {
-    let body = json!({
-        "name": key_name,
-        "permissions": [{
-            "domain": "api",
-            "resource": "write",
+    let body = KeyBody {
+        name: key_name,
+        permissions: &[KeyPermission {
+            domain: "api".into(),
+            resource: Some("write".into()),
         }],
-    });
+    };
     let creds = http_auth_basic::Credentials::new(username, password).as_http_header();
     config
         .api_request(Method::POST, "keys", None)
-        .header("authorization", creds)
-        .body(body.to_string().into_bytes())
+        .header("authorization", &creds)
+        .body(json_body(&body))
         .expect("create_api_key_request request")
 }
 
+/// The body of a request that creates a key.
+#[cfg(feature = "api")]
+#[derive(serde::Serialize)]
+struct KeyBody<'a> {
+    name: &'a str,
+    permissions: &'a [KeyPermission],
+}
+
 /// Parses a request that creates a Hex API key.
+#[cfg(feature = "api")]
 pub fn create_api_key_response(response: http::Response<Vec<u8>>) -> Result<String, ApiError> {
     #[derive(Deserialize)]
     struct Resp {
@@ -131,8 +624,45 @@ pub fn create_api_key_response(response: http::Response<Vec<u8>>) -> Result<Stri
         StatusCode::CREATED => Ok(serde_json::from_slice::<Resp>(&body)?.secret),
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidCredentials),
-        status => Err(ApiError::unexpected_response(status, body)),
+        StatusCode::UNPROCESSABLE_ENTITY if is_key_name_taken(&body) => Err(ApiError::KeyNameTaken),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+#[cfg(feature = "api")]
+fn is_key_name_taken(body: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct Resp {
+        errors: HashMap<String, serde_json::Value>,
+    }
+    serde_json::from_slice::<Resp>(body)
+        .ok()
+        .and_then(|resp| resp.errors.get("name").cloned())
+        .and_then(|error| error.as_str().map(|e| e.contains("already been taken")))
+        .unwrap_or(false)
+}
+
+/// The key name to try next when creating an API key fails with
+/// [`ApiError::KeyNameTaken`], by adding or incrementing a numeric suffix.
+///
+/// "ci" => "ci-2"
+/// "ci-2" => "ci-3"
+///
+/// ```
+/// let mut name = "ci".to_string();
+/// // ... create_api_key_response returned Err(ApiError::KeyNameTaken)
+/// name = hexpm::next_api_key_name(&name);
+/// assert_eq!(name, "ci-2");
+/// ```
+pub fn next_api_key_name(key_name: &str) -> String {
+    if let Some((base, suffix)) = key_name.rsplit_once('-')
+        && let Ok(n) = suffix.parse::<u32>()
+        && !suffix.starts_with('0')
+        && let Some(next) = n.checked_add(1)
+    {
+        return format!("{}-{}", base, next);
     }
+    format!("{}-2", key_name)
 }
 
 /// Create a request that deletes an Hex API key.
@@ -142,6 +672,7 @@ pub fn create_api_key_response(response: http::Response<Vec<u8>>) -> Result<Stri
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.user.ex#L291
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/key.ex#L15
+#[cfg(feature = "api")]
 pub fn remove_api_key_request(
     name_of_key_to_delete: &str,
     api_key: &str,
@@ -158,13 +689,227 @@ pub fn remove_api_key_request(
 }
 
 /// Parses a request that deleted a Hex API key.
+#[cfg(feature = "api")]
 pub fn remove_api_key_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     let (parts, body) = response.into_parts();
     match parts.status {
         StatusCode::NO_CONTENT | StatusCode::OK => Ok(()),
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidCredentials),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+/// A permission granted to a key.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, serde::Serialize)]
+pub struct KeyPermission {
+    /// What the permission applies to, such as `api` or `repository`.
+    pub domain: String,
+    /// The resource within the domain, such as the name of a repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+}
+
+impl KeyPermission {
+    /// Permission to fetch packages from an organisation's repository.
+    pub fn repository(organization: &str) -> Self {
+        Self {
+            domain: "repository".into(),
+            resource: Some(organization.into()),
+        }
+    }
+}
+
+/// A key created for an organisation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OrganizationKey {
+    pub name: String,
+    /// The key itself. Hex only reveals this when the key is created.
+    pub secret: String,
+    pub permissions: Vec<KeyPermission>,
+}
+
+/// Create a request that creates a key for an organisation, such as a key to
+/// fetch packages from the organisation's repository in CI. Unlike user API
+/// keys these belong to the organisation and are authorised with the API key
+/// of one of its members.
+///
+/// API Docs:
+///
+/// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.organization.ex
+///
+/// https://github.com/hexpm/hex/blob/main/lib/hex/api/key.ex
+#[cfg(feature = "api")]
+pub fn create_organization_key_request(
+    organization: &str,
+    key_name: &str,
+    permissions: &[KeyPermission],
+    api_key: &str,
+    config: &Config,
+) -> http::Request<Vec<u8>> {
+    let body = KeyBody {
+        name: key_name,
+        permissions,
+    };
+    config
+        .api_request(
+            Method::POST,
+            &format!("orgs/{}/keys", organization),
+            Some(api_key),
+        )
+        .body(json_body(&body))
+        .expect("create_organization_key_request request")
+}
+
+/// Parses a request that created a key for an organisation.
+#[cfg(feature = "api")]
+pub fn create_organization_key_response(
+    response: http::Response<Vec<u8>>,
+) -> Result<OrganizationKey, ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::CREATED => Ok(serde_json::from_slice(&body)?),
+        StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
+        StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
+        StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
+        StatusCode::NOT_FOUND => Err(ApiError::NotFound),
+        StatusCode::UNPROCESSABLE_ENTITY if is_key_name_taken(&body) => Err(ApiError::KeyNameTaken),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+/// Create a request that revokes a key of an organisation.
+#[cfg(feature = "api")]
+pub fn remove_organization_key_request(
+    organization: &str,
+    key_name: &str,
+    api_key: &str,
+    config: &Config,
+) -> http::Request<Vec<u8>> {
+    config
+        .api_request(
+            Method::DELETE,
+            &format!("orgs/{}/keys/{}", organization, key_name),
+            Some(api_key),
+        )
+        .body(vec![])
+        .expect("remove_organization_key_request request")
+}
+
+/// Parses a request that revoked a key of an organisation.
+#[cfg(feature = "api")]
+pub fn remove_organization_key_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::NO_CONTENT | StatusCode::OK => Ok(()),
+        StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
+        StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
+        StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
+        StatusCode::NOT_FOUND => Err(ApiError::NotFound),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+/// A key as listed by Hex. The key itself is only revealed when it is created.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct KeyInfo {
+    pub name: String,
+    pub permissions: Vec<KeyPermission>,
+    /// Whether this is the key the request listing the keys was made with.
+    #[serde(default)]
+    pub authing_key: bool,
+}
+
+/// Create a request that lists the keys of an organisation.
+#[cfg(feature = "api")]
+pub fn get_organization_keys_request(
+    organization: &str,
+    api_key: &str,
+    config: &Config,
+) -> http::Request<Vec<u8>> {
+    config
+        .api_request(
+            Method::GET,
+            &format!("orgs/{}/keys", organization),
+            Some(api_key),
+        )
+        .body(vec![])
+        .expect("get_organization_keys_request request")
+}
+
+/// Parses a request that listed the keys of an organisation.
+#[cfg(feature = "api")]
+pub fn get_organization_keys_response(
+    response: http::Response<Vec<u8>>,
+) -> Result<Vec<KeyInfo>, ApiError> {
+    get_keys_response(response)
+}
+
+/// Create a request that lists the API keys of the user the API key belongs
+/// to.
+///
+/// API Docs:
+///
+/// https://github.com/hexpm/hex/blob/main/lib/hex/api/key.ex
+#[cfg(feature = "api")]
+pub fn get_api_keys_request(api_key: &str, config: &Config) -> http::Request<Vec<u8>> {
+    config
+        .api_request(Method::GET, "keys", Some(api_key))
+        .body(vec![])
+        .expect("get_api_keys_request request")
+}
+
+/// Parses a request that listed the API keys of a user.
+#[cfg(feature = "api")]
+pub fn get_api_keys_response(response: http::Response<Vec<u8>>) -> Result<Vec<KeyInfo>, ApiError> {
+    get_keys_response(response)
+}
+
+#[cfg(feature = "api")]
+fn get_keys_response(response: http::Response<Vec<u8>>) -> Result<Vec<KeyInfo>, ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::OK => Ok(serde_json::from_slice(&body)?),
+        StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
+        StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
+        StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
+        StatusCode::NOT_FOUND => Err(ApiError::NotFound),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+/// The user an API key belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct User {
+    pub username: String,
+    /// Hex only shows the email address of users who have made it public.
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+/// Create a request that fetches the user the API key belongs to.
+///
+/// API Docs:
+///
+/// https://github.com/hexpm/hex/blob/main/lib/hex/api/user.ex
+#[cfg(feature = "api")]
+pub fn get_current_user_request(api_key: &str, config: &Config) -> http::Request<Vec<u8>> {
+    config
+        .api_request(Method::GET, "users/me", Some(api_key))
+        .body(vec![])
+        .expect("get_current_user_request request")
+}
+
+/// Parses a request that fetched the user an API key belongs to.
+#[cfg(feature = "api")]
+pub fn get_current_user_response(response: http::Response<Vec<u8>>) -> Result<User, ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::OK => Ok(serde_json::from_slice(&body)?),
+        StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
+        StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
+        StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
     }
 }
 
@@ -175,36 +920,42 @@ pub fn remove_api_key_response(response: http::Response<Vec<u8>>) -> Result<(),
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.retire.ex#L75
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/release.ex#L28
+#[cfg(feature = "api")]
 pub fn retire_release_request(
-    package: &str,
-    version: &str,
+    release: &ReleaseRef,
     reason: RetirementReason,
     message: Option<&str>,
     api_key: &str,
     config: &Config,
 ) -> http::Request<Vec<u8>> {
-    let body = json!({
-        "reason": reason.to_str(),
-        "message": message,
-    });
+    #[derive(serde::Serialize)]
+    struct Body<'a> {
+        message: Option<&'a str>,
+        reason: &'a str,
+    }
+    let body = Body {
+        message,
+        reason: reason.to_str(),
+    };
     config
         .api_request(
             Method::POST,
-            &format!("packages/{}/releases/{}/retire", package, version),
+            &format!("{}/retire", release.api_path()),
             Some(api_key),
         )
-        .body(body.to_string().into_bytes())
+        .body(json_body(&body))
         .expect("retire_release_request request")
 }
 
 /// Parses a request that retired a release.
+#[cfg(feature = "api")]
 pub fn retire_release_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     let (parts, body) = response.into_parts();
     match parts.status {
         StatusCode::NO_CONTENT | StatusCode::OK => Ok(()),
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidCredentials),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
     }
 }
 
@@ -215,16 +966,16 @@ pub fn retire_release_response(response: http::Response<Vec<u8>>) -> Result<(),
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.retire.ex#L89
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/release.ex#L35
+#[cfg(feature = "api")]
 pub fn unretire_release_request(
-    package: &str,
-    version: &str,
+    release: &ReleaseRef,
     api_key: &str,
     config: &Config,
 ) -> http::Request<Vec<u8>> {
     config
         .api_request(
             Method::DELETE,
-            &format!("packages/{}/releases/{}/retire", package, version),
+            &format!("{}/retire", release.api_path()),
             Some(api_key),
         )
         .body(vec![])
@@ -232,13 +983,14 @@ pub fn unretire_release_request(
 }
 
 /// Parses a request that un-retired a package version.
+#[cfg(feature = "api")]
 pub fn unretire_release_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     let (parts, body) = response.into_parts();
     match parts.status {
         StatusCode::NO_CONTENT | StatusCode::OK => Ok(()),
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidCredentials),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
     }
 }
 
@@ -259,52 +1011,148 @@ pub fn get_repository_versions_request(
 /// Parse a request that get the names and versions of all of the packages on
 /// the package registry.
 ///
+#[cfg(feature = "registry")]
 pub fn get_repository_versions_response(
     response: http::Response<Vec<u8>>,
     public_key: &[u8],
 ) -> Result<HashMap<String, Vec<Version>>, ApiError> {
+    get_repository_versions_reader_response(response, public_key)?.into_map()
+}
+
+/// Parse a request that get the names and versions of all of the packages on
+/// the package registry, without parsing the version of every package up
+/// front.
+///
+/// The full hex.pm index contains tens of thousands of packages, most of which
+/// a caller will never look at. The returned [`VersionsReader`] keeps the
+/// versions as they were sent by the registry and only parses them when asked.
+///
+#[cfg(feature = "registry")]
+pub fn get_repository_versions_reader_response(
+    response: http::Response<Vec<u8>>,
+    public_key: &[u8],
+) -> Result<VersionsReader, ApiError> {
     let (parts, body) = response.into_parts();
 
     match parts.status {
         StatusCode::OK => (),
-        status => return Err(ApiError::unexpected_response(status, body)),
+        status if status.is_redirection() => return Err(ApiError::redirect(&parts, body)),
+        _ => return Err(ApiError::unexpected_response(&parts, body)),
     };
 
-    let mut decoder = GzDecoder::new(body.reader());
-    let mut body = Vec::new();
-    decoder.read_to_end(&mut body)?;
-
-    let signed = Signed::decode(body.as_slice())?;
+    let payload = decode_signed_resource(body.as_slice(), public_key)?;
 
-    let payload =
-        verify_payload(signed, public_key).map_err(|_| ApiError::IncorrectPayloadSignature)?;
+    Ok(VersionsReader {
+        versions: proto::decode_versions(payload)?,
+    })
+}
 
-    let versions = Versions::decode(payload.as_slice())?
-        .packages
-        .into_iter()
-        .map(|n| {
-            let parse_version = |v: &str| {
-                let err = |_| ApiError::InvalidVersionFormat(v.to_string());
-                Version::parse(v).map_err(err)
-            };
-            let versions = n
-                .versions
-                .iter()
-                .map(|v| parse_version(v.as_str()))
-                .collect::<Result<Vec<Version>, ApiError>>()?;
-            Ok((n.name, versions))
-        })
-        .collect::<Result<HashMap<_, _>, ApiError>>()?;
+/// As [`get_repository_versions_reader_response`], reading the body from a
+/// stream such as an HTTP client's response. The body is inflated as it is
+/// read, so the compressed index is never held in memory in full alongside
+/// the inflated one.
+#[cfg(feature = "registry")]
+pub fn get_repository_versions_stream_response(
+    response: http::Response<impl Read>,
+    public_key: &[u8],
+    config: &Config,
+) -> Result<VersionsReader, ApiError> {
+    let (parts, body) = response.into_parts();
+    if parts.status != StatusCode::OK {
+        let body = read_limited(body, config.max_download_size)?;
+        return get_repository_versions_reader_response(
+            http::Response::from_parts(parts, body),
+            public_key,
+        );
+    }
+    let payload = decode_signed_resource(body, public_key)?;
+    Ok(VersionsReader {
+        versions: proto::decode_versions(payload)?,
+    })
+}
 
-    Ok(versions)
+/// The names and versions of all of the packages on a package registry, as
+/// sent by the registry. Versions are parsed on demand.
+#[cfg(feature = "registry")]
+#[derive(Debug, Clone)]
+pub struct VersionsReader {
+    versions: Versions,
 }
 
-/// Create a request to get the information for a package in the repository.
-///
-/// API Docs:
-///
-/// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.package.ex#L348
-///
+#[cfg(feature = "registry")]
+impl VersionsReader {
+    /// The name of the repository the index belongs to.
+    pub fn repository(&self) -> &str {
+        &self.versions.repository
+    }
+
+    /// The number of packages in the index.
+    pub fn len(&self) -> usize {
+        self.versions.packages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.versions.packages.is_empty()
+    }
+
+    /// Iterate over the name and unparsed versions of every package.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.versions
+            .packages
+            .iter()
+            .map(|package| (package.name.as_str(), package.versions.as_slice()))
+    }
+
+    /// The unparsed versions of a package, if it is in the index.
+    pub fn raw_versions(&self, name: &str) -> Option<&[String]> {
+        self.iter()
+            .find(|(package, _)| *package == name)
+            .map(|(_, versions)| versions)
+    }
+
+    /// Parse the versions of a package, if it is in the index.
+    pub fn versions(&self, name: &str) -> Option<Result<Vec<Version>, ApiError>> {
+        self.raw_versions(name).map(parse_versions)
+    }
+
+    /// Parse the versions of every package in the index.
+    pub fn into_map(self) -> Result<HashMap<String, Vec<Version>>, ApiError> {
+        self.versions
+            .packages
+            .into_iter()
+            .map(|package| Ok((package.name, parse_versions(&package.versions)?)))
+            .collect()
+    }
+
+    /// Parse the versions of every package in the index, keeping the
+    /// packages that parse when others do not. Failures are given with the
+    /// name of their package.
+    pub fn parse_all(self) -> bulk::BulkResult<(String, Vec<Version>), (String, ApiError)> {
+        self.versions
+            .packages
+            .into_iter()
+            .map(|package| match parse_versions(&package.versions) {
+                Ok(versions) => Ok((package.name, versions)),
+                Err(error) => Err((package.name, error)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "registry")]
+fn parse_versions(versions: &[String]) -> Result<Vec<Version>, ApiError> {
+    versions
+        .iter()
+        .map(|v| Version::parse(v).map_err(|_| ApiError::InvalidVersionFormat(v.to_string())))
+        .collect()
+}
+
+/// Create a request to get the information for a package in the repository.
+///
+/// API Docs:
+///
+/// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.package.ex#L348
+///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/package.ex#L36
 pub fn get_package_request(
     name: &str,
@@ -320,6 +1168,7 @@ pub fn get_package_request(
 
 /// Parse a response to get the information for a package in the repository.
 ///
+#[cfg(feature = "registry")]
 pub fn get_package_response(
     response: http::Response<Vec<u8>>,
     public_key: &[u8],
@@ -330,50 +1179,50 @@ pub fn get_package_response(
         StatusCode::OK => (),
         StatusCode::FORBIDDEN => return Err(ApiError::NotFound),
         StatusCode::NOT_FOUND => return Err(ApiError::NotFound),
-        status => {
-            return Err(ApiError::unexpected_response(status, body));
+        status if status.is_redirection() => return Err(ApiError::redirect(&parts, body)),
+        _ => {
+            return Err(ApiError::unexpected_response(&parts, body));
         }
     };
 
-    let mut decoder = GzDecoder::new(body.reader());
-    let mut body = Vec::new();
-    decoder.read_to_end(&mut body)?;
-
-    let signed = Signed::decode(body.as_slice())?;
-
-    let payload =
-        verify_payload(signed, public_key).map_err(|_| ApiError::IncorrectPayloadSignature)?;
+    let payload = decode_signed_resource(body.as_slice(), public_key)?;
 
-    let package = proto::package::Package::decode(payload.as_slice())?;
-    let releases = package
-        .releases
-        .clone()
-        .into_iter()
-        .map(proto_to_release)
-        .collect::<Result<Vec<_>, _>>()?;
-    let package = Package {
-        name: package.name,
-        repository: package.repository,
-        releases,
-    };
+    proto::decode_package(payload)
+}
 
-    Ok(package)
+/// As [`get_package_response`], reading the body from a stream such as an
+/// HTTP client's response. The body is inflated as it is read, so the
+/// compressed record is never held in memory in full alongside the inflated
+/// one.
+#[cfg(feature = "registry")]
+pub fn get_package_stream_response(
+    response: http::Response<impl Read>,
+    public_key: &[u8],
+    config: &Config,
+) -> Result<Package, ApiError> {
+    let (parts, body) = response.into_parts();
+    if parts.status != StatusCode::OK {
+        let body = read_limited(body, config.max_download_size)?;
+        return get_package_response(http::Response::from_parts(parts, body), public_key);
+    }
+    let payload = decode_signed_resource(body, public_key)?;
+    proto::decode_package(payload)
 }
 
 /// Create a request to download a version of a package as a tarball
 /// TODO: Where are the API docs for this?
 pub fn get_package_tarball_request(
-    name: &str,
-    version: &str,
+    release: &ReleaseRef,
     api_key: Option<&str>,
     config: &Config,
 ) -> http::Request<Vec<u8>> {
     config
         .repository_request(
             Method::GET,
-            &format!("tarballs/{}-{}.tar", name, version),
+            &release.repository_path("tarballs", "tar"),
             api_key,
         )
+        .kind(RequestKind::Download)
         .header("accept", "application/x-tar")
         .body(vec![])
         .expect("get_package_tarball_request request")
@@ -381,6 +1230,7 @@ pub fn get_package_tarball_request(
 
 /// Parse a response to download a version of a package as a tarball
 ///
+#[cfg(feature = "registry")]
 pub fn get_package_tarball_response(
     response: http::Response<Vec<u8>>,
     checksum: &[u8],
@@ -390,37 +1240,442 @@ pub fn get_package_tarball_response(
         StatusCode::OK => (),
         StatusCode::FORBIDDEN => return Err(ApiError::NotFound),
         StatusCode::NOT_FOUND => return Err(ApiError::NotFound),
-        status => {
-            return Err(ApiError::unexpected_response(status, body));
+        status if status.is_redirection() => return Err(ApiError::redirect(&parts, body)),
+        _ => {
+            return Err(ApiError::unexpected_response(&parts, body));
         }
     };
-    let body = read_and_check_body(body.reader(), checksum)?;
+    let body = read_and_check_body(body.reader(), checksum, u64::MAX)?;
     Ok(body)
 }
 
+/// As [`get_package_tarball_response`], reading the body from a stream such as
+/// an HTTP client's response. Reading stops with
+/// [`ApiError::ResponseTooLarge`] once the body is larger than
+/// [`Config::max_download_size`], so a server cannot send an endless body.
+#[cfg(feature = "registry")]
+pub fn get_package_tarball_reader_response(
+    response: http::Response<impl Read>,
+    checksum: &[u8],
+    config: &Config,
+) -> Result<Vec<u8>, ApiError> {
+    let (parts, body) = response.into_parts();
+    if parts.status != StatusCode::OK {
+        let body = read_limited(body, config.max_download_size)?;
+        return get_package_tarball_response(http::Response::from_parts(parts, body), checksum);
+    }
+    read_and_check_body(body, checksum, config.max_download_size)
+}
+
+/// As [`get_package_tarball_request`], first looking for the tarball in
+/// `store` by its outer checksum. A request is only made if the store does not
+/// have the tarball, or has one that does not match the checksum.
+#[cfg(feature = "registry")]
+pub fn get_package_tarball_cached_request(
+    release: &ReleaseRef,
+    checksum: &checksum::Checksum,
+    api_key: Option<&str>,
+    config: &Config,
+    store: &impl cache::ArtifactStore,
+) -> cache::TarballFetch {
+    match store.get(checksum) {
+        Some(tarball) if checksum::Checksum::of(&tarball) == *checksum => {
+            cache::TarballFetch::Cached(tarball)
+        }
+        _ => cache::TarballFetch::Download(Box::new(get_package_tarball_request(
+            release, api_key, config,
+        ))),
+    }
+}
+
+/// As [`get_package_tarball_response`], putting the tarball in `store` once it
+/// has been verified so that later requests for it need no download.
+#[cfg(feature = "registry")]
+pub fn get_package_tarball_cached_response(
+    response: http::Response<Vec<u8>>,
+    checksum: &checksum::Checksum,
+    store: &mut impl cache::ArtifactStore,
+) -> Result<Vec<u8>, ApiError> {
+    let tarball = get_package_tarball_response(response, checksum.as_bytes())?;
+    store.put(checksum, &tarball);
+    Ok(tarball)
+}
+
+/// Create a `HEAD` request for the tarball of a version of a package, to find
+/// its size without downloading it.
+pub fn release_size_request(
+    release: &ReleaseRef,
+    api_key: Option<&str>,
+    config: &Config,
+) -> http::Request<Vec<u8>> {
+    config
+        .repository_request(
+            Method::HEAD,
+            &release.repository_path("tarballs", "tar"),
+            api_key,
+        )
+        .body(vec![])
+        .expect("release_size_request request")
+}
+
+/// Parse a response to a [`release_size_request`], returning the size of the
+/// tarball in bytes, or `None` if the repository did not report it.
+pub fn release_size_response(response: http::Response<Vec<u8>>) -> Result<Option<u64>, ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::OK => (),
+        StatusCode::FORBIDDEN => return Err(ApiError::NotFound),
+        StatusCode::NOT_FOUND => return Err(ApiError::NotFound),
+        status if status.is_redirection() => return Err(ApiError::redirect(&parts, body)),
+        _ => return Err(ApiError::unexpected_response(&parts, body)),
+    };
+    Ok(parts
+        .headers
+        .get(http::header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse().ok()))
+}
+
+/// Create a `HEAD` request for the versions index of a repository, to check
+/// that the repository is up and how recently it was updated without
+/// downloading anything. Tools choosing between mirrors can time how long each
+/// takes to respond.
+pub fn ping_repository_request(api_key: Option<&str>, config: &Config) -> http::Request<Vec<u8>> {
+    config
+        .repository_request(Method::HEAD, "versions", api_key)
+        .body(vec![])
+        .expect("ping_repository_request request")
+}
+
+/// Parse a response to a [`ping_repository_request`]. Server errors and rate
+/// limiting are reported as [`RepositoryHealth::Unavailable`] rather than as
+/// errors, so that they can be compared with other repositories.
+pub fn ping_repository_response(
+    response: http::Response<Vec<u8>>,
+) -> Result<RepositoryHealth, ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::OK => (),
+        StatusCode::FORBIDDEN => return Err(ApiError::NotFound),
+        StatusCode::NOT_FOUND => return Err(ApiError::NotFound),
+        status if status.is_redirection() => return Err(ApiError::redirect(&parts, body)),
+        status if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS => {
+            return Ok(RepositoryHealth::Unavailable(status));
+        }
+        _ => return Err(ApiError::unexpected_response(&parts, body)),
+    };
+    let header = |name| parts.headers.get(name)?.to_str().ok();
+    Ok(RepositoryHealth::Available(Freshness {
+        last_modified: header(http::header::LAST_MODIFIED).and_then(parse_http_date),
+        age: header(http::header::AGE)
+            .and_then(|age| age.trim().parse().ok())
+            .map(Duration::from_secs),
+    }))
+}
+
+/// The result of a [`ping_repository_request`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepositoryHealth {
+    /// The repository is serving its versions index.
+    Available(Freshness),
+    /// The repository responded with a server error or is rate limiting.
+    Unavailable(StatusCode),
+}
+
+/// How up to date a repository's versions index is, as reported by its
+/// headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Freshness {
+    /// When the versions index was last updated, from the `last-modified`
+    /// header.
+    pub last_modified: Option<SystemTime>,
+    /// How long the response had been held in a cache, from the `age` header.
+    /// Mirrors behind a CDN may serve an index this much older than the
+    /// origin's.
+    pub age: Option<Duration>,
+}
+
+impl Freshness {
+    /// How long ago the versions index was last updated, or `None` if the
+    /// repository did not say.
+    pub fn staleness(&self, now: SystemTime) -> Option<Duration> {
+        Some(now.duration_since(self.last_modified?).unwrap_or_default())
+    }
+}
+
+/// Parse an HTTP date in the preferred `Wed, 21 Oct 2015 07:28:00 GMT` format.
+fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let mut parts = date.split_ascii_whitespace().skip(1);
+    let day: u64 = parts.next()?.parse().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year: u64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':').map(|n| n.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+    if parts.next()? != "GMT" || year < 1970 || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days since the epoch of the civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146097 + day_of_era).checked_sub(719468)?;
+
+    let seconds = days * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// The validators of a previously downloaded resource, used to make a
+/// conditional request that only downloads the resource again if it has
+/// changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheValidators {
+    /// The `etag` header of the previous response. Weak validators such as
+    /// `W/"abc"` are sent unchanged, as `if-none-match` uses weak comparison.
+    pub etag: Option<String>,
+    /// The `last-modified` header of the previous response.
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    /// The validators of a response.
+    pub fn from_headers(headers: &http::HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &http::HeaderValue| value.to_str().ok())
+                .map(String::from)
+        };
+        Self {
+            etag: header(http::header::ETAG),
+            last_modified: header(http::header::LAST_MODIFIED),
+        }
+    }
+
+    /// Make a request conditional on the resource having changed since these
+    /// validators were recorded.
+    pub fn apply_to(&self, request: &mut http::Request<Vec<u8>>) {
+        let headers = request.headers_mut();
+        if let Some(etag) = self.etag.as_ref().and_then(|v| v.parse().ok()) {
+            headers.insert(http::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(date) = self.last_modified.as_ref().and_then(|v| v.parse().ok()) {
+            headers.insert(http::header::IF_MODIFIED_SINCE, date);
+        }
+    }
+}
+
+/// The result of a conditional request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conditional<T> {
+    /// The resource has not changed since the validators were recorded.
+    NotModified,
+    /// The resource has changed, or the request was not conditional.
+    Modified {
+        value: T,
+        validators: CacheValidators,
+    },
+}
+
+/// As [`get_package_tarball_response`], for a request made conditional with
+/// [`CacheValidators::apply_to`].
+#[cfg(feature = "registry")]
+pub fn get_package_tarball_conditional_response(
+    response: http::Response<Vec<u8>>,
+    checksum: &[u8],
+) -> Result<Conditional<Vec<u8>>, ApiError> {
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Conditional::NotModified);
+    }
+    let validators = CacheValidators::from_headers(response.headers());
+    let value = get_package_tarball_response(response, checksum)?;
+    Ok(Conditional::Modified { value, validators })
+}
+
+/// Create a request to download the documentation of a version of a package
+/// as a gzipped tarball.
+pub fn get_docs_tarball_request(
+    release: &ReleaseRef,
+    api_key: Option<&str>,
+    config: &Config,
+) -> Result<http::Request<Vec<u8>>, ApiError> {
+    release.validate()?;
+
+    Ok(config
+        .repository_request(
+            Method::GET,
+            &release.repository_path("docs", "tar.gz"),
+            api_key,
+        )
+        .kind(RequestKind::Download)
+        .header("accept", "application/x-tar")
+        .body(vec![])
+        .expect("get_docs_tarball_request request"))
+}
+
+/// Parse a response to download the documentation of a version of a package.
+/// The gzipped tarball is returned as sent by the repository.
+pub fn get_docs_tarball_response(response: http::Response<Vec<u8>>) -> Result<Vec<u8>, ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::OK => Ok(body),
+        StatusCode::FORBIDDEN => Err(ApiError::NotFound),
+        StatusCode::NOT_FOUND => Err(ApiError::NotFound),
+        status if status.is_redirection() => Err(ApiError::redirect(&parts, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+/// As [`get_docs_tarball_response`], reading the body from a stream such as an
+/// HTTP client's response. Reading stops with [`ApiError::ResponseTooLarge`]
+/// once the body is larger than [`Config::max_download_size`].
+pub fn get_docs_tarball_reader_response(
+    response: http::Response<impl Read>,
+    config: &Config,
+) -> Result<Vec<u8>, ApiError> {
+    let (parts, body) = response.into_parts();
+    let body = read_limited(body, config.max_download_size)?;
+    get_docs_tarball_response(http::Response::from_parts(parts, body))
+}
+
+/// As [`get_docs_tarball_response`], for a request made conditional with
+/// [`CacheValidators::apply_to`].
+pub fn get_docs_tarball_conditional_response(
+    response: http::Response<Vec<u8>>,
+) -> Result<Conditional<Vec<u8>>, ApiError> {
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Conditional::NotModified);
+    }
+    let validators = CacheValidators::from_headers(response.headers());
+    let value = get_docs_tarball_response(response)?;
+    Ok(Conditional::Modified { value, validators })
+}
+
+/// Where a downloaded artifact came from, for build systems that record
+/// provenance for supply chain audits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// The base URL of the repository the artifact was downloaded from.
+    pub repository: String,
+    /// The URL of the request that downloaded the artifact.
+    pub url: String,
+    /// The `etag` header of the response, if it had one.
+    pub etag: Option<String>,
+    /// The checksum the artifact was verified against, for artifacts that are
+    /// verified by checksum.
+    pub checksum: Option<Vec<u8>>,
+    /// The fingerprint of the public key the artifact's signature was
+    /// verified with, for signed artifacts. See [`public_key_fingerprint`].
+    pub signing_key_fingerprint: Option<String>,
+    pub fetched_at: SystemTime,
+}
+
+impl Provenance {
+    #[cfg(feature = "registry")]
+    fn new(
+        parts: &http::response::Parts,
+        url: &http::Uri,
+        config: &Config,
+        fetched_at: SystemTime,
+    ) -> Self {
+        Self {
+            repository: config.repository_base.to_string(),
+            url: url.to_string(),
+            etag: parts
+                .headers
+                .get(http::header::ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(String::from),
+            checksum: None,
+            signing_key_fingerprint: None,
+            fetched_at,
+        }
+    }
+}
+
+/// As [`get_package_tarball_response`], also returning the [`Provenance`] of
+/// the tarball. `url` is the URI of the request the response is for.
+#[cfg(feature = "registry")]
+pub fn get_package_tarball_response_with_provenance(
+    response: http::Response<Vec<u8>>,
+    checksum: &[u8],
+    url: &http::Uri,
+    config: &Config,
+    fetched_at: SystemTime,
+) -> Result<(Vec<u8>, Provenance), ApiError> {
+    let (parts, body) = response.into_parts();
+    let provenance = Provenance {
+        checksum: Some(checksum.to_vec()),
+        ..Provenance::new(&parts, url, config, fetched_at)
+    };
+    let tarball = get_package_tarball_response(http::Response::from_parts(parts, body), checksum)?;
+    Ok((tarball, provenance))
+}
+
+/// As [`get_package_response`], also returning the [`Provenance`] of the
+/// package record. `url` is the URI of the request the response is for.
+#[cfg(feature = "registry")]
+pub fn get_package_response_with_provenance(
+    response: http::Response<Vec<u8>>,
+    public_key: &[u8],
+    url: &http::Uri,
+    config: &Config,
+    fetched_at: SystemTime,
+) -> Result<(Package, Provenance), ApiError> {
+    let (parts, body) = response.into_parts();
+    let provenance = Provenance::new(&parts, url, config, fetched_at);
+    let package = get_package_response(http::Response::from_parts(parts, body), public_key)?;
+    let provenance = Provenance {
+        signing_key_fingerprint: Some(public_key_fingerprint(public_key)?),
+        ..provenance
+    };
+    Ok((package, provenance))
+}
+
 /// API Docs:
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.publish.ex#L384
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/release_docs.ex#L19
+#[cfg(feature = "api")]
 pub fn remove_docs_request(
-    package_name: &str,
-    version: &str,
+    release: &ReleaseRef,
     api_key: &str,
     config: &Config,
 ) -> Result<http::Request<Vec<u8>>, ApiError> {
-    validate_package_and_version(package_name, version)?;
+    release.validate()?;
 
     Ok(config
         .api_request(
             Method::DELETE,
-            &format!("packages/{}/releases/{}/docs", package_name, version),
+            &format!("{}/docs", release.api_path()),
             Some(api_key),
         )
         .body(vec![])
         .expect("remove_docs_request request"))
 }
 
+#[cfg(feature = "api")]
 pub fn remove_docs_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     let (parts, body) = response.into_parts();
     match parts.status {
@@ -429,36 +1684,41 @@ pub fn remove_docs_response(response: http::Response<Vec<u8>>) -> Result<(), Api
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
         StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
     }
 }
 
+/// Check the tarball with [`docs::validate_docs_tarball`] first to catch
+/// problems that Hex would reject or that would break the published docs.
+///
 /// API Docs:
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.publish.ex#L429
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/release_docs.ex#L11
+#[cfg(feature = "api")]
 pub fn publish_docs_request(
-    package_name: &str,
-    version: &str,
+    release: &ReleaseRef,
     gzipped_tarball: Vec<u8>,
     api_key: &str,
     config: &Config,
 ) -> Result<http::Request<Vec<u8>>, ApiError> {
-    validate_package_and_version(package_name, version)?;
+    release.validate()?;
 
     Ok(config
         .api_request(
             Method::POST,
-            &format!("packages/{}/releases/{}/docs", package_name, version),
+            &format!("{}/docs", release.api_path()),
             Some(api_key),
         )
+        .kind(RequestKind::Upload)
         .header("content-encoding", "x-gzip")
         .header("content-type", "application/x-tar")
         .body(gzipped_tarball)
         .expect("publish_docs_request request"))
 }
 
+#[cfg(feature = "api")]
 pub fn publish_docs_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     let (parts, body) = response.into_parts();
     match parts.status {
@@ -467,7 +1727,43 @@ pub fn publish_docs_response(response: http::Response<Vec<u8>>) -> Result<(), Ap
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
         StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+/// Whether publishing may replace an existing release of the same version.
+///
+/// Hex only permits replacing a release for a limited time after it was first
+/// published. Replacing a release after this window fails with
+/// [`ApiError::LateModification`], which reports the window when Hex gives it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PublishMode {
+    /// Publish a new release, failing with [`ApiError::NotReplacing`] if the
+    /// version has already been published.
+    #[default]
+    New,
+    /// Publish the release, replacing the existing release of the same
+    /// version if there is one.
+    Replace,
+    /// Replace an existing release that is known to still be within the
+    /// modification window, such as to fix a release straight after
+    /// publishing it. Hex is sent the same request as for
+    /// [`Replace`](PublishMode::Replace), so a release past the window fails
+    /// with [`ApiError::LateModification`] in either mode.
+    ForceReplaceWithinWindow,
+}
+
+#[cfg(feature = "api")]
+impl PublishMode {
+    /// The query parameters of the publish endpoint, URL encoded.
+    fn query_string(&self) -> String {
+        let replace = match self {
+            PublishMode::New => false,
+            PublishMode::Replace | PublishMode::ForceReplaceWithinWindow => true,
+        };
+        url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("replace", &replace.to_string())
+            .finish()
     }
 }
 
@@ -476,24 +1772,27 @@ pub fn publish_docs_response(response: http::Response<Vec<u8>>) -> Result<(), Ap
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.publish.ex#L512
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/release.ex#L13
+#[cfg(feature = "api")]
 pub fn publish_package_request(
     release_tarball: Vec<u8>,
     api_key: &str,
     config: &Config,
-    replace: bool,
+    mode: PublishMode,
 ) -> http::Request<Vec<u8>> {
     // TODO: do all the package tarball construction
     config
         .api_request(
             Method::POST,
-            format!("publish?replace={}", replace).as_str(),
+            format!("publish?{}", mode.query_string()).as_str(),
             Some(api_key),
         )
+        .kind(RequestKind::Upload)
         .header("content-type", "application/x-tar")
         .body(release_tarball)
         .expect("publish_package_request request")
 }
 
+#[cfg(feature = "api")]
 pub fn publish_package_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     // TODO: return data from body
     let (parts, body) = response.into_parts();
@@ -508,10 +1807,38 @@ pub fn publish_package_response(response: http::Response<Vec<u8>>) -> Result<(),
             if body.contains("--replace") {
                 return Err(ApiError::NotReplacing);
             }
-            Err(ApiError::LateModification)
+            Err(ApiError::LateModification {
+                window: modification_window(body),
+            })
         }
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+/// Extract the modification window from an error such as "can only modify a
+/// release up to one hour after publication".
+#[cfg(feature = "api")]
+fn modification_window(message: &str) -> Option<Duration> {
+    lazy_static! {
+        static ref WINDOW_PATTERN: Regex =
+            Regex::new(r"up to ([\w-]+) (minute|hour|day)s? after").unwrap();
     }
+    let captures = WINDOW_PATTERN.captures(message)?;
+    let count: u64 = match &captures[1] {
+        "a" | "an" | "one" => 1,
+        "two" => 2,
+        "three" => 3,
+        "six" => 6,
+        "twelve" => 12,
+        "24" | "twenty-four" => 24,
+        count => count.parse().ok()?,
+    };
+    let unit = match &captures[2] {
+        "minute" => 60,
+        "hour" => 60 * 60,
+        _ => 24 * 60 * 60,
+    };
+    Some(Duration::from_secs(count * unit))
 }
 
 /// API Docs:
@@ -519,24 +1846,21 @@ pub fn publish_package_response(response: http::Response<Vec<u8>>) -> Result<(),
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.publish.ex#L371
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/release.ex#L21
+#[cfg(feature = "api")]
 pub fn revert_release_request(
-    package_name: &str,
-    version: &str,
+    release: &ReleaseRef,
     api_key: &str,
     config: &Config,
 ) -> Result<http::Request<Vec<u8>>, ApiError> {
-    validate_package_and_version(package_name, version)?;
+    release.validate()?;
 
     Ok(config
-        .api_request(
-            Method::DELETE,
-            &format!("packages/{}/releases/{}", package_name, version),
-            Some(api_key),
-        )
+        .api_request(Method::DELETE, &release.api_path(), Some(api_key))
         .body(vec![])
         .expect("publish_package_request request"))
 }
 
+#[cfg(feature = "api")]
 pub fn revert_release_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     let (parts, body) = response.into_parts();
     match parts.status {
@@ -545,12 +1869,153 @@ pub fn revert_release_response(response: http::Response<Vec<u8>>) -> Result<(),
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
         StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+/// The steps needed to roll back a release: checking that it exists, removing
+/// its documentation and then reverting the release itself.
+///
+/// The documentation is removed first so that a failure never leaves behind
+/// documentation for a release that no longer exists. Hex responds to the
+/// removal with `404 Not Found` both when the release has no documentation
+/// and when there is no such release, so the release is looked up first and
+/// a missing package, release or repository fails the rollback with
+/// [`ApiError::NotFound`]. Send the request from
+/// [`ReleaseRollback::next_request`] and pass its response to
+/// [`ReleaseRollback::handle_response`] until an outcome is returned.
+///
+/// ```
+/// # fn send(request: http::Request<Vec<u8>>) -> http::Response<Vec<u8>> {
+/// #     let status = if request.method() == http::Method::HEAD { 200 } else { 204 };
+/// #     http::Response::builder().status(status).body(vec![]).unwrap()
+/// # }
+/// let config = hexpm::Config::new();
+/// let release = hexpm::ReleaseRef::new("my_package", hexpm::version::Version::new(1, 0, 0));
+/// let mut rollback = hexpm::rollback_release(release).unwrap();
+/// let outcome = loop {
+///     let request = rollback.next_request("api-key", &config).unwrap();
+///     if let Some(outcome) = rollback.handle_response(send(request)) {
+///         break outcome;
+///     }
+/// };
+/// assert!(outcome.is_complete());
+/// ```
+#[cfg(feature = "api")]
+pub fn rollback_release(release: ReleaseRef) -> Result<ReleaseRollback, ApiError> {
+    release.validate()?;
+    Ok(ReleaseRollback {
+        release,
+        step: RollbackStep::CheckRelease,
+    })
+}
+
+#[cfg(feature = "api")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseRollback {
+    release: ReleaseRef,
+    step: RollbackStep,
+}
+
+#[cfg(feature = "api")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RollbackStep {
+    CheckRelease,
+    RemoveDocs,
+    RevertRelease { docs_removed: bool },
+    Done,
+}
+
+#[cfg(feature = "api")]
+impl ReleaseRollback {
+    /// The next request to send, or `None` once the rollback has finished.
+    pub fn next_request(&self, api_key: &str, config: &Config) -> Option<http::Request<Vec<u8>>> {
+        let request = match self.step {
+            RollbackStep::CheckRelease => {
+                return Some(release_exists_request(&self.release, Some(api_key), config));
+            }
+            RollbackStep::RemoveDocs => remove_docs_request(&self.release, api_key, config),
+            RollbackStep::RevertRelease { .. } => {
+                revert_release_request(&self.release, api_key, config)
+            }
+            RollbackStep::Done => return None,
+        };
+        Some(request.expect("package name and version already validated"))
+    }
+
+    /// Handle the response to the last request returned by
+    /// [`next_request`](Self::next_request), returning the outcome once the
+    /// rollback has finished.
+    pub fn handle_response(
+        &mut self,
+        response: http::Response<Vec<u8>>,
+    ) -> Option<RollbackOutcome> {
+        match self.step {
+            RollbackStep::CheckRelease => match release_exists_response(response) {
+                Ok(true) => self.step = RollbackStep::RemoveDocs,
+                Ok(false) => {
+                    self.step = RollbackStep::Done;
+                    return Some(RollbackOutcome::Failed(ApiError::NotFound));
+                }
+                Err(error) => {
+                    self.step = RollbackStep::Done;
+                    return Some(RollbackOutcome::Failed(error));
+                }
+            },
+            RollbackStep::RemoveDocs => match remove_docs_response(response) {
+                Ok(()) => self.step = RollbackStep::RevertRelease { docs_removed: true },
+                // The release exists, so it had no documentation to remove.
+                Err(ApiError::NotFound) => {
+                    self.step = RollbackStep::RevertRelease {
+                        docs_removed: false,
+                    }
+                }
+                Err(error) => {
+                    self.step = RollbackStep::Done;
+                    return Some(RollbackOutcome::Failed(error));
+                }
+            },
+            RollbackStep::RevertRelease { docs_removed } => {
+                self.step = RollbackStep::Done;
+                return Some(match revert_release_response(response) {
+                    Ok(()) => RollbackOutcome::Complete { docs_removed },
+                    Err(error) => RollbackOutcome::Partial {
+                        docs_removed,
+                        error,
+                    },
+                });
+            }
+            RollbackStep::Done => (),
+        }
+        None
+    }
+}
+
+/// The result of a [`ReleaseRollback`].
+#[cfg(feature = "api")]
+#[derive(Debug)]
+pub enum RollbackOutcome {
+    /// The release was reverted, along with its documentation if it had any.
+    Complete { docs_removed: bool },
+    /// The release could not be found, or its documentation could not be
+    /// removed, so the release was not reverted. Nothing was changed and the
+    /// rollback can be retried.
+    Failed(ApiError),
+    /// The documentation was removed, if it had any, but the release could not
+    /// be reverted and is still published.
+    Partial { docs_removed: bool, error: ApiError },
+}
+
+#[cfg(feature = "api")]
+impl RollbackOutcome {
+    pub fn is_complete(&self) -> bool {
+        matches!(self, Self::Complete { .. })
     }
 }
 
 /// See: https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.owner.ex#L47
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OwnerLevel {
     /// Has every package permission EXCEPT the ability to change who owns the package
     Maintainer,
@@ -567,11 +2032,56 @@ impl Display for OwnerLevel {
     }
 }
 
+/// An owner of a package.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Owner {
+    pub username: String,
+    /// Hex only shows the email address of owners who have made it public.
+    #[serde(default)]
+    pub email: Option<String>,
+    pub level: OwnerLevel,
+}
+
+/// API Docs:
+///
+/// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.owner.ex#L161
+///
+/// https://github.com/hexpm/hex/blob/main/lib/hex/api/package.ex#L19
+#[cfg(feature = "api")]
+pub fn get_owners_request(
+    package_name: &str,
+    api_key: Option<&str>,
+    config: &Config,
+) -> http::Request<Vec<u8>> {
+    config
+        .api_request(
+            Method::GET,
+            &format!("packages/{}/owners", package_name),
+            api_key,
+        )
+        .body(vec![])
+        .expect("get_owners_request request")
+}
+
+#[cfg(feature = "api")]
+pub fn get_owners_response(response: http::Response<Vec<u8>>) -> Result<Vec<Owner>, ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::OK => Ok(serde_json::from_slice(&body)?),
+        StatusCode::NOT_FOUND => Err(ApiError::NotFound),
+        StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
+        StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
+        StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
 /// API Docs:
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.owner.ex#L107
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/package.ex#L19
+#[cfg(feature = "api")]
 pub fn add_owner_request(
     package_name: &str,
     owner: &str,
@@ -579,10 +2089,10 @@ pub fn add_owner_request(
     api_key: &str,
     config: &Config,
 ) -> http::Request<Vec<u8>> {
-    let body = json!({
-        "level": level.to_string(),
-        "transfer": false,
-    });
+    let body = OwnerBody {
+        level,
+        transfer: false,
+    };
 
     config
         .api_request(
@@ -590,10 +2100,19 @@ pub fn add_owner_request(
             &format!("packages/{}/owners/{}", package_name, owner),
             Some(api_key),
         )
-        .body(body.to_string().into_bytes())
+        .body(json_body(&body))
         .expect("add_owner_request request")
 }
 
+/// The body of a request that adds or transfers ownership of a package.
+#[cfg(feature = "api")]
+#[derive(serde::Serialize)]
+struct OwnerBody {
+    level: OwnerLevel,
+    transfer: bool,
+}
+
+#[cfg(feature = "api")]
 pub fn add_owner_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     let (parts, body) = response.into_parts();
     match parts.status {
@@ -602,7 +2121,7 @@ pub fn add_owner_response(response: http::Response<Vec<u8>>) -> Result<(), ApiEr
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
         StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
     }
 }
 
@@ -611,16 +2130,17 @@ pub fn add_owner_response(response: http::Response<Vec<u8>>) -> Result<(), ApiEr
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.owner.ex#L125
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/package.ex#L19
+#[cfg(feature = "api")]
 pub fn transfer_owner_request(
     package_name: &str,
     owner: &str,
     api_key: &str,
     config: &Config,
 ) -> http::Request<Vec<u8>> {
-    let body = json!({
-        "level": OwnerLevel::Full.to_string(),
-        "transfer": true,
-    });
+    let body = OwnerBody {
+        level: OwnerLevel::Full,
+        transfer: true,
+    };
 
     config
         .api_request(
@@ -628,10 +2148,11 @@ pub fn transfer_owner_request(
             &format!("packages/{}/owners/{}", package_name, owner),
             Some(api_key),
         )
-        .body(body.to_string().into_bytes())
+        .body(json_body(&body))
         .expect("transfer_owner_request request")
 }
 
+#[cfg(feature = "api")]
 pub fn transfer_owner_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     let (parts, body) = response.into_parts();
     match parts.status {
@@ -640,7 +2161,7 @@ pub fn transfer_owner_response(response: http::Response<Vec<u8>>) -> Result<(),
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
         StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
     }
 }
 
@@ -649,6 +2170,7 @@ pub fn transfer_owner_response(response: http::Response<Vec<u8>>) -> Result<(),
 /// https://github.com/hexpm/hex/blob/main/lib/mix/tasks/hex.owner.ex#L139
 ///
 /// https://github.com/hexpm/hex/blob/main/lib/hex/api/package.ex#L28
+#[cfg(feature = "api")]
 pub fn remove_owner_request(
     package_name: &str,
     owner: &str,
@@ -665,6 +2187,7 @@ pub fn remove_owner_request(
         .expect("remove_owner_request request")
 }
 
+#[cfg(feature = "api")]
 pub fn remove_owner_response(response: http::Response<Vec<u8>>) -> Result<(), ApiError> {
     let (parts, body) = response.into_parts();
     match parts.status {
@@ -673,18 +2196,22 @@ pub fn remove_owner_response(response: http::Response<Vec<u8>>) -> Result<(), Ap
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
         StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
     }
 }
 
 #[derive(Error, Debug)]
 pub enum ApiError {
+    #[cfg(feature = "api")]
     #[error(transparent)]
     Json(#[from] serde_json::Error),
 
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
+    #[error("the response is not a valid Erlang term: {0}")]
+    InvalidErlangTerm(&'static str),
+
     #[error("the rate limit for the Hex API has been exceeded for this IP")]
     RateLimited,
 
@@ -697,9 +2224,13 @@ pub enum ApiError {
     #[error("the given package name {0} is not valid")]
     InvalidPackageNameFormat(String),
 
+    #[error("the given repository name {0} is not valid")]
+    InvalidRepositoryNameFormat(String),
+
     #[error("the payload signature does not match the downloaded payload")]
     IncorrectPayloadSignature,
 
+    #[cfg(feature = "registry")]
     #[error(transparent)]
     InvalidProtobuf(#[from] prost::DecodeError),
 
@@ -724,28 +2255,214 @@ pub enum ApiError {
     #[error("must explicitly express your intention to replace the release")]
     NotReplacing,
 
-    #[error("can only modify a release up to one hour after publication")]
-    LateModification,
+    #[error("can only modify a release {} after publication", describe_window(.window))]
+    LateModification { window: Option<Duration> },
+
+    #[error("the given key is not a valid PEM encoded PKCS#8 RSA private key")]
+    InvalidSigningKey,
+
+    #[error("the response was larger than the limit of {0} bytes")]
+    ResponseTooLarge(u64),
+
+    #[error("the registry resource has more {entries} than the limit of {limit}")]
+    TooManyEntries { entries: &'static str, limit: usize },
+
+    #[error(
+        "the response ended early while reading {expected_hint}, it may have been cut off by the network or a proxy"
+    )]
+    TruncatedResponse { expected_hint: &'static str },
+
+    #[error("an API key with this name already exists")]
+    KeyNameTaken,
+
+    #[error("the {argument} argument is not valid: {reason}")]
+    InvalidArgument {
+        argument: &'static str,
+        reason: String,
+    },
+
+    #[error("the resource has moved to {location}")]
+    Redirect {
+        status: StatusCode,
+        location: String,
+    },
+
+    #[error("Hex is temporarily unavailable{}", describe_unavailable(.message))]
+    ServiceUnavailable {
+        /// When to try again, from the `retry-after` header.
+        retry_after: Option<RetryAfter>,
+        /// The explanation given by Hex, such as a maintenance notice.
+        message: Option<String>,
+    },
 }
 
-impl ApiError {
-    fn unexpected_response(status: StatusCode, body: Vec<u8>) -> Self {
-        ApiError::UnexpectedResponse(status, String::from_utf8_lossy(&body).to_string())
+fn describe_unavailable(message: &Option<String>) -> String {
+    match message {
+        Some(message) => format!(": {message}"),
+        None => String::new(),
     }
+}
 
-    /// Returns `true` if the api error is [`NotFound`].
-    ///
-    /// [`NotFound`]: ApiError::NotFound
-    pub fn is_not_found(&self) -> bool {
-        matches!(self, Self::NotFound)
-    }
+/// When to retry a request, as given by a `retry-after` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryAfter {
+    Delay(Duration),
+    Date(SystemTime),
 }
 
-/// Read a body and ensure it has the given sha256 digest.
-fn read_and_check_body(reader: impl std::io::Read, checksum: &[u8]) -> Result<Vec<u8>, ApiError> {
-    use std::io::Read;
-    let mut reader = BufReader::new(reader);
-    let mut context = Context::new(&SHA256);
+impl RetryAfter {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.trim().parse() {
+            Ok(seconds) => Some(RetryAfter::Delay(Duration::from_secs(seconds))),
+            Err(_) => parse_http_date(value).map(RetryAfter::Date),
+        }
+    }
+
+    /// How long to wait from `now` before retrying.
+    pub fn delay(&self, now: SystemTime) -> Duration {
+        match self {
+            RetryAfter::Delay(delay) => *delay,
+            RetryAfter::Date(date) => date.duration_since(now).unwrap_or_default(),
+        }
+    }
+}
+
+/// The message of a `503` response body, such as a maintenance notice.
+/// Without the `api` feature the body cannot be parsed and none is given.
+fn maintenance_message(body: &[u8]) -> Option<String> {
+    #[cfg(feature = "api")]
+    {
+        #[derive(Deserialize)]
+        struct Body {
+            message: String,
+        }
+        serde_json::from_slice::<Body>(body)
+            .ok()
+            .map(|body| body.message)
+    }
+    #[cfg(not(feature = "api"))]
+    {
+        let _ = body;
+        None
+    }
+}
+
+fn describe_window(window: &Option<Duration>) -> String {
+    match window.map(|window| window.as_secs()) {
+        Some(3600) => "up to one hour".to_string(),
+        Some(secs) if secs % 3600 == 0 => format!("up to {} hours", secs / 3600),
+        Some(60) => "up to one minute".to_string(),
+        Some(secs) if secs % 60 == 0 => format!("up to {} minutes", secs / 60),
+        Some(1) => "up to one second".to_string(),
+        Some(secs) => format!("up to {} seconds", secs),
+        None => "for a limited time".to_string(),
+    }
+}
+
+impl ApiError {
+    /// An error status that the endpoint has no specific handling for. Hex
+    /// responds to every endpoint with 503 while in maintenance mode, so that
+    /// is recognised here.
+    fn unexpected_response(parts: &http::response::Parts, body: Vec<u8>) -> Self {
+        if parts.status == StatusCode::SERVICE_UNAVAILABLE {
+            return ApiError::ServiceUnavailable {
+                retry_after: parts
+                    .headers
+                    .get(http::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(RetryAfter::parse),
+                message: maintenance_message(&body),
+            };
+        }
+        ApiError::UnexpectedResponse(parts.status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    /// A redirect from a repository endpoint, for transports that do not
+    /// follow redirects automatically. A redirect without a location is
+    /// unexpected.
+    fn redirect(parts: &http::response::Parts, body: Vec<u8>) -> Self {
+        match parts
+            .headers
+            .get(http::header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+        {
+            Some(location) => ApiError::Redirect {
+                status: parts.status,
+                location: location.to_string(),
+            },
+            None => ApiError::unexpected_response(parts, body),
+        }
+    }
+
+    /// Returns `true` if the api error is [`NotFound`].
+    ///
+    /// [`NotFound`]: ApiError::NotFound
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::NotFound)
+    }
+
+    /// A stable machine-readable identifier for the kind of error, suitable
+    /// for returning to clients of services that use this library. Unlike the
+    /// `Display` output these will not change between versions.
+    pub fn code(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "api")]
+            ApiError::Json(_) => "invalid_json",
+            ApiError::Io(_) => "io",
+            ApiError::InvalidErlangTerm(_) => "invalid_erlang_term",
+            ApiError::RateLimited => "rate_limited",
+            ApiError::InvalidCredentials => "invalid_credentials",
+            ApiError::UnexpectedResponse(_, _) => "unexpected_response",
+            ApiError::InvalidPackageNameFormat(_) => "invalid_package_name",
+            ApiError::InvalidRepositoryNameFormat(_) => "invalid_repository_name",
+            ApiError::IncorrectPayloadSignature => "signature_mismatch",
+            #[cfg(feature = "registry")]
+            ApiError::InvalidProtobuf(_) => "invalid_protobuf",
+            ApiError::InvalidVersionFormat(_) => "invalid_version",
+            ApiError::NotFound => "not_found",
+            ApiError::InvalidVersionRequirementFormat(_) => "invalid_version_requirement",
+            ApiError::IncorrectChecksum => "checksum_mismatch",
+            ApiError::InvalidApiKey => "invalid_api_key",
+            ApiError::Forbidden => "forbidden",
+            ApiError::NotReplacing => "not_replacing",
+            ApiError::LateModification { .. } => "late_modification",
+            ApiError::InvalidSigningKey => "invalid_signing_key",
+            ApiError::ResponseTooLarge(_) => "response_too_large",
+            ApiError::TooManyEntries { .. } => "too_many_entries",
+            ApiError::TruncatedResponse { .. } => "truncated_response",
+            ApiError::KeyNameTaken => "key_name_taken",
+            ApiError::InvalidArgument { .. } => "invalid_argument",
+            ApiError::Redirect { .. } => "redirect",
+            ApiError::ServiceUnavailable { .. } => "service_unavailable",
+        }
+    }
+}
+
+/// Errors are serialized as an object with the stable `code` of the error and
+/// a human readable `message`.
+impl serde::Serialize for ApiError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut error = serializer.serialize_struct("ApiError", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+/// Read a body of at most `max_bytes` and ensure it has the given sha256
+/// digest.
+#[cfg(feature = "registry")]
+fn read_and_check_body(
+    reader: impl Read,
+    checksum: &[u8],
+    max_bytes: u64,
+) -> Result<Vec<u8>, ApiError> {
+    let mut reader = BufReader::new(reader);
+    let mut context = Context::new(&SHA256);
     let mut buffer = [0; 1024];
     let mut body = Vec::new();
 
@@ -754,6 +2471,9 @@ fn read_and_check_body(reader: impl std::io::Read, checksum: &[u8]) -> Result<Ve
         if count == 0 {
             break;
         }
+        if body.len() as u64 + count as u64 > max_bytes {
+            return Err(ApiError::ResponseTooLarge(max_bytes));
+        }
         let bytes = &buffer[..count];
         context.update(bytes);
         body.extend_from_slice(bytes);
@@ -767,90 +2487,623 @@ fn read_and_check_body(reader: impl std::io::Read, checksum: &[u8]) -> Result<Ve
     }
 }
 
-fn proto_to_retirement_status(
-    status: Option<proto::package::RetirementStatus>,
-) -> Option<RetirementStatus> {
-    status.map(|stat| RetirementStatus {
-        message: stat.message().into(),
-        reason: proto_to_retirement_reason(stat.reason()),
-    })
+/// The name of a package.
+///
+/// Package names are reference counted so that cloning one is cheap. Names
+/// that appear many times in registry data, such as the name of a dependency
+/// required by every release of a package, share a single allocation.
+///
+/// Maps keyed by `PackageName` can be queried with a `&str`, as it borrows as
+/// a `str`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PackageName(std::sync::Arc<str>);
+
+impl PackageName {
+    /// The longest package name Hex can store.
+    pub const MAX_LENGTH: usize = 255;
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Check a name against Hex's rules: it must start with a lowercase ASCII
+    /// letter, contain only ASCII letters, digits and underscores, and be at
+    /// most [`MAX_LENGTH`](Self::MAX_LENGTH) characters long. Every rule the
+    /// name breaks is reported.
+    pub fn validate(name: &str) -> Result<(), InvalidPackageName> {
+        let mut violations = vec![];
+        let mut chars = name.chars();
+        match chars.next() {
+            None => violations.push(NameViolation::Empty),
+            Some(first) if !first.is_ascii_lowercase() => {
+                violations.push(NameViolation::InvalidStart(first))
+            }
+            Some(_) => (),
+        }
+        violations.extend(
+            chars
+                .enumerate()
+                .filter(|(_, c)| !c.is_ascii_alphanumeric() && *c != '_')
+                .map(|(position, character)| NameViolation::InvalidCharacter {
+                    position: position + 1,
+                    character,
+                }),
+        );
+        let length = name.chars().count();
+        if length > Self::MAX_LENGTH {
+            violations.push(NameViolation::TooLong(length));
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidPackageName {
+                name: name.to_string(),
+                violations,
+            })
+        }
+    }
+
+    /// Turn user input, such as `" Phoenix-LiveView "`, into a valid package
+    /// name by trimming whitespace, lowercasing, and replacing hyphens, dots
+    /// and spaces with underscores. Input that is still invalid after this,
+    /// such as a name starting with a digit, is reported as with
+    /// [`validate`](Self::validate).
+    pub fn normalize(input: &str) -> Result<Self, InvalidPackageName> {
+        let name: String = input
+            .trim()
+            .chars()
+            .map(|c| match c {
+                '-' | '.' | ' ' => '_',
+                c => c.to_ascii_lowercase(),
+            })
+            .collect();
+        Self::validate(&name)?;
+        Ok(name.into())
+    }
 }
 
-fn proto_to_retirement_reason(reason: proto::package::RetirementReason) -> RetirementReason {
-    use proto::package::RetirementReason::*;
-    match reason {
-        RetiredOther => RetirementReason::Other,
-        RetiredInvalid => RetirementReason::Invalid,
-        RetiredSecurity => RetirementReason::Security,
-        RetiredDeprecated => RetirementReason::Deprecated,
-        RetiredRenamed => RetirementReason::Renamed,
-    }
-}
-
-fn proto_to_dep(dep: proto::package::Dependency) -> Result<(String, Dependency), ApiError> {
-    let app = dep.app;
-    let repository = dep.repository;
-    let requirement = Range::new(dep.requirement.clone())
-        .map_err(|_| ApiError::InvalidVersionFormat(dep.requirement))?;
-    Ok((
-        dep.package,
-        Dependency {
-            requirement,
-            optional: dep.optional.is_some(),
-            app,
-            repository,
-        },
-    ))
-}
-
-fn proto_to_release(release: proto::package::Release) -> Result<Release<()>, ApiError> {
-    let dependencies = release
-        .dependencies
-        .clone()
-        .into_iter()
-        .map(proto_to_dep)
-        .collect::<Result<HashMap<_, _>, _>>()?;
-    let version = Version::try_from(release.version.as_str())
-        .expect("Failed to parse version format from Hex");
-    Ok(Release {
-        version,
-        outer_checksum: release.outer_checksum.unwrap_or_default(),
-        retirement_status: proto_to_retirement_status(release.retired),
-        requirements: dependencies,
-        meta: (),
-    })
+/// A rule of package names that a name breaks. Positions count characters
+/// from zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameViolation {
+    Empty,
+    /// The name does not start with a lowercase ASCII letter.
+    InvalidStart(char),
+    /// A character other than an ASCII letter, digit or underscore.
+    InvalidCharacter {
+        position: usize,
+        character: char,
+    },
+    /// The name is longer than [`PackageName::MAX_LENGTH`] characters.
+    TooLong(usize),
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct Package {
+impl Display for NameViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameViolation::Empty => write!(f, "it is empty"),
+            NameViolation::InvalidStart(c) => {
+                write!(f, "it starts with {c:?} rather than a lowercase letter")
+            }
+            NameViolation::InvalidCharacter {
+                position,
+                character,
+            } => write!(f, "it contains {character:?} at position {position}"),
+            NameViolation::TooLong(length) => write!(
+                f,
+                "it is {length} characters long, more than the limit of {}",
+                PackageName::MAX_LENGTH
+            ),
+        }
+    }
+}
+
+/// A package name that breaks Hex's naming rules.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "{name:?} is not a valid package name as {}",
+    describe_violations(violations)
+)]
+pub struct InvalidPackageName {
     pub name: String,
+    pub violations: Vec<NameViolation>,
+}
+
+fn describe_violations(violations: &[NameViolation]) -> String {
+    violations
+        .iter()
+        .map(|violation| violation.to_string())
+        .collect::<Vec<_>>()
+        .join(", and ")
+}
+
+impl std::ops::Deref for PackageName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::borrow::Borrow<str> for PackageName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for PackageName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for PackageName {
+    fn from(name: &str) -> Self {
+        Self(name.into())
+    }
+}
+
+impl From<String> for PackageName {
+    fn from(name: String) -> Self {
+        Self(name.into())
+    }
+}
+
+impl PartialEq<str> for PackageName {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for PackageName {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl Display for PackageName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::fmt::Debug for PackageName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for PackageName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s: std::borrow::Cow<'de, str> = serde::de::Deserialize::deserialize(deserializer)?;
+        Ok(s.as_ref().into())
+    }
+}
+
+impl serde::Serialize for PackageName {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// A release of a package, identifying it in the API and repository.
+///
+/// Releases in the default `hexpm` repository have no `repository`. Releases of
+/// packages belonging to an organisation name the organisation's repository.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReleaseRef {
+    pub package: PackageName,
+    pub version: Version,
+    pub repository: Option<String>,
+}
+
+impl ReleaseRef {
+    pub fn new(package: impl Into<PackageName>, version: Version) -> Self {
+        Self {
+            package: package.into(),
+            version,
+            repository: None,
+        }
+    }
+
+    /// Parse a package name and version as given by a user, such as on the
+    /// command line.
+    pub fn parse(package: &str, version: &str) -> Result<Self, ApiError> {
+        validate_package_and_version(package, version)?;
+        let version = Version::parse(version)
+            .map_err(|_| ApiError::InvalidVersionFormat(version.to_string()))?;
+        Ok(Self::new(package, version))
+    }
+
+    /// Name the repository of the release without checking the name, such as
+    /// the repository of a package record. Use
+    /// [`try_in_repository`](Self::try_in_repository) for names given by a
+    /// user.
+    pub fn in_repository(self, repository: impl Into<String>) -> Self {
+        Self {
+            repository: Some(repository.into()),
+            ..self
+        }
+    }
+
+    /// Name the repository of the release, failing with
+    /// [`ApiError::InvalidRepositoryNameFormat`] if the name is not valid.
+    pub fn try_in_repository(self, repository: &str) -> Result<Self, ApiError> {
+        validate_repository_name(repository)?;
+        Ok(self.in_repository(repository))
+    }
+
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_package_and_version(&self.package, &self.version.to_string())?;
+        match &self.repository {
+            Some(repository) => validate_repository_name(repository),
+            None => Ok(()),
+        }
+    }
+
+    /// Requests for packages in other repositories are made under
+    /// `repos/<repository>/`, both in the API and in the repository. Names
+    /// that were not validated are percent encoded so that they stay within
+    /// their path segment.
+    fn repository_prefix(&self) -> String {
+        match self.repository.as_deref() {
+            None | Some("hexpm") => String::new(),
+            Some(repository) => {
+                let mut prefix = "repos/".to_string();
+                for byte in repository.bytes() {
+                    if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+                        prefix.push(byte as char);
+                    } else {
+                        prefix.push_str(&format!("%{byte:02X}"));
+                    }
+                }
+                prefix.push('/');
+                prefix
+            }
+        }
+    }
+
+    #[cfg(feature = "api")]
+    fn api_path(&self) -> String {
+        format!(
+            "{}packages/{}/releases/{}",
+            self.repository_prefix(),
+            self.package,
+            self.version
+        )
+    }
+
+    fn repository_path(&self, directory: &str, extension: &str) -> String {
+        format!(
+            "{}{directory}/{}-{}.{extension}",
+            self.repository_prefix(),
+            self.package,
+            self.version
+        )
+    }
+}
+
+impl Display for ReleaseRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(repository) = &self.repository {
+            write!(f, "{repository}/")?;
+        }
+        write!(f, "{} {}", self.package, self.version)
+    }
+}
+
+/// Shares the allocation of package names that are seen more than once while
+/// converting registry data.
+#[cfg(feature = "registry")]
+#[derive(Default)]
+pub(crate) struct NameInterner(std::collections::HashSet<PackageName>);
+
+#[cfg(feature = "registry")]
+impl NameInterner {
+    pub(crate) fn intern(&mut self, name: String) -> PackageName {
+        if let Some(name) = self.0.get(name.as_str()) {
+            return name.clone();
+        }
+        let name = PackageName::from(name);
+        self.0.insert(name.clone());
+        name
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize)]
+pub struct Package {
+    pub name: PackageName,
     pub repository: String,
     pub releases: Vec<Release<()>>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize)]
+impl Package {
+    /// Iterate over the releases of this package published on the given
+    /// channel, in the order they appear in the registry.
+    pub fn releases_in_channel(&self, channel: Channel) -> impl Iterator<Item = &Release<()>> {
+        self.releases
+            .iter()
+            .filter(move |release| channel.includes(&release.version))
+    }
+
+    /// Iterate over the releases of this package matching every criterion of
+    /// the filter, in the order they appear in the registry.
+    pub fn filter_releases<'a>(
+        &'a self,
+        filter: &'a ReleaseFilter,
+    ) -> impl Iterator<Item = &'a Release<()>> {
+        self.releases
+            .iter()
+            .filter(move |release| filter.matches(release))
+    }
+
+    /// The changes in requirements between two releases of this package, or
+    /// `None` if either version is not in the registry.
+    pub fn requirements_diff(&self, from: &Version, to: &Version) -> Option<RequirementsDiff> {
+        let release = |version| self.releases.iter().find(|r| &r.version == version);
+        let (from, to) = (&release(from)?.requirements, &release(to)?.requirements);
+
+        let mut diff = RequirementsDiff::default();
+        for (name, new) in to {
+            match from.get(name) {
+                None => diff.added.push((name.clone(), new.clone())),
+                Some(old) if !old.is_equivalent(new) => diff.changed.push(ChangedRequirement {
+                    name: name.clone(),
+                    from: old.clone(),
+                    to: new.clone(),
+                }),
+                Some(_) => (),
+            }
+        }
+        diff.removed = from
+            .iter()
+            .filter(|(name, _)| !to.contains_key(*name))
+            .map(|(name, dependency)| (name.clone(), dependency.clone()))
+            .collect();
+        diff.added.sort_by(|a, b| a.0.cmp(&b.0));
+        diff.removed.sort_by(|a, b| a.0.cmp(&b.0));
+        diff.changed.sort_by(|a, b| a.name.cmp(&b.name));
+        Some(diff)
+    }
+
+    /// Counts of the releases of this package overall and in each major
+    /// version series, such as for a version picker grouped by major version.
+    pub fn version_stats(&self) -> VersionStats {
+        let mut stats = VersionStats::default();
+        let mut series: BTreeMap<u32, SeriesStats> = BTreeMap::new();
+        for release in &self.releases {
+            let version = &release.version;
+            let entry = series.entry(version.major).or_insert_with(|| SeriesStats {
+                major: version.major,
+                releases: 0,
+                pre_releases: 0,
+                retired: 0,
+                latest: version.clone(),
+                latest_stable: None,
+            });
+            entry.releases += 1;
+            stats.releases += 1;
+            if version.is_pre() {
+                entry.pre_releases += 1;
+                stats.pre_releases += 1;
+            } else if entry
+                .latest_stable
+                .as_ref()
+                .is_none_or(|latest| version > latest)
+            {
+                entry.latest_stable = Some(version.clone());
+            }
+            if release.is_retired() {
+                entry.retired += 1;
+                stats.retired += 1;
+            }
+            if version > &entry.latest {
+                entry.latest = version.clone();
+            }
+        }
+        stats.series = series.into_values().rev().collect();
+        stats
+    }
+}
+
+/// Counts of the releases of a package, see [`Package::version_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionStats {
+    pub releases: usize,
+    pub pre_releases: usize,
+    pub retired: usize,
+    /// Each major version series with releases, highest first.
+    pub series: Vec<SeriesStats>,
+}
+
+/// Counts of the releases of a package with the same major version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeriesStats {
+    pub major: u32,
+    pub releases: usize,
+    pub pre_releases: usize,
+    pub retired: usize,
+    /// The highest version in the series, which may be a pre-release.
+    pub latest: Version,
+    /// The highest version in the series that is not a pre-release, if any.
+    pub latest_stable: Option<Version>,
+}
+
+/// The changes in requirements between two releases of a package, each sorted
+/// by package name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequirementsDiff {
+    pub added: Vec<(PackageName, Dependency)>,
+    pub removed: Vec<(PackageName, Dependency)>,
+    pub changed: Vec<ChangedRequirement>,
+}
+
+impl RequirementsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Packages that the newer release requires and the older one did not,
+    /// either because they are new dependencies or because they are no
+    /// longer optional. Upgrading may pull these and their dependencies in.
+    pub fn newly_required(&self) -> impl Iterator<Item = &PackageName> {
+        let added = self
+            .added
+            .iter()
+            .filter(|(_, dependency)| !dependency.optional)
+            .map(|(name, _)| name);
+        let no_longer_optional = self
+            .changed
+            .iter()
+            .filter(|change| change.from.optional && !change.to.optional)
+            .map(|change| &change.name);
+        added.chain(no_longer_optional)
+    }
+}
+
+/// Criteria for selecting releases with [`Package::filter_releases`]. The
+/// default filter matches every release.
+///
+/// ```
+/// use hexpm::ReleaseFilter;
+/// use hexpm::version::Range;
+///
+/// let filter = ReleaseFilter::new()
+///     .exclude_retired()
+///     .exclude_pre()
+///     .in_range(Range::new("~> 1.2".into()).unwrap());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReleaseFilter {
+    exclude_retired: bool,
+    exclude_pre: bool,
+    range: Option<Range>,
+}
+
+impl ReleaseFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skip releases that have been retired.
+    pub fn exclude_retired(mut self) -> Self {
+        self.exclude_retired = true;
+        self
+    }
+
+    /// Skip pre-releases.
+    pub fn exclude_pre(mut self) -> Self {
+        self.exclude_pre = true;
+        self
+    }
+
+    /// Only match releases whose version is in the range. Pre-releases within
+    /// the range match unless they are excluded with
+    /// [`exclude_pre`](Self::exclude_pre).
+    pub fn in_range(mut self, range: Range) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    pub fn matches<Meta>(&self, release: &Release<Meta>) -> bool {
+        if self.exclude_retired && release.is_retired() {
+            return false;
+        }
+        if self.exclude_pre && release.version.is_pre() {
+            return false;
+        }
+        self.range
+            .as_ref()
+            .is_none_or(|range| range.to_pubgrub().contains(&release.version))
+    }
+}
+
+/// A dependency whose requirement differs between two releases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedRequirement {
+    pub name: PackageName,
+    pub from: Dependency,
+    pub to: Dependency,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Release<Meta> {
     /// Release version
     pub version: Version,
     /// All dependencies of the release
-    pub requirements: HashMap<String, Dependency>,
+    #[serde(deserialize_with = "deserialize_requirements")]
+    pub requirements: HashMap<PackageName, Dependency>,
     /// If set the release is retired, a retired release should only be
     /// resolved if it has already been locked in a project
     pub retirement_status: Option<RetirementStatus>,
     /// sha256 checksum of outer package tarball
     /// required when encoding but optional when decoding
-    #[serde(alias = "checksum", deserialize_with = "deserialize_checksum")]
+    #[serde(
+        alias = "checksum",
+        deserialize_with = "deserialize_checksum",
+        serialize_with = "serialize_checksum"
+    )]
     pub outer_checksum: Vec<u8>,
     /// This is not present in all API endpoints so may be absent sometimes.
     pub meta: Meta,
 }
 
+/// Requirements are objects in current API responses, but some older and
+/// private servers give only the requirement string, which is read as a
+/// required dependency on the package of the same name.
+fn deserialize_requirements<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<PackageName, Dependency>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Requirement {
+        Dependency(Dependency),
+        Bare(Range),
+    }
+
+    let requirements: HashMap<PackageName, Requirement> =
+        serde::de::Deserialize::deserialize(deserializer)?;
+    Ok(requirements
+        .into_iter()
+        .map(|(name, requirement)| {
+            let dependency = match requirement {
+                Requirement::Dependency(dependency) => dependency,
+                Requirement::Bare(requirement) => Dependency {
+                    requirement,
+                    optional: false,
+                    app: None,
+                    repository: None,
+                },
+            };
+            (name, dependency)
+        })
+        .collect())
+}
+
 fn deserialize_checksum<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
-    let s: &str = serde::de::Deserialize::deserialize(deserializer)?;
-    base16::decode(s).map_err(serde::de::Error::custom)
+    let s: std::borrow::Cow<'de, str> = serde::de::Deserialize::deserialize(deserializer)?;
+    checksum::Checksum::from_hex(&s)
+        .map(Vec::from)
+        .map_err(serde::de::Error::custom)
+}
+
+fn serialize_checksum<S>(checksum: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&base16::encode_lower(checksum))
 }
 
 impl<Meta> Release<Meta> {
@@ -859,16 +3112,54 @@ impl<Meta> Release<Meta> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize)]
+#[cfg(feature = "api")]
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
 pub struct ReleaseMeta {
     pub app: String,
     pub build_tools: Vec<String>,
+    /// The Elixir version requirement of the release, if it was built with
+    /// Mix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elixir: Option<String>,
+    /// Any other fields sent by the API. These are preserved so that data
+    /// added to the API after this library was written is not lost when the
+    /// metadata is serialized again.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(from = "RetirementStatusFields")]
 pub struct RetirementStatus {
     pub reason: RetirementReason,
     pub message: String,
+    /// The reason given by the registry when it is not one this library
+    /// knows, in which case `reason` is `Other`. Reasons from the protobuf
+    /// registry are given as their number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unknown_reason: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct RetirementStatusFields {
+    reason: String,
+    message: String,
+    #[serde(default)]
+    unknown_reason: Option<String>,
+}
+
+impl From<RetirementStatusFields> for RetirementStatus {
+    fn from(fields: RetirementStatusFields) -> Self {
+        let (reason, unknown_reason) = match RetirementReason::from_known_str(&fields.reason) {
+            Some(reason) => (reason, fields.unknown_reason),
+            None => (RetirementReason::Other, Some(fields.reason)),
+        };
+        Self {
+            reason,
+            message: fields.message,
+            unknown_reason,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -885,19 +3176,33 @@ impl<'de> serde::Deserialize<'de> for RetirementReason {
     where
         D: serde::Deserializer<'de>,
     {
-        let s: &str = serde::de::Deserialize::deserialize(deserializer)?;
-        match s {
-            "other" => Ok(RetirementReason::Other),
-            "invalid" => Ok(RetirementReason::Invalid),
-            "security" => Ok(RetirementReason::Security),
-            "deprecated" => Ok(RetirementReason::Deprecated),
-            "renamed" => Ok(RetirementReason::Renamed),
-            _ => Err(serde::de::Error::custom("unknown retirement reason type")),
-        }
+        let s: std::borrow::Cow<'de, str> = serde::de::Deserialize::deserialize(deserializer)?;
+        RetirementReason::from_known_str(&s)
+            .ok_or_else(|| serde::de::Error::custom("unknown retirement reason type"))
+    }
+}
+
+impl serde::Serialize for RetirementReason {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_str())
     }
 }
 
 impl RetirementReason {
+    fn from_known_str(reason: &str) -> Option<Self> {
+        match reason {
+            "other" => Some(RetirementReason::Other),
+            "invalid" => Some(RetirementReason::Invalid),
+            "security" => Some(RetirementReason::Security),
+            "deprecated" => Some(RetirementReason::Deprecated),
+            "renamed" => Some(RetirementReason::Renamed),
+            _ => None,
+        }
+    }
+
     pub fn to_str(&self) -> &'static str {
         match self {
             RetirementReason::Other => "other",
@@ -909,7 +3214,7 @@ impl RetirementReason {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Dependency {
     /// Version requirement of dependency
     pub requirement: Range,
@@ -923,14 +3228,64 @@ pub struct Dependency {
     pub repository: Option<String>,
 }
 
+impl Dependency {
+    /// Dependencies are equivalent if they match the same versions, even if
+    /// their requirements are written differently.
+    fn is_equivalent(&self, other: &Dependency) -> bool {
+        self.requirement.to_pubgrub() == other.requirement.to_pubgrub()
+            && self.optional == other.optional
+            && self.app == other.app
+            && self.repository == other.repository
+    }
+}
+
 static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), " (", env!("CARGO_PKG_VERSION"), ")");
 
+/// Serialize the JSON body of a request. Bodies are structs rather than
+/// `json!` values so that their fields are always written in declaration
+/// order, keeping request bodies byte for byte the same between releases.
+#[cfg(feature = "api")]
+fn json_body(body: &impl serde::Serialize) -> Vec<u8> {
+    serde_json::to_vec(body).expect("request body serialization")
+}
+
+/// Parse the body of an API response, which is JSON unless the response says
+/// it is in the Erlang term format asked for with [`Config::api_format`].
+#[cfg(feature = "api")]
+fn parse_api_body<T: serde::de::DeserializeOwned>(
+    parts: &http::response::Parts,
+    body: &[u8],
+) -> Result<T, ApiError> {
+    let content_type = parts
+        .headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if content_type.starts_with(ApiFormat::Erlang.content_type()) {
+        let value = etf::decode(body).map_err(ApiError::InvalidErlangTerm)?;
+        Ok(serde_json::from_value(value)?)
+    } else {
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
+fn validate_repository_name(repository: &str) -> Result<(), ApiError> {
+    lazy_static! {
+        static ref REPOSITORY_PATTERN: Regex = Regex::new(r"^[a-z0-9][a-z0-9_\-]*$").unwrap();
+    }
+    if !REPOSITORY_PATTERN.is_match(repository) {
+        return Err(ApiError::InvalidRepositoryNameFormat(
+            repository.to_string(),
+        ));
+    }
+    Ok(())
+}
+
 fn validate_package_and_version(package: &str, version: &str) -> Result<(), ApiError> {
     lazy_static! {
-        static ref PACKAGE_PATTERN: Regex = Regex::new(r"^[a-z]\w*$").unwrap();
         static ref VERSION_PATTERN: Regex = Regex::new(r"^[a-zA-Z-0-9\._-]+$").unwrap();
     }
-    if !PACKAGE_PATTERN.is_match(package) {
+    if PackageName::validate(package).is_err() {
         return Err(ApiError::InvalidPackageNameFormat(package.to_string()));
     }
     if !VERSION_PATTERN.is_match(version) {
@@ -939,68 +3294,368 @@ fn validate_package_and_version(package: &str, version: &str) -> Result<(), ApiE
     Ok(())
 }
 
-// To quote the docs:
-//
-// > All resources will be signed by the repository's private key.
-// > A signed resource is wrapped in a Signed message. The data under
-// > the payload field is signed by the signature field.
-// >
-// > The signature is an (unencoded) RSA signature of the (unencoded)
-// > SHA-512 digest of the payload.
-//
-// https://github.com/hexpm/specifications/blob/master/registry-v2.md#signing
-//
-fn verify_payload(mut signed: Signed, pem_public_key: &[u8]) -> Result<Vec<u8>, ApiError> {
+/// Decompress and decode a signed registry resource, returning the verified
+/// payload.
+///
+/// The body is inflated as it is read, so when it is read from a stream the
+/// compressed resource is never held in full alongside the inflated one. The
+/// payload shares the inflated buffer rather than being copied out of it.
+#[cfg(feature = "registry")]
+fn decode_signed_resource(body: impl Read, public_key: &[u8]) -> Result<Bytes, ApiError> {
+    let inflated = inflate(body, hexpm_defaults::MAX_INFLATED_RESOURCE_SIZE)?;
+    let resource = registry::SignedResource::from_inflated(inflated)?;
+    Ok(resource.verify(&[public_key])?.payload)
+}
+
+/// Decompress a gzip body. Empty bodies and bodies that end part way through
+/// the compressed stream are reported as truncated rather than as the io
+/// error the decoder gives.
+#[cfg(feature = "registry")]
+fn inflate(body: impl Read, limit: u64) -> Result<Vec<u8>, ApiError> {
+    let truncated = ApiError::TruncatedResponse {
+        expected_hint: "gzip compressed data",
+    };
+    let mut body = BufReader::new(body);
+    if body.fill_buf()?.is_empty() {
+        return Err(truncated);
+    }
+    let mut decoder = GzDecoder::new(body);
+    match read_limited(&mut decoder, limit) {
+        // The decoder fails at the point a corrupt stream goes wrong, so if it
+        // used up all of the input then the stream was cut off.
+        Err(ApiError::Io(error))
+            if error.kind() == std::io::ErrorKind::UnexpectedEof
+                || decoder
+                    .get_mut()
+                    .fill_buf()
+                    .is_ok_and(|rest| rest.is_empty()) =>
+        {
+            Err(truncated)
+        }
+        result => result,
+    }
+}
+
+fn read_limited(reader: impl Read, limit: u64) -> Result<Vec<u8>, ApiError> {
+    let mut body = Vec::new();
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut body)?;
+    if body.len() as u64 > limit {
+        return Err(ApiError::ResponseTooLarge(limit));
+    }
+    Ok(body)
+}
+
+/// Verify an RSA signature of the SHA-512 digest of some data, as used for
+/// registry resources.
+#[cfg(feature = "registry")]
+fn verify_signature(data: &[u8], signature: &[u8], pem_public_key: &[u8]) -> Result<(), ApiError> {
     let (_, pem) = x509_parser::pem::parse_x509_pem(pem_public_key)
         .map_err(|_| ApiError::IncorrectPayloadSignature)?;
     let (_, spki) = x509_parser::prelude::SubjectPublicKeyInfo::from_der(&pem.contents)
         .map_err(|_| ApiError::IncorrectPayloadSignature)?;
-    let payload = std::mem::take(&mut signed.payload);
-    let verification = ring::signature::UnparsedPublicKey::new(
+    ring::signature::UnparsedPublicKey::new(
         &ring::signature::RSA_PKCS1_2048_8192_SHA512,
         &spki.subject_public_key,
     )
-    .verify(payload.as_slice(), signed.signature());
+    .verify(data, signature)
+    .map_err(|_| ApiError::IncorrectPayloadSignature)
+}
 
-    if verification.is_ok() {
-        Ok(payload)
-    } else {
-        Err(ApiError::IncorrectPayloadSignature)
+/// The fingerprint of a PEM encoded public key, as `SHA256:` followed by the
+/// lowercase hexadecimal SHA-256 digest of the DER encoded key.
+#[cfg(feature = "registry")]
+pub fn public_key_fingerprint(pem_public_key: &[u8]) -> Result<String, ApiError> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(pem_public_key)
+        .map_err(|_| ApiError::IncorrectPayloadSignature)?;
+    let digest = ring::digest::digest(&SHA256, &pem.contents);
+    Ok(format!("SHA256:{}", base16::encode_lower(digest.as_ref())))
+}
+
+/// Create a detached signature for a release tarball, so that it can be
+/// distributed alongside the tarball and verified with
+/// [`verify_release_signature`].
+///
+/// The signature is made the same way as the registry signs its resources: an
+/// RSA signature of the SHA-512 digest of the tarball. The key must be a PEM
+/// encoded PKCS#8 RSA private key of at least 2048 bits.
+///
+/// Hex itself does not yet support package signatures, so this is intended
+/// for private registries and attestation workflows.
+#[cfg(feature = "registry")]
+pub fn sign_release_tarball(tarball: &[u8], pem_private_key: &[u8]) -> Result<Vec<u8>, ApiError> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(pem_private_key)
+        .map_err(|_| ApiError::InvalidSigningKey)?;
+    let key = ring::signature::RsaKeyPair::from_pkcs8(&pem.contents)
+        .map_err(|_| ApiError::InvalidSigningKey)?;
+    let mut signature = vec![0; key.public().modulus_len()];
+    key.sign(
+        &ring::signature::RSA_PKCS1_SHA512,
+        &ring::rand::SystemRandom::new(),
+        tarball,
+        &mut signature,
+    )
+    .map_err(|_| ApiError::InvalidSigningKey)?;
+    Ok(signature)
+}
+
+/// Verify a detached release tarball signature created by
+/// [`sign_release_tarball`], using the PEM encoded public key of the signer.
+#[cfg(feature = "registry")]
+pub fn verify_release_signature(
+    tarball: &[u8],
+    signature: &[u8],
+    pem_public_key: &[u8],
+) -> Result<(), ApiError> {
+    verify_signature(tarball, signature, pem_public_key)
+}
+
+/// A package as listed by the Hex API.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PackageSummary {
+    pub name: String,
+    pub meta: PackageSummaryMeta,
+    /// The latest release, including pre-releases.
+    #[serde(default)]
+    pub latest_version: Option<Version>,
+    #[serde(default)]
+    pub latest_stable_version: Option<Version>,
+    #[serde(default)]
+    pub downloads: PackageDownloads,
+}
+
+/// How many times a package's releases have been downloaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct PackageDownloads {
+    #[serde(default)]
+    pub all: u64,
+    /// Downloads in the last 90 days.
+    #[serde(default)]
+    pub recent: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PackageSummaryMeta {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub licenses: Vec<String>,
+    #[serde(default)]
+    pub links: std::collections::BTreeMap<String, String>,
+}
+
+impl PackageSummaryMeta {
+    pub fn links(&self) -> links::PackageLinks {
+        links::PackageLinks::new(self.links.clone())
+    }
+}
+
+/// The order of packages listed by [`get_packages_request`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageSort {
+    Name,
+    RecentDownloads,
+    TotalDownloads,
+    InsertedAt,
+    UpdatedAt,
+}
+
+impl PackageSort {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            PackageSort::Name => "name",
+            PackageSort::RecentDownloads => "recent_downloads",
+            PackageSort::TotalDownloads => "total_downloads",
+            PackageSort::InsertedAt => "inserted_at",
+            PackageSort::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+/// The query parameters of a request listing packages. The default query
+/// lists the first page of packages in Hex's default order.
+///
+/// ```
+/// use hexpm::{PackageSort, Query};
+///
+/// let query = Query::new()
+///     .search("name:gleam_* description:\"web & http\"")
+///     .sort(PackageSort::RecentDownloads)
+///     .page(2);
+/// assert_eq!(
+///     query.to_query_string(),
+///     "search=name%3Agleam_*+description%3A%22web+%26+http%22&sort=recent_downloads&page=2"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    search: Option<String>,
+    sort: Option<PackageSort>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only list packages matching a search, using Hex's search syntax.
+    pub fn search(mut self, search: impl Into<String>) -> Self {
+        self.search = Some(search.into());
+        self
+    }
+
+    pub fn sort(mut self, sort: PackageSort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// The page to list, numbered from 1.
+    pub fn page(mut self, page: u32) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    /// The number of packages on each page. Hex caps this at 100.
+    pub fn per_page(mut self, per_page: u32) -> Self {
+        self.per_page = Some(per_page);
+        self
+    }
+
+    /// The parameters URL encoded, without a leading `?`.
+    pub fn to_query_string(&self) -> String {
+        let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+        if let Some(search) = &self.search {
+            serializer.append_pair("search", search);
+        }
+        if let Some(sort) = self.sort {
+            serializer.append_pair("sort", sort.to_str());
+        }
+        if let Some(page) = self.page {
+            serializer.append_pair("page", &page.to_string());
+        }
+        if let Some(per_page) = self.per_page {
+            serializer.append_pair("per_page", &per_page.to_string());
+        }
+        serializer.finish()
+    }
+}
+
+/// Create a request to list the packages in the registry matching a query.
+/// The response is parsed with [`get_packages_page_response`].
+///
+/// API Docs:
+///
+/// https://github.com/hexpm/hex/blob/main/lib/hex/api/package.ex#L6
+#[cfg(feature = "api")]
+pub fn get_packages_request(
+    query: &Query,
+    api_key: Option<&str>,
+    config: &Config,
+) -> http::Request<Vec<u8>> {
+    let query = query.to_query_string();
+    let path = match query.as_str() {
+        "" => "packages".to_string(),
+        query => format!("packages?{}", query),
+    };
+    config
+        .api_request(Method::GET, &path, api_key)
+        .header("accept", config.api_format.content_type())
+        .body(vec![])
+        .expect("get_packages_request request")
+}
+
+/// Create a request to get a page of the packages in the registry, ordered by
+/// name. Pages are numbered from 1 and hold up to 100 packages, so an empty
+/// page means there are no more.
+///
+/// API Docs:
+///
+/// https://github.com/hexpm/hex/blob/main/lib/hex/api/package.ex#L6
+#[cfg(feature = "api")]
+pub fn get_packages_page_request(
+    page: u32,
+    api_key: Option<&str>,
+    config: &Config,
+) -> http::Request<Vec<u8>> {
+    let query = Query::new().sort(PackageSort::Name).page(page);
+    get_packages_request(&query, api_key, config)
+}
+
+/// Parse a response to get a page of the packages in the registry.
+#[cfg(feature = "api")]
+pub fn get_packages_page_response(
+    response: http::Response<Vec<u8>>,
+) -> Result<Vec<PackageSummary>, ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::OK => parse_api_body(&parts, &body),
+        StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
+        StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
+        StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
     }
 }
 
 /// Create a request to get the information for a package release.
 ///
+#[cfg(feature = "api")]
 pub fn get_package_release_request(
-    name: &str,
-    version: &str,
+    release: &ReleaseRef,
     api_key: Option<&str>,
     config: &Config,
 ) -> http::Request<Vec<u8>> {
     config
-        .api_request(
-            Method::GET,
-            &format!("packages/{}/releases/{}", name, version),
-            api_key,
-        )
-        .header("accept", "application/json")
+        .api_request(Method::GET, &release.api_path(), api_key)
+        .header("accept", config.api_format.content_type())
         .body(vec![])
         .expect("get_package_release request")
 }
 
 /// Parse a response to get the information for a package release.
 ///
+#[cfg(feature = "api")]
 pub fn get_package_release_response(
     response: http::Response<Vec<u8>>,
 ) -> Result<Release<ReleaseMeta>, ApiError> {
     let (parts, body) = response.into_parts();
 
     match parts.status {
-        StatusCode::OK => Ok(serde_json::from_slice(&body)?),
+        StatusCode::OK => parse_api_body(&parts, &body),
         StatusCode::NOT_FOUND => Err(ApiError::NotFound),
         StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
         StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
         StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
-        status => Err(ApiError::unexpected_response(status, body)),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
+    }
+}
+
+/// Create a request that checks whether a release exists without downloading
+/// its information, such as before publishing it or its documentation.
+#[cfg(feature = "api")]
+pub fn release_exists_request(
+    release: &ReleaseRef,
+    api_key: Option<&str>,
+    config: &Config,
+) -> http::Request<Vec<u8>> {
+    config
+        .api_request(Method::HEAD, &release.api_path(), api_key)
+        .body(vec![])
+        .expect("release_exists_request request")
+}
+
+/// Parse a response to check whether a release exists.
+#[cfg(feature = "api")]
+pub fn release_exists_response(response: http::Response<Vec<u8>>) -> Result<bool, ApiError> {
+    let (parts, body) = response.into_parts();
+    match parts.status {
+        StatusCode::OK => Ok(true),
+        StatusCode::NOT_FOUND => Ok(false),
+        StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited),
+        StatusCode::UNAUTHORIZED => Err(ApiError::InvalidApiKey),
+        StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
+        _ => Err(ApiError::unexpected_response(&parts, body)),
     }
 }
@@ -0,0 +1,126 @@
+//! Interpreting the `links` of a package's metadata, such as its GitHub
+//! repository and changelog.
+//!
+//! Links are free-form name and URL pairs chosen by the package author, so
+//! the well known ones are recognised by name regardless of case.
+//!
+//! ```
+//! use hexpm::links::PackageLinks;
+//! use hexpm::version::Version;
+//!
+//! let links = PackageLinks::new([("GitHub", "https://github.com/gleam-lang/stdlib")]);
+//! assert_eq!(
+//!     links.changelog_url(&Version::new(0, 33, 0), &Version::new(0, 34, 0)).as_deref(),
+//!     Some("https://github.com/gleam-lang/stdlib/compare/v0.33.0...v0.34.0"),
+//! );
+//! ```
+
+use crate::version::Version;
+
+/// The links of a package, with the well known ones picked out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageLinks {
+    /// The source repository, from a `GitHub`, `GitLab`, `Repository` or
+    /// `Source` link.
+    pub repository: Option<String>,
+    /// From a `Changelog` link.
+    pub changelog: Option<String>,
+    /// From a `Website` or `Homepage` link.
+    pub website: Option<String>,
+    /// Every link, in the order given.
+    pub all: Vec<(String, String)>,
+}
+
+impl PackageLinks {
+    pub fn new<N, U>(links: impl IntoIterator<Item = (N, U)>) -> Self
+    where
+        N: Into<String>,
+        U: Into<String>,
+    {
+        let mut result = Self::default();
+        for (name, url) in links {
+            let (name, url) = (name.into(), url.into());
+            let field = match name.to_ascii_lowercase().as_str() {
+                "github" | "gitlab" | "repository" | "source" => Some(&mut result.repository),
+                "changelog" => Some(&mut result.changelog),
+                "website" | "homepage" => Some(&mut result.website),
+                _ => None,
+            };
+            if let Some(field) = field
+                && field.is_none()
+            {
+                *field = Some(url.clone());
+            }
+            result.all.push((name, url));
+        }
+        result
+    }
+
+    /// The GitHub repository of the package as `owner/name`, if its
+    /// repository link points to one.
+    pub fn github_repository(&self) -> Option<String> {
+        let url = self.repository.as_deref()?;
+        let path = url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .strip_prefix("github.com/")?;
+        let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+        let owner = segments.next()?;
+        let name = segments.next()?.trim_end_matches(".git");
+        Some(format!("{owner}/{name}"))
+    }
+
+    /// A best guess at where to read about the changes between two versions.
+    ///
+    /// For packages on GitHub this compares the tags of the two versions,
+    /// assuming they are tagged `v1.2.3`. Otherwise it is the package's
+    /// changelog, if it links to one.
+    pub fn changelog_url(&self, from: &Version, to: &Version) -> Option<String> {
+        match self.github_repository() {
+            Some(repository) => Some(format!(
+                "https://github.com/{repository}/compare/v{from}...v{to}"
+            )),
+            None => self.changelog.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn well_known_links() {
+        let links = PackageLinks::new([
+            ("Website", "https://gleam.run"),
+            ("Changelog", "https://example.com/CHANGELOG.md"),
+            ("github", "https://github.com/gleam-lang/stdlib.git"),
+            ("Sponsor", "https://example.com/sponsor"),
+        ]);
+        assert_eq!(links.website.as_deref(), Some("https://gleam.run"));
+        assert_eq!(
+            links.changelog.as_deref(),
+            Some("https://example.com/CHANGELOG.md")
+        );
+        assert_eq!(
+            links.github_repository().as_deref(),
+            Some("gleam-lang/stdlib")
+        );
+        assert_eq!(links.all.len(), 4);
+    }
+
+    #[test]
+    fn changelog_url() {
+        let (from, to) = (Version::new(1, 0, 0), Version::new(1, 1, 0));
+        let links = PackageLinks::new([
+            ("GitLab", "https://gitlab.com/someone/thing"),
+            ("Changelog", "https://example.com/CHANGELOG.md"),
+        ]);
+        assert_eq!(links.github_repository(), None);
+        assert_eq!(
+            links.changelog_url(&from, &to).as_deref(),
+            Some("https://example.com/CHANGELOG.md")
+        );
+        assert_eq!(PackageLinks::default().changelog_url(&from, &to), None);
+    }
+}
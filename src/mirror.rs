@@ -0,0 +1,739 @@
+//! Planning the downloads needed to keep a mirror of a Hex repository up to
+//! date.
+//!
+//! A [`MirrorPlan`] compares the versions index of the upstream repository
+//! with the contents of a [`MirrorStore`] and lists the package records and
+//! tarballs to download or remove. The caller performs each task with the
+//! transport and storage of their choice, reporting back as tasks complete.
+//! Plans implement `Serialize` and `Deserialize` so that an interrupted
+//! synchronisation can be saved and resumed.
+//!
+//! The `names` and `versions` resources are not part of the plan, as the
+//! versions index is needed to create the plan and both are to be replaced
+//! once all tasks have completed.
+//!
+//! Clients installing from a mirror can check tarballs against the primary
+//! repository's signed package records with [`verify_mirrored_tarball`], so
+//! that a compromised mirror cannot serve modified tarballs along with package
+//! records to match.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "registry")]
+use crate::Package;
+use crate::bulk::{BulkError, BulkSummary};
+use crate::checksum::Checksum;
+use crate::version::Version;
+use crate::{Config, ReleaseRef};
+
+/// The contents of a mirror.
+pub trait MirrorStore {
+    /// The names of the packages that have a package record in the mirror.
+    fn packages(&self) -> Vec<String>;
+
+    /// The versions of a package that have a tarball in the mirror.
+    fn tarballs(&self, package: &str) -> Vec<Version>;
+}
+
+/// A single step in synchronising a mirror.
+///
+/// The variants are declared in the order the plan performs them, which
+/// [`MirrorPlan::resume`] relies on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MirrorTask {
+    /// Download a release tarball.
+    Tarball { package: String, version: Version },
+    /// Download the record of a package that is new or has changed.
+    Package { package: String },
+    /// Remove the record of a package that is no longer in the repository.
+    RemovePackage { package: String },
+    /// Remove the tarball of a release that is no longer in the repository.
+    RemoveTarball { package: String, version: Version },
+}
+
+impl MirrorTask {
+    /// The request to send to the upstream repository for this task, or
+    /// `None` for tasks that only affect the mirror.
+    pub fn request(
+        &self,
+        api_key: Option<&str>,
+        config: &Config,
+    ) -> Option<http::Request<Vec<u8>>> {
+        match self {
+            MirrorTask::Tarball { package, version } => Some(crate::get_package_tarball_request(
+                &crate::ReleaseRef::new(package.as_str(), version.clone()),
+                api_key,
+                config,
+            )),
+            MirrorTask::Package { package } => {
+                Some(crate::get_package_request(package, api_key, config))
+            }
+            MirrorTask::RemovePackage { .. } | MirrorTask::RemoveTarball { .. } => None,
+        }
+    }
+
+    /// The name of the package the task is for.
+    pub fn package(&self) -> &str {
+        match self {
+            MirrorTask::Tarball { package, .. }
+            | MirrorTask::Package { package }
+            | MirrorTask::RemovePackage { package }
+            | MirrorTask::RemoveTarball { package, .. } => package,
+        }
+    }
+
+    /// Tasks in a later phase must not start until all tasks of earlier
+    /// phases have finished.
+    fn phase(&self) -> u8 {
+        match self {
+            MirrorTask::Tarball { .. } => 0,
+            MirrorTask::Package { .. } => 1,
+            MirrorTask::RemovePackage { .. } | MirrorTask::RemoveTarball { .. } => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for MirrorTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MirrorTask::Tarball { package, version } => {
+                write!(f, "download of tarball {package} {version}")
+            }
+            MirrorTask::Package { package } => write!(f, "download of package {package}"),
+            MirrorTask::RemovePackage { package } => write!(f, "removal of package {package}"),
+            MirrorTask::RemoveTarball { package, version } => {
+                write!(f, "removal of tarball {package} {version}")
+            }
+        }
+    }
+}
+
+/// The tasks needed to bring a mirror up to date, and their progress.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MirrorPlan {
+    pending: VecDeque<MirrorTask>,
+    in_progress: BTreeSet<MirrorTask>,
+    failed: Vec<FailedTask>,
+    completed: usize,
+    downloads: DownloadStats,
+}
+
+/// The number and total size of the downloads completed so far, used to
+/// estimate the size of those remaining.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct DownloadStats {
+    count: u64,
+    bytes: u64,
+}
+
+/// A task that failed, along with a description of the error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedTask {
+    pub task: MirrorTask,
+    pub error: String,
+}
+
+impl std::fmt::Display for FailedTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed: {}", self.task, self.error)
+    }
+}
+
+/// A summary of the progress of a [`MirrorPlan`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MirrorProgress {
+    pub pending: usize,
+    pub in_progress: usize,
+    pub failed: usize,
+    pub completed: usize,
+    /// The total size of the downloads completed so far, in bytes, from their
+    /// `content-length` headers.
+    pub downloaded_bytes: u64,
+}
+
+impl MirrorProgress {
+    pub fn total(&self) -> usize {
+        self.pending + self.in_progress + self.failed + self.completed
+    }
+}
+
+impl MirrorPlan {
+    /// Plan the tasks needed to bring the store up to date with the given
+    /// versions index.
+    ///
+    /// Tarballs are downloaded before package records, and package records
+    /// are updated before tarballs are removed, so that a mirror interrupted
+    /// part way through never has a package record that refers to a missing
+    /// tarball.
+    ///
+    /// Only changes to the versions of a package are detected. Changes to
+    /// retirement status or dependencies of existing releases are not visible
+    /// in the versions index, so package records are to be refreshed
+    /// periodically by other means.
+    pub fn new(index: &HashMap<String, Vec<Version>>, store: &impl MirrorStore) -> Self {
+        let mirrored: BTreeSet<String> = store.packages().into_iter().collect();
+        let mut tarballs = vec![];
+        let mut packages = vec![];
+        let mut removals = vec![];
+
+        let mut names: Vec<_> = index.keys().collect();
+        names.sort();
+        for name in names {
+            let versions = &index[name];
+            let stored = store.tarballs(name);
+            let mut changed = !mirrored.contains(name);
+            for version in versions.iter().filter(|v| !stored.contains(v)) {
+                changed = true;
+                tarballs.push(MirrorTask::Tarball {
+                    package: name.clone(),
+                    version: version.clone(),
+                });
+            }
+            for version in stored.iter().filter(|v| !versions.contains(v)) {
+                changed = true;
+                removals.push(MirrorTask::RemoveTarball {
+                    package: name.clone(),
+                    version: version.clone(),
+                });
+            }
+            if changed {
+                packages.push(MirrorTask::Package {
+                    package: name.clone(),
+                });
+            }
+        }
+
+        for name in mirrored.iter().filter(|name| !index.contains_key(*name)) {
+            removals.push(MirrorTask::RemovePackage {
+                package: name.clone(),
+            });
+            for version in store.tarballs(name) {
+                removals.push(MirrorTask::RemoveTarball {
+                    package: name.clone(),
+                    version,
+                });
+            }
+        }
+
+        Self {
+            pending: tarballs
+                .into_iter()
+                .chain(packages)
+                .chain(removals)
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Take the next task to perform, marking it as in progress.
+    pub fn next_task(&mut self) -> Option<MirrorTask> {
+        let task = self.pending.pop_front()?;
+        self.in_progress.insert(task.clone());
+        Some(task)
+    }
+
+    /// Record that a task has been performed successfully.
+    pub fn complete(&mut self, task: &MirrorTask) {
+        if self.in_progress.remove(task) {
+            self.completed += 1;
+        }
+    }
+
+    /// Record that a task could not be performed.
+    ///
+    /// The pending tasks of the same package in later phases are failed along
+    /// with it, so that a package record is not updated to refer to a tarball
+    /// that is missing from the mirror, and tarballs are not removed while the
+    /// old package record still refers to them. They are queued again by
+    /// [`retry_failed`](Self::retry_failed).
+    pub fn fail(&mut self, task: &MirrorTask, error: impl std::fmt::Display) {
+        if !self.in_progress.remove(task) {
+            return;
+        }
+        self.failed.push(FailedTask {
+            task: task.clone(),
+            error: error.to_string(),
+        });
+        let (held_back, pending) = self.pending.drain(..).partition(|pending: &MirrorTask| {
+            pending.package() == task.package() && pending.phase() > task.phase()
+        });
+        self.pending = pending;
+        for held_back in held_back {
+            self.failed.push(FailedTask {
+                task: held_back,
+                error: format!("held back as the {task} failed"),
+            });
+        }
+    }
+
+    /// The tasks that have failed.
+    pub fn failed(&self) -> &[FailedTask] {
+        &self.failed
+    }
+
+    /// Queue the tasks that have failed to be attempted again.
+    pub fn retry_failed(&mut self) {
+        self.pending
+            .extend(self.failed.drain(..).map(|failed| failed.task));
+        self.pending
+            .make_contiguous()
+            .sort_by_key(MirrorTask::phase);
+    }
+
+    /// Reorder the pending tasks by the given key, lowest first, such as to
+    /// download the most popular packages first. Tasks are only reordered
+    /// within their phase, so tarballs are still downloaded before package
+    /// records.
+    ///
+    /// ```
+    /// # use std::{cmp::Reverse, collections::HashMap};
+    /// # use hexpm::mirror::MirrorPlan;
+    /// # let mut plan = MirrorPlan::default();
+    /// let downloads: HashMap<String, u64> = HashMap::new();
+    /// plan.prioritize(|task| Reverse(downloads.get(task.package()).copied().unwrap_or(0)));
+    /// ```
+    pub fn prioritize<K: Ord>(&mut self, mut key: impl FnMut(&MirrorTask) -> K) {
+        self.pending
+            .make_contiguous()
+            .sort_by_cached_key(|task| (task.phase(), key(task)));
+    }
+
+    /// Take up to `max_concurrent` tasks to perform at once, less any tasks
+    /// already in progress, limiting the load placed on the upstream
+    /// repository.
+    ///
+    /// A batch never mixes phases, and no tasks are returned from a later
+    /// phase while tasks of an earlier phase are still in progress.
+    pub fn next_batch(&mut self, max_concurrent: usize) -> Vec<MirrorTask> {
+        let mut batch = vec![];
+        let running_phase = self.in_progress.iter().map(MirrorTask::phase).min();
+        while self.in_progress.len() < max_concurrent {
+            let Some(next) = self.pending.front() else {
+                break;
+            };
+            if running_phase.is_some_and(|phase| next.phase() > phase)
+                || batch
+                    .first()
+                    .is_some_and(|first: &MirrorTask| next.phase() > first.phase())
+            {
+                break;
+            }
+            batch.extend(self.next_task());
+        }
+        batch
+    }
+
+    /// Record that a download has completed successfully, using the
+    /// `content-length` header of its response to estimate the size of the
+    /// remaining downloads.
+    pub fn complete_download(&mut self, task: &MirrorTask, headers: &http::HeaderMap) {
+        let length = headers
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse::<u64>().ok());
+        if self.in_progress.contains(task)
+            && let Some(length) = length
+        {
+            self.downloads.count += 1;
+            self.downloads.bytes += length;
+        }
+        self.complete(task);
+    }
+
+    /// An estimate of the number of bytes still to be downloaded, based on the
+    /// average size of the downloads completed so far. Returns `None` until a
+    /// download has completed.
+    pub fn estimated_remaining_bytes(&self) -> Option<u64> {
+        let average = self.downloads.bytes.checked_div(self.downloads.count)?;
+        let remaining = self
+            .pending
+            .iter()
+            .chain(&self.in_progress)
+            .filter(|task| task.phase() < 2)
+            .count() as u64;
+        Some(average * remaining)
+    }
+
+    /// Prepare a plan loaded from storage to continue. Tasks that were in
+    /// progress when the plan was saved may not have finished, so they are
+    /// queued to be performed again.
+    pub fn resume(&mut self) {
+        let interrupted = std::mem::take(&mut self.in_progress);
+        for task in interrupted.into_iter().rev() {
+            self.pending.push_front(task);
+        }
+    }
+
+    pub fn progress(&self) -> MirrorProgress {
+        MirrorProgress {
+            pending: self.pending.len(),
+            in_progress: self.in_progress.len(),
+            failed: self.failed.len(),
+            completed: self.completed,
+            downloaded_bytes: self.downloads.bytes,
+        }
+    }
+
+    /// How many of the tasks performed so far succeeded and failed.
+    pub fn summary(&self) -> BulkSummary {
+        BulkSummary {
+            succeeded: self.completed,
+            failed: self.failed.len(),
+        }
+    }
+
+    /// The number of tasks that completed, or every failed task along with
+    /// that number if any failed, for callers that need the whole plan to
+    /// succeed. See [`BulkResult::into_result`](crate::bulk::BulkResult::into_result).
+    pub fn into_result(self) -> Result<usize, BulkError<FailedTask>> {
+        if self.failed.is_empty() {
+            Ok(self.completed)
+        } else {
+            Err(BulkError {
+                errors: self.failed,
+                succeeded: self.completed,
+            })
+        }
+    }
+
+    /// Returns `true` once every task has either completed or failed.
+    pub fn is_finished(&self) -> bool {
+        self.pending.is_empty() && self.in_progress.is_empty()
+    }
+}
+
+/// The result of checking a tarball downloaded from a mirror against the
+/// primary repository. See [`verify_mirrored_tarball`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirrorTrustReport {
+    pub release: ReleaseRef,
+    /// The checksum of the tarball as downloaded from the mirror.
+    pub tarball_checksum: Checksum,
+    /// The checksum in the primary repository's package record, or `None` if
+    /// the primary repository has no such release.
+    pub primary_checksum: Option<Vec<u8>>,
+    /// The checksum in the mirror's package record, if one was given and it
+    /// has the release.
+    pub mirror_checksum: Option<Vec<u8>>,
+}
+
+impl MirrorTrustReport {
+    /// Returns `true` if the tarball is the one the primary repository
+    /// published, regardless of what the mirror's package record says.
+    pub fn is_trusted(&self) -> bool {
+        self.primary_checksum.as_deref() == Some(self.tarball_checksum.as_bytes())
+    }
+
+    /// Returns `true` if the mirror's package record disagrees with the
+    /// primary repository's, which suggests the mirror is stale or has been
+    /// tampered with even if this tarball is trusted.
+    pub fn mirror_record_differs(&self) -> bool {
+        self.mirror_checksum.is_some() && self.mirror_checksum != self.primary_checksum
+    }
+}
+
+/// Check a tarball downloaded from a mirror against the package record from
+/// the primary repository, which must have been fetched from the primary
+/// repository and parsed with [`get_package_response`](crate::get_package_response)
+/// using the primary repository's public key. The mirror's own record of the
+/// package may also be given, so that disagreements between the two are
+/// reported.
+#[cfg(feature = "registry")]
+pub fn verify_mirrored_tarball(
+    tarball: &[u8],
+    version: &Version,
+    primary: &Package,
+    mirror: Option<&Package>,
+) -> MirrorTrustReport {
+    let checksum = |package: &Package| {
+        package
+            .releases
+            .iter()
+            .find(|release| &release.version == version)
+            .map(|release| release.outer_checksum.clone())
+    };
+    MirrorTrustReport {
+        release: ReleaseRef::new(primary.name.clone(), version.clone())
+            .in_repository(primary.repository.clone()),
+        tarball_checksum: Checksum::of(tarball),
+        primary_checksum: checksum(primary),
+        mirror_checksum: mirror.and_then(checksum),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Store(HashMap<String, Vec<Version>>);
+
+    impl MirrorStore for Store {
+        fn packages(&self) -> Vec<String> {
+            self.0.keys().cloned().collect()
+        }
+
+        fn tarballs(&self, package: &str) -> Vec<Version> {
+            self.0.get(package).cloned().unwrap_or_default()
+        }
+    }
+
+    fn v(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    fn tarball(package: &str, version: &str) -> MirrorTask {
+        MirrorTask::Tarball {
+            package: package.to_string(),
+            version: v(version),
+        }
+    }
+
+    fn package(package: &str) -> MirrorTask {
+        MirrorTask::Package {
+            package: package.to_string(),
+        }
+    }
+
+    fn plan() -> MirrorPlan {
+        let index = [
+            ("gleam_stdlib".to_string(), vec![v("0.1.0"), v("0.2.0")]),
+            ("gleam_json".to_string(), vec![v("1.0.0")]),
+            ("unchanged".to_string(), vec![v("1.0.0")]),
+        ]
+        .into();
+        let store = Store(
+            [
+                ("gleam_stdlib".to_string(), vec![v("0.1.0"), v("0.1.1")]),
+                ("unchanged".to_string(), vec![v("1.0.0")]),
+                ("removed".to_string(), vec![v("2.0.0")]),
+            ]
+            .into(),
+        );
+        MirrorPlan::new(&index, &store)
+    }
+
+    fn drain(plan: &mut MirrorPlan) -> Vec<MirrorTask> {
+        let mut tasks = vec![];
+        while let Some(task) = plan.next_task() {
+            plan.complete(&task);
+            tasks.push(task);
+        }
+        tasks
+    }
+
+    #[test]
+    fn plan_tasks() {
+        let mut plan = plan();
+        assert_eq!(
+            drain(&mut plan),
+            vec![
+                tarball("gleam_json", "1.0.0"),
+                tarball("gleam_stdlib", "0.2.0"),
+                package("gleam_json"),
+                package("gleam_stdlib"),
+                MirrorTask::RemoveTarball {
+                    package: "gleam_stdlib".to_string(),
+                    version: v("0.1.1"),
+                },
+                MirrorTask::RemovePackage {
+                    package: "removed".to_string(),
+                },
+                MirrorTask::RemoveTarball {
+                    package: "removed".to_string(),
+                    version: v("2.0.0"),
+                },
+            ]
+        );
+        assert!(plan.is_finished());
+        assert_eq!(plan.progress().completed, 7);
+    }
+
+    #[test]
+    fn task_requests() {
+        let config = Config::new();
+        let request = tarball("gleam_json", "1.0.0")
+            .request(None, &config)
+            .unwrap();
+        assert_eq!(request.uri().path(), "/tarballs/gleam_json-1.0.0.tar");
+        let request = package("gleam_json").request(None, &config).unwrap();
+        assert_eq!(request.uri().path(), "/packages/gleam_json");
+        let removal = MirrorTask::RemovePackage {
+            package: "removed".to_string(),
+        };
+        assert!(removal.request(None, &config).is_none());
+    }
+
+    #[test]
+    fn failures_and_retries() {
+        let mut plan = plan();
+        let task = plan.next_task().unwrap();
+        plan.fail(&task, "connection reset");
+        assert_eq!(plan.failed()[0].error, "connection reset");
+        assert_eq!(plan.progress().failed, 2);
+
+        let remaining = drain(&mut plan);
+        assert!(plan.is_finished());
+        assert!(!remaining.contains(&task));
+
+        assert_eq!(plan.summary().to_string(), "5 succeeded, 2 failed");
+        let error = plan.clone().into_result().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2 of 7 items failed, the first with: \
+             download of tarball gleam_json 1.0.0 failed: connection reset"
+        );
+
+        plan.retry_failed();
+        assert_eq!(drain(&mut plan), vec![task, package("gleam_json")]);
+        assert!(plan.failed().is_empty());
+        assert_eq!(plan.into_result(), Ok(7));
+    }
+
+    #[test]
+    fn failed_tarballs_hold_back_package_records() {
+        let mut plan = plan();
+        let batch = plan.next_batch(5);
+        plan.fail(&batch[1], "timeout");
+        plan.complete(&batch[0]);
+
+        assert_eq!(
+            plan.failed()[1],
+            FailedTask {
+                task: package("gleam_stdlib"),
+                error: "held back as the download of tarball gleam_stdlib 0.2.0 failed".to_string(),
+            }
+        );
+        let remaining = drain(&mut plan);
+        assert!(
+            !remaining
+                .iter()
+                .any(|task| task.package() == "gleam_stdlib")
+        );
+        assert_eq!(plan.progress().failed, 3);
+
+        plan.retry_failed();
+        assert_eq!(
+            drain(&mut plan)[..2],
+            [tarball("gleam_stdlib", "0.2.0"), package("gleam_stdlib")]
+        );
+        assert!(plan.failed().is_empty());
+    }
+
+    #[test]
+    fn resume() {
+        let mut plan = plan();
+        let first = plan.next_task().unwrap();
+        let second = plan.next_task().unwrap();
+        plan.complete(&second);
+
+        let saved = serde_json::to_string(&plan).unwrap();
+        let mut resumed: MirrorPlan = serde_json::from_str(&saved).unwrap();
+        assert_eq!(resumed, plan);
+        resumed.resume();
+
+        let progress = resumed.progress();
+        assert_eq!(progress.in_progress, 0);
+        assert_eq!(progress.completed, 1);
+        assert_eq!(progress.total(), 7);
+        assert_eq!(resumed.next_task(), Some(first));
+    }
+
+    #[test]
+    fn prioritize() {
+        let mut plan = plan();
+        plan.prioritize(|task| std::cmp::Reverse(task.package().to_string()));
+        assert_eq!(
+            drain(&mut plan)[..4],
+            [
+                tarball("gleam_stdlib", "0.2.0"),
+                tarball("gleam_json", "1.0.0"),
+                package("gleam_stdlib"),
+                package("gleam_json"),
+            ]
+        );
+    }
+
+    #[test]
+    fn batches_respect_limits_and_phases() {
+        let mut plan = plan();
+        let batch = plan.next_batch(5);
+        assert_eq!(
+            batch,
+            vec![
+                tarball("gleam_json", "1.0.0"),
+                tarball("gleam_stdlib", "0.2.0")
+            ]
+        );
+        // Package records wait for the tarballs to finish.
+        assert!(plan.next_batch(5).is_empty());
+
+        plan.complete(&batch[0]);
+        assert!(plan.next_batch(5).is_empty());
+        plan.complete(&batch[1]);
+
+        let batch = plan.next_batch(1);
+        assert_eq!(batch, vec![package("gleam_json")]);
+        assert!(plan.next_batch(1).is_empty());
+    }
+
+    #[test]
+    fn retried_tasks_keep_phase_order() {
+        let mut plan = plan();
+        let task = plan.next_task().unwrap();
+        plan.fail(&task, "timeout");
+        plan.retry_failed();
+        assert_eq!(plan.next_task(), Some(tarball("gleam_stdlib", "0.2.0")));
+        assert_eq!(plan.next_task(), Some(task));
+    }
+
+    #[test]
+    fn size_estimates() {
+        let mut plan = plan();
+        assert_eq!(plan.estimated_remaining_bytes(), None);
+        let task = plan.next_task().unwrap();
+        let mut headers = http::HeaderMap::new();
+        headers.insert(http::header::CONTENT_LENGTH, "1000".parse().unwrap());
+        plan.complete_download(&task, &headers);
+        // One tarball and two package records remain.
+        assert_eq!(plan.estimated_remaining_bytes(), Some(3000));
+        assert_eq!(plan.progress().downloaded_bytes, 1000);
+    }
+
+    #[cfg(feature = "registry")]
+    fn record(version: &str, tarball: &[u8]) -> Package {
+        Package {
+            name: "gleam_stdlib".into(),
+            repository: "hexpm".to_string(),
+            releases: vec![crate::Release {
+                version: v(version),
+                requirements: HashMap::new(),
+                retirement_status: None,
+                outer_checksum: Checksum::of(tarball).into(),
+                meta: (),
+            }],
+        }
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn mirrored_tarballs() {
+        let primary = record("0.34.0", b"published");
+
+        let report = verify_mirrored_tarball(b"published", &v("0.34.0"), &primary, None);
+        assert!(report.is_trusted());
+        assert!(!report.mirror_record_differs());
+        assert_eq!(report.release.package, "gleam_stdlib");
+
+        let mirror = record("0.34.0", b"modified");
+        let report = verify_mirrored_tarball(b"modified", &v("0.34.0"), &primary, Some(&mirror));
+        assert!(!report.is_trusted());
+        assert!(report.mirror_record_differs());
+
+        let report = verify_mirrored_tarball(b"published", &v("0.35.0"), &primary, None);
+        assert_eq!(report.primary_checksum, None);
+        assert!(!report.is_trusted());
+    }
+}
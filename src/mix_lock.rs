@@ -0,0 +1,592 @@
+//! Reading and writing Elixir's `mix.lock` file, so that tools written in Rust
+//! can work with the locked dependencies of existing Elixir projects.
+//!
+//! A `mix.lock` file is an Elixir map from package names to tuples describing
+//! the locked dependency:
+//!
+//! ```text
+//! %{
+//!   "jason": {:hex, :jason, "1.4.0", "e855...", [:mix], [{:decimal, "~> 1.0 or ~> 2.0", [hex: :decimal, repo: "hexpm", optional: true]}], "hexpm", "79a3..."},
+//! }
+//! ```
+//!
+//! Hex dependencies are decoded into [`HexLock`] values. Other dependencies,
+//! such as those from git, are kept as the source text of their term so that
+//! they are written back unchanged.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use thiserror::Error;
+
+use crate::checksum::Checksum;
+use crate::version::Version;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MixLockError {
+    #[error("invalid mix.lock syntax at byte {position}: {message}")]
+    Syntax {
+        position: usize,
+        message: &'static str,
+    },
+
+    #[error("the mix.lock entry for {package} is not valid: {reason}")]
+    InvalidEntry {
+        package: String,
+        reason: &'static str,
+    },
+}
+
+/// The contents of a `mix.lock` file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MixLock {
+    /// The locked dependencies, keyed by the name of their OTP application.
+    pub packages: BTreeMap<String, LockEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockEntry {
+    Hex(HexLock),
+    /// A dependency that is not from Hex, as the source text of its term.
+    Other(String),
+}
+
+/// A dependency locked to a release from a Hex repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexLock {
+    /// The name of the package, which may differ from the application name.
+    pub name: String,
+    pub version: Version,
+    /// The checksum of the tarball contents, as found in its `CHECKSUM` file.
+    pub inner_checksum: Vec<u8>,
+    pub build_tools: Vec<String>,
+    pub dependencies: Vec<LockDependency>,
+    pub repository: String,
+    /// The checksum of the whole tarball. Lock files written by versions of
+    /// Hex before 0.21 do not include it.
+    pub outer_checksum: Option<Vec<u8>>,
+}
+
+/// A dependency of a locked Hex package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockDependency {
+    /// The name of the dependency's OTP application.
+    pub app: String,
+    pub requirement: Option<String>,
+    /// The name of the dependency's package.
+    pub package: String,
+    pub repository: String,
+    pub optional: bool,
+}
+
+impl MixLock {
+    /// Parse the contents of a `mix.lock` file.
+    pub fn parse(input: &str) -> Result<Self, MixLockError> {
+        let mut parser = Parser { input, position: 0 };
+        let term = parser.term()?;
+        parser.skip_whitespace();
+        if parser.position != input.len() {
+            return parser.error("unexpected input after the lock map");
+        }
+        let Term::Map(entries) = term.value else {
+            return Err(MixLockError::Syntax {
+                position: term.start,
+                message: "expected a map",
+            });
+        };
+
+        let mut packages = BTreeMap::new();
+        for (key, value) in entries {
+            let name = match key.value {
+                Term::Atom(name) | Term::String(name) => name,
+                _ => {
+                    return Err(MixLockError::Syntax {
+                        position: key.start,
+                        message: "expected a package name",
+                    });
+                }
+            };
+            let entry = match &value.value {
+                Term::Tuple(items) if matches!(items.first(), Some(Term::Atom(a)) if a == "hex") => {
+                    LockEntry::Hex(hex_lock(&name, items)?)
+                }
+                _ => LockEntry::Other(input[value.start..value.end].to_string()),
+            };
+            packages.insert(name, entry);
+        }
+        Ok(Self { packages })
+    }
+}
+
+impl std::fmt::Display for MixLock {
+    /// Write the lock in the format used by Mix.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "%{{")?;
+        for (name, entry) in &self.packages {
+            write!(f, "  {}: ", string(name))?;
+            match entry {
+                LockEntry::Hex(lock) => write_hex_lock(f, lock)?,
+                LockEntry::Other(term) => f.write_str(term)?,
+            }
+            writeln!(f, ",")?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+fn write_hex_lock(f: &mut std::fmt::Formatter<'_>, lock: &HexLock) -> std::fmt::Result {
+    let build_tools: Vec<_> = lock.build_tools.iter().map(|tool| atom(tool)).collect();
+    let dependencies: Vec<_> = lock
+        .dependencies
+        .iter()
+        .map(|dep| {
+            format!(
+                "{{{}, {}, [hex: {}, repo: {}, optional: {}]}}",
+                atom(&dep.app),
+                dep.requirement.as_deref().map_or("nil".to_string(), string),
+                atom(&dep.package),
+                string(&dep.repository),
+                dep.optional
+            )
+        })
+        .collect();
+    write!(
+        f,
+        "{{:hex, {}, {}, {}, [{}], [{}], {}",
+        atom(&lock.name),
+        string(&lock.version.to_string()),
+        string(&base16::encode_lower(&lock.inner_checksum)),
+        build_tools.join(", "),
+        dependencies.join(", "),
+        string(&lock.repository),
+    )?;
+    if let Some(checksum) = &lock.outer_checksum {
+        write!(f, ", {}", string(&base16::encode_lower(checksum)))?;
+    }
+    f.write_char('}')
+}
+
+fn atom(name: &str) -> String {
+    let mut chars = name.chars();
+    let bare = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if bare {
+        format!(":{}", name)
+    } else {
+        format!(":{}", string(name))
+    }
+}
+
+fn string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn hex_lock(package: &str, items: &[Term]) -> Result<HexLock, MixLockError> {
+    let invalid = |reason| MixLockError::InvalidEntry {
+        package: package.to_string(),
+        reason,
+    };
+    let checksum = |term: &Term| match term {
+        Term::String(s) => Checksum::from_hex(s)
+            .map(Vec::from)
+            .map_err(|_| invalid("invalid checksum")),
+        _ => Err(invalid("invalid checksum")),
+    };
+
+    // Older versions of Hex wrote entries without the repository and outer
+    // checksum at the end.
+    let (name, version, inner_checksum, build_tools, dependencies, rest) = match items {
+        [_, name, version, inner, tools, deps, rest @ ..] if rest.len() <= 2 => {
+            (name, version, inner, tools, deps, rest)
+        }
+        _ => return Err(invalid("expected a tuple of 6 to 8 elements")),
+    };
+    let Term::Atom(name) = name else {
+        return Err(invalid("expected the package name as an atom"));
+    };
+    let version = match version {
+        Term::String(version) => Version::parse(version).map_err(|_| invalid("invalid version"))?,
+        _ => return Err(invalid("invalid version")),
+    };
+    let Term::List(build_tools) = build_tools else {
+        return Err(invalid("expected a list of build tools"));
+    };
+    let build_tools = build_tools
+        .iter()
+        .map(|tool| match tool {
+            Term::Atom(tool) => Ok(tool.clone()),
+            _ => Err(invalid("expected a list of build tools")),
+        })
+        .collect::<Result<_, _>>()?;
+    let Term::List(dependencies) = dependencies else {
+        return Err(invalid("expected a list of dependencies"));
+    };
+    let dependencies = dependencies
+        .iter()
+        .map(|dep| lock_dependency(dep).ok_or_else(|| invalid("invalid dependency")))
+        .collect::<Result<_, _>>()?;
+    let repository = match rest.first() {
+        Some(Term::String(repository)) => repository.clone(),
+        None => "hexpm".to_string(),
+        Some(_) => return Err(invalid("expected the repository as a string")),
+    };
+    let outer_checksum = rest.get(1).map(checksum).transpose()?;
+
+    Ok(HexLock {
+        name: name.clone(),
+        version,
+        inner_checksum: checksum(inner_checksum)?,
+        build_tools,
+        dependencies,
+        repository,
+        outer_checksum,
+    })
+}
+
+fn lock_dependency(term: &Term) -> Option<LockDependency> {
+    let Term::Tuple(items) = term else {
+        return None;
+    };
+    let [Term::Atom(app), requirement, Term::List(options)] = items.as_slice() else {
+        return None;
+    };
+    let requirement = match requirement {
+        Term::String(requirement) => Some(requirement.clone()),
+        Term::Atom(nil) if nil == "nil" => None,
+        _ => return None,
+    };
+    let mut dependency = LockDependency {
+        app: app.clone(),
+        requirement,
+        package: app.clone(),
+        repository: "hexpm".to_string(),
+        optional: false,
+    };
+    for option in options {
+        let Term::Tuple(pair) = option else {
+            return None;
+        };
+        match pair.as_slice() {
+            [Term::Atom(key), Term::Atom(package)] if key == "hex" => {
+                dependency.package = package.clone()
+            }
+            [Term::Atom(key), Term::String(repository)] if key == "repo" => {
+                dependency.repository = repository.clone()
+            }
+            [Term::Atom(key), Term::Atom(optional)] if key == "optional" => {
+                dependency.optional = optional == "true"
+            }
+            _ => (),
+        }
+    }
+    Some(dependency)
+}
+
+/// The subset of Elixir terms that appear in lock files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Atom(String),
+    String(String),
+    List(Vec<Term>),
+    Tuple(Vec<Term>),
+    Map(Vec<(Spanned, Spanned)>),
+}
+
+/// A term along with the byte range of its source text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Spanned {
+    value: Term,
+    start: usize,
+    end: usize,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, message: &'static str) -> Result<T, MixLockError> {
+        Err(MixLockError::Syntax {
+            position: self.position,
+            message,
+        })
+    }
+
+    fn rest(&self) -> &str {
+        &self.input[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<(), MixLockError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.error(message)
+        }
+    }
+
+    fn term(&mut self) -> Result<Spanned, MixLockError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let value = match self.peek() {
+            Some('%') => {
+                self.position += 1;
+                self.expect("{", "expected `{` after `%`")?;
+                Term::Map(self.map_entries()?)
+            }
+            Some('{') => {
+                self.position += 1;
+                Term::Tuple(self.elements("}")?)
+            }
+            Some('[') => {
+                self.position += 1;
+                Term::List(self.elements("]")?)
+            }
+            Some('"') => Term::String(self.string()?),
+            Some(':') => {
+                self.position += 1;
+                match self.peek() {
+                    Some('"') => Term::Atom(self.string()?),
+                    _ => Term::Atom(self.identifier()?),
+                }
+            }
+            Some(c) if c.is_ascii_lowercase() => {
+                let name = self.identifier()?;
+                match name.as_str() {
+                    "true" | "false" | "nil" => Term::Atom(name),
+                    _ => return self.error("unexpected identifier"),
+                }
+            }
+            _ => return self.error("expected a term"),
+        };
+        Ok(Spanned {
+            value,
+            start,
+            end: self.position,
+        })
+    }
+
+    /// The elements of a list or tuple, including keyword pairs such as
+    /// `repo: "hexpm"` which are read as two element tuples.
+    fn elements(&mut self, close: &str) -> Result<Vec<Term>, MixLockError> {
+        let mut elements = vec![];
+        loop {
+            if self.eat(close) {
+                return Ok(elements);
+            }
+            match self.keyword_key()? {
+                Some(key) => {
+                    let value = self.term()?.value;
+                    elements.push(Term::Tuple(vec![Term::Atom(key), value]));
+                }
+                None => elements.push(self.term()?.value),
+            }
+            if !self.eat(",") {
+                self.expect(close, "expected `,` or the end of the collection")?;
+                return Ok(elements);
+            }
+        }
+    }
+
+    fn map_entries(&mut self) -> Result<Vec<(Spanned, Spanned)>, MixLockError> {
+        let mut entries = vec![];
+        loop {
+            if self.eat("}") {
+                return Ok(entries);
+            }
+            self.skip_whitespace();
+            let start = self.position;
+            let key = match self.keyword_key()? {
+                Some(key) => Spanned {
+                    value: Term::Atom(key),
+                    start,
+                    end: self.position,
+                },
+                None => {
+                    let key = self.term()?;
+                    self.expect("=>", "expected `=>` after map key")?;
+                    key
+                }
+            };
+            entries.push((key, self.term()?));
+            if !self.eat(",") {
+                self.expect("}", "expected `,` or the end of the map")?;
+                return Ok(entries);
+            }
+        }
+    }
+
+    /// A keyword key such as `repo:` or `"jason":`, if there is one at the
+    /// current position.
+    fn keyword_key(&mut self) -> Result<Option<String>, MixLockError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let key = match self.peek() {
+            Some('"') => self.string()?,
+            Some(c) if c.is_ascii_lowercase() || c == '_' => self.identifier()?,
+            _ => return Ok(None),
+        };
+        if self.rest().starts_with(':') && !self.rest().starts_with("::") {
+            self.position += 1;
+            Ok(Some(key))
+        } else {
+            self.position = start;
+            Ok(None)
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, MixLockError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '?' | '!' | '@')))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return self.error("expected an identifier");
+        }
+        let identifier = rest[..len].to_string();
+        self.position += len;
+        Ok(identifier)
+    }
+
+    fn string(&mut self) -> Result<String, MixLockError> {
+        self.position += 1;
+        let mut string = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += i + 1;
+                    return Ok(string);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) => string.push(c),
+                    None => break,
+                },
+                c => string.push(c),
+            }
+        }
+        self.position = self.input.len();
+        self.error("unterminated string")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK: &str = r#"%{
+  "decimal": {:hex, :decimal, "2.1.1", "5611dca5d4b2c3dd497dec8f68751f1f1a54755e8ed2a966c2633cf885973ad6", [:mix], [], "hexpm", "53cfe5f497ed0e7771ae1a475575603d77425099ba5faef9394932b35020ffcc"},
+  "jason": {:hex, :jason, "1.4.0", "e855647bc964a44e2f67df589ccf49105ae039d4179db7f6271dfd3843dc27e6", [:mix], [{:decimal, "~> 1.0 or ~> 2.0", [hex: :decimal, repo: "hexpm", optional: true]}], "hexpm", "79a3791085b2a0f743ca04cec0f7be26443738779d09302e01318f97bdb82121"},
+  "my_dep": {:git, "https://github.com/example/my_dep.git", "0f1e2d3c", [branch: "main"]},
+}
+"#;
+
+    #[test]
+    fn parse() {
+        let lock = MixLock::parse(LOCK).unwrap();
+        assert_eq!(lock.packages.len(), 3);
+
+        let LockEntry::Hex(jason) = &lock.packages["jason"] else {
+            panic!("expected a hex entry");
+        };
+        assert_eq!(jason.name, "jason");
+        assert_eq!(jason.version, Version::new(1, 4, 0));
+        assert_eq!(jason.inner_checksum.len(), 32);
+        assert_eq!(jason.build_tools, vec!["mix".to_string()]);
+        assert_eq!(jason.repository, "hexpm");
+        assert_eq!(
+            jason.dependencies,
+            vec![LockDependency {
+                app: "decimal".to_string(),
+                requirement: Some("~> 1.0 or ~> 2.0".to_string()),
+                package: "decimal".to_string(),
+                repository: "hexpm".to_string(),
+                optional: true,
+            }]
+        );
+        assert!(jason.outer_checksum.is_some());
+
+        assert_eq!(
+            lock.packages["my_dep"],
+            LockEntry::Other(
+                r#"{:git, "https://github.com/example/my_dep.git", "0f1e2d3c", [branch: "main"]}"#
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let lock = MixLock::parse(LOCK).unwrap();
+        assert_eq!(lock.to_string(), LOCK);
+        assert_eq!(MixLock::parse(&lock.to_string()), Ok(lock));
+    }
+
+    #[test]
+    fn old_format() {
+        let lock = MixLock::parse(
+            r#"%{"poison": {:hex, :poison, "3.1.0", "d9eb636610e096f86f25d9a46f35a9facac35609a7591b3be3326e99a0484665", [:mix], [], "hexpm"}}"#,
+        )
+        .unwrap();
+        let LockEntry::Hex(poison) = &lock.packages["poison"] else {
+            panic!("expected a hex entry");
+        };
+        assert_eq!(poison.outer_checksum, None);
+    }
+
+    #[test]
+    fn renamed_dependency() {
+        let dependency = lock_dependency(&Term::Tuple(vec![
+            Term::Atom("my_app".to_string()),
+            Term::Atom("nil".to_string()),
+            Term::List(vec![Term::Tuple(vec![
+                Term::Atom("hex".to_string()),
+                Term::Atom("their_package".to_string()),
+            ])]),
+        ]))
+        .unwrap();
+        assert_eq!(dependency.app, "my_app");
+        assert_eq!(dependency.package, "their_package");
+        assert_eq!(dependency.requirement, None);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(matches!(
+            MixLock::parse(r#"%{"jason": {:hex, :jason"#),
+            Err(MixLockError::Syntax { .. })
+        ));
+        assert!(matches!(
+            MixLock::parse("[]"),
+            Err(MixLockError::Syntax { position: 0, .. })
+        ));
+        assert_eq!(
+            MixLock::parse(r#"%{"jason": {:hex, :jason, "1.4.0"}}"#),
+            Err(MixLockError::InvalidEntry {
+                package: "jason".to_string(),
+                reason: "expected a tuple of 6 to 8 elements",
+            })
+        );
+    }
+}
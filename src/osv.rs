@@ -0,0 +1,242 @@
+//! Matching of package versions against security advisories in the
+//! [OSV format](https://ossf.github.io/osv-schema/).
+//!
+//! This module does not fetch advisories. Callers load a dataset of OSV
+//! documents by their own means, for example from the
+//! [GitHub Advisory Database](https://github.com/github/advisory-database),
+//! deserialize them into [`Advisory`] values and pass them to [`audit`].
+
+use serde::Deserialize;
+
+use crate::version::Version;
+
+/// The OSV ecosystem name used for packages on Hex.
+pub const ECOSYSTEM: &str = "Hex";
+
+/// An OSV advisory. Only the fields needed to match versions and describe the
+/// vulnerability are decoded, other fields are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Advisory {
+    pub id: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub affected: Vec<Affected>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Affected {
+    pub package: AffectedPackage,
+    #[serde(default)]
+    pub ranges: Vec<AffectedRange>,
+    /// Individual versions that are affected, in addition to the ranges.
+    #[serde(default)]
+    pub versions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AffectedPackage {
+    pub ecosystem: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AffectedRange {
+    /// `SEMVER`, `ECOSYSTEM` or `GIT`. Git ranges are ignored when matching.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    Introduced(String),
+    Fixed(String),
+    LastAffected(String),
+    Limit(String),
+}
+
+impl Advisory {
+    /// Returns `true` if the given version of the given Hex package is
+    /// affected by this advisory.
+    pub fn affects(&self, package: &str, version: &Version) -> bool {
+        self.affected
+            .iter()
+            .filter(|affected| {
+                affected.package.ecosystem == ECOSYSTEM && affected.package.name == package
+            })
+            .any(|affected| affected.affects(version))
+    }
+}
+
+impl Affected {
+    fn affects(&self, version: &Version) -> bool {
+        let listed = self
+            .versions
+            .iter()
+            .any(|v| Version::parse(v).ok().as_ref() == Some(version));
+        listed || self.ranges.iter().any(|range| range.affects(version))
+    }
+}
+
+impl AffectedRange {
+    // https://ossf.github.io/osv-schema/#evaluation
+    fn affects(&self, version: &Version) -> bool {
+        if self.kind != "SEMVER" && self.kind != "ECOSYSTEM" {
+            return false;
+        }
+
+        // "0" is used to mean the beginning of time, which is not a valid
+        // version so it is represented by `None`.
+        let parse = |v: &str| match v {
+            "0" => Some(None),
+            _ => Version::parse(v).ok().map(Some),
+        };
+        let mut events = self
+            .events
+            .iter()
+            .filter_map(|event| {
+                let v = match event {
+                    Event::Introduced(v) | Event::Fixed(v) | Event::LastAffected(v) => v,
+                    Event::Limit(_) => return None,
+                };
+                Some((parse(v)?, event))
+            })
+            .collect::<Vec<_>>();
+        events.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut affected = false;
+        for (bound, event) in events {
+            let at_or_above = bound.as_ref().is_none_or(|bound| version >= bound);
+            match event {
+                Event::Introduced(_) if at_or_above => affected = true,
+                Event::Fixed(_) if at_or_above => affected = false,
+                Event::LastAffected(_) if bound.as_ref().is_some_and(|b| version > b) => {
+                    affected = false
+                }
+                _ => (),
+            }
+        }
+        affected
+    }
+}
+
+/// A package version that is affected by an advisory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding<'a> {
+    pub package: String,
+    pub version: Version,
+    pub advisory: &'a Advisory,
+}
+
+/// Match package versions, such as those of a project's resolved
+/// dependencies, against a set of advisories.
+///
+/// Findings are returned in the order the packages were given.
+pub fn audit<'a, 'p>(
+    packages: impl IntoIterator<Item = (&'p str, &'p Version)>,
+    advisories: &'a [Advisory],
+) -> Vec<Finding<'a>> {
+    packages
+        .into_iter()
+        .flat_map(|(package, version)| {
+            advisories
+                .iter()
+                .filter(move |advisory| advisory.affects(package, version))
+                .map(move |advisory| Finding {
+                    package: package.to_string(),
+                    version: version.clone(),
+                    advisory,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advisory() -> Advisory {
+        serde_json::from_value(serde_json::json!({
+            "id": "GHSA-5v4m-c73v-c7gq",
+            "summary": "Arbitrary code execution in plug",
+            "aliases": ["CVE-2018-1000883"],
+            "modified": "2023-01-01T00:00:00Z",
+            "affected": [{
+                "package": {"ecosystem": "Hex", "name": "plug"},
+                "ranges": [{
+                    "type": "SEMVER",
+                    "events": [
+                        {"introduced": "1.4.0"},
+                        {"fixed": "1.4.4"},
+                        {"introduced": "0"},
+                        {"fixed": "1.3.5"}
+                    ]
+                }],
+                "versions": ["1.5.0-rc.0"]
+            }, {
+                "package": {"ecosystem": "Hex", "name": "cowboy"},
+                "ranges": [{
+                    "type": "ECOSYSTEM",
+                    "events": [{"introduced": "2.0.0"}, {"last_affected": "2.1.0"}]
+                }]
+            }, {
+                "package": {"ecosystem": "npm", "name": "left-pad"},
+                "ranges": [{"type": "SEMVER", "events": [{"introduced": "0"}]}]
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn v(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn affects_ranges() {
+        let advisory = advisory();
+        assert!(advisory.affects("plug", &v("0.1.0")));
+        assert!(advisory.affects("plug", &v("1.3.4")));
+        assert!(!advisory.affects("plug", &v("1.3.5")));
+        assert!(advisory.affects("plug", &v("1.4.0")));
+        assert!(!advisory.affects("plug", &v("1.4.4")));
+        assert!(!advisory.affects("plug", &v("2.0.0")));
+    }
+
+    #[test]
+    fn affects_listed_version() {
+        assert!(advisory().affects("plug", &v("1.5.0-rc.0")));
+    }
+
+    #[test]
+    fn affects_last_affected() {
+        let advisory = advisory();
+        assert!(!advisory.affects("cowboy", &v("1.9.0")));
+        assert!(advisory.affects("cowboy", &v("2.1.0")));
+        assert!(!advisory.affects("cowboy", &v("2.1.1")));
+    }
+
+    #[test]
+    fn other_ecosystems_are_ignored() {
+        assert!(!advisory().affects("left-pad", &v("1.0.0")));
+    }
+
+    #[test]
+    fn audit_packages() {
+        let advisories = vec![advisory()];
+        let plug = v("1.4.1");
+        let cowboy = v("2.2.0");
+        let findings = audit([("plug", &plug), ("cowboy", &cowboy)], &advisories);
+        assert_eq!(
+            findings,
+            vec![Finding {
+                package: "plug".to_string(),
+                version: plug.clone(),
+                advisory: &advisories[0],
+            }]
+        );
+    }
+}
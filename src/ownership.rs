@@ -0,0 +1,164 @@
+//! Checking that an API key may publish a package before doing the work of
+//! building and uploading it, so that CI jobs fail early with a clear reason
+//! rather than with a `403` at upload time.
+//!
+//! The check combines the permissions of the key, the user it belongs to and
+//! the owners of the package. The requests are made with
+//! [`RequestPlan::publish_permission`](crate::plan::RequestPlan::publish_permission).
+//!
+//! ```
+//! use hexpm::{Config, plan::RequestPlan};
+//!
+//! let mut plan = RequestPlan::publish_permission("lustre", "my-api-key", &Config::new());
+//! let requests = plan.take_requests();
+//! assert_eq!(requests[0].uri().path(), "/api/keys");
+//! assert_eq!(requests[1].uri().path(), "/api/users/me");
+//! assert_eq!(requests[2].uri().path(), "/api/packages/lustre/owners");
+//! ```
+
+use crate::{KeyInfo, KeyPermission, Owner, OwnerLevel, User};
+
+/// Whether an API key may publish a package, and if not, why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublishVerdict {
+    /// The key's user owns the package.
+    Allowed { level: OwnerLevel },
+    /// The package has not been published yet, so anyone may publish it.
+    NewPackage,
+    /// The key was not among the keys Hex listed for its user.
+    UnknownKey,
+    /// The key does not have permission to publish packages, such as a
+    /// read-only key or one limited to other packages.
+    MissingPermission { key: String },
+    /// The key's user is not an owner of the package.
+    NotAnOwner {
+        username: String,
+        owners: Vec<String>,
+    },
+}
+
+impl PublishVerdict {
+    /// Returns `true` if publishing the package should succeed.
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Self::Allowed { .. } | Self::NewPackage)
+    }
+}
+
+impl KeyPermission {
+    /// Returns `true` if the permission allows publishing the given package
+    /// to the public repository.
+    pub fn allows_publishing(&self, package: &str) -> bool {
+        match (self.domain.as_str(), self.resource.as_deref()) {
+            ("api", None | Some("write")) => true,
+            ("package", Some(resource)) => {
+                resource == package || resource.strip_prefix("hexpm/") == Some(package)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Decide whether the key Hex marked as the authenticating key in `keys` may
+/// publish `package`. `owners` is `None` if the package does not exist.
+pub fn publish_verdict(
+    package: &str,
+    keys: &[KeyInfo],
+    user: &User,
+    owners: Option<&[Owner]>,
+) -> PublishVerdict {
+    let Some(key) = keys.iter().find(|key| key.authing_key) else {
+        return PublishVerdict::UnknownKey;
+    };
+    if !key
+        .permissions
+        .iter()
+        .any(|permission| permission.allows_publishing(package))
+    {
+        return PublishVerdict::MissingPermission {
+            key: key.name.clone(),
+        };
+    }
+    let Some(owners) = owners else {
+        return PublishVerdict::NewPackage;
+    };
+    match owners.iter().find(|owner| owner.username == user.username) {
+        Some(owner) => PublishVerdict::Allowed { level: owner.level },
+        None => PublishVerdict::NotAnOwner {
+            username: user.username.clone(),
+            owners: owners.iter().map(|owner| owner.username.clone()).collect(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, authing_key: bool, permissions: &[(&str, Option<&str>)]) -> KeyInfo {
+        KeyInfo {
+            name: name.into(),
+            permissions: permissions
+                .iter()
+                .map(|(domain, resource)| KeyPermission {
+                    domain: domain.to_string(),
+                    resource: resource.map(str::to_string),
+                })
+                .collect(),
+            authing_key,
+        }
+    }
+
+    fn owner(username: &str) -> Owner {
+        Owner {
+            username: username.into(),
+            email: None,
+            level: OwnerLevel::Maintainer,
+        }
+    }
+
+    #[test]
+    fn verdicts() {
+        let user = User {
+            username: "louis".into(),
+            email: None,
+        };
+        let owners = [owner("jose"), owner("louis")];
+        let write = key("ci", true, &[("api", Some("write"))]);
+        let read = key("ci", true, &[("api", Some("read"))]);
+        let scoped = key("ci", true, &[("package", Some("hexpm/lustre"))]);
+        let other = key("laptop", false, &[("api", None)]);
+
+        assert_eq!(
+            publish_verdict("lustre", &[other.clone(), write], &user, Some(&owners)),
+            PublishVerdict::Allowed {
+                level: OwnerLevel::Maintainer
+            }
+        );
+        assert_eq!(
+            publish_verdict("lustre", std::slice::from_ref(&scoped), &user, None),
+            PublishVerdict::NewPackage
+        );
+        assert_eq!(
+            publish_verdict("wisp", &[scoped], &user, None),
+            PublishVerdict::MissingPermission { key: "ci".into() }
+        );
+        assert_eq!(
+            publish_verdict("lustre", &[read], &user, Some(&owners)),
+            PublishVerdict::MissingPermission { key: "ci".into() }
+        );
+        assert_eq!(
+            publish_verdict("lustre", std::slice::from_ref(&other), &user, Some(&owners)),
+            PublishVerdict::UnknownKey
+        );
+        let admin = key("admin", true, &[("api", None)]);
+        let verdict = publish_verdict("lustre", &[admin], &user, Some(&owners[..1]));
+        assert_eq!(
+            verdict,
+            PublishVerdict::NotAnOwner {
+                username: "louis".into(),
+                owners: vec!["jose".into()]
+            }
+        );
+        assert!(!verdict.is_allowed());
+    }
+}
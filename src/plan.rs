@@ -0,0 +1,339 @@
+//! Batches of independent requests whose responses are combined into one
+//! result.
+//!
+//! Some information takes several requests to gather, such as the owners of
+//! many packages or many pages of the package listing. A [`RequestPlan`] holds
+//! the requests, which the caller may send concurrently with the transport of
+//! their choice, and combines the parsed responses once they have all
+//! arrived.
+//!
+//! ```
+//! use hexpm::{Config, plan::RequestPlan};
+//!
+//! let mut plan = RequestPlan::owners(&["gleam_stdlib", "lustre"], None, &Config::new());
+//! let requests = plan.take_requests();
+//! assert_eq!(requests.len(), 2);
+//! let responses = requests
+//!     .iter()
+//!     .map(|_| http::Response::builder().status(200).body(b"[]".to_vec()).unwrap())
+//!     .collect();
+//! let owners = plan.finish(responses).unwrap();
+//! assert!(owners["lustre"].is_empty());
+//! ```
+
+use std::collections::HashMap;
+
+use crate::ownership::{PublishVerdict, publish_verdict};
+use crate::{ApiError, Config, KeyInfo, Owner, PackageSummary};
+
+type Finish<T> = Box<dyn FnOnce(Vec<http::Response<Vec<u8>>>) -> Result<T, ApiError> + Send>;
+
+/// A set of requests that can be sent in any order or all at once, and a
+/// function combining their responses.
+pub struct RequestPlan<T> {
+    requests: Vec<http::Request<Vec<u8>>>,
+    finish: Finish<T>,
+}
+
+impl<T: 'static> RequestPlan<T> {
+    /// Create a plan that parses each response with `parse` and combines the
+    /// results in request order with `fold`, starting from `init`, for
+    /// endpoints that have no constructor of their own.
+    pub fn new<P>(
+        requests: Vec<http::Request<Vec<u8>>>,
+        parse: impl Fn(http::Response<Vec<u8>>) -> Result<P, ApiError> + Send + 'static,
+        init: T,
+        mut fold: impl FnMut(T, P) -> T + Send + 'static,
+    ) -> Self
+    where
+        T: Send,
+    {
+        let expected = requests.len();
+        let finish = move |responses: Vec<http::Response<Vec<u8>>>| {
+            if responses.len() != expected {
+                return Err(ApiError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("expected {expected} responses, got {}", responses.len()),
+                )));
+            }
+            responses
+                .into_iter()
+                .try_fold(init, |acc, response| Ok(fold(acc, parse(response)?)))
+        };
+        Self {
+            requests,
+            finish: Box::new(finish),
+        }
+    }
+
+    /// The number of requests in the plan.
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Take the requests to send, leaving the plan ready to
+    /// [`finish`](Self::finish).
+    pub fn take_requests(&mut self) -> Vec<http::Request<Vec<u8>>> {
+        std::mem::take(&mut self.requests)
+    }
+
+    /// Combine the responses, which must be in the same order as the requests
+    /// they answer. The first response that fails to parse is returned as the
+    /// error.
+    pub fn finish(self, responses: Vec<http::Response<Vec<u8>>>) -> Result<T, ApiError> {
+        (self.finish)(responses)
+    }
+}
+
+impl RequestPlan<HashMap<String, Vec<Owner>>> {
+    /// The owners of each of the packages, keyed by package name.
+    pub fn owners(packages: &[&str], api_key: Option<&str>, config: &Config) -> Self {
+        let requests = packages
+            .iter()
+            .map(|package| crate::get_owners_request(package, api_key, config))
+            .collect();
+        let mut names = packages
+            .iter()
+            .map(|package| package.to_string())
+            .collect::<Vec<_>>()
+            .into_iter();
+        Self::new(
+            requests,
+            crate::get_owners_response,
+            HashMap::new(),
+            move |mut owners, package_owners| {
+                if let Some(name) = names.next() {
+                    owners.insert(name, package_owners);
+                }
+                owners
+            },
+        )
+    }
+}
+
+impl RequestPlan<HashMap<String, Vec<KeyInfo>>> {
+    /// The keys of each of the organisations, keyed by organisation name.
+    pub fn organization_keys(organizations: &[&str], api_key: &str, config: &Config) -> Self {
+        let requests = organizations
+            .iter()
+            .map(|organization| crate::get_organization_keys_request(organization, api_key, config))
+            .collect();
+        let mut names = organizations
+            .iter()
+            .map(|organization| organization.to_string())
+            .collect::<Vec<_>>()
+            .into_iter();
+        Self::new(
+            requests,
+            crate::get_organization_keys_response,
+            HashMap::new(),
+            move |mut keys, organization_keys| {
+                if let Some(name) = names.next() {
+                    keys.insert(name, organization_keys);
+                }
+                keys
+            },
+        )
+    }
+}
+
+impl RequestPlan<Vec<KeyInfo>> {
+    /// The API keys of the user the API key belongs to. Hex lists them in a
+    /// single response, so this plan has one request.
+    pub fn api_keys(api_key: &str, config: &Config) -> Self {
+        let requests = vec![crate::get_api_keys_request(api_key, config)];
+        Self::new(requests, crate::get_api_keys_response, vec![], |_, keys| {
+            keys
+        })
+    }
+}
+
+impl RequestPlan<PublishVerdict> {
+    /// Whether the API key may publish the package to the public repository,
+    /// from the key's permissions, its user and the package's owners.
+    pub fn publish_permission(package: &str, api_key: &str, config: &Config) -> Self {
+        let requests = vec![
+            crate::get_api_keys_request(api_key, config),
+            crate::get_current_user_request(api_key, config),
+            crate::get_owners_request(package, Some(api_key), config),
+        ];
+        let package = package.to_string();
+        let finish = move |responses: Vec<http::Response<Vec<u8>>>| {
+            let Ok([keys, user, owners]) = <[_; 3]>::try_from(responses) else {
+                return Err(ApiError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "expected 3 responses",
+                )));
+            };
+            let keys = crate::get_api_keys_response(keys)?;
+            let user = crate::get_current_user_response(user)?;
+            let owners = match crate::get_owners_response(owners) {
+                Ok(owners) => Some(owners),
+                Err(ApiError::NotFound) => None,
+                Err(error) => return Err(error),
+            };
+            Ok(publish_verdict(&package, &keys, &user, owners.as_deref()))
+        };
+        Self {
+            requests,
+            finish: Box::new(finish),
+        }
+    }
+}
+
+impl RequestPlan<Vec<PackageSummary>> {
+    /// The packages on the given pages of the package listing, in name order.
+    /// Pages past the end of the listing are empty.
+    pub fn packages(
+        pages: std::ops::RangeInclusive<u32>,
+        api_key: Option<&str>,
+        config: &Config,
+    ) -> Self {
+        let requests = pages
+            .map(|page| crate::get_packages_page_request(page, api_key, config))
+            .collect();
+        Self::new(
+            requests,
+            crate::get_packages_page_response,
+            vec![],
+            |mut packages, page| {
+                packages.extend(page);
+                packages
+            },
+        )
+    }
+}
+
+impl<T> std::fmt::Debug for RequestPlan<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestPlan")
+            .field("requests", &self.requests)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ok(body: serde_json::Value) -> http::Response<Vec<u8>> {
+        http::Response::builder()
+            .status(200)
+            .body(body.to_string().into_bytes())
+            .unwrap()
+    }
+
+    #[test]
+    fn owners() {
+        let mut plan = RequestPlan::owners(&["lustre", "gleam_stdlib"], None, &Config::new());
+        let requests = plan.take_requests();
+        let paths: Vec<_> = requests.iter().map(|r| r.uri().path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/api/packages/lustre/owners",
+                "/api/packages/gleam_stdlib/owners"
+            ]
+        );
+
+        let owners = plan
+            .finish(vec![
+                ok(json!([{"username": "alice", "level": "full"}])),
+                ok(json!([
+                    {"username": "bob", "email": "bob@example.com", "level": "full"},
+                    {"username": "other", "level": "maintainer"},
+                ])),
+            ])
+            .unwrap();
+        assert_eq!(
+            owners["lustre"],
+            vec![Owner {
+                username: "alice".into(),
+                email: None,
+                level: crate::OwnerLevel::Full,
+            }]
+        );
+        assert_eq!(owners["gleam_stdlib"].len(), 2);
+        assert_eq!(
+            owners["gleam_stdlib"][1].level,
+            crate::OwnerLevel::Maintainer
+        );
+    }
+
+    #[test]
+    fn packages() {
+        let mut plan = RequestPlan::packages(1..=2, None, &Config::new());
+        let requests = plan.take_requests();
+        assert_eq!(
+            requests[1].uri().to_string(),
+            "https://hex.pm/api/packages?sort=name&page=2"
+        );
+        let packages = plan
+            .finish(vec![
+                ok(json!([{
+                    "name": "gleam_stdlib",
+                    "meta": {
+                        "description": "A standard library",
+                        "licenses": ["Apache-2.0"],
+                        "links": {"Changelog": "https://example.com/CHANGELOG.md"},
+                    },
+                    "latest_version": "1.0.0-rc1",
+                    "latest_stable_version": "0.34.0",
+                }])),
+                ok(json!([])),
+            ])
+            .unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(
+            packages[0].latest_stable_version,
+            Some(crate::version::Version::new(0, 34, 0))
+        );
+        assert_eq!(
+            packages[0].meta.links().changelog.as_deref(),
+            Some("https://example.com/CHANGELOG.md")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let config = Config::new();
+        let mut plan = RequestPlan::organization_keys(&["acme", "initech"], "key", &config);
+        let requests = plan.take_requests();
+        assert_eq!(requests[0].uri().path(), "/api/orgs/acme/keys");
+        let forbidden = http::Response::builder().status(403).body(vec![]).unwrap();
+        assert!(matches!(
+            plan.finish(vec![ok(json!([])), forbidden]),
+            Err(ApiError::Forbidden)
+        ));
+
+        let plan = RequestPlan::api_keys("key", &config);
+        assert_eq!(plan.len(), 1);
+        assert!(matches!(plan.finish(vec![]), Err(ApiError::Io(_))));
+    }
+
+    #[test]
+    fn publish_permission() {
+        let plan = |owners: http::Response<Vec<u8>>| {
+            let mut plan = RequestPlan::publish_permission("lustre", "key", &Config::new());
+            assert_eq!(plan.take_requests().len(), 3);
+            let keys = ok(json!([
+                {"name": "ci", "authing_key": true, "permissions": [{"domain": "api"}]},
+            ]));
+            let user = ok(json!({"username": "louis", "email": null}));
+            plan.finish(vec![keys, user, owners]).unwrap()
+        };
+        assert_eq!(
+            plan(ok(json!([{"username": "louis", "level": "full"}]))),
+            PublishVerdict::Allowed {
+                level: crate::OwnerLevel::Full
+            }
+        );
+        let not_found = http::Response::builder().status(404).body(vec![]).unwrap();
+        assert_eq!(plan(not_found), PublishVerdict::NewPackage);
+    }
+}
@@ -0,0 +1,216 @@
+//! Reporting errors as [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807)
+//! problem details, for services that proxy Hex such as internal registries
+//! and caching proxies, so that their clients get consistent machine-readable
+//! errors whichever layer they came from.
+//!
+//! ```
+//! use hexpm::ApiError;
+//! use std::time::SystemTime;
+//!
+//! let problem = ApiError::NotFound.problem(SystemTime::now());
+//! assert_eq!(problem.status, 404);
+//! assert_eq!(problem.problem_type, "urn:hexpm:error:not_found");
+//! ```
+
+use std::time::{Duration, SystemTime};
+
+use http::StatusCode;
+
+use crate::ApiError;
+
+/// The media type of a problem details document.
+pub const CONTENT_TYPE: &str = "application/problem+json";
+
+/// A problem details document describing an [`ApiError`], as created by
+/// [`ApiError::problem`].
+///
+/// The `type` is a URN made from the error's stable [`code`](ApiError::code),
+/// which is also given as the `code` extension member for clients that
+/// already match on it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// The reason phrase of the status, which is the same for every problem
+    /// of a type.
+    pub title: &'static str,
+    pub status: u16,
+    /// The error message, specific to this occurrence.
+    pub detail: String,
+    pub code: &'static str,
+    /// How long to wait before retrying, in whole seconds, for errors that
+    /// say so.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_seconds"
+    )]
+    pub retry_after: Option<Duration>,
+}
+
+fn serialize_seconds<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_u64(duration.as_secs()),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl ProblemDetails {
+    /// A response with the problem as its JSON body, and a `retry-after`
+    /// header when there is a time to retry.
+    #[cfg(feature = "api")]
+    pub fn response(&self) -> http::Response<Vec<u8>> {
+        let mut builder = http::Response::builder()
+            .status(self.status)
+            .header(http::header::CONTENT_TYPE, CONTENT_TYPE);
+        if let Some(retry_after) = self.retry_after {
+            builder = builder.header(http::header::RETRY_AFTER, retry_after.as_secs());
+        }
+        let body = serde_json::to_vec(self).expect("problem details JSON");
+        builder.body(body).expect("problem details response")
+    }
+}
+
+impl ApiError {
+    /// The status a service should respond with when it fails with this
+    /// error while acting for a client.
+    ///
+    /// Errors in a request the client sent, such as an invalid package name,
+    /// keep their meaning, as do the answers Hex gave, such as
+    /// [`NotFound`](ApiError::NotFound). A response from Hex that could not be
+    /// used, such as one with a bad checksum, is `502 Bad Gateway`, since the
+    /// fault lies upstream rather than with the client.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::InvalidCredentials | ApiError::InvalidApiKey => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden => StatusCode::FORBIDDEN,
+            ApiError::InvalidPackageNameFormat(_)
+            | ApiError::InvalidRepositoryNameFormat(_)
+            | ApiError::InvalidVersionFormat(_)
+            | ApiError::InvalidVersionRequirementFormat(_)
+            | ApiError::InvalidArgument { .. } => StatusCode::BAD_REQUEST,
+            ApiError::NotReplacing | ApiError::LateModification { .. } | ApiError::KeyNameTaken => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ApiError::InvalidSigningKey => StatusCode::INTERNAL_SERVER_ERROR,
+            #[cfg(feature = "api")]
+            ApiError::Json(_) => StatusCode::BAD_GATEWAY,
+            #[cfg(feature = "registry")]
+            ApiError::InvalidProtobuf(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Io(_)
+            | ApiError::InvalidErlangTerm(_)
+            | ApiError::UnexpectedResponse(_, _)
+            | ApiError::IncorrectPayloadSignature
+            | ApiError::IncorrectChecksum
+            | ApiError::ResponseTooLarge(_)
+            | ApiError::TooManyEntries { .. }
+            | ApiError::TruncatedResponse { .. }
+            | ApiError::Redirect { .. } => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// The error as problem details, with any time to retry measured from
+    /// `now`.
+    pub fn problem(&self, now: SystemTime) -> ProblemDetails {
+        let status = self.status();
+        let retry_after = match self {
+            ApiError::ServiceUnavailable {
+                retry_after: Some(retry_after),
+                ..
+            } => Some(retry_after.delay(now)),
+            _ => None,
+        };
+        ProblemDetails {
+            problem_type: format!("urn:hexpm:error:{}", self.code()),
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            detail: self.to_string(),
+            code: self.code(),
+            retry_after,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetryAfter;
+
+    #[test]
+    fn statuses() {
+        assert_eq!(ApiError::NotFound.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            ApiError::RateLimited.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(ApiError::InvalidApiKey.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            ApiError::InvalidVersionFormat("1".into()).status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            ApiError::IncorrectChecksum.status(),
+            StatusCode::BAD_GATEWAY
+        );
+        assert_eq!(
+            ApiError::UnexpectedResponse(StatusCode::IM_A_TEAPOT, String::new()).status(),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[test]
+    fn problem() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let error = ApiError::ServiceUnavailable {
+            retry_after: Some(RetryAfter::Date(now + Duration::from_secs(120))),
+            message: Some("down for maintenance".into()),
+        };
+        assert_eq!(
+            error.problem(now),
+            ProblemDetails {
+                problem_type: "urn:hexpm:error:service_unavailable".into(),
+                title: "Service Unavailable",
+                status: 503,
+                detail: "Hex is temporarily unavailable: down for maintenance".into(),
+                code: "service_unavailable",
+                retry_after: Some(Duration::from_secs(120)),
+            }
+        );
+        assert_eq!(ApiError::Forbidden.problem(now).retry_after, None);
+    }
+
+    #[cfg(feature = "api")]
+    #[test]
+    fn response() {
+        let now = SystemTime::UNIX_EPOCH;
+        let response = ApiError::RateLimited.problem(now).response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[http::header::CONTENT_TYPE], CONTENT_TYPE);
+        assert!(response.headers().get(http::header::RETRY_AFTER).is_none());
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "type": "urn:hexpm:error:rate_limited",
+                "title": "Too Many Requests",
+                "status": 429,
+                "detail": "the rate limit for the Hex API has been exceeded for this IP",
+                "code": "rate_limited",
+            })
+        );
+
+        let error = ApiError::ServiceUnavailable {
+            retry_after: Some(RetryAfter::Delay(Duration::from_secs(30))),
+            message: None,
+        };
+        let response = error.problem(now).response();
+        assert_eq!(response.headers()[http::header::RETRY_AFTER], "30");
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["retry_after"], 30);
+    }
+}
@@ -0,0 +1,233 @@
+//! Settings kept with a project, such as in a build tool's configuration
+//! file, for the repositories the project fetches packages from.
+//!
+//! [`ProjectSettings`] can be serialized with any serde format, so build
+//! tools load and save it themselves alongside their other settings. Each
+//! repository's settings become a [`Config`] with
+//! [`ProjectSettings::config`], and the keys trusted to sign their registry
+//! resources a [`TrustStore`] with [`ProjectSettings::trust_store`].
+//!
+//! ```
+//! use hexpm::project::{ProjectSettings, RepositorySettings};
+//!
+//! let mut settings = ProjectSettings::default();
+//! settings.repositories.insert(
+//!     "acme".to_string(),
+//!     RepositorySettings {
+//!         repository_base: Some("https://repo.acme.dev".to_string()),
+//!         trusted_keys: vec!["-----BEGIN PUBLIC KEY-----...".to_string()],
+//!         ..RepositorySettings::default()
+//!     },
+//! );
+//! let config = settings.config("acme").unwrap();
+//! assert_eq!(config.repository_base, "https://repo.acme.dev/");
+//! assert_eq!(settings.trust_store().keys("acme").len(), 1);
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{AuthStyle, Config};
+
+/// The name of the public hex.pm repository, which needs no settings.
+pub const HEXPM: &str = "hexpm";
+
+/// The repository settings of a project.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectSettings {
+    /// Settings by repository name, such as `hexpm` or `hexpm:acme` for an
+    /// organization's repository.
+    #[serde(default)]
+    pub repositories: BTreeMap<String, RepositorySettings>,
+}
+
+/// The settings of a repository. Settings that are not given keep the
+/// defaults of [`Config::new`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepositorySettings {
+    /// The base URL of the API, with or without a trailing slash.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_base: Option<String>,
+    /// The base URL of the repository, with or without a trailing slash.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository_base: Option<String>,
+    /// Mirrors of the repository in order of preference. The first is used
+    /// in place of the repository base, and the rest are for callers that
+    /// fall back to another mirror when one fails.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+    /// PEM encoded public keys trusted to sign the repository's registry
+    /// resources.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trusted_keys: Vec<String>,
+    pub auth_style: AuthStyle,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SettingsError {
+    #[error("no settings are given for the repository {0}")]
+    UnknownRepository(String),
+
+    #[error("the {field} of the repository {repository} is not a valid URL: {value:?}")]
+    InvalidUrl {
+        repository: String,
+        field: &'static str,
+        value: String,
+    },
+}
+
+impl ProjectSettings {
+    /// The configuration for requests to a repository. The hex.pm repository
+    /// can be used without settings, but any other must have them.
+    pub fn config(&self, repository: &str) -> Result<Config, SettingsError> {
+        let mut config = Config::new();
+        let settings = match self.repositories.get(repository) {
+            Some(settings) => settings,
+            None if repository == HEXPM => return Ok(config),
+            None => return Err(SettingsError::UnknownRepository(repository.to_string())),
+        };
+        let url = |field: &'static str, value: &str| {
+            crate::base_url(value).ok_or_else(|| SettingsError::InvalidUrl {
+                repository: repository.to_string(),
+                field,
+                value: value.to_string(),
+            })
+        };
+        if let Some(api_base) = &settings.api_base {
+            config.api_base = url("api_base", api_base)?;
+        }
+        if let Some(mirror) = settings.mirrors.first() {
+            config.repository_base = url("mirrors", mirror)?;
+        } else if let Some(repository_base) = &settings.repository_base {
+            config.repository_base = url("repository_base", repository_base)?;
+        }
+        config.auth_style = settings.auth_style;
+        Ok(config)
+    }
+
+    /// The keys trusted to sign each repository's registry resources.
+    pub fn trust_store(&self) -> TrustStore {
+        let mut store = TrustStore::default();
+        for (repository, settings) in &self.repositories {
+            for key in &settings.trusted_keys {
+                store.trust(repository, key.as_bytes());
+            }
+        }
+        store
+    }
+}
+
+/// The public keys trusted to sign the registry resources of each
+/// repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustStore {
+    keys: BTreeMap<String, Vec<Vec<u8>>>,
+}
+
+impl TrustStore {
+    /// Trust a PEM encoded public key to sign a repository's resources.
+    /// Trusting a key twice has no effect.
+    pub fn trust(&mut self, repository: &str, pem_public_key: &[u8]) {
+        let keys = self.keys.entry(repository.to_string()).or_default();
+        if !keys.iter().any(|key| key == pem_public_key) {
+            keys.push(pem_public_key.to_vec());
+        }
+    }
+
+    /// The keys trusted for a repository, in the order they were trusted.
+    pub fn keys(&self, repository: &str) -> Vec<&[u8]> {
+        self.keys
+            .get(repository)
+            .map(|keys| keys.iter().map(Vec::as_slice).collect())
+            .unwrap_or_default()
+    }
+
+    /// Verify a gzipped signed resource from a repository with any of the
+    /// keys trusted for it. A repository with no trusted keys verifies
+    /// nothing.
+    #[cfg(feature = "registry")]
+    pub fn verify(
+        &self,
+        repository: &str,
+        body: &[u8],
+    ) -> Result<crate::registry::VerifiedPayload, crate::ApiError> {
+        crate::registry::verify_signed(body, &self.keys(repository))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> ProjectSettings {
+        toml::from_str(
+            r#"
+[repositories."hexpm:acme"]
+api_base = "https://hex.acme.dev/api"
+trusted_keys = ["acme key", "acme key"]
+auth_style = "bearer"
+
+[repositories.mirrored]
+repository_base = "https://repo.example.com"
+mirrors = ["https://mirror.example.com/hex/", "https://backup.example.com/hex/"]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let settings = settings();
+        let saved = toml::to_string(&settings).unwrap();
+        assert_eq!(toml::from_str::<ProjectSettings>(&saved).unwrap(), settings);
+        assert!(!saved.contains("repository_base = \"https://repo.acme"));
+    }
+
+    #[test]
+    fn configs() {
+        let settings = settings();
+        let acme = settings.config("hexpm:acme").unwrap();
+        assert_eq!(acme.api_base, "https://hex.acme.dev/api/");
+        assert_eq!(acme.repository_base, "https://repo.hex.pm/");
+        assert_eq!(acme.auth_style, AuthStyle::Bearer);
+
+        let mirrored = settings.config("mirrored").unwrap();
+        assert_eq!(mirrored.repository_base, "https://mirror.example.com/hex/");
+        assert_eq!(mirrored.auth_style, AuthStyle::ApiKey);
+
+        assert_eq!(
+            settings.config(HEXPM).unwrap().repository_base,
+            "https://repo.hex.pm/"
+        );
+        assert_eq!(
+            settings.config("other").unwrap_err(),
+            SettingsError::UnknownRepository("other".into())
+        );
+
+        let mut invalid = settings.clone();
+        invalid
+            .repositories
+            .get_mut("mirrored")
+            .unwrap()
+            .mirrors
+            .insert(0, "ftp://mirror".into());
+        assert_eq!(
+            invalid.config("mirrored").unwrap_err(),
+            SettingsError::InvalidUrl {
+                repository: "mirrored".into(),
+                field: "mirrors",
+                value: "ftp://mirror".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn trust_store() {
+        let store = settings().trust_store();
+        assert_eq!(store.keys("hexpm:acme"), vec![&b"acme key"[..]]);
+        assert!(store.keys("mirrored").is_empty());
+    }
+}
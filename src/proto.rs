@@ -1,5 +1,326 @@
+//! The protobuf messages of the registry, as generated from the schemas in
+//! the [registry
+//! specification](https://github.com/hexpm/specifications/blob/master/registry-v2.md).
+//!
+//! Payloads verified with [`registry::verify_signed`](crate::registry::verify_signed)
+//! can be decoded with these and converted to this crate's types with
+//! `TryFrom`, as [`get_package_response`](crate::get_package_response) does.
+
 #![allow(clippy::enum_variant_names)]
 
 pub mod package;
 pub mod signed;
 pub mod versions;
+
+use std::collections::HashMap;
+
+use bytes::Buf;
+use prost::Message;
+use prost::encoding::{DecodeContext, WireType, decode_key, decode_varint, skip_field};
+use thiserror::Error;
+
+use crate::hexpm_defaults::{
+    MAX_REGISTRY_DEPENDENCIES, MAX_REGISTRY_PACKAGES, MAX_REGISTRY_RELEASES,
+};
+use crate::version::{Range, Version};
+use crate::{ApiError, NameInterner, PackageName, RetirementReason, RetirementStatus};
+
+/// Decode the verified payload of a package record and convert it to a
+/// [`Package`](crate::Package).
+///
+/// This and [`decode_versions`] are where registry payloads are checked:
+/// strings that are not UTF-8 fail to decode with
+/// [`ApiError::InvalidProtobuf`], and records with more releases or
+/// dependencies than the [limits](crate::hexpm_defaults::MAX_REGISTRY_RELEASES)
+/// fail with [`ApiError::TooManyEntries`]. The entries are counted on the wire
+/// before anything is decoded, so an oversized payload is rejected without
+/// building its messages.
+pub fn decode_package(mut payload: impl Buf) -> Result<crate::Package, ApiError> {
+    let payload = payload.copy_to_bytes(payload.remaining());
+    check_entries(
+        &payload,
+        &[
+            (1, "releases", MAX_REGISTRY_RELEASES),
+            (3, "dependencies", MAX_REGISTRY_DEPENDENCIES),
+        ],
+    )?;
+    let package = package::Package::decode(payload)?;
+    Ok(crate::Package::try_from(package)?)
+}
+
+/// Decode the verified payload of a versions index, see [`decode_package`].
+pub fn decode_versions(mut payload: impl Buf) -> Result<versions::Versions, ApiError> {
+    let payload = payload.copy_to_bytes(payload.remaining());
+    check_entries(
+        &payload,
+        &[
+            (1, "packages", MAX_REGISTRY_PACKAGES),
+            (2, "releases", MAX_REGISTRY_RELEASES),
+        ],
+    )?;
+    Ok(versions::Versions::decode(payload)?)
+}
+
+/// Count the occurrences of a repeated length delimited field of an encoded
+/// message, given as its tag, name and limit, and of the nested fields that
+/// follow within each occurrence.
+fn check_entries(
+    mut message: &[u8],
+    limits: &[(u32, &'static str, usize)],
+) -> Result<(), ApiError> {
+    let Some(&(field, entries, limit)) = limits.first() else {
+        return Ok(());
+    };
+    let mut count = 0;
+    while !message.is_empty() {
+        let (tag, wire_type) = decode_key(&mut message)?;
+        let start = message;
+        skip_field(wire_type, tag, &mut message, DecodeContext::default())?;
+        if tag != field || wire_type != WireType::LengthDelimited {
+            continue;
+        }
+        count += 1;
+        if count > limit {
+            return Err(ApiError::TooManyEntries { entries, limit });
+        }
+        let mut entry = &start[..start.len() - message.len()];
+        decode_varint(&mut entry)?;
+        check_entries(entry, &limits[1..])?;
+    }
+    Ok(())
+}
+
+/// A registry message that does not fit this crate's types.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    #[error("release has an invalid version {0:?}")]
+    InvalidVersion(String),
+
+    #[error("the requirement {requirement:?} on {package} is invalid")]
+    InvalidRequirement {
+        package: String,
+        requirement: String,
+    },
+}
+
+impl From<ConversionError> for ApiError {
+    fn from(error: ConversionError) -> Self {
+        match error {
+            ConversionError::InvalidVersion(version) => ApiError::InvalidVersionFormat(version),
+            ConversionError::InvalidRequirement { requirement, .. } => {
+                ApiError::InvalidVersionFormat(requirement)
+            }
+        }
+    }
+}
+
+impl From<package::RetirementReason> for RetirementReason {
+    fn from(reason: package::RetirementReason) -> Self {
+        use package::RetirementReason::*;
+        match reason {
+            RetiredOther => RetirementReason::Other,
+            RetiredInvalid => RetirementReason::Invalid,
+            RetiredSecurity => RetirementReason::Security,
+            RetiredDeprecated => RetirementReason::Deprecated,
+            RetiredRenamed => RetirementReason::Renamed,
+        }
+    }
+}
+
+impl From<package::RetirementStatus> for RetirementStatus {
+    fn from(status: package::RetirementStatus) -> Self {
+        // Reasons added to the registry after this library was written are
+        // kept as their number rather than failing the whole package.
+        let unknown_reason = package::RetirementReason::try_from(status.reason)
+            .is_err()
+            .then(|| status.reason.to_string());
+        RetirementStatus {
+            reason: status.reason().into(),
+            message: status.message.unwrap_or_default(),
+            unknown_reason,
+        }
+    }
+}
+
+impl TryFrom<package::Dependency> for (PackageName, crate::Dependency) {
+    type Error = ConversionError;
+
+    fn try_from(dependency: package::Dependency) -> Result<Self, Self::Error> {
+        convert_dependency(dependency, &mut NameInterner::default())
+    }
+}
+
+impl TryFrom<package::Release> for crate::Release<()> {
+    type Error = ConversionError;
+
+    fn try_from(release: package::Release) -> Result<Self, Self::Error> {
+        convert_release(release, &mut NameInterner::default())
+    }
+}
+
+impl TryFrom<package::Package> for crate::Package {
+    type Error = ConversionError;
+
+    /// Names are shared between the releases of the package, so that a
+    /// dependency required by every release is only allocated once.
+    fn try_from(package: package::Package) -> Result<Self, Self::Error> {
+        let mut names = NameInterner::default();
+        let releases = package
+            .releases
+            .into_iter()
+            .map(|release| convert_release(release, &mut names))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(crate::Package {
+            name: names.intern(package.name),
+            repository: package.repository,
+            releases,
+        })
+    }
+}
+
+fn convert_dependency(
+    dependency: package::Dependency,
+    names: &mut NameInterner,
+) -> Result<(PackageName, crate::Dependency), ConversionError> {
+    let requirement = Range::new(dependency.requirement.clone()).map_err(|_| {
+        ConversionError::InvalidRequirement {
+            package: dependency.package.clone(),
+            requirement: dependency.requirement,
+        }
+    })?;
+    Ok((
+        names.intern(dependency.package),
+        crate::Dependency {
+            requirement,
+            optional: dependency.optional.is_some(),
+            app: dependency.app,
+            repository: dependency.repository,
+        },
+    ))
+}
+
+fn convert_release(
+    release: package::Release,
+    names: &mut NameInterner,
+) -> Result<crate::Release<()>, ConversionError> {
+    let requirements = release
+        .dependencies
+        .into_iter()
+        .map(|dependency| convert_dependency(dependency, names))
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    let version = Version::try_from(release.version.as_str())
+        .map_err(|_| ConversionError::InvalidVersion(release.version))?;
+    Ok(crate::Release {
+        version,
+        requirements,
+        retirement_status: release.retired.map(RetirementStatus::from),
+        outer_checksum: release.outer_checksum.unwrap_or_default(),
+        meta: (),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(requirement: &str) -> package::Dependency {
+        package::Dependency {
+            package: "plug".into(),
+            requirement: requirement.into(),
+            optional: Some(true),
+            app: None,
+            repository: None,
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        let release = package::Release {
+            version: "1.0.0".into(),
+            inner_checksum: vec![],
+            dependencies: vec![dependency("~> 1.14")],
+            retired: Some(package::RetirementStatus {
+                reason: 99,
+                message: None,
+            }),
+            outer_checksum: Some(vec![1; 32]),
+        };
+        let converted = crate::Release::try_from(release.clone()).unwrap();
+        assert_eq!(converted.version, Version::new(1, 0, 0));
+        assert!(converted.requirements["plug"].optional);
+        let status = converted.retirement_status.unwrap();
+        assert_eq!(status.reason, RetirementReason::Other);
+        assert_eq!(status.unknown_reason.as_deref(), Some("99"));
+
+        let invalid = package::Release {
+            dependencies: vec![dependency("~> nope")],
+            ..release.clone()
+        };
+        assert_eq!(
+            crate::Release::try_from(invalid),
+            Err(ConversionError::InvalidRequirement {
+                package: "plug".into(),
+                requirement: "~> nope".into()
+            })
+        );
+        let invalid = package::Release {
+            version: "one".into(),
+            ..release
+        };
+        assert_eq!(
+            crate::Release::try_from(invalid),
+            Err(ConversionError::InvalidVersion("one".into()))
+        );
+    }
+
+    #[test]
+    fn decode_limits() {
+        let package = package::Package {
+            name: "plug".into(),
+            repository: "hexpm".into(),
+            releases: vec![package::Release {
+                version: "1.0.0".into(),
+                inner_checksum: vec![],
+                dependencies: vec![dependency("~> 1.0"); MAX_REGISTRY_DEPENDENCIES + 1],
+                retired: None,
+                outer_checksum: None,
+            }],
+        };
+        assert!(matches!(
+            decode_package(package.encode_to_vec().as_slice()),
+            Err(ApiError::TooManyEntries {
+                entries: "dependencies",
+                limit: MAX_REGISTRY_DEPENDENCIES,
+            })
+        ));
+
+        let versions = versions::Versions {
+            packages: vec![versions::VersionsPackage {
+                name: "plug".into(),
+                versions: vec!["1.0.0".into()],
+                retired: vec![],
+            }],
+            repository: "hexpm".into(),
+        };
+        let mut payload = versions.encode_to_vec();
+        assert_eq!(decode_versions(payload.as_slice()).unwrap(), versions);
+
+        // Empty entries are counted before they are decoded
+        let empty_packages = [0x0a, 0x00].repeat(MAX_REGISTRY_PACKAGES + 1);
+        assert!(matches!(
+            decode_versions(empty_packages.as_slice()),
+            Err(ApiError::TooManyEntries {
+                entries: "packages",
+                limit: MAX_REGISTRY_PACKAGES,
+            })
+        ));
+
+        // The name of the package is at the end of its message
+        let name = payload.iter().rposition(|byte| *byte == b'g').unwrap();
+        payload[name] = 0xff;
+        assert!(matches!(
+            decode_versions(payload.as_slice()),
+            Err(ApiError::InvalidProtobuf(_))
+        ));
+    }
+}
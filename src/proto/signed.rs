@@ -2,8 +2,8 @@
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Signed {
     /// Signed contents
-    #[prost(bytes = "vec", required, tag = "1")]
-    pub payload: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "bytes", required, tag = "1")]
+    pub payload: ::prost::bytes::Bytes,
     /// The signature
     #[prost(bytes = "vec", optional, tag = "2")]
     pub signature: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
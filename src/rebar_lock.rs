@@ -0,0 +1,436 @@
+//! Reading and writing rebar3's `rebar.lock` file, so that tools written in
+//! Rust can work with the locked dependencies of Erlang projects.
+//!
+//! A `rebar.lock` file holds a list of locked dependencies, and for packages
+//! from Hex the checksums of their tarballs:
+//!
+//! ```text
+//! {"1.2.0",
+//! [{<<"cowboy">>,{pkg,<<"cowboy">>,<<"2.9.0">>},0},
+//!  {<<"cowlib">>,{pkg,<<"cowlib">>,<<"2.11.0">>},1}]}.
+//! [
+//! {pkg_hash,[
+//!  {<<"cowboy">>, <<"2C72...">>},
+//!  {<<"cowlib">>, <<"DB9F...">>}]},
+//! {pkg_hash_ext,[
+//!  {<<"cowboy">>, <<"3AFD...">>},
+//!  {<<"cowlib">>, <<"E1E1...">>}]}
+//! ].
+//! ```
+//!
+//! The `pkg_hash` checksums are the inner checksums found in a tarball's
+//! `CHECKSUM` file, and the `pkg_hash_ext` checksums are those of the whole
+//! tarball. Dependencies that are not from Hex, such as those from git, are
+//! kept as the source text of their term so that they are written back
+//! unchanged.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::checksum::Checksum;
+use crate::erlang::{self, Binaries, Spanned, SyntaxError, Term};
+use crate::mix_lock::HexLock;
+use crate::version::Version;
+
+/// The lock format version written by current versions of rebar3.
+pub const LOCK_VERSION: &str = "1.2.0";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RebarLockError {
+    #[error("invalid rebar.lock syntax at byte {position}: {message}")]
+    Syntax {
+        position: usize,
+        message: &'static str,
+    },
+
+    #[error("the rebar.lock entry for {app} is not valid: {reason}")]
+    InvalidEntry { app: String, reason: &'static str },
+}
+
+/// The contents of a `rebar.lock` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebarLock {
+    /// The version of the lock format, `None` for lock files written before
+    /// rebar3 recorded checksums.
+    pub version: Option<String>,
+    /// The locked dependencies, in the order they appear in the file.
+    pub dependencies: Vec<RebarDependency>,
+}
+
+/// A locked dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebarDependency {
+    /// The name of the dependency's OTP application.
+    pub app: String,
+    pub source: RebarSource,
+    /// How deep in the dependency tree the dependency is, where 0 is a
+    /// dependency of the project itself.
+    pub level: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebarSource {
+    /// A package from Hex. The package name differs from the application name
+    /// when the dependency is aliased, as in
+    /// `{my_app, {pkg, their_package}}`.
+    Package {
+        name: String,
+        version: Version,
+        inner_checksum: Option<Vec<u8>>,
+        outer_checksum: Option<Vec<u8>>,
+    },
+    /// A dependency that is not from Hex, as the source text of its term.
+    Other(String),
+}
+
+impl RebarLock {
+    /// Parse the contents of a `rebar.lock` file.
+    pub fn parse(input: &str) -> Result<Self, RebarLockError> {
+        let mut terms = erlang::parse_terms(input)?.into_iter();
+        let (Some(first), attributes, None) = (terms.next(), terms.next(), terms.next()) else {
+            return syntax_error(0, "expected the lock and optionally its attributes");
+        };
+
+        let (version, locks) = match first.value {
+            Term::Tuple(mut items) if items.len() == 2 => {
+                let locks = items.pop().expect("two items");
+                match items.pop().expect("two items").value {
+                    Term::String(version) => (Some(version), locks),
+                    _ => return syntax_error(first.start, "expected the lock version"),
+                }
+            }
+            _ => (None, first),
+        };
+        let Term::List(locks) = locks.value else {
+            return syntax_error(locks.start, "expected a list of locked dependencies");
+        };
+
+        let mut inner = HashMap::new();
+        let mut outer = HashMap::new();
+        if let Some(attributes) = attributes {
+            read_hashes(attributes, &mut inner, &mut outer)?;
+        }
+
+        let dependencies = locks
+            .into_iter()
+            .map(|lock| dependency(input, lock, &mut inner, &mut outer))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            version,
+            dependencies,
+        })
+    }
+}
+
+fn syntax_error<T>(position: usize, message: &'static str) -> Result<T, RebarLockError> {
+    Err(RebarLockError::Syntax { position, message })
+}
+
+impl From<SyntaxError> for RebarLockError {
+    fn from(error: SyntaxError) -> Self {
+        RebarLockError::Syntax {
+            position: error.position,
+            message: error.message,
+        }
+    }
+}
+
+/// Read the `pkg_hash` and `pkg_hash_ext` attributes into maps of application
+/// name to checksum.
+fn read_hashes(
+    attributes: Spanned,
+    inner: &mut HashMap<String, Vec<u8>>,
+    outer: &mut HashMap<String, Vec<u8>>,
+) -> Result<(), RebarLockError> {
+    let Term::List(attributes) = attributes.value else {
+        return syntax_error(attributes.start, "expected a list of lock attributes");
+    };
+    for attribute in attributes {
+        let Term::Tuple(items) = &attribute.value else {
+            continue;
+        };
+        let (map, entries) = match items.as_slice() {
+            [key, value] if key.atom() == Some("pkg_hash") => (&mut *inner, value),
+            [key, value] if key.atom() == Some("pkg_hash_ext") => (&mut *outer, value),
+            _ => continue,
+        };
+        let Term::List(entries) = &entries.value else {
+            return syntax_error(entries.start, "expected a list of checksums");
+        };
+        for entry in entries {
+            let Some([app, hash]) = entry.tuple().and_then(|pair| pair.binaries()) else {
+                return syntax_error(entry.start, "expected an app name and checksum");
+            };
+            let hash = Checksum::from_hex(hash).map_err(|_| RebarLockError::InvalidEntry {
+                app: app.to_string(),
+                reason: "invalid checksum",
+            })?;
+            map.insert(app.to_string(), hash.into());
+        }
+    }
+    Ok(())
+}
+
+fn dependency(
+    input: &str,
+    lock: Spanned,
+    inner: &mut HashMap<String, Vec<u8>>,
+    outer: &mut HashMap<String, Vec<u8>>,
+) -> Result<RebarDependency, RebarLockError> {
+    let Term::Tuple(items) = lock.value else {
+        return syntax_error(lock.start, "expected a locked dependency");
+    };
+    let [app, source, level] = items.as_slice() else {
+        return syntax_error(lock.start, "expected a locked dependency");
+    };
+    let Term::Binary(app) = &app.value else {
+        return syntax_error(app.start, "expected the app name as a binary");
+    };
+    let Term::Integer(level) = level.value else {
+        return syntax_error(level.start, "expected the dependency level");
+    };
+    let invalid = |reason| RebarLockError::InvalidEntry {
+        app: app.clone(),
+        reason,
+    };
+
+    let source = match source.tuple() {
+        Some([tag, rest @ ..]) if tag.atom() == Some("pkg") => {
+            let Some([name, version]) = rest.get(..2).and_then(|rest| rest.binaries()) else {
+                return Err(invalid("expected a package name and version"));
+            };
+            RebarSource::Package {
+                name: name.to_string(),
+                version: Version::parse(version).map_err(|_| invalid("invalid version"))?,
+                inner_checksum: inner.remove(app),
+                outer_checksum: outer.remove(app),
+            }
+        }
+        _ => RebarSource::Other(input[source.start..source.end].to_string()),
+    };
+    Ok(RebarDependency {
+        app: app.clone(),
+        source,
+        level,
+    })
+}
+
+impl std::fmt::Display for RebarLock {
+    /// Write the lock in the format used by rebar3.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let locks: Vec<_> = self
+            .dependencies
+            .iter()
+            .map(|dependency| {
+                let source = match &dependency.source {
+                    RebarSource::Package { name, version, .. } => {
+                        format!("{{pkg,{},{}}}", binary(name), binary(&version.to_string()))
+                    }
+                    RebarSource::Other(term) => term.clone(),
+                };
+                format!(
+                    "{{{},{},{}}}",
+                    binary(&dependency.app),
+                    source,
+                    dependency.level
+                )
+            })
+            .collect();
+        let locks = format!("[{}]", locks.join(",\n "));
+
+        let Some(version) = &self.version else {
+            return writeln!(f, "{}.", locks);
+        };
+        writeln!(f, "{{\"{}\",\n{}}}.", version, locks)?;
+
+        let hashes = |checksum: fn(&RebarSource) -> Option<&Vec<u8>>| {
+            let hashes: Vec<_> = self
+                .dependencies
+                .iter()
+                .filter_map(|dependency| {
+                    let checksum = checksum(&dependency.source)?;
+                    Some(format!(
+                        "{{{}, {}}}",
+                        binary(&dependency.app),
+                        binary(&base16::encode_upper(checksum))
+                    ))
+                })
+                .collect();
+            hashes.join(",\n ")
+        };
+        let inner = hashes(|source| match source {
+            RebarSource::Package { inner_checksum, .. } => inner_checksum.as_ref(),
+            RebarSource::Other(_) => None,
+        });
+        let outer = hashes(|source| match source {
+            RebarSource::Package { outer_checksum, .. } => outer_checksum.as_ref(),
+            RebarSource::Other(_) => None,
+        });
+        writeln!(
+            f,
+            "[\n{{pkg_hash,[\n {}]}},\n{{pkg_hash_ext,[\n {}]}}\n].",
+            inner, outer
+        )
+    }
+}
+
+fn binary(s: &str) -> String {
+    format!("<<\"{}\">>", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl RebarDependency {
+    /// Create a locked dependency from a Hex package locked in a `mix.lock`
+    /// file.
+    pub fn from_hex_lock(app: &str, lock: &HexLock, level: u32) -> Self {
+        Self {
+            app: app.to_string(),
+            source: RebarSource::Package {
+                name: lock.name.clone(),
+                version: lock.version.clone(),
+                inner_checksum: Some(lock.inner_checksum.clone()),
+                outer_checksum: lock.outer_checksum.clone(),
+            },
+            level,
+        }
+    }
+
+    /// Convert a locked Hex package into the representation used by
+    /// `mix.lock` files. Returns `None` for dependencies that are not from
+    /// Hex or that have no inner checksum.
+    ///
+    /// `rebar.lock` files do not record the dependencies or build tools of
+    /// locked packages, so the dependencies are left empty and the build tool
+    /// is assumed to be rebar3.
+    pub fn to_hex_lock(&self) -> Option<HexLock> {
+        let RebarSource::Package {
+            name,
+            version,
+            inner_checksum,
+            outer_checksum,
+        } = &self.source
+        else {
+            return None;
+        };
+        Some(HexLock {
+            name: name.clone(),
+            version: version.clone(),
+            inner_checksum: inner_checksum.clone()?,
+            build_tools: vec!["rebar3".to_string()],
+            dependencies: vec![],
+            repository: "hexpm".to_string(),
+            outer_checksum: outer_checksum.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK: &str = r#"{"1.2.0",
+[{<<"cowboy">>,{pkg,<<"cowboy">>,<<"2.9.0">>},0},
+ {<<"cowlib">>,{pkg,<<"cowlib">>,<<"2.11.0">>},1},
+ {<<"my_dep">>,{git,"https://github.com/example/my_dep.git",{ref,"0f1e2d3c"}},0}]}.
+[
+{pkg_hash,[
+ {<<"cowboy">>, <<"2C729F934B4E1AA149AFF882F57C6372C15399A20D54F65C8D67BEF583021BDE">>},
+ {<<"cowlib">>, <<"DB9F1D4B2C3EE8D3D4A71F3F63B6E42D2B8E1CF43B2F8B0E84AA1BE4F1E5F4C1">>}]},
+{pkg_hash_ext,[
+ {<<"cowboy">>, <<"3AFDCCB7183CC6F143CB14D3CF51FA00E53DB9EC80CDCD525482F5E99BC41D6B">>},
+ {<<"cowlib">>, <<"E1E1284DC3FC030A64B1AD0D8382AE7E99DA46C3246B815318A4B848873800A4">>}]}
+].
+"#;
+
+    #[test]
+    fn parse() {
+        let lock = RebarLock::parse(LOCK).unwrap();
+        assert_eq!(lock.version.as_deref(), Some("1.2.0"));
+        assert_eq!(lock.dependencies.len(), 3);
+
+        let cowlib = &lock.dependencies[1];
+        assert_eq!(cowlib.app, "cowlib");
+        assert_eq!(cowlib.level, 1);
+        let RebarSource::Package {
+            name,
+            version,
+            inner_checksum,
+            outer_checksum,
+        } = &cowlib.source
+        else {
+            panic!("expected a package");
+        };
+        assert_eq!(name, "cowlib");
+        assert_eq!(version, &Version::new(2, 11, 0));
+        assert_eq!(inner_checksum.as_ref().map(Vec::len), Some(32));
+        assert_eq!(outer_checksum.as_ref().map(Vec::len), Some(32));
+
+        assert_eq!(
+            lock.dependencies[2].source,
+            RebarSource::Other(
+                r#"{git,"https://github.com/example/my_dep.git",{ref,"0f1e2d3c"}}"#.to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let lock = RebarLock::parse(LOCK).unwrap();
+        assert_eq!(lock.to_string(), LOCK);
+        assert_eq!(RebarLock::parse(&lock.to_string()), Ok(lock));
+    }
+
+    #[test]
+    fn old_format() {
+        let lock = RebarLock::parse(
+            "%% a comment\n[{<<\"my_app\">>,{pkg,<<\"their_package\">>,<<\"1.0.0\">>},0}].\n",
+        )
+        .unwrap();
+        assert_eq!(lock.version, None);
+        assert_eq!(
+            lock.dependencies[0].source,
+            RebarSource::Package {
+                name: "their_package".to_string(),
+                version: Version::new(1, 0, 0),
+                inner_checksum: None,
+                outer_checksum: None,
+            }
+        );
+        assert_eq!(
+            lock.to_string(),
+            "[{<<\"my_app\">>,{pkg,<<\"their_package\">>,<<\"1.0.0\">>},0}].\n"
+        );
+    }
+
+    #[test]
+    fn hex_lock_conversion() {
+        let lock = RebarLock::parse(LOCK).unwrap();
+        let cowboy = lock.dependencies[0].to_hex_lock().unwrap();
+        assert_eq!(cowboy.name, "cowboy");
+        assert_eq!(cowboy.build_tools, vec!["rebar3".to_string()]);
+        assert_eq!(
+            RebarDependency::from_hex_lock("cowboy", &cowboy, 0),
+            lock.dependencies[0]
+        );
+        assert_eq!(lock.dependencies[2].to_hex_lock(), None);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(matches!(
+            RebarLock::parse("[{<<\"cowboy\">>"),
+            Err(RebarLockError::Syntax { .. })
+        ));
+        assert!(matches!(
+            RebarLock::parse("[]"),
+            Err(RebarLockError::Syntax { position: 2, .. })
+        ));
+        assert_eq!(
+            RebarLock::parse("[{<<\"cowboy\">>,{pkg,<<\"cowboy\">>,<<\"2\">>},0}]."),
+            Err(RebarLockError::InvalidEntry {
+                app: "cowboy".to_string(),
+                reason: "invalid version",
+            })
+        );
+    }
+}
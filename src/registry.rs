@@ -0,0 +1,146 @@
+//! Low level access to signed registry resources, for verifying resources
+//! that this crate has no typed functions for, such as the `/names` resource,
+//! or resources fetched by other means like a mirror on disk.
+//!
+//! The payload of a verified resource is the encoded protobuf message, which
+//! the caller decodes with the schemas from the [registry
+//! specification](https://github.com/hexpm/specifications/blob/master/registry-v2.md).
+//!
+//! ```
+//! use hexpm::{ApiError, registry::verify_signed};
+//!
+//! let body = b"not a gzipped resource";
+//! let keys: [&[u8]; 1] = [b"-----BEGIN PUBLIC KEY-----..."];
+//! assert!(matches!(
+//!     verify_signed(body, &keys),
+//!     Err(ApiError::Io(_) | ApiError::TruncatedResponse { .. })
+//! ));
+//! ```
+
+use bytes::Bytes;
+use prost::Message;
+
+use crate::ApiError;
+use crate::proto::signed::Signed;
+
+/// A registry resource that has been decompressed and decoded, but whose
+/// signature has not been checked yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedResource {
+    payload: Bytes,
+    signature: Vec<u8>,
+}
+
+/// The payload of a registry resource whose signature has been checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedPayload {
+    pub(crate) payload: Bytes,
+    key_index: usize,
+}
+
+impl SignedResource {
+    /// Decode a gzip compressed signed resource, as served by a registry.
+    pub fn decode(body: &[u8]) -> Result<Self, ApiError> {
+        let inflated = crate::inflate(body, crate::hexpm_defaults::MAX_INFLATED_RESOURCE_SIZE)?;
+        Self::from_inflated(inflated)
+    }
+
+    pub(crate) fn from_inflated(inflated: Vec<u8>) -> Result<Self, ApiError> {
+        if inflated.is_empty() {
+            return Err(ApiError::TruncatedResponse {
+                expected_hint: "a signed registry resource",
+            });
+        }
+        let signed = Signed::decode(Bytes::from(inflated))?;
+        Ok(Self {
+            payload: signed.payload,
+            signature: signed.signature.unwrap_or_default(),
+        })
+    }
+
+    /// The payload, which must not be trusted until it has been verified.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    // To quote the docs:
+    //
+    // > All resources will be signed by the repository's private key.
+    // > A signed resource is wrapped in a Signed message. The data under
+    // > the payload field is signed by the signature field.
+    // >
+    // > The signature is an (unencoded) RSA signature of the (unencoded)
+    // > SHA-512 digest of the payload.
+    //
+    // https://github.com/hexpm/specifications/blob/master/registry-v2.md#signing
+    //
+    /// Check the signature against each of the PEM encoded public keys in
+    /// turn, such as the old and new keys of a repository that is rotating
+    /// its key, returning the payload if any of them match.
+    pub fn verify(self, pem_public_keys: &[&[u8]]) -> Result<VerifiedPayload, ApiError> {
+        let key_index = pem_public_keys
+            .iter()
+            .position(|key| crate::verify_signature(&self.payload, &self.signature, key).is_ok())
+            .ok_or(ApiError::IncorrectPayloadSignature)?;
+        Ok(VerifiedPayload {
+            payload: self.payload,
+            key_index,
+        })
+    }
+}
+
+impl VerifiedPayload {
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn into_payload(self) -> Vec<u8> {
+        self.payload.into()
+    }
+
+    /// The position of the key that verified the signature in the list of
+    /// keys given.
+    pub fn key_index(&self) -> usize {
+        self.key_index
+    }
+}
+
+/// Decode a gzip compressed signed resource and verify its signature with any
+/// of the given PEM encoded public keys.
+pub fn verify_signed(body: &[u8], pem_public_keys: &[&[u8]]) -> Result<VerifiedPayload, ApiError> {
+    SignedResource::decode(body)?.verify(pem_public_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_keys::TestKeys;
+
+    #[test]
+    fn verifies_with_any_key() {
+        let keys = TestKeys::new();
+        let other_key = std::include_bytes!("../test/public_key").as_slice();
+        let body = keys.sign_resource(b"names".to_vec());
+
+        let verified = verify_signed(&body, &[other_key, keys.public_key()]).unwrap();
+        assert_eq!(verified.payload(), b"names");
+        assert_eq!(verified.key_index(), 1);
+        assert_eq!(verified.into_payload(), b"names".to_vec());
+
+        assert!(matches!(
+            verify_signed(&body, &[other_key]),
+            Err(ApiError::IncorrectPayloadSignature)
+        ));
+        assert!(matches!(
+            verify_signed(&body, &[]),
+            Err(ApiError::IncorrectPayloadSignature)
+        ));
+        let resource = SignedResource::decode(&body).unwrap();
+        assert_eq!(resource.payload(), b"names");
+        assert!(!resource.signature().is_empty());
+    }
+}
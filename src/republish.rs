@@ -0,0 +1,270 @@
+//! Publishing the documentation of many releases one after another, such as
+//! when regenerating the documentation of every historical version of a
+//! package.
+//!
+//! A [`DocsRepublish`] hands out one
+//! [`publish_docs_request`](crate::publish_docs_request) at a time along with
+//! how long to wait before sending it, pacing the requests with a
+//! [`Scheduler`] so that the API's rate limit is respected. Requests that are
+//! rate limited anyway are retried, and the outcome for every release is
+//! collected into a [`RepublishReport`].
+//!
+//! ```
+//! use hexpm::{Config, ReleaseRef, republish::DocsRepublish, version::Version};
+//! use std::time::SystemTime;
+//!
+//! let config = Config::new();
+//! let releases = vec![
+//!     ReleaseRef::new("lustre", Version::new(4, 0, 0)),
+//!     ReleaseRef::new("lustre", Version::new(4, 1, 0)),
+//! ];
+//! let mut republish = DocsRepublish::new(releases, &config);
+//! let now = SystemTime::now();
+//! while let Some((_request, _delay)) =
+//!     republish.next("my-api-key", &config, now, |_release| Some(b"docs".to_vec()))
+//! {
+//!     // Wait for the delay, then send the request.
+//!     let response = http::Response::builder().status(201).body(vec![]).unwrap();
+//!     republish.handle_response(response, now);
+//! }
+//! assert_eq!(republish.finish().published().count(), 2);
+//! ```
+
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use crate::schedule::Scheduler;
+use crate::{ApiError, Config, ReleaseRef};
+
+/// The number of times a release is tried before a rate limit is reported as
+/// its outcome.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// What happened when publishing the documentation of a release.
+#[derive(Debug)]
+pub enum DocsOutcome {
+    Published,
+    /// No documentation tarball was given for the release.
+    Skipped,
+    Failed(ApiError),
+}
+
+/// The outcome for every release, in the order they were finished.
+#[derive(Debug, Default)]
+pub struct RepublishReport {
+    pub outcomes: Vec<(ReleaseRef, DocsOutcome)>,
+}
+
+impl RepublishReport {
+    pub fn published(&self) -> impl Iterator<Item = &ReleaseRef> {
+        self.releases(|outcome| matches!(outcome, DocsOutcome::Published))
+    }
+
+    pub fn skipped(&self) -> impl Iterator<Item = &ReleaseRef> {
+        self.releases(|outcome| matches!(outcome, DocsOutcome::Skipped))
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = (&ReleaseRef, &ApiError)> {
+        self.outcomes
+            .iter()
+            .filter_map(|(release, outcome)| match outcome {
+                DocsOutcome::Failed(error) => Some((release, error)),
+                _ => None,
+            })
+    }
+
+    /// Whether no release failed.
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none()
+    }
+
+    fn releases(&self, want: fn(&DocsOutcome) -> bool) -> impl Iterator<Item = &ReleaseRef> {
+        self.outcomes
+            .iter()
+            .filter(move |(_, outcome)| want(outcome))
+            .map(|(release, _)| release)
+    }
+}
+
+/// The progress of publishing the documentation of a list of releases.
+#[derive(Debug)]
+pub struct DocsRepublish {
+    queue: VecDeque<(ReleaseRef, u32)>,
+    in_flight: Option<(ReleaseRef, u32, http::Uri)>,
+    scheduler: Scheduler,
+    report: RepublishReport,
+}
+
+impl DocsRepublish {
+    /// Plan publishing the documentation of `releases` in order, assuming the
+    /// rate limit of the configured [`profile`](Config::profile) until the
+    /// API reports its own.
+    pub fn new(releases: Vec<ReleaseRef>, config: &Config) -> Self {
+        Self {
+            queue: releases.into_iter().map(|release| (release, 0)).collect(),
+            in_flight: None,
+            scheduler: Scheduler::with_profile(&config.profile()),
+            report: RepublishReport::default(),
+        }
+    }
+
+    /// The number of releases not yet finished.
+    pub fn remaining(&self) -> usize {
+        self.queue.len() + usize::from(self.in_flight.is_some())
+    }
+
+    /// Create the request for the next release, along with how long after
+    /// `now` to wait before sending it, or `None` once every release is
+    /// finished. `docs` is asked for the gzipped documentation tarball of the
+    /// release, and releases it has none for are skipped.
+    ///
+    /// Each request's response must be given to
+    /// [`handle_response`](Self::handle_response) before the next request is
+    /// created. Releases that cannot be published, such as those with
+    /// invalid names, are recorded as failed rather than returned as errors.
+    ///
+    /// # Panics
+    ///
+    /// If the previous request has not had its response handled.
+    pub fn next(
+        &mut self,
+        api_key: &str,
+        config: &Config,
+        now: SystemTime,
+        mut docs: impl FnMut(&ReleaseRef) -> Option<Vec<u8>>,
+    ) -> Option<(http::Request<Vec<u8>>, Duration)> {
+        assert!(
+            self.in_flight.is_none(),
+            "the previous docs request has not had its response handled"
+        );
+        while let Some((release, attempts)) = self.queue.pop_front() {
+            let Some(tarball) = docs(&release) else {
+                self.report.outcomes.push((release, DocsOutcome::Skipped));
+                continue;
+            };
+            match crate::publish_docs_request(&release, tarball, api_key, config) {
+                Ok(request) => {
+                    self.scheduler.push(request);
+                    let (request, delay) = self.scheduler.next(now).expect("a request was queued");
+                    self.in_flight = Some((release, attempts + 1, request.uri().clone()));
+                    return Some((request, delay));
+                }
+                Err(error) => {
+                    self.report
+                        .outcomes
+                        .push((release, DocsOutcome::Failed(error)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Record the response to the last request, received at `now`. Rate
+    /// limited releases are tried again a few times before being reported as
+    /// failed.
+    ///
+    /// # Panics
+    ///
+    /// If there is no request waiting for a response.
+    pub fn handle_response(&mut self, response: http::Response<Vec<u8>>, now: SystemTime) {
+        let (release, attempts, uri) = self
+            .in_flight
+            .take()
+            .expect("no docs request is waiting for a response");
+        self.scheduler.observe(&uri, response.headers(), now);
+        match crate::publish_docs_response(response) {
+            Ok(()) => self.report.outcomes.push((release, DocsOutcome::Published)),
+            Err(ApiError::RateLimited) if attempts < MAX_ATTEMPTS => {
+                self.queue.push_front((release, attempts));
+            }
+            Err(error) => self
+                .report
+                .outcomes
+                .push((release, DocsOutcome::Failed(error))),
+        }
+    }
+
+    /// The outcomes so far.
+    pub fn report(&self) -> &RepublishReport {
+        &self.report
+    }
+
+    pub fn finish(self) -> RepublishReport {
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hexpm_defaults::Profile;
+    use crate::version::Version;
+
+    fn response(status: u16, remaining: u32, reset: u64) -> http::Response<Vec<u8>> {
+        http::Response::builder()
+            .status(status)
+            .header("x-ratelimit-limit", "10")
+            .header("x-ratelimit-remaining", remaining)
+            .header("x-ratelimit-reset", reset)
+            .body(vec![])
+            .unwrap()
+    }
+
+    #[test]
+    fn republishes_with_pacing() {
+        let config = Config::with_profile(Profile {
+            rate_limit: None,
+            ..Profile::hexpm()
+        });
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let release = |minor| ReleaseRef::new("lustre", Version::new(4, minor, 0));
+        let mut republish = DocsRepublish::new(
+            vec![release(0), release(1), release(2), release(3)],
+            &config,
+        );
+        let docs = |release: &ReleaseRef| (release.version.minor != 1).then(|| b"docs".to_vec());
+
+        let (request, delay) = republish.next("key", &config, now, docs).unwrap();
+        assert_eq!(
+            request.uri().path(),
+            "/api/packages/lustre/releases/4.0.0/docs"
+        );
+        assert_eq!(delay, Duration::ZERO);
+        republish.handle_response(response(201, 0, 1060), now);
+
+        // 4.1.0 has no docs, and 4.2.0 waits for the next window.
+        let (request, delay) = republish.next("key", &config, now, docs).unwrap();
+        assert_eq!(
+            request.uri().path(),
+            "/api/packages/lustre/releases/4.2.0/docs"
+        );
+        assert_eq!(delay, Duration::from_secs(60));
+        republish.handle_response(response(429, 0, 1120), now);
+        let (request, _) = republish.next("key", &config, now, docs).unwrap();
+        assert_eq!(
+            request.uri().path(),
+            "/api/packages/lustre/releases/4.2.0/docs"
+        );
+        republish.handle_response(response(201, 5, 1120), now);
+
+        republish.next("key", &config, now, docs).unwrap();
+        republish.handle_response(response(403, 4, 1120), now);
+        assert!(republish.next("key", &config, now, docs).is_none());
+        assert_eq!(republish.remaining(), 0);
+
+        let report = republish.finish();
+        let minors = |releases: Vec<&ReleaseRef>| -> Vec<u32> {
+            releases
+                .iter()
+                .map(|release| release.version.minor)
+                .collect()
+        };
+        assert_eq!(minors(report.published().collect()), vec![0, 2]);
+        assert_eq!(minors(report.skipped().collect()), vec![1]);
+        let failed: Vec<_> = report.failed().collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0.version.minor, 3);
+        assert!(matches!(failed[0].1, ApiError::Forbidden));
+        assert!(!report.is_success());
+    }
+}
@@ -0,0 +1,1584 @@
+//! Everything a lock file needs to record about a resolved dependency.
+//!
+//! Version resolution picks a version for each package, but lock files also
+//! record the OTP application name, which differs from the package name when
+//! a dependency is aliased, as in `{:my_json, hex: :jason}`, along with the
+//! repository and checksum of the release. [`resolved_packages`] gathers these
+//! from the registry's package records so lock file writers have them in one
+//! place, and [`dependency_edges`] recovers the dependency graph between the
+//! selected packages. [`required_packages`] finds which of the selected
+//! packages are needed for a given compilation target, so that Gleam projects
+//! do not download dependencies only used on other targets, and [`explain`]
+//! answers why a package was selected at its version.
+//!
+//! Packages can be swapped for caller provided releases with
+//! [`ResolutionOptions`], such as to lock a locally patched fork of a
+//! dependency under its original name, and [`what_if`] shows how a
+//! resolution would change if some releases were withdrawn.
+//!
+//! Registry data can contain packages that depend on each other in a cycle.
+//! [`dependency_cycles`] finds them, and [`what_if`] reports the cycle a
+//! package is in when it cannot be resolved.
+//!
+//! Version solvers backtrack less when they decide the packages that many
+//! others depend on first. A [`PriorityHintSource`], such as the download
+//! counts of [`download_priorities`], gives [`decision_order`] the order to
+//! decide packages in.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+#[cfg(feature = "api")]
+use serde_json::json;
+use thiserror::Error;
+
+use crate::rebar_lock::{RebarDependency, RebarSource};
+use crate::tarball::{PackageMetadata, Target};
+use crate::version::{Range, Version};
+use crate::{Dependency, Package, PackageName, PackageSummary, Release};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ResolutionError {
+    #[error("no registry record was given for the resolved package {0}")]
+    MissingPackage(PackageName),
+
+    #[error("the registry has no release {version} of the resolved package {package}")]
+    MissingRelease {
+        package: PackageName,
+        version: Version,
+    },
+
+    #[error(
+        "{package} is required from the {} repository by {} and from the {} repository by {}",
+        first.repository, first.dependant, second.repository, second.dependant
+    )]
+    ConflictingRepositories {
+        package: PackageName,
+        first: RequiredFrom,
+        second: RequiredFrom,
+    },
+
+    #[error("{package} is in more than one repository: {}", repositories.join(", "))]
+    Shadowed {
+        package: PackageName,
+        repositories: Vec<String>,
+    },
+}
+
+impl ResolutionError {
+    /// A stable machine-readable identifier for the kind of error, as with
+    /// [`ApiError::code`](crate::ApiError::code).
+    pub fn code(&self) -> &'static str {
+        match self {
+            ResolutionError::MissingPackage(_) => "missing_package",
+            ResolutionError::MissingRelease { .. } => "missing_release",
+            ResolutionError::ConflictingRepositories { .. } => "conflicting_repositories",
+            ResolutionError::Shadowed { .. } => "shadowed",
+        }
+    }
+
+    /// The error as a JSON document for tools that present it themselves,
+    /// such as editors. It has the `code` and human readable `message` of the
+    /// error along with the packages, versions and repositories involved.
+    ///
+    /// ```
+    /// use hexpm::resolution::ResolutionError;
+    ///
+    /// let error = ResolutionError::MissingPackage("plug".into());
+    /// assert_eq!(
+    ///     error.to_json(),
+    ///     serde_json::json!({
+    ///         "code": "missing_package",
+    ///         "message": "no registry record was given for the resolved package plug",
+    ///         "package": "plug",
+    ///     })
+    /// );
+    /// ```
+    #[cfg(feature = "api")]
+    pub fn to_json(&self) -> serde_json::Value {
+        let source = |source: &RequiredFrom| json!({"dependant": source.dependant.as_str(), "repository": source.repository});
+        let mut json = match self {
+            ResolutionError::MissingPackage(package) => json!({"package": package.as_str()}),
+            ResolutionError::MissingRelease { package, version } => {
+                json!({"package": package.as_str(), "version": version.to_string()})
+            }
+            ResolutionError::ConflictingRepositories {
+                package,
+                first,
+                second,
+            } => json!({
+                "package": package.as_str(),
+                "required_from": [source(first), source(second)],
+            }),
+            ResolutionError::Shadowed {
+                package,
+                repositories,
+            } => json!({"package": package.as_str(), "repositories": repositories}),
+        };
+        json["code"] = self.code().into();
+        json["message"] = self.to_string().into();
+        json
+    }
+}
+
+/// Which record to use when a package name exists in both hex.pm and an
+/// organisation's repository, for clients that look packages up in several
+/// repositories at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ShadowingPolicy {
+    /// Use the organisation's package.
+    PreferOrganization,
+    /// Use the public package. This matches Hex, which only uses a package
+    /// from an organisation when the dependency names the organisation.
+    #[default]
+    PreferPublic,
+    /// Refuse to choose, returning [`ResolutionError::Shadowed`].
+    Error,
+}
+
+impl ShadowingPolicy {
+    /// Choose between the records of a package found in different
+    /// repositories. The public repository is `hexpm` and organisation
+    /// repositories are `hexpm:<organisation>`. When the package is in more
+    /// than one organisation's repository there is no single one to prefer,
+    /// so that is always an error.
+    pub fn choose<'a>(
+        &self,
+        name: &PackageName,
+        candidates: &'a [Package],
+    ) -> Result<&'a Package, ResolutionError> {
+        let (public, organizations): (Vec<_>, Vec<_>) = candidates
+            .iter()
+            .partition(|package| package.repository == "hexpm");
+        match (self, public.first(), organizations.as_slice()) {
+            (_, None, []) => Err(ResolutionError::MissingPackage(name.clone())),
+            (_, Some(public), []) => Ok(public),
+            (_, None, [organization]) => Ok(organization),
+            (ShadowingPolicy::PreferPublic, Some(public), _) => Ok(public),
+            (ShadowingPolicy::PreferOrganization, _, [organization]) => Ok(organization),
+            _ => Err(ResolutionError::Shadowed {
+                package: name.clone(),
+                repositories: candidates
+                    .iter()
+                    .map(|package| package.repository.clone())
+                    .collect(),
+            }),
+        }
+    }
+}
+
+/// A package requiring a dependency from a repository. See
+/// [`ResolutionError::ConflictingRepositories`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequiredFrom {
+    pub dependant: PackageName,
+    pub repository: String,
+}
+
+/// A package picked by version resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPackage {
+    pub name: PackageName,
+    /// The name of the package's OTP application. This is the package name
+    /// unless a package depending on it names the application differently.
+    pub app: String,
+    pub version: Version,
+    pub repository: String,
+    /// The sha256 checksum of the release tarball.
+    pub outer_checksum: Vec<u8>,
+}
+
+impl ResolvedPackage {
+    /// The entry for this package in a `rebar.lock` file. `level` is how deep
+    /// in the dependency tree the package is, where 0 is a dependency of the
+    /// project itself.
+    ///
+    /// The registry does not record the inner checksum of releases, so it is
+    /// left unset.
+    pub fn to_rebar_dependency(&self, level: u32) -> RebarDependency {
+        RebarDependency {
+            app: self.app.clone(),
+            source: RebarSource::Package {
+                name: self.name.to_string(),
+                version: self.version.clone(),
+                inner_checksum: None,
+                outer_checksum: Some(self.outer_checksum.clone()),
+            },
+            level,
+        }
+    }
+}
+
+/// A dependency of one selected package on another, with the requirement as
+/// the dependant's release states it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyEdge {
+    pub from: PackageName,
+    pub to: PackageName,
+    /// The requirement, optional flag, application name and repository of the
+    /// dependency.
+    pub dependency: Dependency,
+}
+
+impl DependencyEdge {
+    /// The requirement as written by the dependant, such as `~> 1.0`.
+    pub fn requirement(&self) -> &str {
+        self.dependency.requirement.as_str()
+    }
+}
+
+/// Releases used in place of those in a package's registry record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    /// The repository to record for the package. When unset the repository
+    /// of the replaced package is kept, or `hexpm` if it is not in the
+    /// registry.
+    pub repository: Option<String>,
+    pub releases: Vec<Release<()>>,
+}
+
+/// Changes to the registry data that resolution works from, much like
+/// Cargo's `[patch]` section.
+///
+/// ```
+/// use hexpm::resolution::{Replacement, ResolutionOptions};
+/// use std::collections::HashMap;
+///
+/// let options = ResolutionOptions::new().replace(
+///     "jason",
+///     Replacement {
+///         repository: None,
+///         releases: vec![],
+///     },
+/// );
+/// let packages = options.apply(&HashMap::new());
+/// assert!(packages["jason"].releases.is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolutionOptions {
+    replacements: HashMap<PackageName, Replacement>,
+    withdrawn: HashMap<PackageName, Vec<Version>>,
+    priorities: HashMap<PackageName, u64>,
+}
+
+impl ResolutionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the given releases for a package, with their own versions and
+    /// requirements, instead of those in its registry record. The package
+    /// keeps its name, so dependants still require it as before.
+    pub fn replace(mut self, name: impl Into<PackageName>, replacement: Replacement) -> Self {
+        self.replacements.insert(name.into(), replacement);
+        self
+    }
+
+    pub fn is_replaced(&self, name: &str) -> bool {
+        self.replacements.contains_key(name)
+    }
+
+    /// Decide packages with higher priorities first, such as those from
+    /// [`download_priorities`]. Packages without a priority are decided last.
+    pub fn priorities(mut self, priorities: HashMap<PackageName, u64>) -> Self {
+        self.priorities = priorities;
+        self
+    }
+
+    /// Leave a release out of the package records, as if it had been retired
+    /// and could no longer be picked.
+    pub fn withdraw(mut self, name: impl Into<PackageName>, version: Version) -> Self {
+        self.withdrawn.entry(name.into()).or_default().push(version);
+        self
+    }
+
+    /// The package records with replacements applied, to be given to the
+    /// version solver and then to [`resolved_packages`] and the other
+    /// functions of this module. Replaced packages that are not in the
+    /// registry are added, and withdrawn releases are removed.
+    pub fn apply(&self, packages: &HashMap<PackageName, Package>) -> HashMap<PackageName, Package> {
+        let mut packages = packages.clone();
+        for (name, replacement) in &self.replacements {
+            let repository = match (&replacement.repository, packages.get(name)) {
+                (Some(repository), _) => repository.clone(),
+                (None, Some(package)) => package.repository.clone(),
+                (None, None) => "hexpm".to_string(),
+            };
+            let package = Package {
+                name: name.clone(),
+                repository,
+                releases: replacement.releases.clone(),
+            };
+            packages.insert(name.clone(), package);
+        }
+        for (name, versions) in &self.withdrawn {
+            if let Some(package) = packages.get_mut(name) {
+                package
+                    .releases
+                    .retain(|release| !versions.contains(&release.version));
+            }
+        }
+        packages
+    }
+}
+
+/// Look up the selected version of every package in the registry's package
+/// records, returning the resolved packages sorted by name.
+///
+/// Application names are taken from the requirements of the other selected
+/// releases, falling back to the package name when no requirement names one.
+/// Should dependants disagree on the name, the dependant whose package name
+/// sorts first wins, so the result does not depend on `HashMap` iteration
+/// order.
+pub fn resolved_packages(
+    selected: &HashMap<PackageName, Version>,
+    packages: &HashMap<PackageName, Package>,
+) -> Result<Vec<ResolvedPackage>, ResolutionError> {
+    let releases = selected_releases(selected, packages)?;
+
+    let mut apps: HashMap<&PackageName, &str> = HashMap::new();
+    for (_, _, release) in &releases {
+        for (name, dependency) in &release.requirements {
+            if let Some(app) = dependency.app.as_deref() {
+                apps.entry(name).or_insert(app);
+            }
+        }
+    }
+
+    let resolved = releases
+        .into_iter()
+        .map(|(name, package, release)| ResolvedPackage {
+            name: name.clone(),
+            app: apps.get(name).copied().unwrap_or(name).to_string(),
+            version: release.version.clone(),
+            repository: package.repository.clone(),
+            outer_checksum: release.outer_checksum.clone(),
+        })
+        .collect();
+    Ok(resolved)
+}
+
+/// The dependencies between the selected packages, sorted by dependant and
+/// then dependency. Requirements on packages that were not selected, which
+/// can only be optional ones, have no edge.
+pub fn dependency_edges(
+    selected: &HashMap<PackageName, Version>,
+    packages: &HashMap<PackageName, Package>,
+) -> Result<Vec<DependencyEdge>, ResolutionError> {
+    let mut edges: Vec<DependencyEdge> = selected_releases(selected, packages)?
+        .into_iter()
+        .flat_map(|(from, _, release)| {
+            release
+                .requirements
+                .iter()
+                .filter(|(to, _)| selected.contains_key(*to))
+                .map(|(to, dependency)| DependencyEdge {
+                    from: from.clone(),
+                    to: to.clone(),
+                    dependency: dependency.clone(),
+                })
+        })
+        .collect();
+    edges.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+    Ok(edges)
+}
+
+/// Compilation target information for [`required_packages`].
+#[derive(Debug, Clone, Copy)]
+pub struct TargetFilter<'a> {
+    /// The target being compiled for.
+    pub target: Target,
+    /// The tarball metadata of the selected packages. Packages without
+    /// metadata are assumed to need all their dependencies.
+    pub metadata: &'a HashMap<PackageName, PackageMetadata>,
+}
+
+impl TargetFilter<'_> {
+    fn excludes(&self, edge: &DependencyEdge) -> bool {
+        self.metadata
+            .get(&edge.from)
+            .is_some_and(|metadata| !metadata.needs_dependency_on(&edge.to, self.target))
+    }
+}
+
+/// The selected packages that are needed by the given root packages, such as
+/// the direct dependencies of a project, sorted by name. Optional
+/// dependencies are followed as long as they were selected.
+///
+/// When a [`TargetFilter`] is given, dependencies that the dependant only
+/// declares for other targets are not followed, so packages needed only on
+/// other targets are left out.
+pub fn required_packages(
+    roots: &[PackageName],
+    selected: &HashMap<PackageName, Version>,
+    packages: &HashMap<PackageName, Package>,
+    filter: Option<TargetFilter<'_>>,
+) -> Result<Vec<PackageName>, ResolutionError> {
+    let edges = dependency_edges(selected, packages)?;
+    let mut required: Vec<PackageName> = vec![];
+    let mut stack: Vec<&PackageName> = roots.iter().collect();
+    while let Some(name) = stack.pop() {
+        if !selected.contains_key(name) {
+            return Err(ResolutionError::MissingPackage(name.clone()));
+        }
+        if required.contains(name) {
+            continue;
+        }
+        required.push(name.clone());
+        stack.extend(
+            edges
+                .iter()
+                .filter(|edge| &edge.from == name)
+                .filter(|edge| !filter.is_some_and(|filter| filter.excludes(edge)))
+                .map(|edge| &edge.to),
+        );
+    }
+    required.sort();
+    Ok(required)
+}
+
+/// A selected release requiring another package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequirementStep {
+    pub package: PackageName,
+    pub version: Version,
+    pub dependency: PackageName,
+    pub requirement: Range,
+}
+
+impl std::fmt::Display for RequirementStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} requires {} {}",
+            self.package, self.version, self.dependency, self.requirement
+        )
+    }
+}
+
+/// Why a package was selected, as found by [`explain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub package: PackageName,
+    pub version: Version,
+    /// The shortest chain of requirements from a root package to this one,
+    /// which is empty if the package is a root itself.
+    pub path: Vec<RequirementStep>,
+    /// Every requirement on the package by the selected releases, which
+    /// together limit the versions it could be resolved to, sorted by
+    /// dependant.
+    pub requirements: Vec<RequirementStep>,
+}
+
+impl Explanation {
+    /// The explanation as lines of text, for commands such as `deps why`.
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![format!("{} {}", self.package, self.version)];
+        match self.path.first() {
+            None => lines.push("  is a direct dependency".into()),
+            Some(step) => {
+                lines.push(format!("  is required through {}", step.package));
+                lines.extend(self.path.iter().map(|step| format!("    {step}")));
+            }
+        }
+        if !self.requirements.is_empty() {
+            lines.push("  is constrained by".into());
+            lines.extend(self.requirements.iter().map(|step| format!("    {step}")));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Explain why `package` was selected: the chain of requirements from the
+/// root packages, such as the direct dependencies of a project, that led to
+/// it, and every requirement that constrained its version. Returns `None` if
+/// the package is selected but not required by any of the roots.
+pub fn explain(
+    package: &str,
+    roots: &[PackageName],
+    selected: &HashMap<PackageName, Version>,
+    packages: &HashMap<PackageName, Package>,
+) -> Result<Option<Explanation>, ResolutionError> {
+    let Some((package, version)) = selected.get_key_value(package) else {
+        return Err(ResolutionError::MissingPackage(package.into()));
+    };
+    let step = |edge: &DependencyEdge| RequirementStep {
+        package: edge.from.clone(),
+        version: selected[&edge.from].clone(),
+        dependency: edge.to.clone(),
+        requirement: edge.dependency.requirement.clone(),
+    };
+    let edges = dependency_edges(selected, packages)?;
+
+    // A breadth first search from the roots, visiting dependencies in name
+    // order so that the same path is found every time.
+    let mut roots: Vec<&PackageName> = roots.iter().collect();
+    roots.sort();
+    let mut reached_by: HashMap<&PackageName, Option<&DependencyEdge>> = HashMap::new();
+    let mut queue = VecDeque::new();
+    for root in roots {
+        if !selected.contains_key(root) {
+            return Err(ResolutionError::MissingPackage(root.clone()));
+        }
+        if reached_by.insert(root, None).is_none() {
+            queue.push_back(root);
+        }
+    }
+    while let Some(name) = queue.pop_front() {
+        if name == package {
+            break;
+        }
+        for edge in edges.iter().filter(|edge| &edge.from == name) {
+            if !reached_by.contains_key(&edge.to) {
+                reached_by.insert(&edge.to, Some(edge));
+                queue.push_back(&edge.to);
+            }
+        }
+    }
+    if !reached_by.contains_key(package) {
+        return Ok(None);
+    }
+
+    let mut path = vec![];
+    let mut current = package;
+    while let Some(Some(edge)) = reached_by.get(current) {
+        path.push(step(edge));
+        current = &edge.from;
+    }
+    path.reverse();
+    let requirements = edges
+        .iter()
+        .filter(|edge| &edge.to == package)
+        .map(step)
+        .collect();
+    Ok(Some(Explanation {
+        package: package.clone(),
+        version: version.clone(),
+        path,
+        requirements,
+    }))
+}
+
+/// A requirement on a package, by a selected release or by the project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    /// The package with the requirement, or `None` for a root requirement.
+    pub dependant: Option<PackageName>,
+    pub requirement: Range,
+}
+
+/// A package whose selected version differs between two resolutions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionChange {
+    pub package: PackageName,
+    /// The previous version, or `None` if the package was not selected.
+    pub from: Option<Version>,
+    /// The new version, or `None` if the package is no longer needed.
+    pub to: Option<Version>,
+}
+
+/// The result of [`what_if`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WhatIf {
+    /// A selection satisfying every requirement was found.
+    Resolved {
+        selected: HashMap<PackageName, Version>,
+        /// The changes from the original selection, sorted by package.
+        changes: Vec<SelectionChange>,
+    },
+    /// No release of the package satisfies all of its constraints.
+    Unresolvable {
+        package: PackageName,
+        constraints: Vec<Constraint>,
+        /// The dependency cycle the package is in, if any, which is often
+        /// why no release of it can be found.
+        cycle: Option<DependencyCycle>,
+    },
+}
+
+/// How important it is to decide a package early during resolution, where
+/// higher priorities are decided first.
+pub trait PriorityHintSource {
+    /// The priority of a package, or `None` if there is no hint for it.
+    fn priority(&self, package: &str) -> Option<u64>;
+}
+
+impl PriorityHintSource for HashMap<PackageName, u64> {
+    fn priority(&self, package: &str) -> Option<u64> {
+        self.get(package).copied()
+    }
+}
+
+impl<F: Fn(&str) -> Option<u64>> PriorityHintSource for F {
+    fn priority(&self, package: &str) -> Option<u64> {
+        self(package)
+    }
+}
+
+/// Priorities from the recent download counts of packages as listed by
+/// [`get_packages_page_response`](crate::get_packages_page_response), as
+/// widely used packages tend to be depended upon by many others.
+pub fn download_priorities(summaries: &[PackageSummary]) -> HashMap<PackageName, u64> {
+    summaries
+        .iter()
+        .map(|summary| (summary.name.as_str().into(), summary.downloads.recent))
+        .collect()
+}
+
+/// The order to decide packages in: highest priority first, then packages
+/// without a hint, with ties broken by name.
+pub fn decision_order<'a>(
+    packages: impl IntoIterator<Item = &'a PackageName>,
+    hints: &(impl PriorityHintSource + ?Sized),
+) -> Vec<&'a PackageName> {
+    let mut packages: Vec<_> = packages.into_iter().collect();
+    packages.sort_by_cached_key(|name| (std::cmp::Reverse(hints.priority(name)), *name));
+    packages
+}
+
+/// Re-resolve an existing selection as if the packages had been changed by
+/// `options`, such as to see which projects would be affected by retiring a
+/// release before retiring it.
+///
+/// `roots` are the requirements of the project itself. Packages are only
+/// moved from their selected versions when those are no longer available or
+/// no longer satisfy the other selected releases, in which case the highest
+/// satisfying release is picked, preferring those that are not
+/// pre-releases. Newly required packages are added and packages no longer
+/// required are removed.
+///
+/// This keeps the rest of the selection as it is rather than searching for
+/// the best resolution, so a full version solver may still find one where
+/// this reports [`WhatIf::Unresolvable`], but the changes it reports are the
+/// smallest needed.
+pub fn what_if(
+    roots: &HashMap<PackageName, Range>,
+    selected: &HashMap<PackageName, Version>,
+    packages: &HashMap<PackageName, Package>,
+    options: &ResolutionOptions,
+) -> WhatIf {
+    let packages = options.apply(packages);
+    let mut current = selected.clone();
+    // Each step picks a different release for a package, so a selection that
+    // keeps changing for longer than there are releases is going in circles.
+    let mut steps = packages
+        .values()
+        .map(|package| package.releases.len())
+        .sum::<usize>()
+        + 1;
+    loop {
+        let constraints = constraints(roots, &current, &packages);
+        let unsatisfied = decision_order(constraints.keys().copied(), &options.priorities)
+            .into_iter()
+            .map(|name| (name, &constraints[name]))
+            .find(|(name, constraints)| {
+                let version = current.get(*name);
+                !version.is_some_and(|version| {
+                    release_of(&packages, name, version).is_some()
+                        && satisfies(version, constraints)
+                })
+            });
+        let Some((name, constraints)) = unsatisfied else {
+            current.retain(|name, _| constraints.contains_key(name));
+            break;
+        };
+        let replacement = packages.get(name).and_then(|package| {
+            let candidates = package
+                .releases
+                .iter()
+                .map(|release| &release.version)
+                .filter(|version| satisfies(version, constraints));
+            candidates.max_by_key(|version| (!version.is_pre(), *version))
+        });
+        steps = steps.saturating_sub(1);
+        match replacement {
+            Some(version) if steps > 0 => {
+                current.insert(name.clone(), version.clone());
+            }
+            _ => {
+                let cycle = dependency_cycles(&packages)
+                    .into_iter()
+                    .find(|cycle| cycle.contains(name));
+                return WhatIf::Unresolvable {
+                    package: name.clone(),
+                    constraints: constraints.clone(),
+                    cycle,
+                };
+            }
+        }
+    }
+
+    let mut changes: Vec<SelectionChange> = selected
+        .keys()
+        .chain(current.keys())
+        .filter(|name| selected.get(*name) != current.get(*name))
+        .map(|name| SelectionChange {
+            package: name.clone(),
+            from: selected.get(name).cloned(),
+            to: current.get(name).cloned(),
+        })
+        .collect();
+    changes.sort_by(|a, b| a.package.cmp(&b.package));
+    changes.dedup();
+    WhatIf::Resolved {
+        selected: current,
+        changes,
+    }
+}
+
+/// Packages that depend on each other in a loop, as found by
+/// [`dependency_cycles`]. Each step's dependency is the package of the next
+/// step, and the last step's dependency is the package of the first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyCycle {
+    pub steps: Vec<RequirementStep>,
+}
+
+impl DependencyCycle {
+    /// The packages in the cycle, starting with the first by name.
+    pub fn packages(&self) -> impl Iterator<Item = &PackageName> {
+        self.steps.iter().map(|step| &step.package)
+    }
+
+    pub fn contains(&self, package: &str) -> bool {
+        self.packages().any(|name| name.as_str() == package)
+    }
+}
+
+impl std::fmt::Display for DependencyCycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{step}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the dependency cycles among the given packages, one for each group of
+/// packages that depend on each other, sorted by their first package.
+///
+/// A package depends on another when any of its releases has a requirement
+/// on it that is not optional, and the steps of a cycle show the latest such
+/// release. The shortest cycle through the first package of a group by name
+/// is given, so a group with more than one cycle is reported once.
+///
+/// ```
+/// use hexpm::resolution::dependency_cycles;
+/// use hexpm::{Dependency, Package, Release, version::{Range, Version}};
+/// use std::collections::HashMap;
+///
+/// let package = |name: &str, dependency: &str| {
+///     let requirement = Dependency {
+///         requirement: Range::new("~> 1.0".into()).unwrap(),
+///         optional: false,
+///         app: None,
+///         repository: None,
+///     };
+///     let release = Release {
+///         version: Version::new(1, 0, 0),
+///         requirements: HashMap::from([(dependency.into(), requirement)]),
+///         retirement_status: None,
+///         outer_checksum: vec![],
+///         meta: (),
+///     };
+///     let package = Package {
+///         name: name.into(),
+///         repository: "hexpm".into(),
+///         releases: vec![release],
+///     };
+///     (name.into(), package)
+/// };
+/// let packages = HashMap::from([package("chicken", "egg"), package("egg", "chicken")]);
+/// let cycles = dependency_cycles(&packages);
+/// assert_eq!(
+///     cycles[0].to_string(),
+///     "chicken 1.0.0 requires egg ~> 1.0, egg 1.0.0 requires chicken ~> 1.0"
+/// );
+/// ```
+pub fn dependency_cycles(packages: &HashMap<PackageName, Package>) -> Vec<DependencyCycle> {
+    let graph = requirement_graph(packages);
+
+    // Group the packages into strongly connected components with Kosaraju's
+    // algorithm, first ordering them by when their depth first search
+    // finishes.
+    let mut visited = HashSet::new();
+    let mut order = vec![];
+    for &start in graph.keys() {
+        if !visited.insert(start) {
+            continue;
+        }
+        let mut stack = vec![(start, graph[start].keys())];
+        while let Some((node, dependencies)) = stack.last_mut() {
+            let node = *node;
+            match dependencies.next().copied() {
+                Some(dependency) => {
+                    if visited.insert(dependency) {
+                        stack.push((dependency, graph[dependency].keys()));
+                    }
+                }
+                None => {
+                    order.push(node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+    let mut dependants: BTreeMap<&PackageName, Vec<&PackageName>> = BTreeMap::new();
+    for (&package, dependencies) in &graph {
+        for &dependency in dependencies.keys() {
+            dependants.entry(dependency).or_default().push(package);
+        }
+    }
+    let mut assigned = HashSet::new();
+    let mut cycles = vec![];
+    for &root in order.iter().rev() {
+        if !assigned.insert(root) {
+            continue;
+        }
+        let mut component = HashSet::from([root]);
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            for &dependant in dependants.get(node).into_iter().flatten() {
+                if assigned.insert(dependant) {
+                    component.insert(dependant);
+                    stack.push(dependant);
+                }
+            }
+        }
+        let first = *component.iter().min().expect("components are not empty");
+        if component.len() > 1 || graph[first].contains_key(first) {
+            cycles.push(shortest_cycle(&graph, &component, first));
+        }
+    }
+    cycles.sort_by(|a, b| a.steps[0].package.cmp(&b.steps[0].package));
+    cycles
+}
+
+/// For each package, the packages it depends on, with the requirement of its
+/// latest release that depends on each.
+type RequirementGraph<'a> = BTreeMap<&'a PackageName, BTreeMap<&'a PackageName, RequirementStep>>;
+
+fn requirement_graph(packages: &HashMap<PackageName, Package>) -> RequirementGraph<'_> {
+    let mut graph = BTreeMap::new();
+    for (name, package) in packages {
+        let mut releases: Vec<_> = package.releases.iter().collect();
+        releases.sort_by(|a, b| b.version.cmp(&a.version));
+        let mut dependencies = BTreeMap::new();
+        for release in releases {
+            for (dependency, requirement) in &release.requirements {
+                // Packages that were not given cannot be part of a cycle.
+                let Some((dependency, _)) = packages.get_key_value(dependency) else {
+                    continue;
+                };
+                if requirement.optional {
+                    continue;
+                }
+                dependencies
+                    .entry(dependency)
+                    .or_insert_with(|| RequirementStep {
+                        package: name.clone(),
+                        version: release.version.clone(),
+                        dependency: dependency.clone(),
+                        requirement: requirement.requirement.clone(),
+                    });
+            }
+        }
+        graph.insert(name, dependencies);
+    }
+    graph
+}
+
+/// The shortest cycle from `first` back to itself within a component, found
+/// with a breadth first search.
+fn shortest_cycle(
+    graph: &RequirementGraph<'_>,
+    component: &HashSet<&PackageName>,
+    first: &PackageName,
+) -> DependencyCycle {
+    let mut previous: HashMap<&PackageName, &PackageName> = HashMap::new();
+    let mut queue = VecDeque::from([first]);
+    while let Some(node) = queue.pop_front() {
+        for (&dependency, step) in &graph[node] {
+            if dependency == first {
+                let mut steps = vec![step.clone()];
+                let mut current = node;
+                while current != first {
+                    let dependant = previous[current];
+                    steps.push(graph[dependant][current].clone());
+                    current = dependant;
+                }
+                steps.reverse();
+                return DependencyCycle { steps };
+            }
+            if component.contains(dependency) && !previous.contains_key(dependency) {
+                previous.insert(dependency, node);
+                queue.push_back(dependency);
+            }
+        }
+    }
+    unreachable!("every package of a component is on a cycle through the others")
+}
+
+fn release_of<'a>(
+    packages: &'a HashMap<PackageName, Package>,
+    name: &str,
+    version: &Version,
+) -> Option<&'a Release<()>> {
+    packages
+        .get(name)?
+        .releases
+        .iter()
+        .find(|release| &release.version == version)
+}
+
+fn satisfies(version: &Version, constraints: &[Constraint]) -> bool {
+    constraints
+        .iter()
+        .all(|constraint| constraint.requirement.to_pubgrub().contains(version))
+}
+
+/// The constraints on every package required from the roots through the
+/// available selected releases, sorted by package and then dependant.
+/// Optional requirements only count when the package is selected.
+fn constraints<'a>(
+    roots: &'a HashMap<PackageName, Range>,
+    selected: &HashMap<PackageName, Version>,
+    packages: &'a HashMap<PackageName, Package>,
+) -> BTreeMap<&'a PackageName, Vec<Constraint>> {
+    let mut constraints: BTreeMap<_, Vec<_>> = roots
+        .iter()
+        .map(|(name, requirement)| {
+            let constraint = Constraint {
+                dependant: None,
+                requirement: requirement.clone(),
+            };
+            (name, vec![constraint])
+        })
+        .collect();
+    let mut stack: Vec<&PackageName> = roots.keys().collect();
+    let mut visited: Vec<&PackageName> = vec![];
+    while let Some(name) = stack.pop() {
+        if visited.contains(&name) {
+            continue;
+        }
+        visited.push(name);
+        let Some(release) = selected
+            .get(name)
+            .and_then(|version| release_of(packages, name, version))
+        else {
+            continue;
+        };
+        for (dependency, requirement) in &release.requirements {
+            if requirement.optional && !selected.contains_key(dependency) {
+                continue;
+            }
+            constraints.entry(dependency).or_default().push(Constraint {
+                dependant: Some(name.clone()),
+                requirement: requirement.requirement.clone(),
+            });
+            stack.push(dependency);
+        }
+    }
+    for constraints in constraints.values_mut() {
+        constraints.sort_by(|a, b| a.dependant.cmp(&b.dependant));
+    }
+    constraints
+}
+
+type SelectedRelease<'a> = (&'a PackageName, &'a Package, &'a Release<()>);
+
+/// The selected release of every package, sorted by package name.
+fn selected_releases<'a>(
+    selected: &'a HashMap<PackageName, Version>,
+    packages: &'a HashMap<PackageName, Package>,
+) -> Result<Vec<SelectedRelease<'a>>, ResolutionError> {
+    let mut releases = Vec::with_capacity(selected.len());
+    for (name, version) in selected {
+        let package = packages
+            .get(name)
+            .ok_or_else(|| ResolutionError::MissingPackage(name.clone()))?;
+        let release = package
+            .releases
+            .iter()
+            .find(|release| &release.version == version)
+            .ok_or_else(|| ResolutionError::MissingRelease {
+                package: name.clone(),
+                version: version.clone(),
+            })?;
+        releases.push((name, package, release));
+    }
+    releases.sort_by(|a, b| a.0.cmp(b.0));
+    check_repositories(&releases)?;
+    Ok(releases)
+}
+
+/// Ensure no package is required from two different repositories, such as
+/// from both hex.pm and an organisation's repository with a package of the
+/// same name. A requirement without a repository is for the dependant's own
+/// repository.
+fn check_repositories(releases: &[SelectedRelease<'_>]) -> Result<(), ResolutionError> {
+    let mut sources: HashMap<&PackageName, RequiredFrom> = HashMap::new();
+    for (dependant, package, release) in releases {
+        let mut requirements: Vec<_> = release.requirements.iter().collect();
+        requirements.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, dependency) in requirements {
+            let source = RequiredFrom {
+                dependant: (*dependant).clone(),
+                repository: dependency
+                    .repository
+                    .clone()
+                    .unwrap_or_else(|| package.repository.clone()),
+            };
+            match sources.get(name) {
+                Some(first) if first.repository != source.repository => {
+                    return Err(ResolutionError::ConflictingRepositories {
+                        package: name.clone(),
+                        first: first.clone(),
+                        second: source,
+                    });
+                }
+                Some(_) => (),
+                None => {
+                    sources.insert(name, source);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, release};
+
+    fn package(name: &str, version: &str, requirements: &[(&str, Option<&str>)]) -> Package {
+        let release =
+            requirements
+                .iter()
+                .fold(release(version), |release, (name, app)| match app {
+                    Some(app) => release.requires_app(name, "~> 1.0", app),
+                    None => release.requires(name, "~> 1.0"),
+                });
+        fixtures::package(name, [release.checksum(name.len() as u8)])
+    }
+
+    fn packages() -> HashMap<PackageName, Package> {
+        [
+            package("phoenix", "1.7.0", &[("jason", Some("my_json"))]),
+            package("jason", "1.4.0", &[("decimal", None)]),
+            package("decimal", "2.1.0", &[]),
+        ]
+        .into_iter()
+        .map(|package| (package.name.clone(), package))
+        .collect()
+    }
+
+    fn selected(versions: &[(&str, &str)]) -> HashMap<PackageName, Version> {
+        versions
+            .iter()
+            .map(|(name, version)| ((*name).into(), Version::parse(version).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn resolve() {
+        let selected = selected(&[
+            ("phoenix", "1.7.0"),
+            ("jason", "1.4.0"),
+            ("decimal", "2.1.0"),
+        ]);
+        let resolved = resolved_packages(&selected, &packages()).unwrap();
+        let apps: Vec<_> = resolved
+            .iter()
+            .map(|package| (package.name.as_str(), package.app.as_str()))
+            .collect();
+        assert_eq!(
+            apps,
+            vec![
+                ("decimal", "decimal"),
+                ("jason", "my_json"),
+                ("phoenix", "phoenix")
+            ]
+        );
+
+        let jason = resolved[1].to_rebar_dependency(1);
+        assert_eq!(jason.app, "my_json");
+        assert_eq!(
+            jason.source,
+            RebarSource::Package {
+                name: "jason".into(),
+                version: Version::new(1, 4, 0),
+                inner_checksum: None,
+                outer_checksum: Some(vec![5; 32]),
+            }
+        );
+    }
+
+    #[test]
+    fn deterministic() {
+        let mut packages = packages();
+        packages.insert(
+            "absinthe".into(),
+            package("absinthe", "1.7.0", &[("jason", Some("absinthe_json"))]),
+        );
+        let selected = selected(&[
+            ("absinthe", "1.7.0"),
+            ("phoenix", "1.7.0"),
+            ("jason", "1.4.0"),
+            ("decimal", "2.1.0"),
+        ]);
+        let first = resolved_packages(&selected, &packages).unwrap();
+        assert_eq!(first[2].app, "absinthe_json");
+        // Each clone of a `HashMap` keeps its hasher, so rebuild the maps to
+        // get a new iteration order every time.
+        for _ in 0..32 {
+            let selected: HashMap<_, _> = selected.clone().into_iter().collect();
+            let packages: HashMap<_, _> = packages.clone().into_iter().collect();
+            assert_eq!(resolved_packages(&selected, &packages).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn edges() {
+        let mut packages = packages();
+        packages.insert(
+            "jason".into(),
+            package("jason", "1.4.0", &[("decimal", None), ("poison", None)]),
+        );
+        let selected = selected(&[
+            ("phoenix", "1.7.0"),
+            ("jason", "1.4.0"),
+            ("decimal", "2.1.0"),
+        ]);
+        let edges = dependency_edges(&selected, &packages).unwrap();
+        let pairs: Vec<_> = edges
+            .iter()
+            .map(|edge| (edge.from.as_str(), edge.to.as_str()))
+            .collect();
+        assert_eq!(pairs, vec![("jason", "decimal"), ("phoenix", "jason")]);
+        assert_eq!(edges[1].requirement(), "~> 1.0");
+        assert_eq!(edges[1].dependency.app.as_deref(), Some("my_json"));
+        assert!(!edges[1].dependency.optional);
+    }
+
+    #[test]
+    fn target_filter() {
+        let mut packages = packages();
+        packages.insert(
+            "lustre".into(),
+            package(
+                "lustre",
+                "4.0.0",
+                &[("gleam_erlang", None), ("gleam_json", None)],
+            ),
+        );
+        packages.insert(
+            "gleam_erlang".into(),
+            package("gleam_erlang", "0.25.0", &[]),
+        );
+        packages.insert("gleam_json".into(), package("gleam_json", "1.0.0", &[]));
+        let selected = selected(&[
+            ("lustre", "4.0.0"),
+            ("gleam_erlang", "0.25.0"),
+            ("gleam_json", "1.0.0"),
+            ("decimal", "2.1.0"),
+        ]);
+        let metadata = crate::tarball::parse_metadata(
+            r#"{<<"name">>,<<"lustre">>}.
+{<<"version">>,<<"4.0.0">>}.
+{<<"extra">>,[{<<"gleam">>,[{<<"dependency_targets">>,[{<<"gleam_erlang">>,[<<"erlang">>]}]}]}]}.
+"#,
+        )
+        .unwrap();
+        let metadata = [("lustre".into(), metadata)].into_iter().collect();
+        let roots = ["lustre".into()];
+        let required = |target| {
+            let filter = TargetFilter {
+                target,
+                metadata: &metadata,
+            };
+            required_packages(&roots, &selected, &packages, Some(filter)).unwrap()
+        };
+
+        assert_eq!(
+            required(Target::JavaScript),
+            vec![PackageName::from("gleam_json"), "lustre".into()]
+        );
+        assert_eq!(
+            required(Target::Erlang),
+            vec![
+                PackageName::from("gleam_erlang"),
+                "gleam_json".into(),
+                "lustre".into()
+            ]
+        );
+        assert_eq!(
+            required_packages(&roots, &selected, &packages, None)
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(
+            required_packages(&["plug".into()], &selected, &packages, None),
+            Err(ResolutionError::MissingPackage("plug".into()))
+        );
+    }
+
+    #[cfg(feature = "api")]
+    #[test]
+    fn conflicting_repositories() {
+        let mut packages = packages();
+        let mut app = package("app", "1.0.0", &[("jason", None)]);
+        app.repository = "hexpm:acme".to_string();
+        packages.insert("app".into(), app);
+        let selected = selected(&[
+            ("app", "1.0.0"),
+            ("phoenix", "1.7.0"),
+            ("jason", "1.4.0"),
+            ("decimal", "2.1.0"),
+        ]);
+        let error = resolved_packages(&selected, &packages).unwrap_err();
+        assert_eq!(
+            error,
+            ResolutionError::ConflictingRepositories {
+                package: "jason".into(),
+                first: RequiredFrom {
+                    dependant: "app".into(),
+                    repository: "hexpm:acme".to_string(),
+                },
+                second: RequiredFrom {
+                    dependant: "phoenix".into(),
+                    repository: "hexpm".to_string(),
+                },
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "jason is required from the hexpm:acme repository by app and from the hexpm repository by phoenix"
+        );
+        assert_eq!(
+            error.to_json(),
+            json!({
+                "code": "conflicting_repositories",
+                "message": error.to_string(),
+                "package": "jason",
+                "required_from": [
+                    {"dependant": "app", "repository": "hexpm:acme"},
+                    {"dependant": "phoenix", "repository": "hexpm"},
+                ],
+            })
+        );
+
+        let mut app = package("app", "1.0.0", &[("jason", None)]);
+        app.repository = "hexpm:acme".to_string();
+        for dependency in app.releases[0].requirements.values_mut() {
+            dependency.repository = Some("hexpm".to_string());
+        }
+        packages.insert("app".into(), app);
+        assert!(resolved_packages(&selected, &packages).is_ok());
+    }
+
+    #[test]
+    fn shadowing() {
+        let name = PackageName::from("jason");
+        let public = package("jason", "1.4.0", &[]);
+        let mut acme = package("jason", "1.4.1", &[]);
+        acme.repository = "hexpm:acme".to_string();
+        let mut initech = acme.clone();
+        initech.repository = "hexpm:initech".to_string();
+        let both = [public.clone(), acme.clone()];
+
+        let chosen = |policy: ShadowingPolicy, candidates: &[Package]| {
+            policy
+                .choose(&name, candidates)
+                .map(|package| package.repository.clone())
+        };
+        assert_eq!(
+            chosen(ShadowingPolicy::default(), &both),
+            Ok("hexpm".to_string())
+        );
+        assert_eq!(
+            chosen(ShadowingPolicy::PreferOrganization, &both),
+            Ok("hexpm:acme".to_string())
+        );
+        assert_eq!(
+            chosen(ShadowingPolicy::Error, &both),
+            Err(ResolutionError::Shadowed {
+                package: name.clone(),
+                repositories: vec!["hexpm".to_string(), "hexpm:acme".to_string()],
+            })
+        );
+        assert_eq!(
+            chosen(ShadowingPolicy::Error, std::slice::from_ref(&acme)),
+            Ok("hexpm:acme".to_string())
+        );
+        assert!(matches!(
+            chosen(
+                ShadowingPolicy::PreferOrganization,
+                &[public, acme, initech]
+            ),
+            Err(ResolutionError::Shadowed { .. })
+        ));
+        assert_eq!(
+            chosen(ShadowingPolicy::PreferPublic, &[]),
+            Err(ResolutionError::MissingPackage(name.clone()))
+        );
+    }
+
+    #[test]
+    fn missing_records() {
+        let packages = packages();
+        assert_eq!(
+            resolved_packages(&selected(&[("plug", "1.0.0")]), &packages),
+            Err(ResolutionError::MissingPackage("plug".into()))
+        );
+        assert_eq!(
+            resolved_packages(&selected(&[("jason", "1.3.0")]), &packages),
+            Err(ResolutionError::MissingRelease {
+                package: "jason".into(),
+                version: Version::new(1, 3, 0),
+            })
+        );
+    }
+
+    #[test]
+    fn replacements() {
+        let mut fork = package("jason", "1.5.0-patched", &[]);
+        fork.releases[0].outer_checksum = vec![];
+        let options = ResolutionOptions::new()
+            .replace(
+                "jason",
+                Replacement {
+                    repository: None,
+                    releases: fork.releases,
+                },
+            )
+            .replace(
+                "local_only",
+                Replacement {
+                    repository: Some("acme".into()),
+                    releases: vec![],
+                },
+            );
+        assert!(options.is_replaced("jason"));
+        assert!(!options.is_replaced("decimal"));
+
+        let packages = options.apply(&packages());
+        assert_eq!(packages["local_only"].repository, "acme");
+        let selected = selected(&[("phoenix", "1.7.0"), ("jason", "1.5.0-patched")]);
+        let resolved = resolved_packages(&selected, &packages).unwrap();
+        assert_eq!(resolved[0].name, "jason");
+        assert_eq!(resolved[0].app, "my_json");
+        assert_eq!(resolved[0].repository, "hexpm");
+        assert!(resolved[0].outer_checksum.is_empty());
+        // The fork no longer depends on decimal.
+        assert_eq!(
+            required_packages(&["phoenix".into()], &selected, &packages, None).unwrap(),
+            vec![PackageName::from("jason"), "phoenix".into()]
+        );
+        assert!(resolved_packages(&selected, &self::packages()).is_err());
+    }
+
+    #[test]
+    fn explains_why() {
+        let mut packages = packages();
+        let plug = package("plug", "1.14.0", &[("decimal", None)]);
+        packages.insert(plug.name.clone(), plug);
+        let selected = selected(&[
+            ("phoenix", "1.7.0"),
+            ("jason", "1.4.0"),
+            ("decimal", "2.1.0"),
+            ("plug", "1.14.0"),
+        ]);
+        let roots = ["phoenix".into(), "plug".into()];
+
+        let why = explain("decimal", &roots, &selected, &packages)
+            .unwrap()
+            .unwrap();
+        assert_eq!(why.version, Version::new(2, 1, 0));
+        let chain: Vec<_> = why.path.iter().map(|step| step.to_string()).collect();
+        assert_eq!(chain, vec!["plug 1.14.0 requires decimal ~> 1.0"]);
+        assert_eq!(
+            why.to_text(),
+            "decimal 2.1.0\n\
+             \x20 is required through plug\n\
+             \x20   plug 1.14.0 requires decimal ~> 1.0\n\
+             \x20 is constrained by\n\
+             \x20   jason 1.4.0 requires decimal ~> 1.0\n\
+             \x20   plug 1.14.0 requires decimal ~> 1.0"
+        );
+
+        let why = explain("decimal", &roots[..1], &selected, &packages)
+            .unwrap()
+            .unwrap();
+        let chain: Vec<_> = why.path.iter().map(|step| step.package.as_str()).collect();
+        assert_eq!(chain, vec!["phoenix", "jason"]);
+
+        let why = explain("phoenix", &roots, &selected, &packages)
+            .unwrap()
+            .unwrap();
+        assert!(why.path.is_empty() && why.requirements.is_empty());
+        assert_eq!(
+            explain("plug", &roots[..1], &selected, &packages).unwrap(),
+            None
+        );
+        assert_eq!(
+            explain("ecto", &roots, &selected, &packages),
+            Err(ResolutionError::MissingPackage("ecto".into()))
+        );
+    }
+
+    #[test]
+    fn what_if_withdrawn() {
+        let mut packages = packages();
+        let jason = packages.get_mut("jason").unwrap();
+        let decimal = jason.releases[0].requirements.get_mut("decimal").unwrap();
+        decimal.requirement = Range::new("~> 2.0".into()).unwrap();
+        let mut older = jason.releases[0].clone();
+        older.version = Version::new(1, 3, 0);
+        older.requirements.clear();
+        let mut pre = older.clone();
+        pre.version = Version::parse("1.5.0-rc.1").unwrap();
+        jason.releases.extend([older, pre]);
+        let selected = selected(&[
+            ("phoenix", "1.7.0"),
+            ("jason", "1.4.0"),
+            ("decimal", "2.1.0"),
+        ]);
+        let roots = HashMap::from([(
+            PackageName::from("phoenix"),
+            Range::new("~> 1.7".into()).unwrap(),
+        )]);
+
+        let unchanged = what_if(&roots, &selected, &packages, &ResolutionOptions::new());
+        assert_eq!(
+            unchanged,
+            WhatIf::Resolved {
+                selected: selected.clone(),
+                changes: vec![]
+            }
+        );
+
+        // Without 1.4.0 jason falls back to 1.3.0 rather than the
+        // pre-release, and decimal is no longer needed.
+        let options = ResolutionOptions::new().withdraw("jason", Version::new(1, 4, 0));
+        let WhatIf::Resolved { changes, .. } = what_if(&roots, &selected, &packages, &options)
+        else {
+            panic!("expected a resolution");
+        };
+        assert_eq!(
+            changes,
+            vec![
+                SelectionChange {
+                    package: "decimal".into(),
+                    from: Some(Version::new(2, 1, 0)),
+                    to: None,
+                },
+                SelectionChange {
+                    package: "jason".into(),
+                    from: Some(Version::new(1, 4, 0)),
+                    to: Some(Version::new(1, 3, 0)),
+                },
+            ]
+        );
+
+        let options = ResolutionOptions::new().withdraw("decimal", Version::new(2, 1, 0));
+        assert_eq!(
+            what_if(&roots, &selected, &packages, &options),
+            WhatIf::Unresolvable {
+                package: "decimal".into(),
+                constraints: vec![Constraint {
+                    dependant: Some("jason".into()),
+                    requirement: Range::new("~> 2.0".into()).unwrap(),
+                }],
+                cycle: None,
+            }
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let cyclic: HashMap<PackageName, Package> = [
+            package("a", "1.0.0", &[("b", None)]),
+            package("b", "1.0.0", &[("c", None)]),
+            package("c", "1.0.0", &[("a", None), ("d", None)]),
+            package("d", "1.0.0", &[("missing", None)]),
+            package("self", "1.0.0", &[("self", None)]),
+        ]
+        .into_iter()
+        .map(|package| (package.name.as_str().into(), package))
+        .collect();
+        let cycles = dependency_cycles(&cyclic);
+        let names: Vec<Vec<&str>> = cycles
+            .iter()
+            .map(|cycle| cycle.packages().map(PackageName::as_str).collect())
+            .collect();
+        assert_eq!(names, vec![vec!["a", "b", "c"], vec!["self"]]);
+        assert_eq!(cycles[1].to_string(), "self 1.0.0 requires self ~> 1.0");
+        assert!(dependency_cycles(&packages()).is_empty());
+
+        // Nothing satisfies the requirement on c, which is part of the cycle.
+        let roots = HashMap::from([("a".into(), Range::new("~> 1.0".into()).unwrap())]);
+        let mut cyclic = cyclic;
+        cyclic.get_mut("b").unwrap().releases[0]
+            .requirements
+            .get_mut("c")
+            .unwrap()
+            .requirement = Range::new("~> 2.0".into()).unwrap();
+        let selected = HashMap::new();
+        match what_if(&roots, &selected, &cyclic, &ResolutionOptions::new()) {
+            WhatIf::Unresolvable { package, cycle, .. } => {
+                assert_eq!(package, "c");
+                assert!(cycle.unwrap().contains("c"));
+            }
+            resolved => panic!("expected the cycle to be unresolvable: {resolved:?}"),
+        }
+    }
+
+    #[test]
+    fn priorities() {
+        let summaries: Vec<PackageSummary> = serde_json::from_value(serde_json::json!([
+            {"name": "decimal", "meta": {}, "downloads": {"all": 900, "recent": 50}},
+            {"name": "jason", "meta": {}, "downloads": {"all": 100, "recent": 80}},
+        ]))
+        .unwrap();
+        let priorities = download_priorities(&summaries);
+        let names: Vec<PackageName> = ["phoenix".into(), "decimal".into(), "jason".into()].into();
+        let order: Vec<_> = decision_order(&names, &priorities)
+            .into_iter()
+            .map(PackageName::as_str)
+            .collect();
+        assert_eq!(order, vec!["jason", "decimal", "phoenix"]);
+        let phoenix_first = |name: &str| (name == "phoenix").then_some(1);
+        assert_eq!(
+            decision_order(&names, &phoenix_first)[0].as_str(),
+            "phoenix"
+        );
+
+        // Neither decimal nor plug has a release satisfying its constraints,
+        // and the one decided first is reported.
+        let mut packages = packages();
+        packages.insert("plug".into(), package("plug", "1.0.0", &[]));
+        let selected = selected(&[
+            ("phoenix", "1.7.0"),
+            ("jason", "1.4.0"),
+            ("decimal", "2.1.0"),
+        ]);
+        let roots = HashMap::from([
+            ("jason".into(), Range::new("~> 1.4".into()).unwrap()),
+            ("plug".into(), Range::new("~> 2.0".into()).unwrap()),
+        ]);
+        let options = ResolutionOptions::new();
+        let unresolvable =
+            |options: &ResolutionOptions| match what_if(&roots, &selected, &packages, options) {
+                WhatIf::Unresolvable { package, .. } => package,
+                other => panic!("{other:?}"),
+            };
+        assert_eq!(unresolvable(&options).as_str(), "decimal");
+        let options = options.priorities(HashMap::from([("plug".into(), 1)]));
+        assert_eq!(unresolvable(&options).as_str(), "plug");
+    }
+}
@@ -0,0 +1,185 @@
+//! Writing retirement messages for common reasons in a consistent form, for
+//! tools and bots that retire releases.
+//!
+//! Hex only accepts retirement messages of between
+//! [`MIN_MESSAGE_LENGTH`] and [`MAX_MESSAGE_LENGTH`] characters, so every
+//! [`Retirement`] checks its message before a request is made rather than
+//! having the API reject it.
+//!
+//! ```
+//! use hexpm::{Config, ReleaseRef, RetirementReason, retirement::Retirement};
+//! use hexpm::version::Version;
+//!
+//! let retirement = Retirement::superseded_by(&Version::new(1, 2, 1)).unwrap();
+//! assert_eq!(retirement.reason(), &RetirementReason::Deprecated);
+//! assert_eq!(retirement.message(), "Superseded by 1.2.1, please upgrade");
+//!
+//! let release = ReleaseRef::new("gleam_json", Version::new(1, 2, 0));
+//! let request = retirement.request(&release, "my-api-key", &Config::new());
+//! assert_eq!(request.uri().path(), "/api/packages/gleam_json/releases/1.2.0/retire");
+//! ```
+
+use thiserror::Error;
+
+use crate::version::Version;
+use crate::{Config, ReleaseRef, RetirementReason};
+
+/// The shortest retirement message Hex accepts, in characters.
+pub const MIN_MESSAGE_LENGTH: usize = 3;
+
+/// The longest retirement message Hex accepts, in characters.
+pub const MAX_MESSAGE_LENGTH: usize = 140;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RetirementMessageError {
+    #[error(
+        "the retirement message is {length} characters long, less than the minimum of {MIN_MESSAGE_LENGTH}"
+    )]
+    TooShort { length: usize },
+
+    #[error(
+        "the retirement message is {length} characters long, more than the maximum of {MAX_MESSAGE_LENGTH}"
+    )]
+    TooLong { length: usize },
+}
+
+/// A reason for retiring a release along with a message Hex will accept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Retirement {
+    reason: RetirementReason,
+    message: String,
+}
+
+impl Retirement {
+    /// A retirement with a message of the caller's own, which is trimmed and
+    /// checked against Hex's length limits.
+    pub fn new(reason: RetirementReason, message: &str) -> Result<Self, RetirementMessageError> {
+        let message = message.trim();
+        let length = message.chars().count();
+        if length < MIN_MESSAGE_LENGTH {
+            return Err(RetirementMessageError::TooShort { length });
+        }
+        if length > MAX_MESSAGE_LENGTH {
+            return Err(RetirementMessageError::TooLong { length });
+        }
+        Ok(Self {
+            reason,
+            message: message.to_string(),
+        })
+    }
+
+    /// A release with a security vulnerability, referring to its advisory
+    /// such as `CVE-2024-1234` or `GHSA-xxxx-xxxx-xxxx`, and to the first
+    /// version with a fix if there is one.
+    pub fn security(
+        advisory: &str,
+        fixed_in: Option<&Version>,
+    ) -> Result<Self, RetirementMessageError> {
+        let message = match fixed_in {
+            Some(version) => format!("Vulnerable to {advisory}, fixed in {version}"),
+            None => format!("Vulnerable to {advisory}"),
+        };
+        Self::new(RetirementReason::Security, &message)
+    }
+
+    /// A release of a package that has been renamed, published under its
+    /// new name.
+    pub fn renamed_to(package: &str) -> Result<Self, RetirementMessageError> {
+        Self::new(
+            RetirementReason::Renamed,
+            &format!("Renamed to {package}, please switch to it"),
+        )
+    }
+
+    /// A release replaced by a later version, such as one fixing a
+    /// regression.
+    pub fn superseded_by(version: &Version) -> Result<Self, RetirementMessageError> {
+        Self::new(
+            RetirementReason::Deprecated,
+            &format!("Superseded by {version}, please upgrade"),
+        )
+    }
+
+    pub fn reason(&self) -> &RetirementReason {
+        &self.reason
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Create the request retiring a release, as with
+    /// [`retire_release_request`](crate::retire_release_request).
+    pub fn request(
+        &self,
+        release: &ReleaseRef,
+        api_key: &str,
+        config: &Config,
+    ) -> http::Request<Vec<u8>> {
+        crate::retire_release_request(
+            release,
+            self.reason.clone(),
+            Some(&self.message),
+            api_key,
+            config,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates() {
+        let security = Retirement::security("CVE-2024-1234", Some(&Version::new(2, 0, 1))).unwrap();
+        assert_eq!(security.reason(), &RetirementReason::Security);
+        assert_eq!(
+            security.message(),
+            "Vulnerable to CVE-2024-1234, fixed in 2.0.1"
+        );
+        assert_eq!(
+            Retirement::security("GHSA-abcd-efgh-ijkl", None)
+                .unwrap()
+                .message(),
+            "Vulnerable to GHSA-abcd-efgh-ijkl"
+        );
+
+        let renamed = Retirement::renamed_to("gleam_otp").unwrap();
+        assert_eq!(renamed.reason(), &RetirementReason::Renamed);
+        assert_eq!(
+            renamed.message(),
+            "Renamed to gleam_otp, please switch to it"
+        );
+    }
+
+    #[test]
+    fn message_length() {
+        assert_eq!(
+            Retirement::new(RetirementReason::Other, " ok "),
+            Err(RetirementMessageError::TooShort { length: 2 })
+        );
+        let longest = "é".repeat(MAX_MESSAGE_LENGTH);
+        assert!(Retirement::new(RetirementReason::Other, &longest).is_ok());
+        assert_eq!(
+            Retirement::renamed_to(&"a".repeat(120)),
+            Err(RetirementMessageError::TooLong { length: 152 })
+        );
+    }
+
+    #[test]
+    fn request_body() {
+        let release = ReleaseRef::new("gleam_json", Version::new(1, 2, 0));
+        let request = Retirement::superseded_by(&Version::new(1, 2, 1))
+            .unwrap()
+            .request(&release, "key", &Config::new());
+        let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "reason": "deprecated",
+                "message": "Superseded by 1.2.1, please upgrade"
+            })
+        );
+    }
+}
@@ -0,0 +1,429 @@
+//! Pacing bulk requests to stay within the Hex API's rate limits.
+//!
+//! Hex reports the state of the rate limit in the `x-ratelimit-limit`,
+//! `x-ratelimit-remaining` and `x-ratelimit-reset` headers of its responses. A
+//! [`Scheduler`] records these for each host it sees and hands out queued
+//! requests along with how long to wait before sending each one, so that
+//! tools making many requests, such as mirrors and bots, are not rate limited.
+//!
+//! ```
+//! use hexpm::{Config, schedule::Scheduler};
+//! use std::time::{Duration, SystemTime};
+//!
+//! let config = Config::new();
+//! let mut scheduler = Scheduler::new();
+//! scheduler.push(hexpm::get_owners_request("gleam_stdlib", None, &config));
+//! scheduler.push(hexpm::get_owners_request("lustre", None, &config));
+//!
+//! let now = SystemTime::now();
+//! let (request, delay) = scheduler.next(now).unwrap();
+//! assert_eq!(delay, Duration::ZERO);
+//! # let reset = now.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() + 60;
+//! let response = http::Response::builder()
+//!     .header("x-ratelimit-limit", "100")
+//!     .header("x-ratelimit-remaining", "0")
+//!     .header("x-ratelimit-reset", reset.to_string())
+//!     .body(Vec::<u8>::new())
+//!     .unwrap();
+//! scheduler.observe(request.uri(), response.headers(), now);
+//!
+//! let (_, delay) = scheduler.next(now).unwrap();
+//! assert!(delay > Duration::from_secs(50));
+//! ```
+//!
+//! Tools sending requests from several threads can share the same accounting
+//! through the [`RateLimitState`] in [`Config::rate_limits`](crate::Config::rate_limits).
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::RetryAfter;
+use crate::hexpm_defaults::Profile;
+
+/// The state of a rate limit, as reported by Hex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The number of requests allowed in each window.
+    pub limit: u32,
+    /// The number of requests left in the current window.
+    pub remaining: u32,
+    /// When the current window ends.
+    pub reset: SystemTime,
+}
+
+impl RateLimit {
+    /// Read the rate limit headers of a response, if it has them all.
+    pub fn from_headers(headers: &http::HeaderMap) -> Option<Self> {
+        let number =
+            |name| -> Option<u64> { headers.get(name)?.to_str().ok()?.trim().parse().ok() };
+        Some(Self {
+            limit: number("x-ratelimit-limit")?.try_into().ok()?,
+            remaining: number("x-ratelimit-remaining")?.try_into().ok()?,
+            reset: SystemTime::UNIX_EPOCH + Duration::from_secs(number("x-ratelimit-reset")?),
+        })
+    }
+}
+
+/// A rate limit along with the length of its window, used to predict later
+/// windows until Hex reports on them.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    rate_limit: RateLimit,
+    window: Duration,
+}
+
+impl Bucket {
+    fn from_headers(headers: &http::HeaderMap, now: SystemTime) -> Option<Self> {
+        let rate_limit = RateLimit::from_headers(headers)?;
+        let window = rate_limit
+            .reset
+            .duration_since(now)
+            .unwrap_or_default()
+            .max(Duration::from_secs(1));
+        Some(Self { rate_limit, window })
+    }
+
+    /// Use up a request, returning how long after `now` it may be sent.
+    fn take(&mut self, now: SystemTime) -> Duration {
+        let RateLimit {
+            limit,
+            remaining,
+            reset,
+        } = &mut self.rate_limit;
+        // Without news from Hex, assume a new window started once the last
+        // one ended.
+        if *reset <= now {
+            *reset = now + self.window;
+            *remaining = *limit;
+        }
+        if *remaining == 0 {
+            *reset += self.window;
+            *remaining = *limit;
+        }
+        *remaining = remaining.saturating_sub(1);
+        let window_start = *reset - self.window;
+        window_start.duration_since(now).unwrap_or_default()
+    }
+}
+
+/// A queue of requests handed out with the delays needed to respect the rate
+/// limits of the hosts they are sent to.
+///
+/// The scheduler assumes each request it hands out is sent after its delay,
+/// and adjusts its estimates whenever a response is passed to
+/// [`observe`](Self::observe). Hosts that have not reported a rate limit,
+/// such as the repository, are not delayed.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    queue: VecDeque<http::Request<Vec<u8>>>,
+    buckets: HashMap<String, Bucket>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A scheduler that assumes the API of a deployment has its usual
+    /// [`rate_limit`](Profile::rate_limit) until the API reports otherwise,
+    /// so that the first burst of requests is paced too.
+    pub fn with_profile(profile: &Profile) -> Self {
+        let mut scheduler = Self::new();
+        if let Some((limit, window)) = profile.rate_limit {
+            let rate_limit = RateLimit {
+                limit,
+                remaining: limit,
+                // A window in the past, so that a new one starts with the
+                // first request.
+                reset: SystemTime::UNIX_EPOCH,
+            };
+            scheduler.buckets.insert(
+                host(&profile.api_base).to_string(),
+                Bucket { rate_limit, window },
+            );
+        }
+        scheduler
+    }
+
+    /// Add a request to the end of the queue.
+    pub fn push(&mut self, request: http::Request<Vec<u8>>) {
+        self.queue.push_back(request);
+    }
+
+    /// The number of requests in the queue.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Take the next request from the queue, along with how long to wait
+    /// after `now` before sending it.
+    pub fn next(&mut self, now: SystemTime) -> Option<(http::Request<Vec<u8>>, Duration)> {
+        let request = self.queue.pop_front()?;
+        let delay = match self.buckets.get_mut(host(request.uri())) {
+            Some(bucket) => bucket.take(now),
+            None => Duration::ZERO,
+        };
+        Some((request, delay))
+    }
+
+    /// Record the rate limit reported by a response to a request for `uri`,
+    /// received at `now`. Responses without rate limit headers are ignored.
+    pub fn observe(&mut self, uri: &http::Uri, headers: &http::HeaderMap, now: SystemTime) {
+        if let Some(bucket) = Bucket::from_headers(headers, now) {
+            self.buckets.insert(host(uri).to_string(), bucket);
+        }
+    }
+
+    /// The last rate limit reported by a host, such as `hex.pm`.
+    pub fn rate_limit(&self, host: &str) -> Option<RateLimit> {
+        self.buckets.get(host).map(|bucket| bucket.rate_limit)
+    }
+}
+
+/// Rate limit accounting that can be shared between threads, so that tools
+/// sending requests concurrently pace themselves together rather than each
+/// thread using up the limit on its own.
+///
+/// Before sending a request, call [`reserve`](Self::reserve) and wait for the
+/// delay it returns. Pass every response to [`observe`](Self::observe), which
+/// records the reported rate limit and, when a response asks for requests to
+/// be retried later, holds back all requests to that host until then.
+///
+/// ```
+/// use hexpm::Config;
+/// use std::time::{Duration, SystemTime};
+///
+/// let config = Config::new();
+/// let request = hexpm::get_owners_request("lustre", None, &config);
+/// let now = SystemTime::now();
+/// assert_eq!(config.rate_limits.reserve(request.uri(), now), Duration::ZERO);
+///
+/// let response = http::Response::builder()
+///     .status(429)
+///     .header("retry-after", "30")
+///     .body(Vec::<u8>::new())
+///     .unwrap();
+/// // Clones of the configuration share the state.
+/// let other_thread = config.clone();
+/// other_thread.rate_limits.observe(request.uri(), &response, now);
+/// assert_eq!(
+///     config.rate_limits.reserve(request.uri(), now),
+///     Duration::from_secs(30)
+/// );
+/// ```
+#[derive(Debug, Default)]
+pub struct RateLimitState {
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    bucket: Option<Bucket>,
+    paused_until: Option<SystemTime>,
+}
+
+impl RateLimitState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claim a request to the host of `uri`, returning how long after `now`
+    /// to wait before sending it. Hosts that have not reported a rate limit
+    /// or asked for a pause are not delayed.
+    pub fn reserve(&self, uri: &http::Uri, now: SystemTime) -> Duration {
+        let mut hosts = self.lock();
+        let Some(state) = hosts.get_mut(host(uri)) else {
+            return Duration::ZERO;
+        };
+        let paused = state
+            .paused_until
+            .and_then(|until| until.duration_since(now).ok())
+            .unwrap_or_default();
+        match &mut state.bucket {
+            Some(bucket) => paused + bucket.take(now + paused),
+            None => paused,
+        }
+    }
+
+    /// Record a response to a request for `uri`, received at `now`. The rate
+    /// limit headers are recorded, and responses with a `retry-after` header,
+    /// such as those to rate limited requests or sent during maintenance,
+    /// pause requests to the host until the time given.
+    pub fn observe<B>(&self, uri: &http::Uri, response: &http::Response<B>, now: SystemTime) {
+        let headers = response.headers();
+        let retry_after = headers
+            .get(http::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(RetryAfter::parse);
+        let mut hosts = self.lock();
+        let state = hosts.entry(host(uri).to_string()).or_default();
+        if let Some(bucket) = Bucket::from_headers(headers, now) {
+            state.bucket = Some(bucket);
+        }
+        if let Some(retry_after) = retry_after {
+            let until = now + retry_after.delay(now);
+            state.paused_until = state.paused_until.max(Some(until));
+        }
+    }
+
+    /// The last rate limit reported by a host, such as `hex.pm`.
+    pub fn rate_limit(&self, host: &str) -> Option<RateLimit> {
+        let hosts = self.lock();
+        Some(hosts.get(host)?.bucket?.rate_limit)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, HostState>> {
+        // The state is consistent after every update, so a panic in another
+        // thread while holding the lock does not invalidate it.
+        self.hosts
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn host(uri: &http::Uri) -> &str {
+    uri.authority().map_or("", |authority| authority.as_str())
+}
+
+#[cfg(all(test, feature = "api"))]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    fn rate_limited(limit: u32, remaining: u32, reset: u64) -> http::HeaderMap {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-ratelimit-limit", limit.into());
+        headers.insert("x-ratelimit-remaining", remaining.into());
+        headers.insert("x-ratelimit-reset", reset.into());
+        headers
+    }
+
+    #[test]
+    fn delays() {
+        let config = Config::new();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let mut scheduler = Scheduler::new();
+        for package in ["a", "b", "c", "d", "e"] {
+            scheduler.push(crate::get_owners_request(package, None, &config));
+        }
+        scheduler.push(crate::get_package_request("f", None, &config));
+        let api = crate::get_owners_request("a", None, &config).uri().clone();
+        scheduler.observe(&api, &rate_limited(2, 1, 1060), now);
+        assert_eq!(
+            scheduler.rate_limit("hex.pm").map(|limit| limit.remaining),
+            Some(1)
+        );
+
+        let delays: Vec<_> = std::iter::from_fn(|| scheduler.next(now))
+            .map(|(_, delay)| delay.as_secs())
+            .collect();
+        // One request is left in this window, two in each of the next, and the
+        // repository is not rate limited.
+        assert_eq!(delays, vec![0, 60, 60, 120, 120, 0]);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn windows_pass() {
+        let config = Config::new();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let mut scheduler = Scheduler::new();
+        let request = crate::get_owners_request("a", None, &config);
+        scheduler.observe(request.uri(), &rate_limited(1, 0, 1030), now);
+        scheduler.push(request.clone());
+        scheduler.push(request);
+
+        let later = now + Duration::from_secs(40);
+        assert_eq!(scheduler.next(later).unwrap().1, Duration::ZERO);
+        assert_eq!(scheduler.next(later).unwrap().1, Duration::from_secs(30));
+
+        scheduler.observe(
+            &"https://hex.pm/api/".parse().unwrap(),
+            &http::HeaderMap::new(),
+            later,
+        );
+        assert_eq!(scheduler.rate_limit("hex.pm").unwrap().limit, 1);
+    }
+
+    #[test]
+    fn assumed_rate_limit() {
+        let profile = Profile {
+            rate_limit: Some((2, Duration::from_secs(60))),
+            ..Profile::hexpm()
+        };
+        let config = Config::with_profile(profile.clone());
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let mut scheduler = Scheduler::with_profile(&profile);
+        for package in ["a", "b", "c"] {
+            scheduler.push(crate::get_owners_request(package, None, &config));
+        }
+        let delays: Vec<_> = std::iter::from_fn(|| scheduler.next(now))
+            .map(|(_, delay)| delay.as_secs())
+            .collect();
+        assert_eq!(delays, vec![0, 0, 60]);
+
+        let unlimited = Profile {
+            rate_limit: None,
+            ..profile
+        };
+        assert!(
+            Scheduler::with_profile(&unlimited)
+                .rate_limit("hex.pm")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn shared_state() {
+        let config = Config::new();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let api = crate::get_owners_request("a", None, &config).uri().clone();
+        let response = |status: u16, headers: http::HeaderMap| {
+            let mut response = http::Response::builder().status(status).body(()).unwrap();
+            *response.headers_mut() = headers;
+            response
+        };
+        config
+            .rate_limits
+            .observe(&api, &response(200, rate_limited(4, 4, 1060)), now);
+
+        // Four threads claim the four requests left in this window, and so
+        // the next request waits for the next window.
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let config = config.clone();
+                let api = &api;
+                scope.spawn(move || {
+                    assert_eq!(config.rate_limits.reserve(api, now), Duration::ZERO)
+                });
+            }
+        });
+        assert_eq!(
+            config.rate_limits.reserve(&api, now),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            config.rate_limits.rate_limit("hex.pm").unwrap().remaining,
+            3
+        );
+
+        // A pause applies to every request to the host, beyond the rate
+        // limit's own delays.
+        let mut headers = http::HeaderMap::new();
+        headers.insert("retry-after", 300.into());
+        config
+            .rate_limits
+            .observe(&api, &response(429, headers), now);
+        assert_eq!(
+            config.rate_limits.reserve(&api, now),
+            Duration::from_secs(300)
+        );
+        let repository = crate::get_package_request("a", None, &config).uri().clone();
+        assert_eq!(config.rate_limits.reserve(&repository, now), Duration::ZERO);
+    }
+}
@@ -0,0 +1,247 @@
+//! Searching package metadata held locally, so that tools can answer search
+//! queries without asking the API each time.
+//!
+//! The index does not fetch anything itself. Callers add a [`SearchEntry`] for
+//! each package as they fetch its metadata, and use [`SearchIndex::apply_diff`]
+//! when the versions index is refreshed to find out which entries to fetch
+//! again.
+
+use std::collections::HashMap;
+
+use crate::index::IndexDiff;
+use crate::tarball::PackageMetadata;
+
+/// The searchable metadata of a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchEntry {
+    pub name: String,
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+}
+
+impl From<&PackageMetadata> for SearchEntry {
+    fn from(metadata: &PackageMetadata) -> Self {
+        Self {
+            name: metadata.name.clone(),
+            description: metadata.description.clone(),
+            keywords: vec![],
+        }
+    }
+}
+
+/// A package matching a search query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit<'a> {
+    pub entry: &'a SearchEntry,
+    /// How well the package matches. Higher is better, and the value is only
+    /// meaningful relative to other hits for the same query.
+    pub score: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    entries: HashMap<String, Indexed>,
+}
+
+/// An entry along with the lowercased text that queries are matched against.
+#[derive(Debug, Clone)]
+struct Indexed {
+    entry: SearchEntry,
+    name: String,
+    keywords: Vec<String>,
+    description: String,
+    description_words: Vec<String>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SearchEntry> {
+        self.entries.get(name).map(|indexed| &indexed.entry)
+    }
+
+    /// Add an entry, returning the entry it replaced, if any.
+    pub fn insert(&mut self, entry: SearchEntry) -> Option<SearchEntry> {
+        let description = entry
+            .description
+            .as_deref()
+            .unwrap_or_default()
+            .to_lowercase();
+        let indexed = Indexed {
+            name: entry.name.to_lowercase(),
+            keywords: entry.keywords.iter().map(|k| k.to_lowercase()).collect(),
+            description_words: words(&description).map(String::from).collect(),
+            description,
+            entry,
+        };
+        self.entries
+            .insert(indexed.entry.name.clone(), indexed)
+            .map(|old| old.entry)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<SearchEntry> {
+        self.entries.remove(name).map(|indexed| indexed.entry)
+    }
+
+    /// Bring the index up to date with a refreshed versions index.
+    ///
+    /// Removed packages are dropped from the search index. The returned
+    /// packages are those that are new or have new releases, whose metadata
+    /// should be fetched and inserted again.
+    pub fn apply_diff(&mut self, diff: &IndexDiff) -> Vec<String> {
+        for name in &diff.removed_packages {
+            let _ = self.entries.remove(name);
+        }
+        let mut outdated: Vec<String> = diff
+            .added_packages
+            .iter()
+            .chain(diff.added_versions.keys())
+            .cloned()
+            .collect();
+        outdated.sort();
+        outdated
+    }
+
+    /// Find the packages matching every word of the query, best match first.
+    ///
+    /// A word matches a package if it appears in its name, keywords or
+    /// description. Matches in the name rank highest, then keywords, then the
+    /// description.
+    pub fn search(&self, query: &str) -> Vec<SearchHit<'_>> {
+        let query = query.to_lowercase();
+        let terms: Vec<&str> = words(&query).collect();
+        if terms.is_empty() {
+            return vec![];
+        }
+
+        let mut hits: Vec<SearchHit<'_>> = self
+            .entries
+            .values()
+            .filter_map(|indexed| {
+                let score = terms
+                    .iter()
+                    .map(|term| indexed.score(term))
+                    .try_fold(0, |total, score| (score > 0).then_some(total + score))?;
+                Some(SearchHit {
+                    entry: &indexed.entry,
+                    score,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.entry.name.len().cmp(&b.entry.name.len()))
+                .then_with(|| a.entry.name.cmp(&b.entry.name))
+        });
+        hits
+    }
+}
+
+impl Indexed {
+    fn score(&self, term: &str) -> u32 {
+        if self.name == term {
+            100
+        } else if self.name.starts_with(term) {
+            50
+        } else if self.name.contains(term) {
+            25
+        } else if self.keywords.iter().any(|keyword| keyword == term) {
+            20
+        } else if self.description_words.iter().any(|word| word == term) {
+            10
+        } else if self.description.contains(term) {
+            5
+        } else {
+            0
+        }
+    }
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, description: &str, keywords: &[&str]) -> SearchEntry {
+        SearchEntry {
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+
+    fn index() -> SearchIndex {
+        let mut index = SearchIndex::new();
+        for entry in [
+            entry("json", "A JSON library", &[]),
+            entry(
+                "jason",
+                "A blazing fast JSON parser and generator",
+                &["json"],
+            ),
+            entry("gleam_json", "Work with JSON in Gleam", &["gleam"]),
+            entry("plug", "Compose web applications with functions", &["http"]),
+            entry("thoas", "A blazing fast JSON parser", &[]),
+        ] {
+            let _ = index.insert(entry);
+        }
+        index
+    }
+
+    fn names(hits: Vec<SearchHit<'_>>) -> Vec<&str> {
+        hits.into_iter()
+            .map(|hit| hit.entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn ranking() {
+        let index = index();
+        assert_eq!(
+            names(index.search("json")),
+            vec!["json", "gleam_json", "jason", "thoas"]
+        );
+        assert_eq!(names(index.search("JSON  Parser")), vec!["jason", "thoas"]);
+        assert_eq!(names(index.search("http")), vec!["plug"]);
+        assert_eq!(names(index.search("appl")), vec!["plug"]);
+        assert!(index.search("nothing").is_empty());
+        assert!(index.search(" ").is_empty());
+    }
+
+    #[test]
+    fn incremental_update() {
+        let mut index = index();
+        let diff = IndexDiff {
+            added_packages: vec!["poison".to_string()],
+            removed_packages: vec!["thoas".to_string()],
+            added_versions: HashMap::from([("jason".to_string(), vec![])]),
+            removed_versions: HashMap::new(),
+        };
+        assert_eq!(
+            index.apply_diff(&diff),
+            vec!["jason".to_string(), "poison".to_string()]
+        );
+        assert_eq!(index.get("thoas"), None);
+
+        let old = index.insert(entry("jason", "A fast JSON library", &[]));
+        assert_eq!(old.unwrap().keywords, vec!["json".to_string()]);
+        let _ = index.insert(entry("poison", "An incredibly fast JSON library", &[]));
+        assert_eq!(index.len(), 5);
+        assert_eq!(names(index.search("fast")), vec!["jason", "poison"]);
+    }
+}
@@ -0,0 +1,341 @@
+//! Serving a registry directory over the Hex repository protocol, for
+//! self-hosting a small private registry or for end to end tests of clients.
+//!
+//! This module is only built with the `server` feature. The directory holds
+//! the files of a repository with resources that are already signed, such as
+//! one built by `mix hex.registry build`:
+//!
+//! ```text
+//! public_key
+//! names
+//! versions
+//! packages/<package>
+//! tarballs/<package>-<version>.tar
+//! ```
+//!
+//! As with the rest of this crate there is no IO here. [`RegistryServer`]
+//! turns the parts of a request into a response, so it can be used from any
+//! HTTP server, and reads the files through a [`RegistryFiles`] given by the
+//! caller.
+//!
+//! ```
+//! use hexpm::server::RegistryServer;
+//! use std::path::PathBuf;
+//!
+//! let root = PathBuf::from("/srv/hex/public");
+//! let server = RegistryServer::new(move |path: &str| std::fs::read(root.join(path)).ok());
+//!
+//! let (parts, _) = http::Request::get("/packages/not_hosted_here")
+//!     .body(())
+//!     .unwrap()
+//!     .into_parts();
+//! let response = server.handle(&parts);
+//! assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+//! ```
+
+use std::collections::HashMap;
+
+use http::{Method, StatusCode, header};
+
+use crate::PackageName;
+use crate::checksum::Checksum;
+use crate::version::Version;
+
+/// Where a [`RegistryServer`] reads the files of a repository from.
+pub trait RegistryFiles {
+    /// The contents of the file at `path`, relative to the registry
+    /// directory and using `/` as the separator, or `None` if there is no
+    /// such file.
+    fn read(&self, path: &str) -> Option<Vec<u8>>;
+}
+
+impl RegistryFiles for HashMap<String, Vec<u8>> {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        self.get(path).cloned()
+    }
+}
+
+impl<F: Fn(&str) -> Option<Vec<u8>>> RegistryFiles for F {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        self(path)
+    }
+}
+
+/// A resource of the repository protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resource {
+    PublicKey,
+    Names,
+    Versions,
+    Package(PackageName),
+    Tarball {
+        package: PackageName,
+        version: Version,
+    },
+}
+
+impl Resource {
+    /// The resource at a request path relative to the repository root, such
+    /// as `packages/gleam_stdlib`, or `None` if the path is not a resource.
+    /// Package names and versions are validated, so the file path of a
+    /// resource can never leave the registry directory.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let package = |name: &str| {
+            PackageName::validate(name)
+                .is_ok()
+                .then(|| PackageName::from(name))
+        };
+        match path.split_once('/') {
+            None if path == "public_key" => Some(Self::PublicKey),
+            None if path == "names" => Some(Self::Names),
+            None if path == "versions" => Some(Self::Versions),
+            Some(("packages", name)) => package(name).map(Self::Package),
+            Some(("tarballs", file)) => {
+                // Package names cannot contain `-`, so the first one ends it
+                let (name, version) = file.strip_suffix(".tar")?.split_once('-')?;
+                Some(Self::Tarball {
+                    package: package(name)?,
+                    version: Version::parse(version).ok()?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The path of the resource's file in the registry directory.
+    pub fn file_path(&self) -> String {
+        match self {
+            Self::PublicKey => "public_key".to_string(),
+            Self::Names => "names".to_string(),
+            Self::Versions => "versions".to_string(),
+            Self::Package(name) => format!("packages/{name}"),
+            Self::Tarball { package, version } => format!("tarballs/{package}-{version}.tar"),
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::PublicKey => "application/x-pem-file",
+            Self::Tarball { .. } => "application/x-tar",
+            _ => "application/octet-stream",
+        }
+    }
+}
+
+/// Serves the files of a registry directory, see the [module
+/// documentation](self).
+#[derive(Debug, Clone)]
+pub struct RegistryServer<F> {
+    files: F,
+    base_path: String,
+}
+
+impl<F: RegistryFiles> RegistryServer<F> {
+    /// A server for a repository at the root of the host, such as
+    /// `http://localhost:8000/`.
+    pub fn new(files: F) -> Self {
+        Self {
+            files,
+            base_path: "/".to_string(),
+        }
+    }
+
+    /// Serve the repository under a path, such as `/repo` to match a
+    /// [`Config::repository_base`](crate::Config::repository_base) of
+    /// `http://localhost:8000/repo/`.
+    pub fn with_base_path(mut self, base_path: &str) -> Self {
+        self.base_path = format!("/{}/", base_path.trim_matches('/')).replace("//", "/");
+        self
+    }
+
+    /// The response to a request. `GET` and `HEAD` requests for resources are
+    /// answered with an `etag`, and with `304 Not Modified` when the request's
+    /// `if-none-match` header has the current one.
+    pub fn handle(&self, request: &http::request::Parts) -> http::Response<Vec<u8>> {
+        if request.method != Method::GET && request.method != Method::HEAD {
+            return http::Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .header(header::ALLOW, "GET, HEAD")
+                .body(vec![])
+                .expect("method not allowed response");
+        }
+        let found = request
+            .uri
+            .path()
+            .strip_prefix(&self.base_path)
+            .and_then(Resource::from_path)
+            .and_then(|resource| Some((self.files.read(&resource.file_path())?, resource)));
+        let Some((body, resource)) = found else {
+            return http::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(vec![])
+                .expect("not found response");
+        };
+
+        let etag = format!("\"{}\"", Checksum::of(&body).to_hex());
+        let not_modified = request
+            .headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == etag
+            });
+        let builder = http::Response::builder()
+            .header(header::ETAG, &etag)
+            .header(header::CONTENT_TYPE, resource.content_type());
+        let response = if not_modified {
+            builder.status(StatusCode::NOT_MODIFIED).body(vec![])
+        } else {
+            let builder = builder
+                .status(StatusCode::OK)
+                .header(header::CONTENT_LENGTH, body.len());
+            match request.method {
+                Method::HEAD => builder.body(vec![]),
+                _ => builder.body(body),
+            }
+        };
+        response.expect("resource response")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_keys::TestKeys;
+    use crate::{Config, Package, ReleaseRef};
+
+    fn files(keys: &TestKeys, tarball: &[u8]) -> HashMap<String, Vec<u8>> {
+        let versions = HashMap::from([("my_package".to_string(), vec![Version::new(1, 0, 0)])]);
+        let package = Package {
+            name: "my_package".into(),
+            repository: "hexpm".into(),
+            releases: vec![],
+        };
+        HashMap::from([
+            ("public_key".to_string(), keys.public_key().to_vec()),
+            (
+                "versions".to_string(),
+                keys.versions_resource("hexpm", &versions),
+            ),
+            (
+                "packages/my_package".to_string(),
+                keys.package_resource(&package),
+            ),
+            (
+                "tarballs/my_package-1.0.0.tar".to_string(),
+                tarball.to_vec(),
+            ),
+        ])
+    }
+
+    fn serve(
+        server: &RegistryServer<impl RegistryFiles>,
+        request: http::Request<Vec<u8>>,
+    ) -> http::Response<Vec<u8>> {
+        server.handle(&request.into_parts().0)
+    }
+
+    #[test]
+    fn resources() {
+        assert_eq!(Resource::from_path("names"), Some(Resource::Names));
+        assert_eq!(
+            Resource::from_path("tarballs/my_package-1.0.0-rc.1.tar"),
+            Some(Resource::Tarball {
+                package: "my_package".into(),
+                version: Version::parse("1.0.0-rc.1").unwrap(),
+            })
+        );
+        for path in [
+            "",
+            "versions/",
+            "packages/",
+            "packages/../public_key",
+            "packages/a/b",
+            "tarballs/my_package.tar",
+            "tarballs/my_package-1.0.tar",
+            "tarballs/../my_package-1.0.0.tar",
+        ] {
+            assert_eq!(Resource::from_path(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn end_to_end() {
+        let keys = TestKeys::new();
+        let tarball = b"not really a tarball";
+        let server = RegistryServer::new(files(&keys, tarball)).with_base_path("repo");
+        let mut config = Config::new();
+        config.repository_base = "http://localhost:8000/repo/".parse().unwrap();
+
+        let response = serve(
+            &server,
+            crate::get_repository_versions_request(None, &config),
+        );
+        let versions =
+            crate::get_repository_versions_response(response, keys.public_key()).unwrap();
+        assert_eq!(versions["my_package"], vec![Version::new(1, 0, 0)]);
+
+        let response = serve(
+            &server,
+            crate::get_package_request("my_package", None, &config),
+        );
+        let package = crate::get_package_response(response, keys.public_key()).unwrap();
+        assert_eq!(package.name, "my_package");
+
+        let release = ReleaseRef::new("my_package", Version::new(1, 0, 0));
+        let response = serve(
+            &server,
+            crate::get_package_tarball_request(&release, None, &config),
+        );
+        let downloaded =
+            crate::get_package_tarball_response(response, Checksum::of(tarball).as_bytes())
+                .unwrap();
+        assert_eq!(downloaded, tarball);
+
+        let response = serve(
+            &server,
+            crate::get_package_request("missing", None, &config),
+        );
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn methods_and_etags() {
+        let keys = TestKeys::new();
+        let server = RegistryServer::new(files(&keys, b"tarball"));
+        let request = |method: Method| {
+            http::Request::builder()
+                .method(method)
+                .uri("/tarballs/my_package-1.0.0.tar")
+                .body(vec![])
+                .unwrap()
+        };
+
+        let response = serve(&server, request(Method::GET));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-tar"
+        );
+        let etag = response.headers()[header::ETAG].clone();
+
+        let response = serve(&server, request(Method::HEAD));
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "7");
+        assert!(response.body().is_empty());
+
+        let mut conditional = request(Method::GET);
+        let _ = conditional
+            .headers_mut()
+            .insert(header::IF_NONE_MATCH, etag.clone());
+        let response = serve(&server, conditional);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+
+        let response = serve(&server, request(Method::PUT));
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[header::ALLOW], "GET, HEAD");
+    }
+}
@@ -0,0 +1,202 @@
+//! A canonical form of built requests for snapshot tests, such as with
+//! `insta`, so that clients built on this crate can check the requests they
+//! make without depending on header order, credentials or the exact bytes of
+//! binary bodies.
+//!
+//! A [`RequestSnapshot`] holds the method and URI of a request, its headers
+//! sorted by name with credentials redacted, and its body as pretty printed
+//! JSON, UTF-8 text, or the size and checksum of binary data. It can be
+//! serialized or written as text with [`to_text`](RequestSnapshot::to_text).
+//!
+//! ```
+//! use hexpm::{Config, snapshot::RequestSnapshot};
+//!
+//! let request = hexpm::get_current_user_request("my-api-key", &Config::new());
+//! let snapshot = RequestSnapshot::new(&request);
+//! assert_eq!(
+//!     snapshot.to_text(),
+//!     "GET https://hex.pm/api/users/me
+//! accept: application/json
+//! authorization: [redacted]
+//! content-type: application/json
+//! user-agent: [user agent]
+//! "
+//! );
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::checksum::Checksum;
+
+/// Headers whose values are replaced with [`REDACTED`] by default.
+pub const REDACTED_HEADERS: &[&str] = &["authorization", "cookie", "proxy-authorization"];
+
+/// The value redacted headers are given.
+pub const REDACTED: &str = "[redacted]";
+
+/// The canonical form of a request.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct RequestSnapshot {
+    pub method: String,
+    pub uri: String,
+    /// The headers by name. Repeated headers have their values joined with
+    /// `, `.
+    pub headers: BTreeMap<String, String>,
+    pub body: SnapshotBody,
+}
+
+/// The canonical form of a request body.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SnapshotBody {
+    Empty,
+    /// A JSON body, with object keys sorted.
+    Json {
+        value: serde_json::Value,
+    },
+    Text {
+        text: String,
+    },
+    /// A body that is not text, such as a tarball.
+    Binary {
+        size: usize,
+        sha256: String,
+    },
+}
+
+impl RequestSnapshot {
+    /// The snapshot of a request with the [default redactions](REDACTED_HEADERS).
+    /// The `user-agent` header is replaced too, as it includes the version of
+    /// this crate.
+    pub fn new(request: &http::Request<Vec<u8>>) -> Self {
+        let mut headers = BTreeMap::<String, String>::new();
+        for (name, value) in request.headers() {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) || value.is_sensitive() {
+                REDACTED.to_string()
+            } else if name == http::header::USER_AGENT {
+                "[user agent]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            headers
+                .entry(name.to_string())
+                .and_modify(|values| {
+                    values.push_str(", ");
+                    values.push_str(&value)
+                })
+                .or_insert(value);
+        }
+        Self {
+            method: request.method().to_string(),
+            uri: request.uri().to_string(),
+            body: SnapshotBody::new(request.body()),
+            headers,
+        }
+    }
+
+    /// Replace the value of another header with [`REDACTED`], such as a
+    /// custom header carrying a token.
+    pub fn redact(mut self, header: &str) -> Self {
+        let header = header.to_ascii_lowercase();
+        if let Some(value) = self.headers.get_mut(&header) {
+            *value = REDACTED.to_string();
+        }
+        self
+    }
+
+    /// The snapshot as text in the form of an HTTP request, with the body
+    /// after a blank line.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", self.method, self.uri);
+        for (name, value) in &self.headers {
+            text.push_str(&format!("{name}: {value}\n"));
+        }
+        let body = match &self.body {
+            SnapshotBody::Empty => return text,
+            SnapshotBody::Json { value } => {
+                serde_json::to_string_pretty(value).expect("JSON value serialization")
+            }
+            SnapshotBody::Text { text } => text.clone(),
+            SnapshotBody::Binary { size, sha256 } => {
+                format!("<{size} bytes, sha256 {sha256}>")
+            }
+        };
+        text.push('\n');
+        text.push_str(&body);
+        text.push('\n');
+        text
+    }
+}
+
+impl SnapshotBody {
+    fn new(body: &[u8]) -> Self {
+        if body.is_empty() {
+            return SnapshotBody::Empty;
+        }
+        if let Ok(value) = serde_json::from_slice(body) {
+            return SnapshotBody::Json { value };
+        }
+        match std::str::from_utf8(body) {
+            Ok(text) if !text.contains('\0') => SnapshotBody::Text {
+                text: text.to_string(),
+            },
+            _ => SnapshotBody::Binary {
+                size: body.len(),
+                sha256: Checksum::of(body).to_hex(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::Version;
+    use crate::{Config, ReleaseRef, RetirementReason};
+
+    #[test]
+    fn json_body() {
+        let release = ReleaseRef::new("gleam_json", Version::new(1, 0, 0));
+        let request = crate::retire_release_request(
+            &release,
+            RetirementReason::Security,
+            Some("Vulnerable"),
+            "key",
+            &Config::new(),
+        );
+        let snapshot = RequestSnapshot::new(&request);
+        assert_eq!(
+            snapshot.body,
+            SnapshotBody::Json {
+                value: serde_json::json!({"message": "Vulnerable", "reason": "security"})
+            }
+        );
+        assert!(
+            snapshot.to_text().ends_with(
+                "\n\n{\n  \"message\": \"Vulnerable\",\n  \"reason\": \"security\"\n}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn binary_body_and_redaction() {
+        let request = http::Request::post("https://repo.example.com/upload")
+            .header("x-token", "secret")
+            .header("accept", "text/plain")
+            .header("accept", "application/json")
+            .body(vec![0x1f, 0x8b, 0, 0])
+            .unwrap();
+        let snapshot = RequestSnapshot::new(&request).redact("X-Token");
+        assert_eq!(snapshot.headers["x-token"], REDACTED);
+        assert_eq!(snapshot.headers["accept"], "text/plain, application/json");
+        assert_eq!(
+            snapshot.body,
+            SnapshotBody::Binary {
+                size: 4,
+                sha256: Checksum::of(&[0x1f, 0x8b, 0, 0]).to_hex(),
+            }
+        );
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["body"]["type"], "binary");
+    }
+}
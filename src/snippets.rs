@@ -0,0 +1,146 @@
+//! Generation of the "add this to your project" snippets shown to users, in
+//! the format of each build tool's manifest.
+
+use crate::version::{Range, Version};
+
+/// A build tool whose manifest format snippets can be generated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuildTool {
+    /// `mix.exs`
+    Mix,
+    /// `rebar.config`
+    Rebar3,
+    /// `gleam.toml`
+    Gleam,
+}
+
+impl BuildTool {
+    /// The name of the manifest file the snippets are written for.
+    pub fn manifest(&self) -> &'static str {
+        match self {
+            BuildTool::Mix => "mix.exs",
+            BuildTool::Rebar3 => "rebar.config",
+            BuildTool::Gleam => "gleam.toml",
+        }
+    }
+}
+
+/// The requirement to suggest for depending on the given version of a
+/// package, in the style conventionally used by the build tool.
+///
+/// Mix and rebar3 use the pessimistic operator. For stable versions the patch
+/// version is omitted so that compatible minor releases are accepted, while
+/// versions before 1.0.0 and pre-releases keep it as their minor releases may
+/// contain breaking changes:
+///
+/// ```text
+/// 1.14.2     => ~> 1.14
+/// 0.3.1      => ~> 0.3.1
+/// 2.0.0-rc.1 => ~> 2.0.0-rc.1
+/// ```
+///
+/// Gleam uses an explicit range up to the next major version:
+///
+/// ```text
+/// 1.14.2 => >= 1.14.2 and < 2.0.0
+/// ```
+pub fn suggested_requirement(version: &Version, tool: BuildTool) -> Range {
+    let version = Version {
+        build: None,
+        ..version.clone()
+    };
+    let spec = match tool {
+        BuildTool::Mix | BuildTool::Rebar3 if version.major == 0 || version.is_pre() => {
+            format!("~> {}", version)
+        }
+        BuildTool::Mix | BuildTool::Rebar3 => format!("~> {}.{}", version.major, version.minor),
+        BuildTool::Gleam => match version.major.checked_add(1) {
+            Some(major) => format!(">= {} and < {}", version, Version::new(major, 0, 0)),
+            // There is no next major version to stop at
+            None => format!(">= {}", version),
+        },
+    };
+    Range::new(spec).expect("suggested requirement is valid")
+}
+
+/// A dependency declaration for the given version of a package, ready to be
+/// added to the build tool's manifest.
+///
+/// ```text
+/// Mix    => {:plug, "~> 1.14"}
+/// Rebar3 => {plug, "~> 1.14"}
+/// Gleam  => plug = ">= 1.14.2 and < 2.0.0"
+/// ```
+pub fn dependency_snippet(package: &str, version: &Version, tool: BuildTool) -> String {
+    let requirement = suggested_requirement(version, tool);
+    match tool {
+        BuildTool::Mix => format!("{{:{}, \"{}\"}}", package, requirement),
+        BuildTool::Rebar3 => format!("{{{}, \"{}\"}}", package, requirement),
+        BuildTool::Gleam => format!("{} = \"{}\"", package, requirement),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn suggested_requirements() {
+        let requirement = |version, tool| suggested_requirement(&v(version), tool).to_string();
+        assert_eq!(requirement("1.14.2", BuildTool::Mix), "~> 1.14");
+        assert_eq!(requirement("0.3.1", BuildTool::Mix), "~> 0.3.1");
+        assert_eq!(
+            requirement("2.0.0-rc.1", BuildTool::Rebar3),
+            "~> 2.0.0-rc.1"
+        );
+        assert_eq!(requirement("1.4.0+build", BuildTool::Mix), "~> 1.4");
+        assert_eq!(
+            requirement("1.14.2", BuildTool::Gleam),
+            ">= 1.14.2 and < 2.0.0"
+        );
+        assert_eq!(
+            requirement("0.3.1", BuildTool::Gleam),
+            ">= 0.3.1 and < 1.0.0"
+        );
+        assert_eq!(
+            requirement("4294967295.1.0", BuildTool::Gleam),
+            ">= 4294967295.1.0"
+        );
+        assert!(
+            suggested_requirement(&v("4294967295.1.0"), BuildTool::Gleam)
+                .to_pubgrub()
+                .contains(&v("4294967295.1.0"))
+        );
+    }
+
+    #[test]
+    fn suggested_requirement_includes_version() {
+        for version in ["1.14.2", "0.3.1", "2.0.0-rc.1"] {
+            for tool in [BuildTool::Mix, BuildTool::Rebar3, BuildTool::Gleam] {
+                let requirement = suggested_requirement(&v(version), tool);
+                assert!(requirement.to_pubgrub().contains(&v(version)));
+            }
+        }
+    }
+
+    #[test]
+    fn snippets() {
+        let version = v("1.14.2");
+        assert_eq!(
+            dependency_snippet("plug", &version, BuildTool::Mix),
+            r#"{:plug, "~> 1.14"}"#
+        );
+        assert_eq!(
+            dependency_snippet("plug", &version, BuildTool::Rebar3),
+            r#"{plug, "~> 1.14"}"#
+        );
+        assert_eq!(
+            dependency_snippet("plug", &version, BuildTool::Gleam),
+            r#"plug = ">= 1.14.2 and < 2.0.0""#
+        );
+    }
+}
@@ -0,0 +1,1221 @@
+//! Reading the contents of package tarballs.
+//!
+//! A package tarball is an uncompressed tar archive containing the files
+//! `VERSION`, `CHECKSUM`, `metadata.config` and `contents.tar.gz`. The
+//! functions here read individual files from the archive without unpacking
+//! the rest, which avoids decompressing the package contents when only the
+//! metadata is needed. [`PackageMetadata::to_config`] writes the
+//! `metadata.config` file for tools that build tarballs, and [`lint_tarball`]
+//! looks for common mistakes in the files of a built tarball before it is
+//! published.
+
+use thiserror::Error;
+
+use crate::erlang::{self, Spanned, SyntaxError, Term};
+use crate::links::PackageLinks;
+use crate::version::{Range, Version};
+use crate::{NameViolation, PackageName};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TarballError {
+    #[error("the package tarball is not a valid tar archive")]
+    InvalidArchive,
+
+    #[error("the package tarball does not contain {0}")]
+    MissingFile(&'static str),
+
+    #[error("invalid metadata.config syntax at byte {position}: {message}")]
+    MetadataSyntax {
+        position: usize,
+        message: &'static str,
+    },
+
+    #[error("the metadata field {0} is missing or not valid")]
+    InvalidMetadataField(&'static str),
+
+    #[error("the package tarball has format version {0}, which is not supported")]
+    UnsupportedFormat(String),
+
+    #[error("the package tarball's CHECKSUM file is not valid: {0}")]
+    InvalidChecksumFile(#[from] crate::checksum::ChecksumError),
+}
+
+impl From<SyntaxError> for TarballError {
+    fn from(error: SyntaxError) -> Self {
+        TarballError::MetadataSyntax {
+            position: error.position,
+            message: error.message,
+        }
+    }
+}
+
+/// The metadata of a package release, as found in the `metadata.config` file
+/// of its tarball. Fields that are not listed here are ignored, other than
+/// those in `extra`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageMetadata {
+    pub name: String,
+    pub version: Version,
+    /// The name of the OTP application, if it differs from the package name.
+    pub app: Option<String>,
+    pub description: Option<String>,
+    pub licenses: Vec<String>,
+    /// Links to the package's website, source code and so on, in the order
+    /// they were given.
+    pub links: Vec<(String, String)>,
+    pub build_tools: Vec<String>,
+    pub requirements: Vec<MetadataRequirement>,
+    /// The files included in the package.
+    pub files: Vec<String>,
+    /// The Elixir version requirement of the package, if it has one.
+    pub elixir: Option<String>,
+    /// Free-form metadata used by build tools and ecosystems, such as the
+    /// compilation targets of a Gleam package, in the order given.
+    pub extra: Vec<(String, MetadataValue)>,
+}
+
+impl PackageMetadata {
+    pub fn links(&self) -> PackageLinks {
+        PackageLinks::new(self.links.iter().cloned())
+    }
+
+    /// The value of a field of the `extra` metadata.
+    pub fn extra_field(&self, key: &str) -> Option<&MetadataValue> {
+        self.extra
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// The targets a Gleam package's dependency is needed on, or `None` if it
+    /// is needed on all of them.
+    ///
+    /// Hex has no standard way to declare this, so it is read from the
+    /// `dependency_targets` list of the `gleam` field of the `extra`
+    /// metadata, in the form `[{<<"dependency">>, [<<"erlang">>]}]`.
+    /// Dependencies not in the list are needed on every target.
+    pub fn dependency_targets(&self, dependency: &str) -> Option<Vec<Target>> {
+        let targets = self
+            .extra_field("gleam")?
+            .get("dependency_targets")?
+            .get(dependency)?
+            .as_list()?;
+        Some(
+            targets
+                .iter()
+                .filter_map(|target| target.as_str()?.parse().ok())
+                .collect(),
+        )
+    }
+
+    /// Whether a dependency of the package is needed when compiling for the
+    /// given target. See [`dependency_targets`](Self::dependency_targets).
+    pub fn needs_dependency_on(&self, dependency: &str, target: Target) -> bool {
+        self.dependency_targets(dependency)
+            .is_none_or(|targets| targets.contains(&target))
+    }
+
+    /// Check the metadata against the rules Hex applies when publishing,
+    /// reporting every problem found rather than only the first, so that they
+    /// can all be shown to the user at once.
+    pub fn validate(&self) -> Result<(), Vec<MetadataViolation>> {
+        let mut violations = vec![];
+        let mut violation =
+            |field: String, kind| violations.push(MetadataViolation { field, kind });
+
+        if let Err(error) = PackageName::validate(&self.name) {
+            violation("name".into(), ViolationKind::InvalidName(error.violations));
+        }
+        if self.app.as_deref().is_some_and(str::is_empty) {
+            violation("app".into(), ViolationKind::Empty);
+        }
+        match self.description.as_deref().map(str::trim) {
+            None | Some("") => violation("description".into(), ViolationKind::Missing),
+            Some(description) if description.chars().count() > MAX_DESCRIPTION_LENGTH => violation(
+                "description".into(),
+                ViolationKind::TooLong {
+                    max: MAX_DESCRIPTION_LENGTH,
+                },
+            ),
+            Some(_) => (),
+        }
+        if self.licenses.is_empty() {
+            violation("licenses".into(), ViolationKind::Missing);
+        }
+        for (index, license) in self.licenses.iter().enumerate() {
+            if license.trim().is_empty() {
+                violation(format!("licenses[{index}]"), ViolationKind::Empty);
+            }
+        }
+        for (name, url) in &self.links {
+            let is_web_url = url
+                .parse::<http::Uri>()
+                .is_ok_and(|uri| matches!(uri.scheme_str(), Some("http" | "https")));
+            if !is_web_url {
+                violation(format!("links.{name}"), ViolationKind::InvalidUrl);
+            }
+        }
+        if self.build_tools.is_empty() {
+            violation("build_tools".into(), ViolationKind::Missing);
+        }
+        let mut seen = std::collections::HashSet::new();
+        for (index, requirement) in self.requirements.iter().enumerate() {
+            let field = |name: &str| format!("requirements[{index}].{name}");
+            if let Err(error) = PackageName::validate(&requirement.name) {
+                violation(field("name"), ViolationKind::InvalidName(error.violations));
+            } else if !seen.insert(&requirement.name) {
+                violation(field("name"), ViolationKind::Duplicate);
+            }
+            if Range::new(requirement.requirement.clone()).is_err() {
+                violation(field("requirement"), ViolationKind::InvalidRequirement);
+            }
+        }
+        if self.files.is_empty() {
+            violation("files".into(), ViolationKind::Missing);
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Write the metadata as the contents of a `metadata.config` file.
+    /// Reading the result with [`parse_metadata`] gives back the same
+    /// metadata.
+    pub fn to_config(&self) -> String {
+        use MetadataValue::{Atom, Binary, List, Tuple};
+
+        let text = |value: &str| Binary(value.to_string());
+        let texts = |values: &[String]| List(values.iter().map(|value| text(value)).collect());
+        let proplist = |fields: Vec<(&str, MetadataValue)>| {
+            List(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| Tuple(vec![text(key), value]))
+                    .collect(),
+            )
+        };
+
+        let mut fields = vec![
+            ("name", text(&self.name)),
+            ("version", text(&self.version.to_string())),
+        ];
+        if let Some(app) = &self.app {
+            fields.push(("app", text(app)));
+        }
+        if let Some(description) = &self.description {
+            fields.push(("description", text(description)));
+        }
+        fields.push(("licenses", texts(&self.licenses)));
+        let links = self
+            .links
+            .iter()
+            .map(|(name, url)| (name.as_str(), text(url)));
+        fields.push(("links", proplist(links.collect())));
+        let requirements = self.requirements.iter().map(|requirement| {
+            let mut properties = vec![];
+            if let Some(app) = &requirement.app {
+                properties.push(("app", text(app)));
+            }
+            properties.push(("optional", Atom(requirement.optional.to_string())));
+            properties.push(("requirement", text(&requirement.requirement)));
+            if let Some(repository) = &requirement.repository {
+                properties.push(("repository", text(repository)));
+            }
+            Tuple(vec![text(&requirement.name), proplist(properties)])
+        });
+        fields.push(("requirements", List(requirements.collect())));
+        fields.push(("files", texts(&self.files)));
+        fields.push(("build_tools", texts(&self.build_tools)));
+        if let Some(elixir) = &self.elixir {
+            fields.push(("elixir", text(elixir)));
+        }
+        if !self.extra.is_empty() {
+            let extra = self
+                .extra
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.clone()));
+            fields.push(("extra", proplist(extra.collect())));
+        }
+
+        let mut out = String::new();
+        for (key, value) in fields {
+            out.push('{');
+            erlang::write_binary(&mut out, key);
+            out.push(',');
+            value.write(&mut out);
+            out.push_str("}.\n");
+        }
+        out
+    }
+}
+
+/// A compilation target of a Gleam package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+    Erlang,
+    JavaScript,
+}
+
+impl std::str::FromStr for Target {
+    type Err = ();
+
+    fn from_str(target: &str) -> Result<Self, Self::Err> {
+        match target {
+            "erlang" => Ok(Target::Erlang),
+            "javascript" => Ok(Target::JavaScript),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A value in the `extra` metadata of a package. Maps are written by Hex as
+/// lists of key and value tuples, see [`MetadataValue::get`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataValue {
+    Binary(String),
+    Atom(String),
+    Integer(u32),
+    List(Vec<MetadataValue>),
+    Tuple(Vec<MetadataValue>),
+}
+
+impl MetadataValue {
+    /// The contents of a binary.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MetadataValue::Binary(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[MetadataValue]> {
+        match self {
+            MetadataValue::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Look up a key in a list of `{Key, Value}` tuples with binary keys, as
+    /// maps are written.
+    pub fn get(&self, key: &str) -> Option<&MetadataValue> {
+        self.as_list()?.iter().find_map(|item| match item {
+            MetadataValue::Tuple(pair) => match pair.as_slice() {
+                [MetadataValue::Binary(k), value] if k == key => Some(value),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    fn from_term(term: &Spanned) -> Self {
+        let items = |items: &[Spanned]| items.iter().map(MetadataValue::from_term).collect();
+        match &term.value {
+            Term::Binary(value) => MetadataValue::Binary(value.clone()),
+            Term::Atom(value) => MetadataValue::Atom(value.clone()),
+            Term::Integer(value) => MetadataValue::Integer(*value),
+            Term::String(value) => MetadataValue::List(
+                value
+                    .chars()
+                    .map(|c| MetadataValue::Integer(c as u32))
+                    .collect(),
+            ),
+            Term::List(values) => MetadataValue::List(items(values)),
+            Term::Tuple(values) => MetadataValue::Tuple(items(values)),
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        let items = |out: &mut String, items: &[MetadataValue]| {
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                item.write(out);
+            }
+        };
+        match self {
+            MetadataValue::Binary(value) => erlang::write_binary(out, value),
+            MetadataValue::Atom(value)
+                if value.starts_with(|c: char| c.is_ascii_lowercase())
+                    && value
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '@')) =>
+            {
+                out.push_str(value)
+            }
+            MetadataValue::Atom(value) => {
+                out.push('\'');
+                for c in value.chars() {
+                    if matches!(c, '\'' | '\\') {
+                        out.push('\\');
+                    }
+                    out.push(c);
+                }
+                out.push('\'');
+            }
+            MetadataValue::Integer(value) => out.push_str(&value.to_string()),
+            MetadataValue::List(values) => {
+                out.push('[');
+                items(out, values);
+                out.push(']');
+            }
+            MetadataValue::Tuple(values) => {
+                out.push('{');
+                items(out, values);
+                out.push('}');
+            }
+        }
+    }
+}
+
+/// The longest description Hex accepts, in characters.
+pub const MAX_DESCRIPTION_LENGTH: usize = 300;
+
+/// A problem with a field of a package's metadata, found by
+/// [`PackageMetadata::validate`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{field}: {kind}")]
+pub struct MetadataViolation {
+    /// The path of the field, such as `description`, `links.GitHub` or
+    /// `requirements[0].requirement`.
+    pub field: String,
+    pub kind: ViolationKind,
+}
+
+/// The kind of a [`MetadataViolation`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    #[error("is required")]
+    Missing,
+
+    #[error("must not be empty")]
+    Empty,
+
+    #[error("must be at most {max} characters long")]
+    TooLong { max: usize },
+
+    #[error("is not a valid package name")]
+    InvalidName(Vec<NameViolation>),
+
+    #[error("is not a valid version requirement")]
+    InvalidRequirement,
+
+    #[error("is not an http or https URL")]
+    InvalidUrl,
+
+    #[error("is given more than once")]
+    Duplicate,
+}
+
+/// A dependency of a package release, as found in its metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataRequirement {
+    /// The name of the package depended upon.
+    pub name: String,
+    /// The name of the OTP application, if it differs from the package name.
+    pub app: Option<String>,
+    pub requirement: String,
+    pub optional: bool,
+    pub repository: Option<String>,
+}
+
+/// The version of the package tarball format, from the tarball's `VERSION`
+/// file. Hex has written version 3 since before the registry was signed, and
+/// the versions before it cannot be read by Hex's own tools.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FormatVersion {
+    /// The current format, with a `metadata.config` file of Erlang terms, a
+    /// gzipped `contents.tar.gz` archive, and a `CHECKSUM` file holding the
+    /// [inner checksum](crate::checksum::inner_checksum) of the three.
+    V3,
+    /// A version this library does not know, such as one from a later
+    /// revision of the format, as given in the `VERSION` file.
+    Unknown(String),
+}
+
+impl FormatVersion {
+    /// The version written by Hex today.
+    pub const CURRENT: FormatVersion = FormatVersion::V3;
+
+    /// Parse the contents of a `VERSION` file.
+    pub fn parse(file: &[u8]) -> Self {
+        match String::from_utf8_lossy(file).trim() {
+            "3" => FormatVersion::V3,
+            version => FormatVersion::Unknown(version.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            FormatVersion::V3 => "3",
+            FormatVersion::Unknown(version) => version,
+        }
+    }
+
+    /// Whether this library can read tarballs of this version.
+    pub fn is_supported(&self) -> bool {
+        self.metadata_file().is_some()
+    }
+
+    /// The name of the file holding the release metadata, if known.
+    pub fn metadata_file(&self) -> Option<&'static str> {
+        match self {
+            FormatVersion::V3 => Some("metadata.config"),
+            FormatVersion::Unknown(_) => None,
+        }
+    }
+
+    /// The name of the gzipped archive of the package's files, if known.
+    pub fn contents_file(&self) -> Option<&'static str> {
+        match self {
+            FormatVersion::V3 => Some("contents.tar.gz"),
+            FormatVersion::Unknown(_) => None,
+        }
+    }
+
+    /// Whether the tarball has a `CHECKSUM` file of its other files.
+    pub fn has_inner_checksum(&self) -> bool {
+        matches!(self, FormatVersion::V3)
+    }
+}
+
+impl std::fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The files of a package tarball, as read by [`unpack`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnpackedTarball<'a> {
+    pub format: FormatVersion,
+    pub metadata: PackageMetadata,
+    /// The inner checksum from the `CHECKSUM` file.
+    pub checksum: Vec<u8>,
+    /// The gzipped archive of the package's files.
+    pub contents: &'a [u8],
+}
+
+/// Read the format version of a package tarball from its `VERSION` file.
+pub fn read_format_version(tarball: &[u8]) -> Result<FormatVersion, TarballError> {
+    let file = read_file(tarball, "VERSION")?.ok_or(TarballError::MissingFile("VERSION"))?;
+    Ok(FormatVersion::parse(file))
+}
+
+/// Read the files of a package tarball, first checking that its format
+/// version is one this library supports. An unsupported version is reported
+/// as [`TarballError::UnsupportedFormat`] with the version found, so that
+/// tools can tell the user their client needs upgrading.
+pub fn unpack(tarball: &[u8]) -> Result<UnpackedTarball<'_>, TarballError> {
+    let format = read_format_version(tarball)?;
+    let (Some(metadata_file), Some(contents_file)) =
+        (format.metadata_file(), format.contents_file())
+    else {
+        return Err(TarballError::UnsupportedFormat(format.to_string()));
+    };
+    let metadata =
+        read_file(tarball, metadata_file)?.ok_or(TarballError::MissingFile(metadata_file))?;
+    let checksum = read_file(tarball, "CHECKSUM")?.ok_or(TarballError::MissingFile("CHECKSUM"))?;
+    let contents =
+        read_file(tarball, contents_file)?.ok_or(TarballError::MissingFile(contents_file))?;
+    Ok(UnpackedTarball {
+        metadata: parse_metadata(&String::from_utf8_lossy(metadata))?,
+        checksum: crate::checksum::parse_checksum_file(checksum)?,
+        contents,
+        format,
+    })
+}
+
+/// Read a single file from a package tarball without unpacking the others.
+pub fn read_file<'a>(tarball: &'a [u8], name: &str) -> Result<Option<&'a [u8]>, TarballError> {
+    for entry in entries(tarball) {
+        let entry = entry?;
+        if entry.path == name.as_bytes() {
+            return Ok(Some(entry.data));
+        }
+    }
+    Ok(None)
+}
+
+/// Files larger than this that are not text are reported by [`lint_tarball`]
+/// as [`LintFinding::LargeBinary`].
+pub const LARGE_BINARY_SIZE: u64 = 1024 * 1024;
+
+/// Top level directories of build output, fetched dependencies and editor
+/// state, which are recreated on the user's machine and only make a package
+/// larger.
+#[cfg(feature = "registry")]
+const BUILD_DIRECTORIES: &[&str] = &["_build", "deps", ".elixir_ls"];
+
+/// Extensions of compiled files.
+#[cfg(feature = "registry")]
+const COMPILED_EXTENSIONS: &[&str] = &["beam", "o"];
+
+/// A likely mistake in the files of a package tarball, found by
+/// [`lint_tarball`]. These do not stop a package being published, so are
+/// best shown as warnings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintFinding {
+    /// The metadata names licenses, but there is no licence file such as
+    /// `LICENSE` or `COPYING` at the root of the package.
+    MissingLicenseFile,
+    /// Compiled files or a directory of build output, such as `_build`.
+    BuildArtifact(String),
+    /// A binary file larger than [`LARGE_BINARY_SIZE`], such as a vendored
+    /// library or executable.
+    LargeBinary { path: String, size: u64 },
+    /// A path that is not valid UTF-8, which some systems cannot unpack. The
+    /// path is given with invalid bytes replaced.
+    NonUtf8Path(String),
+}
+
+impl std::fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintFinding::MissingLicenseFile => {
+                write!(f, "licenses are given but there is no licence file")
+            }
+            LintFinding::BuildArtifact(path) => write!(f, "{path} is build output"),
+            LintFinding::LargeBinary { path, size } => {
+                write!(f, "{path} is a binary file of {size} bytes")
+            }
+            LintFinding::NonUtf8Path(path) => write!(f, "{path} is not a valid UTF-8 path"),
+        }
+    }
+}
+
+/// Look for common publishing mistakes in the files of a package tarball,
+/// such as included build output. A missing licence file is reported first,
+/// then the findings for each file in the order the files appear.
+#[cfg(feature = "registry")]
+pub fn lint_tarball(tarball: &[u8]) -> Result<Vec<LintFinding>, TarballError> {
+    let metadata = read_metadata(tarball)?;
+    let contents = read_file(tarball, "contents.tar.gz")?
+        .ok_or(TarballError::MissingFile("contents.tar.gz"))?;
+    let contents = crate::inflate(contents, crate::hexpm_defaults::MAX_INFLATED_RESOURCE_SIZE)
+        .map_err(|_| TarballError::InvalidArchive)?;
+
+    let mut findings = vec![];
+    let mut has_license = false;
+    let mut build_directories = std::collections::HashSet::new();
+    for entry in entries(&contents) {
+        let entry = entry?;
+        let path = match std::str::from_utf8(&entry.path) {
+            Ok(path) => path.trim_start_matches("./").to_string(),
+            Err(_) => {
+                let path = String::from_utf8_lossy(&entry.path).into_owned();
+                findings.push(LintFinding::NonUtf8Path(path));
+                continue;
+            }
+        };
+        let (top, rest) = path.split_once('/').unwrap_or((&path, ""));
+        if BUILD_DIRECTORIES.contains(&top) && (!rest.is_empty() || entry.kind == b'5') {
+            if build_directories.insert(top.to_string()) {
+                findings.push(LintFinding::BuildArtifact(top.to_string()));
+            }
+            continue;
+        }
+        if !matches!(entry.kind, b'0' | b'\0' | b'7') {
+            continue;
+        }
+        if rest.is_empty() {
+            let name = top.to_ascii_uppercase();
+            has_license |= ["LICENSE", "LICENCE", "COPYING"]
+                .iter()
+                .any(|prefix| name.starts_with(prefix));
+        }
+        let extension = path.rsplit_once('.').map(|(_, extension)| extension);
+        if extension.is_some_and(|extension| COMPILED_EXTENSIONS.contains(&extension)) {
+            findings.push(LintFinding::BuildArtifact(path));
+            continue;
+        }
+        let size = entry.data.len() as u64;
+        // As git does, a file with a NUL byte near its start is taken to be
+        // binary.
+        if size > LARGE_BINARY_SIZE && entry.data.iter().take(8000).any(|&b| b == 0) {
+            findings.push(LintFinding::LargeBinary { path, size });
+        }
+    }
+    if !metadata.licenses.is_empty() && !has_license {
+        findings.insert(0, LintFinding::MissingLicenseFile);
+    }
+    Ok(findings)
+}
+
+/// An entry of a tar archive.
+pub(crate) struct Entry<'a> {
+    pub path: std::borrow::Cow<'a, [u8]>,
+    /// The type flag of the entry, such as `b'0'` for a file or `b'2'` for a
+    /// symbolic link.
+    #[cfg_attr(not(feature = "registry"), allow(dead_code))]
+    pub kind: u8,
+    pub data: &'a [u8],
+}
+
+/// Iterate over the entries of a tar archive. Long paths given by GNU or pax
+/// extension headers are applied to the entry they describe.
+pub(crate) fn entries(tarball: &[u8]) -> impl Iterator<Item = Result<Entry<'_>, TarballError>> {
+    let mut rest = tarball;
+    let mut long_path = None;
+    std::iter::from_fn(move || {
+        loop {
+            if rest.len() < 512 {
+                rest = &[];
+                return Some(Err(TarballError::InvalidArchive));
+            }
+            let (header, after) = rest.split_at(512);
+            // The archive ends with blocks of zeros.
+            if header.iter().all(|&b| b == 0) {
+                rest = &[];
+                return None;
+            }
+            let Some(size) = std::str::from_utf8(&header[124..136])
+                .ok()
+                .and_then(|size| usize::from_str_radix(size.trim_matches(['\0', ' ']), 8).ok())
+            else {
+                rest = &[];
+                return Some(Err(TarballError::InvalidArchive));
+            };
+            let padded = size.div_ceil(512) * 512;
+            if after.len() < padded {
+                rest = &[];
+                return Some(Err(TarballError::InvalidArchive));
+            }
+            let data = &after[..size];
+            rest = &after[padded..];
+
+            match header[156] {
+                // A GNU long name for the next entry.
+                b'L' => long_path = Some(until_nul(data).to_vec()),
+                // Pax extended attributes for the next entry, as records of
+                // the form "<length> <key>=<value>\n".
+                b'x' => {
+                    if let Some(path) = pax_path(data) {
+                        long_path = Some(path.to_vec());
+                    }
+                }
+                // Global pax attributes.
+                b'g' => (),
+                kind => {
+                    let path = match long_path.take() {
+                        Some(path) => path.into(),
+                        None if &header[257..262] == b"ustar" && header[345] != 0 => {
+                            let mut path = until_nul(&header[345..500]).to_vec();
+                            path.push(b'/');
+                            path.extend_from_slice(until_nul(&header[..100]));
+                            path.into()
+                        }
+                        None => until_nul(&header[..100]).into(),
+                    };
+                    return Some(Ok(Entry { path, kind, data }));
+                }
+            }
+        }
+    })
+}
+
+fn until_nul(field: &[u8]) -> &[u8] {
+    field.split(|&b| b == 0).next().unwrap_or_default()
+}
+
+fn pax_path(records: &[u8]) -> Option<&[u8]> {
+    let mut rest = records;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let length: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..length)?;
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(path);
+        }
+        rest = &rest[length..];
+    }
+    None
+}
+
+/// Read the metadata of a release from its package tarball.
+pub fn read_metadata(tarball: &[u8]) -> Result<PackageMetadata, TarballError> {
+    let file = read_file(tarball, "metadata.config")?
+        .ok_or(TarballError::MissingFile("metadata.config"))?;
+    parse_metadata(&String::from_utf8_lossy(file))
+}
+
+/// Parse the contents of a `metadata.config` file.
+pub fn parse_metadata(input: &str) -> Result<PackageMetadata, TarballError> {
+    let terms = erlang::parse_terms(input)?;
+    let fields: Vec<(&str, &Spanned)> = terms
+        .iter()
+        .filter_map(|term| match term.tuple()? {
+            [key, value] => Some((binary(key)?, value)),
+            _ => None,
+        })
+        .collect();
+    let field = |name| fields.iter().find(|(key, _)| *key == name).map(|(_, v)| *v);
+    let string = |name: &'static str| match field(name) {
+        None => Ok(None),
+        Some(value) => binary(value)
+            .map(|value| Some(value.to_string()))
+            .ok_or(TarballError::InvalidMetadataField(name)),
+    };
+    let strings = |name: &'static str| match field(name) {
+        None => Ok(vec![]),
+        Some(value) => binaries(value).ok_or(TarballError::InvalidMetadataField(name)),
+    };
+
+    let name = string("name")?.ok_or(TarballError::InvalidMetadataField("name"))?;
+    let version = string("version")?
+        .and_then(|version| Version::parse(&version).ok())
+        .ok_or(TarballError::InvalidMetadataField("version"))?;
+    let links = match field("links").map(|links| &links.value) {
+        None => vec![],
+        Some(Term::List(links)) => links
+            .iter()
+            .map(|link| match link.tuple()? {
+                [name, url] => Some((binary(name)?.to_string(), binary(url)?.to_string())),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+            .ok_or(TarballError::InvalidMetadataField("links"))?,
+        Some(_) => return Err(TarballError::InvalidMetadataField("links")),
+    };
+    let requirements = match field("requirements").map(|requirements| &requirements.value) {
+        None => vec![],
+        Some(Term::List(requirements)) => requirements
+            .iter()
+            .map(requirement)
+            .collect::<Option<_>>()
+            .ok_or(TarballError::InvalidMetadataField("requirements"))?,
+        Some(_) => return Err(TarballError::InvalidMetadataField("requirements")),
+    };
+    let extra = match field("extra").map(|extra| &extra.value) {
+        None => vec![],
+        Some(Term::List(extra)) => extra
+            .iter()
+            .map(|field| match field.tuple()? {
+                [key, value] => Some((binary(key)?.to_string(), MetadataValue::from_term(value))),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+            .ok_or(TarballError::InvalidMetadataField("extra"))?,
+        Some(_) => return Err(TarballError::InvalidMetadataField("extra")),
+    };
+
+    Ok(PackageMetadata {
+        name,
+        version,
+        app: string("app")?,
+        description: string("description")?,
+        licenses: strings("licenses")?,
+        links,
+        build_tools: strings("build_tools")?,
+        requirements,
+        files: strings("files")?,
+        elixir: string("elixir")?,
+        extra,
+    })
+}
+
+/// A requirement, either as `{Name, Properties}` or, in metadata written by
+/// older versions of Hex, as a property list including the name.
+fn requirement(term: &Spanned) -> Option<MetadataRequirement> {
+    let (name, properties) = match &term.value {
+        Term::Tuple(items) => match items.as_slice() {
+            [name, properties] => (Some(binary(name)?), properties),
+            _ => return None,
+        },
+        Term::List(_) => (None, term),
+        _ => return None,
+    };
+    let Term::List(properties) = &properties.value else {
+        return None;
+    };
+    let property = |key| {
+        properties
+            .iter()
+            .find_map(|property| match property.tuple()? {
+                [k, value] if binary(k) == Some(key) => Some(value),
+                _ => None,
+            })
+    };
+    let text = |key| property(key).and_then(binary).map(String::from);
+    Some(MetadataRequirement {
+        name: name.map(String::from).or_else(|| text("name"))?,
+        app: text("app"),
+        requirement: text("requirement")?,
+        optional: property("optional").and_then(Spanned::atom) == Some("true"),
+        repository: text("repository"),
+    })
+}
+
+fn binary(term: &Spanned) -> Option<&str> {
+    match &term.value {
+        Term::Binary(value) => Some(value),
+        _ => None,
+    }
+}
+
+fn binaries(term: &Spanned) -> Option<Vec<String>> {
+    match &term.value {
+        Term::List(items) => items
+            .iter()
+            .map(|item| binary(item).map(String::from))
+            .collect(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARBALL: &[u8] = std::include_bytes!("../test/gleam_stdlib-0.14.0.tar");
+
+    #[test]
+    fn read_files() {
+        assert_eq!(read_file(TARBALL, "VERSION"), Ok(Some(&b"3"[..])));
+        assert_eq!(read_file(TARBALL, "missing"), Ok(None));
+        assert_eq!(
+            read_file(&TARBALL[..700], "contents.tar.gz"),
+            Err(TarballError::InvalidArchive)
+        );
+    }
+
+    #[test]
+    fn format_versions() {
+        assert_eq!(read_format_version(TARBALL), Ok(FormatVersion::V3));
+        assert_eq!(FormatVersion::parse(b"3\n"), FormatVersion::CURRENT);
+        let future = FormatVersion::parse(b"4");
+        assert_eq!(future, FormatVersion::Unknown("4".into()));
+        assert!(!future.is_supported());
+        assert_eq!(future.contents_file(), None);
+
+        let unpacked = unpack(TARBALL).unwrap();
+        assert_eq!(unpacked.format, FormatVersion::V3);
+        assert_eq!(unpacked.metadata.name, "gleam_stdlib");
+        assert_eq!(unpacked.checksum.len(), crate::checksum::CHECKSUM_LENGTH);
+        assert_eq!(
+            Some(unpacked.contents),
+            read_file(TARBALL, "contents.tar.gz").unwrap()
+        );
+
+        // Rewrite the VERSION file's contents, which are one byte long
+        let mut future = TARBALL.to_vec();
+        let version = read_file(TARBALL, "VERSION").unwrap().unwrap();
+        let offset = version.as_ptr() as usize - TARBALL.as_ptr() as usize;
+        future[offset] = b'4';
+        assert_eq!(
+            unpack(&future),
+            Err(TarballError::UnsupportedFormat("4".into()))
+        );
+    }
+
+    #[test]
+    fn read_tarball_metadata() {
+        let metadata = read_metadata(TARBALL).unwrap();
+        assert_eq!(metadata.name, "gleam_stdlib");
+        assert_eq!(metadata.version, Version::new(0, 14, 0));
+        assert_eq!(metadata.app.as_deref(), Some("gleam_stdlib"));
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("A standard library for the Gleam programming language")
+        );
+        assert_eq!(metadata.licenses, vec!["Apache 2.0".to_string()]);
+        assert_eq!(
+            metadata.links,
+            vec![(
+                "GitHub".to_string(),
+                "https://github.com/gleam-lang/stdlib".to_string()
+            )]
+        );
+        assert_eq!(
+            metadata.links().github_repository().as_deref(),
+            Some("gleam-lang/stdlib")
+        );
+        assert_eq!(metadata.build_tools, vec!["rebar3".to_string()]);
+        assert!(metadata.files.contains(&"src/gleam/list.gleam".to_string()));
+        assert!(metadata.requirements.is_empty());
+        assert_eq!(metadata.elixir, None);
+    }
+
+    #[test]
+    fn requirements() {
+        let metadata = parse_metadata(
+            r#"{<<"name">>,<<"plug">>}.
+{<<"version">>,<<"1.14.0">>}.
+{<<"requirements">>,
+ [{<<"mime">>,
+   [{<<"app">>,<<"mime">>},
+    {<<"optional">>,false},
+    {<<"requirement">>,<<"~> 1.0 or ~> 2.0">>},
+    {<<"repository">>,<<"hexpm">>}]},
+  {<<"telemetry">>,
+   [{<<"optional">>,true},{<<"requirement">>,<<"~> 1.0">>}]}]}.
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata.requirements,
+            vec![
+                MetadataRequirement {
+                    name: "mime".to_string(),
+                    app: Some("mime".to_string()),
+                    requirement: "~> 1.0 or ~> 2.0".to_string(),
+                    optional: false,
+                    repository: Some("hexpm".to_string()),
+                },
+                MetadataRequirement {
+                    name: "telemetry".to_string(),
+                    app: None,
+                    requirement: "~> 1.0".to_string(),
+                    optional: true,
+                    repository: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn old_style_requirements() {
+        let metadata = parse_metadata(
+            r#"{<<"name">>,<<"old">>}.
+{<<"version">>,<<"0.1.0">>}.
+{<<"requirements">>,[[{<<"name">>,<<"poison">>},{<<"optional">>,false},{<<"requirement">>,<<"~> 2.0">>}]]}.
+"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.requirements[0].name, "poison");
+        assert_eq!(metadata.requirements[0].requirement, "~> 2.0");
+    }
+
+    #[test]
+    fn invalid_metadata() {
+        assert_eq!(
+            parse_metadata(r#"{<<"version">>,<<"1.0.0">>}."#),
+            Err(TarballError::InvalidMetadataField("name"))
+        );
+        assert_eq!(
+            parse_metadata(r#"{<<"name">>,<<"x">>}.{<<"version">>,<<"1">>}."#),
+            Err(TarballError::InvalidMetadataField("version"))
+        );
+        assert!(matches!(
+            parse_metadata(r#"{<<"name">>,<<"x">>}"#),
+            Err(TarballError::MetadataSyntax { .. })
+        ));
+    }
+
+    #[test]
+    fn extra_metadata() {
+        let metadata = parse_metadata(
+            r#"{<<"name">>,<<"gleam_json">>}.
+{<<"version">>,<<"1.0.0">>}.
+{<<"description">>,<<"Work with JSON in Gleam.\nFor \"all\" targets. ✨"/utf8>>}.
+{<<"extra">>,
+ [{<<"gleam">>,
+   [{<<"target">>,[<<"erlang">>,<<"javascript">>]},{<<"version">>,1}]},
+  {<<"tagged">>,{ok,'Elixir.Thing'}}]}.
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("Work with JSON in Gleam.\nFor \"all\" targets. ✨")
+        );
+        let gleam = metadata.extra_field("gleam").unwrap();
+        let targets: Vec<_> = gleam
+            .get("target")
+            .and_then(MetadataValue::as_list)
+            .unwrap()
+            .iter()
+            .filter_map(MetadataValue::as_str)
+            .collect();
+        assert_eq!(targets, vec!["erlang", "javascript"]);
+        assert_eq!(gleam.get("version"), Some(&MetadataValue::Integer(1)));
+        assert_eq!(metadata.extra_field("missing"), None);
+
+        assert_eq!(parse_metadata(&metadata.to_config()), Ok(metadata));
+    }
+
+    #[test]
+    fn metadata_round_trip() {
+        let metadata = read_metadata(TARBALL).unwrap();
+        assert_eq!(parse_metadata(&metadata.to_config()), Ok(metadata));
+
+        let metadata = PackageMetadata {
+            name: "plug".to_string(),
+            version: Version::parse("1.14.0-rc.1").unwrap(),
+            app: None,
+            description: None,
+            licenses: vec![],
+            links: vec![],
+            build_tools: vec!["mix".to_string()],
+            requirements: vec![MetadataRequirement {
+                name: "mime".to_string(),
+                app: Some("mime".to_string()),
+                requirement: "~> 1.0 or ~> 2.0".to_string(),
+                optional: true,
+                repository: Some("hexpm".to_string()),
+            }],
+            files: vec!["lib/plug.ex".to_string()],
+            elixir: Some("~> 1.10".to_string()),
+            extra: vec![("note".to_string(), MetadataValue::Atom("it's".to_string()))],
+        };
+        let config = metadata.to_config();
+        assert!(config.starts_with("{<<\"name\">>,<<\"plug\">>}.\n"));
+        assert!(config.contains("{<<\"optional\">>,true}"));
+        assert_eq!(parse_metadata(&config), Ok(metadata));
+    }
+
+    #[test]
+    fn validate_metadata() {
+        let metadata = read_metadata(TARBALL).unwrap();
+        assert_eq!(metadata.validate(), Ok(()));
+
+        let requirement = |name: &str, requirement: &str| MetadataRequirement {
+            name: name.to_string(),
+            app: None,
+            requirement: requirement.to_string(),
+            optional: false,
+            repository: None,
+        };
+        let invalid = PackageMetadata {
+            name: "Plug".to_string(),
+            description: Some("x".repeat(301)),
+            licenses: vec![" ".to_string()],
+            links: vec![("Docs".to_string(), "ftp://example.com".to_string())],
+            requirements: vec![requirement("mime", "~> 1.0"), requirement("mime", "1.0 or")],
+            files: vec![],
+            ..metadata
+        };
+        let violations = invalid.validate().unwrap_err();
+        let fields: Vec<_> = violations
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "name: is not a valid package name",
+                "description: must be at most 300 characters long",
+                "licenses[0]: must not be empty",
+                "links.Docs: is not an http or https URL",
+                "requirements[1].name: is given more than once",
+                "requirements[1].requirement: is not a valid version requirement",
+                "files: is required",
+            ]
+        );
+        assert_eq!(
+            violations[0].kind,
+            ViolationKind::InvalidName(vec![NameViolation::InvalidStart('P')])
+        );
+    }
+
+    #[cfg(feature = "registry")]
+    fn tar(entries: &[(&[u8], u8, &[u8])]) -> Vec<u8> {
+        let mut tar = vec![];
+        for (path, kind, data) in entries {
+            let mut header = vec![0; 512];
+            header[..path.len()].copy_from_slice(path);
+            header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+            header[156] = *kind;
+            tar.extend(header);
+            tar.extend(*data);
+            tar.resize(tar.len().div_ceil(512) * 512, 0);
+        }
+        tar.extend([0; 1024]);
+        tar
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn lint() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let package = |licenses: &str, contents: &[(&[u8], u8, &[u8])]| {
+            let metadata = format!(
+                "{{<<\"name\">>,<<\"my_package\">>}}.\n\
+                 {{<<\"version\">>,<<\"1.0.0\">>}}.\n\
+                 {{<<\"licenses\">>,[{licenses}]}}.\n"
+            );
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&tar(contents)).unwrap();
+            let contents = encoder.finish().unwrap();
+            tar(&[
+                (b"metadata.config", b'0', metadata.as_bytes()),
+                (b"contents.tar.gz", b'0', &contents),
+            ])
+        };
+
+        let clean = package(
+            "<<\"MIT\">>",
+            &[
+                (b"LICENSE.md", b'0', b"MIT"),
+                (b"lib/my_package.ex", b'0', b"defmodule MyPackage do end"),
+            ],
+        );
+        assert_eq!(lint_tarball(&clean), Ok(vec![]));
+
+        let binary = [vec![0; 16], vec![1; LARGE_BINARY_SIZE as usize]].concat();
+        let messy = package(
+            "<<\"MIT\">>",
+            &[
+                (b"_build/", b'5', b""),
+                (b"_build/dev/lib/my_package.beam", b'0', b"FOR1"),
+                (b"_build/dev/lib/other.beam", b'0', b"FOR1"),
+                (b"ebin/my_package.beam", b'0', b"FOR1"),
+                (b"priv/vendored.so", b'0', &binary),
+                (
+                    b"priv/data.json",
+                    b'0',
+                    &vec![b' '; 2 * LARGE_BINARY_SIZE as usize],
+                ),
+                (b"lib/caf\xe9.ex", b'0', b""),
+            ],
+        );
+        assert_eq!(
+            lint_tarball(&messy),
+            Ok(vec![
+                LintFinding::MissingLicenseFile,
+                LintFinding::BuildArtifact("_build".into()),
+                LintFinding::BuildArtifact("ebin/my_package.beam".into()),
+                LintFinding::LargeBinary {
+                    path: "priv/vendored.so".into(),
+                    size: LARGE_BINARY_SIZE + 16,
+                },
+                LintFinding::NonUtf8Path("lib/caf\u{fffd}.ex".into()),
+            ])
+        );
+
+        let unlicensed = package("", &[(b"lib/my_package.ex", b'0', b"")]);
+        assert_eq!(lint_tarball(&unlicensed), Ok(vec![]));
+    }
+
+    #[test]
+    fn dependency_targets() {
+        let metadata = parse_metadata(
+            r#"{<<"name">>,<<"lustre">>}.
+{<<"version">>,<<"4.0.0">>}.
+{<<"extra">>,
+ [{<<"gleam">>,
+   [{<<"dependency_targets">>,
+     [{<<"gleam_erlang">>,[<<"erlang">>]},
+      {<<"gleam_javascript">>,[<<"javascript">>,<<"wasm">>]}]}]}]}.
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata.dependency_targets("gleam_erlang"),
+            Some(vec![Target::Erlang])
+        );
+        assert_eq!(
+            metadata.dependency_targets("gleam_javascript"),
+            Some(vec![Target::JavaScript])
+        );
+        assert_eq!(metadata.dependency_targets("gleam_stdlib"), None);
+        assert!(!metadata.needs_dependency_on("gleam_erlang", Target::JavaScript));
+        assert!(metadata.needs_dependency_on("gleam_stdlib", Target::JavaScript));
+    }
+}
@@ -0,0 +1,221 @@
+//! Signed registry resources for tests, so that mock repositories can serve
+//! any package or versions index without committing signed fixtures.
+//!
+//! This module is only built with the `test-keys` feature.
+//!
+//! ```
+//! use hexpm::test_keys::TestKeys;
+//!
+//! let keys = TestKeys::new();
+//! let body = keys.package_resource(&hexpm::Package {
+//!     name: "my_package".into(),
+//!     repository: "hexpm".into(),
+//!     releases: vec![],
+//! });
+//! let response = http::Response::builder().status(200).body(body).unwrap();
+//! let package = hexpm::get_package_response(response, keys.public_key()).unwrap();
+//! assert_eq!(package.name, "my_package");
+//! ```
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use flate2::{Compression, write::GzEncoder};
+use prost::Message;
+
+use crate::proto::{package, signed::Signed, versions};
+use crate::version::Version;
+use crate::{Package, RetirementReason};
+
+/// An RSA key pair for signing registry resources in tests.
+///
+/// Generating RSA keys needs a big number library that this crate does not
+/// otherwise depend on, so every `TestKeys` holds the same 2048 bit key pair.
+/// The private key is public knowledge and must never be trusted outside of
+/// tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestKeys {
+    private_key: &'static [u8],
+    public_key: &'static [u8],
+}
+
+impl Default for TestKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestKeys {
+    pub fn new() -> Self {
+        Self {
+            private_key: std::include_bytes!("../test/release_signing_private_key"),
+            public_key: std::include_bytes!("../test/release_signing_public_key"),
+        }
+    }
+
+    /// The PEM encoded PKCS#8 private key.
+    pub fn private_key(&self) -> &[u8] {
+        self.private_key
+    }
+
+    /// The PEM encoded public key, to verify resources signed with these keys.
+    pub fn public_key(&self) -> &[u8] {
+        self.public_key
+    }
+
+    /// Sign a registry payload, returning the gzipped signed resource as a
+    /// repository would serve it.
+    pub fn sign_resource(&self, payload: Vec<u8>) -> Vec<u8> {
+        let signature = crate::sign_release_tarball(&payload, self.private_key)
+            .expect("test key is a valid signing key");
+        let signed = Signed {
+            payload: payload.into(),
+            signature: Some(signature),
+        };
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&signed.encode_to_vec())
+            .expect("writing to a Vec");
+        encoder.finish().expect("writing to a Vec")
+    }
+
+    /// The signed versions index of a repository, as parsed by
+    /// [`get_repository_versions_response`](crate::get_repository_versions_response).
+    pub fn versions_resource(
+        &self,
+        repository: &str,
+        versions: &HashMap<String, Vec<Version>>,
+    ) -> Vec<u8> {
+        let mut packages: Vec<_> = versions
+            .iter()
+            .map(|(name, versions)| versions::VersionsPackage {
+                name: name.clone(),
+                versions: versions.iter().map(Version::to_string).collect(),
+                retired: vec![],
+            })
+            .collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        let payload = versions::Versions {
+            packages,
+            repository: repository.to_string(),
+        };
+        self.sign_resource(payload.encode_to_vec())
+    }
+
+    /// The signed registry record of a package, as parsed by
+    /// [`get_package_response`](crate::get_package_response).
+    pub fn package_resource(&self, package: &Package) -> Vec<u8> {
+        let releases = package
+            .releases
+            .iter()
+            .map(|release| package::Release {
+                version: release.version.to_string(),
+                inner_checksum: vec![],
+                dependencies: release
+                    .requirements
+                    .iter()
+                    .map(|(name, dependency)| package::Dependency {
+                        package: name.to_string(),
+                        requirement: dependency.requirement.as_str().to_string(),
+                        optional: dependency.optional.then_some(true),
+                        app: dependency.app.clone(),
+                        repository: dependency.repository.clone(),
+                    })
+                    .collect(),
+                retired: release.retirement_status.as_ref().map(|status| {
+                    package::RetirementStatus {
+                        reason: status
+                            .unknown_reason
+                            .as_deref()
+                            .and_then(|reason| reason.parse().ok())
+                            .unwrap_or_else(|| proto_retirement_reason(&status.reason).into()),
+                        message: Some(status.message.clone()),
+                    }
+                }),
+                outer_checksum: Some(release.outer_checksum.clone()),
+            })
+            .collect();
+        let payload = package::Package {
+            releases,
+            name: package.name.to_string(),
+            repository: package.repository.clone(),
+        };
+        self.sign_resource(payload.encode_to_vec())
+    }
+}
+
+fn proto_retirement_reason(reason: &RetirementReason) -> package::RetirementReason {
+    match reason {
+        RetirementReason::Other => package::RetirementReason::RetiredOther,
+        RetirementReason::Invalid => package::RetirementReason::RetiredInvalid,
+        RetirementReason::Security => package::RetirementReason::RetiredSecurity,
+        RetirementReason::Deprecated => package::RetirementReason::RetiredDeprecated,
+        RetirementReason::Renamed => package::RetirementReason::RetiredRenamed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dependency, Release, RetirementStatus, version::Range};
+
+    fn ok(body: Vec<u8>) -> http::Response<Vec<u8>> {
+        http::Response::builder().status(200).body(body).unwrap()
+    }
+
+    #[test]
+    fn versions_round_trip() {
+        let keys = TestKeys::new();
+        let versions = HashMap::from([
+            (
+                "gleam_stdlib".to_string(),
+                vec![Version::new(0, 1, 0), Version::new(0, 2, 0)],
+            ),
+            ("lustre".to_string(), vec![Version::new(1, 0, 0)]),
+        ]);
+        let body = keys.versions_resource("hexpm", &versions);
+        assert_eq!(
+            crate::get_repository_versions_response(ok(body), keys.public_key()).unwrap(),
+            versions
+        );
+    }
+
+    #[test]
+    fn package_round_trip() {
+        let keys = TestKeys::new();
+        let package = Package {
+            name: "lustre".into(),
+            repository: "hexpm".into(),
+            releases: vec![Release {
+                version: Version::new(1, 0, 0),
+                requirements: HashMap::from([(
+                    "gleam_stdlib".into(),
+                    Dependency {
+                        requirement: Range::new("~> 0.34".into()).unwrap(),
+                        optional: true,
+                        app: Some("gleam_stdlib".into()),
+                        repository: None,
+                    },
+                )]),
+                retirement_status: Some(RetirementStatus {
+                    reason: RetirementReason::Security,
+                    message: "CVE-0000".into(),
+                    unknown_reason: None,
+                }),
+                outer_checksum: vec![1; 32],
+                meta: (),
+            }],
+        };
+        let body = keys.package_resource(&package);
+        assert_eq!(
+            crate::get_package_response(ok(body.clone()), keys.public_key()).unwrap(),
+            package
+        );
+
+        let other_key = std::include_bytes!("../test/public_key");
+        assert!(matches!(
+            crate::get_package_response(ok(body), other_key),
+            Err(crate::ApiError::IncorrectPayloadSignature)
+        ));
+    }
+}
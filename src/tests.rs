@@ -7,6 +7,10 @@ use super::*;
 use mockito::Matcher;
 use serde_json::json;
 
+fn release_ref(package: &str, version: &str) -> ReleaseRef {
+    ReleaseRef::new(package, Version::parse(version).unwrap())
+}
+
 async fn http_send<Body: Into<reqwest::Body>>(
     request: http::Request<Body>,
 ) -> Result<http::Response<Vec<u8>>, reqwest::Error> {
@@ -184,14 +188,14 @@ async fn remove_docs_success() {
     let mut config = Config::new();
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
-    let result = crate::remove_docs_response(
-        http_send(crate::remove_docs_request(package, version, key, &config).unwrap())
-            .await
-            .unwrap(),
+    crate::remove_docs_response(
+        http_send(
+            crate::remove_docs_request(&release_ref(package, version), key, &config).unwrap(),
+        )
+        .await
+        .unwrap(),
     )
     .unwrap();
-
-    assert_eq!(result, ());
     mock.assert();
 }
 
@@ -217,14 +221,14 @@ async fn revert_release_success() {
     let mut config = Config::new();
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
-    let result = crate::revert_release_response(
-        http_send(crate::revert_release_request(package, version, key, &config).unwrap())
-            .await
-            .unwrap(),
+    crate::revert_release_response(
+        http_send(
+            crate::revert_release_request(&release_ref(package, version), key, &config).unwrap(),
+        )
+        .await
+        .unwrap(),
     )
     .unwrap();
-
-    assert_eq!(result, ());
     mock.assert();
 }
 
@@ -255,7 +259,7 @@ async fn add_owner_success() {
     let mut config = Config::new();
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
-    let result = crate::add_owner_response(
+    crate::add_owner_response(
         http_send(crate::add_owner_request(
             package, owner, level, key, &config,
         ))
@@ -263,8 +267,6 @@ async fn add_owner_success() {
         .unwrap(),
     )
     .unwrap();
-
-    assert_eq!(result, ());
     mock.assert();
 }
 
@@ -294,14 +296,12 @@ async fn transfer_owner_success() {
     let mut config = Config::new();
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
-    let result = crate::transfer_owner_response(
+    crate::transfer_owner_response(
         http_send(crate::transfer_owner_request(package, owner, key, &config))
             .await
             .unwrap(),
     )
     .unwrap();
-
-    assert_eq!(result, ());
     mock.assert();
 }
 
@@ -327,14 +327,12 @@ async fn remove_owner_success() {
     let mut config = Config::new();
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
-    let result = crate::remove_owner_response(
+    crate::remove_owner_response(
         http_send(crate::remove_owner_request(package, owner, key, &config))
             .await
             .unwrap(),
     )
     .unwrap();
-
-    assert_eq!(result, ());
     mock.assert();
 }
 
@@ -356,14 +354,12 @@ async fn remove_key_success() {
     let mut config = Config::new();
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
-    let result = crate::remove_api_key_response(
+    crate::remove_api_key_response(
         http_send(crate::remove_api_key_request(name, key, &config))
             .await
             .unwrap(),
     )
     .unwrap();
-
-    assert_eq!(result, ());
     mock.assert();
 }
 
@@ -385,14 +381,12 @@ async fn remove_key_success_2() {
     let mut config = Config::new();
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
-    let result = crate::remove_api_key_response(
+    crate::remove_api_key_response(
         http_send(crate::remove_api_key_request(name, key, &config))
             .await
             .unwrap(),
     )
     .unwrap();
-
-    assert_eq!(result, ());
     mock.assert();
 }
 
@@ -419,9 +413,11 @@ async fn remove_docs_unknown_package_version() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::remove_docs_response(
-        http_send(crate::remove_docs_request(package, version, key, &config).unwrap())
-            .await
-            .unwrap(),
+        http_send(
+            crate::remove_docs_request(&release_ref(package, version), key, &config).unwrap(),
+        )
+        .await
+        .unwrap(),
     )
     .unwrap_err();
 
@@ -456,9 +452,11 @@ async fn remove_docs_rate_limted() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::remove_docs_response(
-        http_send(crate::remove_docs_request(package, version, key, &config).unwrap())
-            .await
-            .unwrap(),
+        http_send(
+            crate::remove_docs_request(&release_ref(package, version), key, &config).unwrap(),
+        )
+        .await
+        .unwrap(),
     )
     .unwrap_err();
 
@@ -500,9 +498,11 @@ async fn remove_docs_invalid_key() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::remove_docs_response(
-        http_send(crate::remove_docs_request(package, version, key, &config).unwrap())
-            .await
-            .unwrap(),
+        http_send(
+            crate::remove_docs_request(&release_ref(package, version), key, &config).unwrap(),
+        )
+        .await
+        .unwrap(),
     )
     .unwrap_err();
 
@@ -544,9 +544,11 @@ async fn remove_docs_forbidden() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::remove_docs_response(
-        http_send(crate::remove_docs_request(package, version, key, &config).unwrap())
-            .await
-            .unwrap(),
+        http_send(
+            crate::remove_docs_request(&release_ref(package, version), key, &config).unwrap(),
+        )
+        .await
+        .unwrap(),
     )
     .unwrap_err();
 
@@ -566,7 +568,7 @@ async fn remove_docs_bad_package_name() {
 
     let config = Config::new();
 
-    match crate::remove_docs_request(package, version, key, &config).unwrap_err() {
+    match crate::remove_docs_request(&release_ref(package, version), key, &config).unwrap_err() {
         ApiError::InvalidPackageNameFormat(p) if p == package => (),
         result => panic!("expected Err(ApiError::BadPackage), got {:?}", result),
     }
@@ -596,9 +598,12 @@ async fn publish_docs_success() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::publish_docs_response(
-        http_send(crate::publish_docs_request(package, version, tarball, key, &config).unwrap())
-            .await
-            .unwrap(),
+        http_send(
+            crate::publish_docs_request(&release_ref(package, version), tarball, key, &config)
+                .unwrap(),
+        )
+        .await
+        .unwrap(),
     );
 
     match result {
@@ -618,25 +623,86 @@ async fn publish_docs_bad_package_name() {
 
     let config = Config::new();
 
-    match crate::publish_docs_request(package, version, tarball, key, &config).unwrap_err() {
+    match crate::publish_docs_request(&release_ref(package, version), tarball, key, &config)
+        .unwrap_err()
+    {
         ApiError::InvalidPackageNameFormat(p) if p == package => (),
         result => panic!("expected Err(ApiError::BadPackage), got {:?}", result),
     }
 }
 
-#[tokio::test]
-async fn publish_docs_bad_package_version() {
-    let key = "my-api-key-here";
+#[test]
+fn release_ref_bad_version() {
     let package = "name";
     let version = "invalid version";
-    let tarball = std::include_bytes!("../test/example.tar.gz").to_vec();
 
-    let config = Config::new();
-
-    match crate::publish_docs_request(package, version, tarball, key, &config).unwrap_err() {
+    match ReleaseRef::parse(package, version).unwrap_err() {
         ApiError::InvalidVersionFormat(v) if v == version => (),
         result => panic!("expected ApiError::BadPackage, got {:?}", result),
     }
+    assert_eq!(
+        ReleaseRef::parse("name", "1.1.0").unwrap(),
+        release_ref("name", "1.1.0")
+    );
+}
+
+#[test]
+fn release_ref_repository() {
+    let config = Config::new();
+    let release = release_ref("private_pkg", "1.0.0").in_repository("acme");
+    assert_eq!(release.to_string(), "acme/private_pkg 1.0.0");
+
+    let request = crate::get_package_tarball_request(&release, None, &config);
+    assert_eq!(
+        request.uri().to_string(),
+        "https://repo.hex.pm/repos/acme/tarballs/private_pkg-1.0.0.tar"
+    );
+    let request = crate::get_package_release_request(&release, None, &config);
+    assert_eq!(
+        request.uri().to_string(),
+        "https://hex.pm/api/repos/acme/packages/private_pkg/releases/1.0.0"
+    );
+
+    let release = release.in_repository("hexpm");
+    let request = crate::get_docs_tarball_request(&release, None, &config).unwrap();
+    assert_eq!(
+        request.uri().to_string(),
+        "https://repo.hex.pm/docs/private_pkg-1.0.0.tar.gz"
+    );
+}
+
+#[test]
+fn release_ref_invalid_repository() {
+    let config = Config::new();
+    for repository in ["acme/../other?x=1", "ac me", "", "Acme"] {
+        let result = release_ref("jason", "1.0.0").try_in_repository(repository);
+        match result {
+            Err(ApiError::InvalidRepositoryNameFormat(r)) if r == repository => (),
+            result => panic!(
+                "expected ApiError::InvalidRepositoryNameFormat, got {:?}",
+                result
+            ),
+        }
+    }
+
+    // Names that were not checked are kept within their path segment, and
+    // requests that validate the release reject them.
+    let release = release_ref("jason", "1.0.0").in_repository("acme/../other?x=1");
+    let request = crate::get_package_tarball_request(&release, None, &config);
+    assert_eq!(
+        request.uri().to_string(),
+        "https://repo.hex.pm/repos/acme%2F%2E%2E%2Fother%3Fx%3D1/tarballs/jason-1.0.0.tar"
+    );
+    let release = release_ref("jason", "1.0.0").in_repository("ac me");
+    let request = crate::get_package_release_request(&release, None, &config);
+    assert_eq!(
+        request.uri().path(),
+        "/api/repos/ac%20me/packages/jason/releases/1.0.0"
+    );
+    assert!(matches!(
+        crate::get_docs_tarball_request(&release, None, &config),
+        Err(ApiError::InvalidRepositoryNameFormat(_))
+    ));
 }
 
 #[tokio::test]
@@ -663,9 +729,12 @@ async fn publish_docs_not_found() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::publish_docs_response(
-        http_send(crate::publish_docs_request(package, version, tarball, key, &config).unwrap())
-            .await
-            .unwrap(),
+        http_send(
+            crate::publish_docs_request(&release_ref(package, version), tarball, key, &config)
+                .unwrap(),
+        )
+        .await
+        .unwrap(),
     );
 
     match result {
@@ -700,9 +769,12 @@ async fn publish_docs_rate_limit() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::publish_docs_response(
-        http_send(crate::publish_docs_request(package, version, tarball, key, &config).unwrap())
-            .await
-            .unwrap(),
+        http_send(
+            crate::publish_docs_request(&release_ref(package, version), tarball, key, &config)
+                .unwrap(),
+        )
+        .await
+        .unwrap(),
     );
 
     match result {
@@ -744,9 +816,12 @@ async fn publish_docs_invalid_api_key() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::publish_docs_response(
-        http_send(crate::publish_docs_request(package, version, tarball, key, &config).unwrap())
-            .await
-            .unwrap(),
+        http_send(
+            crate::publish_docs_request(&release_ref(package, version), tarball, key, &config)
+                .unwrap(),
+        )
+        .await
+        .unwrap(),
     );
 
     match result {
@@ -788,9 +863,12 @@ async fn publish_docs_forbidden() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::publish_docs_response(
-        http_send(crate::publish_docs_request(package, version, tarball, key, &config).unwrap())
-            .await
-            .unwrap(),
+        http_send(
+            crate::publish_docs_request(&release_ref(package, version), tarball, key, &config)
+                .unwrap(),
+        )
+        .await
+        .unwrap(),
     );
 
     match result {
@@ -801,6 +879,7 @@ async fn publish_docs_forbidden() {
     mock.assert();
 }
 
+#[cfg(feature = "registry")]
 #[tokio::test]
 async fn get_package_ok_test() {
     let response_body = std::include_bytes!("../test/package_exfmt");
@@ -829,7 +908,7 @@ async fn get_package_ok_test() {
 
     assert_eq!(
         Package {
-            name: "exfmt".to_string(),
+            name: "exfmt".into(),
             repository: "hexpm".to_string(),
             releases: vec![
                 Release {
@@ -941,6 +1020,7 @@ async fn get_package_ok_test() {
     mock.assert();
 }
 
+#[cfg(feature = "registry")]
 #[tokio::test]
 async fn get_package_not_found() {
     let config = Config::new();
@@ -959,6 +1039,7 @@ async fn get_package_not_found() {
     assert!(error.is_not_found());
 }
 
+#[cfg(feature = "registry")]
 #[tokio::test]
 async fn get_repository_versions_ok_test() {
     let response_body = std::include_bytes!("../test/versions");
@@ -1003,6 +1084,41 @@ async fn get_repository_versions_ok_test() {
     mock.assert();
 }
 
+#[cfg(feature = "registry")]
+#[tokio::test]
+async fn get_repository_versions_signed_by_test_keys() {
+    let keys = crate::test_keys::TestKeys::new();
+    let versions = HashMap::from([(
+        "lustre".to_string(),
+        vec![Version::new(4, 0, 0), Version::new(4, 1, 0)],
+    )]);
+
+    // Set up test server
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("GET", "/versions")
+        .expect(1)
+        .with_status(200)
+        .with_body(keys.versions_resource("hexpm", &versions))
+        .create_async()
+        .await;
+
+    // Test!
+    let mut config = Config::new();
+    config.repository_base = http::Uri::try_from(server.url()).unwrap();
+
+    let response = http_send(crate::get_repository_versions_request(None, &config))
+        .await
+        .unwrap();
+    assert_eq!(
+        crate::get_repository_versions_response(response, keys.public_key()).unwrap(),
+        versions
+    );
+
+    mock.assert();
+}
+
+#[cfg(feature = "registry")]
 #[tokio::test]
 async fn get_repository_tarball_ok_test() {
     let config = Config::new();
@@ -1011,8 +1127,7 @@ async fn get_repository_tarball_ok_test() {
 
     let downloaded = crate::get_package_tarball_response(
         http_send(crate::get_package_tarball_request(
-            "gleam_stdlib",
-            "0.14.0",
+            &release_ref("gleam_stdlib", "0.14.0"),
             None,
             &config,
         ))
@@ -1028,6 +1143,53 @@ async fn get_repository_tarball_ok_test() {
     );
 }
 
+#[cfg(feature = "registry")]
+#[test]
+fn cached_tarball() {
+    let config = Config::new();
+    let release = release_ref("gleam_stdlib", "0.14.0");
+    let tarball = std::include_bytes!("../test/gleam_stdlib-0.14.0.tar");
+    let checksum = checksum::Checksum::of(tarball);
+    let mut store = HashMap::new();
+
+    let request =
+        match crate::get_package_tarball_cached_request(&release, &checksum, None, &config, &store)
+        {
+            cache::TarballFetch::Download(request) => request,
+            fetch => panic!("expected a download, got {fetch:?}"),
+        };
+    assert_eq!(request.uri().path(), "/tarballs/gleam_stdlib-0.14.0.tar");
+
+    let response = http::Response::builder()
+        .status(200)
+        .body(tarball.to_vec())
+        .unwrap();
+    let downloaded =
+        crate::get_package_tarball_cached_response(response, &checksum, &mut store).unwrap();
+    assert_eq!(downloaded, tarball);
+    assert_eq!(store.get(&checksum), Some(&tarball.to_vec()));
+
+    match crate::get_package_tarball_cached_request(&release, &checksum, None, &config, &store) {
+        cache::TarballFetch::Cached(cached) => assert_eq!(cached, tarball),
+        fetch => panic!("expected the cached tarball, got {fetch:?}"),
+    }
+
+    // A corrupt entry is downloaded again, and a failed download stores nothing
+    let _ = store.insert(checksum, b"corrupt".to_vec());
+    assert!(matches!(
+        crate::get_package_tarball_cached_request(&release, &checksum, None, &config, &store),
+        cache::TarballFetch::Download(_)
+    ));
+    let mut empty = HashMap::new();
+    let response = http::Response::builder()
+        .status(200)
+        .body(b"corrupt".to_vec())
+        .unwrap();
+    assert!(crate::get_package_tarball_cached_response(response, &checksum, &mut empty).is_err());
+    assert!(empty.is_empty());
+}
+
+#[cfg(feature = "registry")]
 #[tokio::test]
 async fn get_repository_tarball_bad_checksum_test() {
     let config = Config::new();
@@ -1035,8 +1197,7 @@ async fn get_repository_tarball_bad_checksum_test() {
 
     let err = crate::get_package_tarball_response(
         http_send(crate::get_package_tarball_request(
-            "gleam_stdlib",
-            "0.14.0",
+            &release_ref("gleam_stdlib", "0.14.0"),
             None,
             &config,
         ))
@@ -1052,6 +1213,7 @@ async fn get_repository_tarball_bad_checksum_test() {
     );
 }
 
+#[cfg(feature = "registry")]
 #[tokio::test]
 async fn get_repository_tarball_not_found_test() {
     let config = Config::new();
@@ -1059,8 +1221,7 @@ async fn get_repository_tarball_not_found_test() {
 
     let err = crate::get_package_tarball_response(
         http_send(crate::get_package_tarball_request(
-            "gleam_stdlib",
-            "unknown-version",
+            &release_ref("gleam_stdlib", "99.99.99"),
             None,
             &config,
         ))
@@ -1092,9 +1253,14 @@ async fn publish_package_success() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::publish_package_response(
-        http_send(crate::publish_package_request(tarball, key, &config, false))
-            .await
-            .unwrap(),
+        http_send(crate::publish_package_request(
+            tarball,
+            key,
+            &config,
+            PublishMode::New,
+        ))
+        .await
+        .unwrap(),
     );
 
     match result {
@@ -1131,13 +1297,20 @@ async fn modify_package_late() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::publish_package_response(
-        http_send(crate::publish_package_request(tarball, key, &config, true))
-            .await
-            .unwrap(),
+        http_send(crate::publish_package_request(
+            tarball,
+            key,
+            &config,
+            PublishMode::Replace,
+        ))
+        .await
+        .unwrap(),
     );
 
     match result {
-        Err(ApiError::LateModification) => (),
+        Err(ApiError::LateModification { window }) => {
+            assert_eq!(window, Some(std::time::Duration::from_secs(60 * 60)))
+        }
         result => panic!("expected Err(ApiError::LateModification), got {:?}", result),
     }
 
@@ -1170,9 +1343,14 @@ async fn not_replacing() {
     config.api_base = http::Uri::try_from(server.url()).unwrap();
 
     let result = crate::publish_package_response(
-        http_send(crate::publish_package_request(tarball, key, &config, false))
-            .await
-            .unwrap(),
+        http_send(crate::publish_package_request(
+            tarball,
+            key,
+            &config,
+            PublishMode::New,
+        ))
+        .await
+        .unwrap(),
     );
 
     match result {
@@ -1188,8 +1366,7 @@ async fn get_package_release_not_found() {
     let config = Config::new();
     let error = crate::get_package_release_response(
         http_send(crate::get_package_release_request(
-            "louissaysthispackagedoesnotexist",
-            "1.0.1",
+            &release_ref("louissaysthispackagedoesnotexist", "1.0.1"),
             None,
             &config,
         ))
@@ -1206,7 +1383,9 @@ async fn get_package_release_ok() {
     let config = Config::new();
     let resp = crate::get_package_release_response(
         http_send(crate::get_package_release_request(
-            "clint", "0.0.1", None, &config,
+            &release_ref("clint", "0.0.1"),
+            None,
+            &config,
         ))
         .await
         .unwrap(),
@@ -1245,8 +1424,1624 @@ async fn get_package_release_ok() {
             ],
             meta: ReleaseMeta {
                 app: "clint".into(),
-                build_tools: vec!["mix".into()]
+                build_tools: vec!["mix".into()],
+                ..resp.meta.clone()
             }
         }
     )
 }
+
+fn release(version: &str) -> Release<()> {
+    Release {
+        version: Version::parse(version).unwrap(),
+        requirements: HashMap::new(),
+        retirement_status: None,
+        outer_checksum: vec![],
+        meta: (),
+    }
+}
+
+#[test]
+fn releases_in_channel() {
+    let package = Package {
+        name: "gleam_stdlib".into(),
+        repository: "hexpm".to_string(),
+        releases: vec![
+            release("1.0.0-rc1"),
+            release("1.0.0"),
+            release("1.1.0-beta"),
+        ],
+    };
+    let versions = |channel| {
+        package
+            .releases_in_channel(channel)
+            .map(|r| r.version.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(versions(Channel::Stable), vec!["1.0.0"]);
+    assert_eq!(versions(Channel::Pre), vec!["1.0.0-rc1", "1.1.0-beta"]);
+    assert_eq!(
+        versions(Channel::All),
+        vec!["1.0.0-rc1", "1.0.0", "1.1.0-beta"]
+    );
+}
+
+#[test]
+fn filter_releases() {
+    let mut retired = release("1.2.0");
+    retired.retirement_status = Some(RetirementStatus {
+        reason: RetirementReason::Security,
+        message: String::new(),
+        unknown_reason: None,
+    });
+    let package = Package {
+        name: "gleam_stdlib".into(),
+        repository: "hexpm".to_string(),
+        releases: vec![
+            release("0.9.0"),
+            release("1.0.0"),
+            release("1.1.0-rc1"),
+            retired,
+            release("2.0.0"),
+        ],
+    };
+    let versions = |filter: &ReleaseFilter| {
+        package
+            .filter_releases(filter)
+            .map(|r| r.version.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(versions(&ReleaseFilter::new()).len(), 5);
+    assert_eq!(
+        versions(&ReleaseFilter::new().exclude_retired()),
+        vec!["0.9.0", "1.0.0", "1.1.0-rc1", "2.0.0"]
+    );
+    assert_eq!(
+        versions(&ReleaseFilter::new().exclude_pre()),
+        vec!["0.9.0", "1.0.0", "1.2.0", "2.0.0"]
+    );
+    let range = Range::new("~> 1.0".into()).unwrap();
+    assert_eq!(
+        versions(&ReleaseFilter::new().in_range(range.clone())),
+        vec!["1.0.0", "1.1.0-rc1", "1.2.0"]
+    );
+    assert_eq!(
+        versions(
+            &ReleaseFilter::new()
+                .exclude_retired()
+                .exclude_pre()
+                .in_range(range)
+        ),
+        vec!["1.0.0"]
+    );
+}
+
+#[test]
+fn version_stats() {
+    let mut retired = release("1.2.0");
+    retired.retirement_status = Some(RetirementStatus {
+        reason: RetirementReason::Invalid,
+        message: String::new(),
+        unknown_reason: None,
+    });
+    let package = Package {
+        name: "lustre".into(),
+        repository: "hexpm".to_string(),
+        releases: vec![
+            release("1.0.0"),
+            retired,
+            release("2.0.0-rc1"),
+            release("1.1.0"),
+            release("3.0.0-rc1"),
+            release("2.0.0"),
+        ],
+    };
+    let stats = package.version_stats();
+    assert_eq!(
+        (stats.releases, stats.pre_releases, stats.retired),
+        (6, 2, 1)
+    );
+    assert_eq!(
+        stats.series,
+        vec![
+            SeriesStats {
+                major: 3,
+                releases: 1,
+                pre_releases: 1,
+                retired: 0,
+                latest: Version::parse("3.0.0-rc1").unwrap(),
+                latest_stable: None,
+            },
+            SeriesStats {
+                major: 2,
+                releases: 2,
+                pre_releases: 1,
+                retired: 0,
+                latest: Version::new(2, 0, 0),
+                latest_stable: Some(Version::new(2, 0, 0)),
+            },
+            SeriesStats {
+                major: 1,
+                releases: 3,
+                pre_releases: 0,
+                retired: 1,
+                latest: Version::new(1, 2, 0),
+                latest_stable: Some(Version::new(1, 2, 0)),
+            },
+        ]
+    );
+    assert_eq!(
+        Package {
+            releases: vec![],
+            ..package
+        }
+        .version_stats(),
+        VersionStats::default()
+    );
+}
+
+fn dependency(requirement: &str, optional: bool) -> Dependency {
+    Dependency {
+        requirement: Range::new(requirement.into()).unwrap(),
+        optional,
+        app: None,
+        repository: None,
+    }
+}
+
+#[test]
+fn requirements_diff() {
+    let with_requirements = |version, requirements: &[(&str, &str, bool)]| Release {
+        requirements: requirements
+            .iter()
+            .map(|(name, requirement, optional)| {
+                ((*name).into(), dependency(requirement, *optional))
+            })
+            .collect(),
+        ..release(version)
+    };
+    let package = Package {
+        name: "wisp".into(),
+        repository: "hexpm".to_string(),
+        releases: vec![
+            with_requirements(
+                "1.0.0",
+                &[
+                    ("gleam_stdlib", "~> 0.34", false),
+                    ("mist", ">= 1.0.0 and < 2.0.0", false),
+                    ("simplifile", "~> 1.0", true),
+                    ("exception", "~> 1.0", false),
+                ],
+            ),
+            with_requirements(
+                "2.0.0",
+                &[
+                    ("gleam_stdlib", "~> 0.40", false),
+                    ("mist", "~> 1.0", false),
+                    ("simplifile", "~> 1.0", false),
+                    ("logging", "~> 1.0", false),
+                    ("telemetry", "~> 1.0", true),
+                ],
+            ),
+        ],
+    };
+    let v1 = Version::new(1, 0, 0);
+    let v2 = Version::new(2, 0, 0);
+
+    let diff = package.requirements_diff(&v1, &v2).unwrap();
+    assert_eq!(
+        diff.added,
+        vec![
+            ("logging".into(), dependency("~> 1.0", false)),
+            ("telemetry".into(), dependency("~> 1.0", true)),
+        ]
+    );
+    assert_eq!(
+        diff.removed,
+        vec![("exception".into(), dependency("~> 1.0", false))]
+    );
+    // mist's requirement is written differently but matches the same versions.
+    assert_eq!(
+        diff.changed,
+        vec![
+            ChangedRequirement {
+                name: "gleam_stdlib".into(),
+                from: dependency("~> 0.34", false),
+                to: dependency("~> 0.40", false),
+            },
+            ChangedRequirement {
+                name: "simplifile".into(),
+                from: dependency("~> 1.0", true),
+                to: dependency("~> 1.0", false),
+            },
+        ]
+    );
+    assert_eq!(
+        diff.newly_required()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["logging", "simplifile"]
+    );
+
+    assert!(package.requirements_diff(&v1, &v1).unwrap().is_empty());
+    assert_eq!(package.requirements_diff(&v1, &Version::new(3, 0, 0)), None);
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn stream_responses() {
+    let config = Config::new();
+    let body = std::include_bytes!("../test/versions");
+    let public_key = std::include_bytes!("../test/public_key");
+    // Hands out the body a few bytes at a time, as a socket would.
+    struct Chunked(std::io::Cursor<Vec<u8>>);
+    impl std::io::Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(7);
+            self.0.read(&mut buf[..len])
+        }
+    }
+    let response = |status: u16, body: &[u8]| {
+        http::Response::builder()
+            .status(status)
+            .body(Chunked(std::io::Cursor::new(body.to_vec())))
+            .unwrap()
+    };
+
+    let reader =
+        crate::get_repository_versions_stream_response(response(200, body), public_key, &config)
+            .unwrap();
+    assert_eq!(reader.repository(), "hexpm");
+    assert!(reader.versions("exfmt").is_some());
+
+    assert!(matches!(
+        crate::get_repository_versions_stream_response(
+            response(200, &body[..body.len() / 2]),
+            public_key,
+            &config
+        ),
+        Err(ApiError::TruncatedResponse { .. })
+    ));
+    assert!(matches!(
+        crate::get_repository_versions_stream_response(response(500, b"oops"), public_key, &config),
+        Err(ApiError::UnexpectedResponse(StatusCode::INTERNAL_SERVER_ERROR, body)) if body == "oops"
+    ));
+
+    let keys = crate::test_keys::TestKeys::new();
+    let package = Package {
+        name: "lustre".into(),
+        repository: "hexpm".into(),
+        releases: vec![],
+    };
+    let body = keys.package_resource(&package);
+    let streamed =
+        crate::get_package_stream_response(response(200, &body), keys.public_key(), &config)
+            .unwrap();
+    assert_eq!(streamed.name, "lustre");
+    assert!(matches!(
+        crate::get_package_stream_response(response(404, b""), keys.public_key(), &config),
+        Err(ApiError::NotFound)
+    ));
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn get_repository_versions_reader() {
+    let response = http::Response::builder()
+        .status(200)
+        .body(std::include_bytes!("../test/versions").to_vec())
+        .unwrap();
+    let reader = crate::get_repository_versions_reader_response(
+        response,
+        std::include_bytes!("../test/public_key"),
+    )
+    .unwrap();
+
+    assert_eq!(reader.repository(), "hexpm");
+    assert!(!reader.is_empty());
+    assert_eq!(reader.iter().count(), reader.len());
+    assert_eq!(
+        reader.raw_versions("exfmt").unwrap().first().unwrap(),
+        "0.0.0"
+    );
+    assert_eq!(
+        reader.versions("exfmt").unwrap().unwrap().last(),
+        Some(&Version::parse("0.5.0").unwrap())
+    );
+    assert!(
+        reader
+            .versions("louissaysthispackagedoesnotexist")
+            .is_none()
+    );
+
+    let map = reader.clone().into_map().unwrap();
+    assert_eq!(map.len(), reader.len());
+    let all = reader.clone().parse_all();
+    assert!(all.is_complete());
+    assert_eq!(all.len(), reader.len());
+}
+
+#[test]
+fn package_name() {
+    let name = PackageName::from("gleam_stdlib");
+    assert_eq!(name, "gleam_stdlib");
+    assert_eq!(name.to_string(), "gleam_stdlib");
+    assert_eq!(format!("{:?}", name), "\"gleam_stdlib\"");
+
+    let map: HashMap<PackageName, u32> = [(name.clone(), 1)].into();
+    assert_eq!(map.get("gleam_stdlib"), Some(&1));
+
+    let json = serde_json::to_string(&name).unwrap();
+    assert_eq!(serde_json::from_str::<PackageName>(&json).unwrap(), name);
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn name_interner_shares_allocations() {
+    let mut names = NameInterner::default();
+    let a = names.intern("gleam_stdlib".to_string());
+    let b = names.intern("gleam_stdlib".to_string());
+    assert!(std::sync::Arc::ptr_eq(&a.0, &b.0));
+}
+
+#[test]
+fn host_header() {
+    let mut config = Config::new();
+    let request = crate::get_package_request("gleam_stdlib", None, &config);
+    assert!(request.headers().get("host").is_none());
+
+    config.host_header = true;
+    let request = crate::get_package_request("gleam_stdlib", None, &config);
+    assert_eq!(request.headers().get("host").unwrap(), "repo.hex.pm");
+    let request = crate::remove_api_key_request("key", "secret", &config);
+    assert_eq!(request.headers().get("host").unwrap(), "hex.pm");
+}
+
+#[test]
+fn wire_headers_title_case() {
+    let mut config = Config::new();
+    config.host_header = true;
+    let request = crate::remove_api_key_request("key", "secret", &config);
+    let headers = crate::wire_headers(request.headers(), HeaderCase::Title);
+    let names = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![
+            "Host",
+            "User-Agent",
+            "Authorization",
+            "Content-Type",
+            "Accept"
+        ]
+    );
+    assert_eq!(headers[0].1, b"hex.pm");
+
+    let headers = crate::wire_headers(request.headers(), HeaderCase::Lower);
+    assert_eq!(headers[0].0, "host");
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn sign_and_verify_release_tarball() {
+    let tarball = std::include_bytes!("../test/example.tar");
+    let private_key = std::include_bytes!("../test/release_signing_private_key");
+    let public_key = std::include_bytes!("../test/release_signing_public_key");
+
+    let signature = crate::sign_release_tarball(tarball, private_key).unwrap();
+    crate::verify_release_signature(tarball, &signature, public_key).unwrap();
+
+    let mut tampered = tarball.to_vec();
+    tampered[0] ^= 1;
+    assert!(matches!(
+        crate::verify_release_signature(&tampered, &signature, public_key),
+        Err(ApiError::IncorrectPayloadSignature)
+    ));
+
+    let other_key = std::include_bytes!("../test/public_key");
+    assert!(matches!(
+        crate::verify_release_signature(tarball, &signature, other_key),
+        Err(ApiError::IncorrectPayloadSignature)
+    ));
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn sign_release_tarball_invalid_key() {
+    let public_key = std::include_bytes!("../test/release_signing_public_key");
+    assert!(matches!(
+        crate::sign_release_tarball(b"tarball", public_key),
+        Err(ApiError::InvalidSigningKey)
+    ));
+}
+
+#[test]
+fn api_error_code() {
+    assert_eq!(ApiError::RateLimited.code(), "rate_limited");
+    assert_eq!(ApiError::IncorrectChecksum.code(), "checksum_mismatch");
+    assert_eq!(
+        ApiError::UnexpectedResponse(StatusCode::IM_A_TEAPOT, "".into()).code(),
+        "unexpected_response"
+    );
+}
+
+#[test]
+fn api_error_serialize() {
+    assert_eq!(
+        serde_json::to_value(ApiError::NotFound).unwrap(),
+        json!({"code": "not_found", "message": "resource was not found"})
+    );
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn inflate_limit() {
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&[0; 1024]).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    assert_eq!(
+        crate::inflate(compressed.as_slice(), 1024).unwrap().len(),
+        1024
+    );
+    assert!(matches!(
+        crate::inflate(compressed.as_slice(), 1023),
+        Err(ApiError::ResponseTooLarge(1023))
+    ));
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn download_size_limit() {
+    let mut config = Config::new();
+    config.max_download_size = 1024;
+    let response = |status: u16, body: Box<dyn std::io::Read>| {
+        http::Response::builder().status(status).body(body).unwrap()
+    };
+
+    let tarball = [7; 1024];
+    let checksum = ring::digest::digest(&ring::digest::SHA256, &tarball);
+    let downloaded = crate::get_package_tarball_reader_response(
+        response(200, Box::new(&tarball[..])),
+        checksum.as_ref(),
+        &config,
+    )
+    .unwrap();
+    assert_eq!(downloaded, tarball);
+
+    // A server sending an endless body is cut off.
+    let endless = || Box::new(std::io::repeat(7));
+    assert!(matches!(
+        crate::get_package_tarball_reader_response(
+            response(200, endless()),
+            checksum.as_ref(),
+            &config
+        ),
+        Err(ApiError::ResponseTooLarge(1024))
+    ));
+    assert!(matches!(
+        crate::get_package_tarball_reader_response(
+            response(500, endless()),
+            checksum.as_ref(),
+            &config
+        ),
+        Err(ApiError::ResponseTooLarge(1024))
+    ));
+    assert!(matches!(
+        crate::get_docs_tarball_reader_response(response(200, endless()), &config),
+        Err(ApiError::ResponseTooLarge(1024))
+    ));
+    assert!(
+        crate::get_docs_tarball_reader_response(response(404, Box::new(&b""[..])), &config)
+            .unwrap_err()
+            .is_not_found()
+    );
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn registry_resources_reject_garbage() {
+    let public_key = std::include_bytes!("../test/public_key");
+    let ok = |body: &[u8]| {
+        http::Response::builder()
+            .status(200)
+            .body(body.to_vec())
+            .unwrap()
+    };
+    for body in [
+        &b""[..],
+        b"garbage",
+        &std::include_bytes!("../test/versions")[..100],
+    ] {
+        assert!(crate::get_repository_versions_response(ok(body), public_key).is_err());
+        assert!(crate::get_package_response(ok(body), public_key).is_err());
+    }
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn registry_resources_report_truncation() {
+    let public_key = std::include_bytes!("../test/public_key");
+    let versions = std::include_bytes!("../test/versions");
+    let ok = |body: &[u8]| {
+        http::Response::builder()
+            .status(200)
+            .body(body.to_vec())
+            .unwrap()
+    };
+    for body in [&b""[..], &versions[..100], &versions[..versions.len() - 1]] {
+        assert!(matches!(
+            crate::get_repository_versions_response(ok(body), public_key),
+            Err(ApiError::TruncatedResponse {
+                expected_hint: "gzip compressed data"
+            })
+        ));
+    }
+
+    use flate2::{Compression, write::GzEncoder};
+    let empty = GzEncoder::new(Vec::new(), Compression::default())
+        .finish()
+        .unwrap();
+    let error = crate::get_package_response(ok(&empty), public_key).unwrap_err();
+    assert_eq!(error.code(), "truncated_response");
+    assert_eq!(
+        error.to_string(),
+        "the response ended early while reading a signed registry resource, \
+         it may have been cut off by the network or a proxy"
+    );
+}
+
+#[test]
+fn release_meta_round_trip() {
+    let json = json!({
+        "app": "plug",
+        "build_tools": ["mix"],
+        "elixir": "~> 1.10",
+        "otp_app": {"env": []},
+    });
+    let meta: ReleaseMeta = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(meta.app, "plug");
+    assert_eq!(meta.build_tools, vec!["mix".to_string()]);
+    assert_eq!(meta.elixir.as_deref(), Some("~> 1.10"));
+    assert_eq!(meta.extra.get("otp_app"), Some(&json!({"env": []})));
+    assert_eq!(serde_json::to_value(&meta).unwrap(), json);
+}
+
+#[test]
+fn release_meta_without_optional_fields() {
+    let json = json!({"app": "gleam_stdlib", "build_tools": ["gleam"]});
+    let meta: ReleaseMeta = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(meta.elixir, None);
+    assert!(meta.extra.is_empty());
+    assert_eq!(serde_json::to_value(&meta).unwrap(), json);
+}
+
+#[test]
+fn unknown_retirement_reason_json() {
+    let json = json!({"reason": "compromised", "message": "Key leaked"});
+    let status: RetirementStatus = serde_json::from_value(json).unwrap();
+    assert_eq!(
+        status,
+        RetirementStatus {
+            reason: RetirementReason::Other,
+            message: "Key leaked".into(),
+            unknown_reason: Some("compromised".into()),
+        }
+    );
+    let round_trip = serde_json::from_value(serde_json::to_value(&status).unwrap()).unwrap();
+    assert_eq!(status, round_trip);
+
+    let json = json!({"reason": "security", "message": ""});
+    let status: RetirementStatus = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(status.reason, RetirementReason::Security);
+    assert_eq!(status.unknown_reason, None);
+    assert_eq!(serde_json::to_value(&status).unwrap(), json);
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn unknown_retirement_reason_protobuf() {
+    let keys = crate::test_keys::TestKeys::new();
+    let mut release = release("1.0.0");
+    release.retirement_status = Some(RetirementStatus {
+        reason: RetirementReason::Other,
+        message: "Key leaked".into(),
+        unknown_reason: Some("9".into()),
+    });
+    let package = Package {
+        name: "lustre".into(),
+        repository: "hexpm".into(),
+        releases: vec![release],
+    };
+    let response = http::Response::builder()
+        .status(200)
+        .body(keys.package_resource(&package))
+        .unwrap();
+    assert_eq!(
+        crate::get_package_response(response, keys.public_key()).unwrap(),
+        package
+    );
+}
+
+#[test]
+fn create_api_key_name_taken() {
+    let body = json!({
+        "status": 422,
+        "message": "Validation error(s)",
+        "errors": {"name": "has already been taken"},
+    });
+    let response = http::Response::builder()
+        .status(422)
+        .body(body.to_string().into_bytes())
+        .unwrap();
+    assert!(matches!(
+        crate::create_api_key_response(response),
+        Err(ApiError::KeyNameTaken)
+    ));
+
+    let body = json!({"status": 422, "errors": {"permissions": "is invalid"}});
+    let response = http::Response::builder()
+        .status(422)
+        .body(body.to_string().into_bytes())
+        .unwrap();
+    assert!(matches!(
+        crate::create_api_key_response(response),
+        Err(ApiError::UnexpectedResponse(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            _
+        ))
+    ));
+}
+
+#[test]
+fn next_api_key_name() {
+    assert_eq!(crate::next_api_key_name("ci"), "ci-2");
+    assert_eq!(crate::next_api_key_name("ci-2"), "ci-3");
+    assert_eq!(crate::next_api_key_name("ci-99"), "ci-100");
+    assert_eq!(crate::next_api_key_name("my-laptop"), "my-laptop-2");
+    assert_eq!(crate::next_api_key_name("build-01"), "build-01-2");
+    assert_eq!(crate::next_api_key_name("ci-4294967295"), "ci-4294967295-2");
+}
+
+fn rollback_responses(statuses: &[u16]) -> (Vec<http::Request<Vec<u8>>>, crate::RollbackOutcome) {
+    let config = Config::new();
+    let mut rollback = crate::rollback_release(release_ref("gleam_stdlib", "0.5.0")).unwrap();
+    let mut requests = vec![];
+    for &status in statuses {
+        let request = rollback.next_request("my-api-key", &config).unwrap();
+        requests.push(request);
+        let response = http::Response::builder()
+            .status(status)
+            .body(vec![])
+            .unwrap();
+        if let Some(outcome) = rollback.handle_response(response) {
+            assert!(rollback.next_request("my-api-key", &config).is_none());
+            return (requests, outcome);
+        }
+    }
+    panic!("rollback did not finish")
+}
+
+#[test]
+fn rollback_release_complete() {
+    let (requests, outcome) = rollback_responses(&[200, 204, 204]);
+    let paths: Vec<_> = requests
+        .iter()
+        .map(|r| (r.method().clone(), r.uri().path().to_string()))
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            (
+                Method::HEAD,
+                "/api/packages/gleam_stdlib/releases/0.5.0".to_string()
+            ),
+            (
+                Method::DELETE,
+                "/api/packages/gleam_stdlib/releases/0.5.0/docs".to_string()
+            ),
+            (
+                Method::DELETE,
+                "/api/packages/gleam_stdlib/releases/0.5.0".to_string()
+            ),
+        ]
+    );
+    assert!(matches!(
+        outcome,
+        crate::RollbackOutcome::Complete { docs_removed: true }
+    ));
+}
+
+#[test]
+fn rollback_release_without_docs() {
+    let (_, outcome) = rollback_responses(&[200, 404, 204]);
+    assert!(matches!(
+        outcome,
+        crate::RollbackOutcome::Complete {
+            docs_removed: false
+        }
+    ));
+}
+
+#[test]
+fn rollback_release_docs_failed() {
+    let (requests, outcome) = rollback_responses(&[200, 403]);
+    assert_eq!(requests.len(), 2);
+    assert!(matches!(
+        outcome,
+        crate::RollbackOutcome::Failed(ApiError::Forbidden)
+    ));
+}
+
+#[test]
+fn rollback_release_not_found() {
+    let (requests, outcome) = rollback_responses(&[404]);
+    assert_eq!(requests.len(), 1);
+    assert!(matches!(
+        outcome,
+        crate::RollbackOutcome::Failed(ApiError::NotFound)
+    ));
+}
+
+#[test]
+fn rollback_release_partial() {
+    let (_, outcome) = rollback_responses(&[200, 204, 429]);
+    assert!(matches!(
+        outcome,
+        crate::RollbackOutcome::Partial {
+            docs_removed: true,
+            error: ApiError::RateLimited
+        }
+    ));
+}
+
+#[test]
+fn late_modification_unknown_window() {
+    let response = http::Response::builder()
+        .status(422)
+        .body(b"{\"errors\": {\"inserted_at\": \"too late\"}}".to_vec())
+        .unwrap();
+    let error = crate::publish_package_response(response).unwrap_err();
+    assert!(matches!(error, ApiError::LateModification { window: None }));
+    assert_eq!(
+        error.to_string(),
+        "can only modify a release for a limited time after publication"
+    );
+}
+
+#[test]
+fn late_modification_windows() {
+    let window = |message: &str| {
+        let response = http::Response::builder()
+            .status(422)
+            .body(message.as_bytes().to_vec())
+            .unwrap();
+        match crate::publish_package_response(response) {
+            Err(ApiError::LateModification { window }) => window,
+            result => panic!("expected Err(ApiError::LateModification), got {:?}", result),
+        }
+    };
+    assert_eq!(
+        window("can only modify a release up to twenty-four hours after publication"),
+        Some(std::time::Duration::from_secs(24 * 60 * 60))
+    );
+    assert_eq!(
+        window("can only modify a release up to 30 minutes after publication"),
+        Some(std::time::Duration::from_secs(30 * 60))
+    );
+
+    let describe = |secs| {
+        ApiError::LateModification {
+            window: Some(std::time::Duration::from_secs(secs)),
+        }
+        .to_string()
+    };
+    assert_eq!(
+        describe(7200),
+        "can only modify a release up to 2 hours after publication"
+    );
+    assert_eq!(
+        describe(90 * 60),
+        "can only modify a release up to 90 minutes after publication"
+    );
+    assert_eq!(
+        describe(30),
+        "can only modify a release up to 30 seconds after publication"
+    );
+    assert_eq!(
+        describe(90),
+        "can only modify a release up to 90 seconds after publication"
+    );
+}
+
+#[test]
+fn publish_modes() {
+    let config = Config::new();
+    let query = |mode| {
+        crate::publish_package_request(vec![], "key", &config, mode)
+            .uri()
+            .query()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(query(PublishMode::New), "replace=false");
+    assert_eq!(query(PublishMode::Replace), "replace=true");
+    assert_eq!(query(PublishMode::ForceReplaceWithinWindow), "replace=true");
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn repository_redirects() {
+    let response = http::Response::builder()
+        .status(301)
+        .header("location", "https://repo.example.com/versions")
+        .body(vec![])
+        .unwrap();
+    match crate::get_repository_versions_response(
+        response,
+        std::include_bytes!("../test/public_key"),
+    ) {
+        Err(ApiError::Redirect { status, location }) => {
+            assert_eq!(status, StatusCode::MOVED_PERMANENTLY);
+            assert_eq!(location, "https://repo.example.com/versions");
+        }
+        result => panic!("expected Err(ApiError::Redirect), got {:?}", result),
+    }
+
+    let response = http::Response::builder()
+        .status(302)
+        .header("location", "/tarballs/gleam_stdlib-0.14.0.tar")
+        .body(vec![])
+        .unwrap();
+    assert!(matches!(
+        crate::get_package_tarball_response(response, &[]),
+        Err(ApiError::Redirect { location, .. }) if location == "/tarballs/gleam_stdlib-0.14.0.tar"
+    ));
+
+    // Without a location there is nowhere to retry.
+    let response = http::Response::builder().status(302).body(vec![]).unwrap();
+    assert!(matches!(
+        crate::get_package_response(response, std::include_bytes!("../test/public_key")),
+        Err(ApiError::UnexpectedResponse(StatusCode::FOUND, _))
+    ));
+}
+
+#[test]
+fn configured_headers() {
+    let mut config = Config::new();
+    config
+        .headers
+        .insert("x-request-id", "abc123".parse().unwrap());
+    config.headers.insert("accept", "*/*".parse().unwrap());
+
+    let request = crate::get_package_request("gleam_stdlib", None, &config);
+    assert_eq!(request.headers()["x-request-id"], "abc123");
+    let accept: Vec<_> = request.headers().get_all("accept").iter().collect();
+    assert_eq!(accept, vec!["*/*"]);
+
+    let request = crate::publish_package_request(vec![], "key", &config, PublishMode::New);
+    assert_eq!(request.headers()["x-request-id"], "abc123");
+    let content_type: Vec<_> = request.headers().get_all("content-type").iter().collect();
+    assert_eq!(content_type, vec!["application/x-tar"]);
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn service_unavailable() {
+    let response = http::Response::builder()
+        .status(503)
+        .header("retry-after", "120")
+        .body(br#"{"status": 503, "message": "Hex is down for maintenance"}"#.to_vec())
+        .unwrap();
+    let error = crate::get_package_tarball_response(response, &[]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Hex is temporarily unavailable: Hex is down for maintenance"
+    );
+    assert_eq!(error.code(), "service_unavailable");
+    match error {
+        ApiError::ServiceUnavailable {
+            retry_after: Some(retry_after),
+            ..
+        } => assert_eq!(
+            retry_after.delay(std::time::SystemTime::UNIX_EPOCH),
+            std::time::Duration::from_secs(120)
+        ),
+        error => panic!("unexpected error {error:?}"),
+    }
+
+    let response = http::Response::builder()
+        .status(503)
+        .header("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")
+        .body(b"<html>Service Unavailable</html>".to_vec())
+        .unwrap();
+    let now = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1445412420);
+    match crate::remove_owner_response(response) {
+        Err(ApiError::ServiceUnavailable {
+            retry_after: Some(retry_after),
+            message: None,
+        }) => assert_eq!(retry_after.delay(now), std::time::Duration::from_secs(60)),
+        result => panic!("unexpected result {result:?}"),
+    }
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn package_tarball_provenance() {
+    let tarball = std::include_bytes!("../test/gleam_stdlib-0.14.0.tar").to_vec();
+    let checksum = ring::digest::digest(&ring::digest::SHA256, &tarball);
+    let config = Config::new();
+    let request =
+        crate::get_package_tarball_request(&release_ref("gleam_stdlib", "0.14.0"), None, &config);
+    let fetched_at = std::time::SystemTime::UNIX_EPOCH;
+    let response = http::Response::builder()
+        .status(200)
+        .header("etag", "\"abc123\"")
+        .body(tarball.clone())
+        .unwrap();
+
+    let (downloaded, provenance) = crate::get_package_tarball_response_with_provenance(
+        response,
+        checksum.as_ref(),
+        request.uri(),
+        &config,
+        fetched_at,
+    )
+    .unwrap();
+    assert_eq!(downloaded, tarball);
+    assert_eq!(
+        provenance,
+        crate::Provenance {
+            repository: "https://repo.hex.pm/".to_string(),
+            url: "https://repo.hex.pm/tarballs/gleam_stdlib-0.14.0.tar".to_string(),
+            etag: Some("\"abc123\"".to_string()),
+            checksum: Some(checksum.as_ref().to_vec()),
+            signing_key_fingerprint: None,
+            fetched_at,
+        }
+    );
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn package_provenance() {
+    let config = Config::new();
+    let request = crate::get_package_request("exfmt", None, &config);
+    let public_key = std::include_bytes!("../test/public_key");
+    let response = http::Response::builder()
+        .status(200)
+        .body(std::include_bytes!("../test/package_exfmt").to_vec())
+        .unwrap();
+
+    let (package, provenance) = crate::get_package_response_with_provenance(
+        response,
+        public_key,
+        request.uri(),
+        &config,
+        std::time::SystemTime::UNIX_EPOCH,
+    )
+    .unwrap();
+    assert_eq!(package.name, "exfmt");
+    assert_eq!(provenance.etag, None);
+    assert_eq!(provenance.checksum, None);
+    assert_eq!(
+        provenance.signing_key_fingerprint,
+        Some(crate::public_key_fingerprint(public_key).unwrap())
+    );
+    assert!(
+        provenance
+            .signing_key_fingerprint
+            .unwrap()
+            .starts_with("SHA256:")
+    );
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn conditional_tarball_fetch() {
+    let config = Config::new();
+    let tarball = std::include_bytes!("../test/gleam_stdlib-0.14.0.tar").to_vec();
+    let checksum = ring::digest::digest(&ring::digest::SHA256, &tarball);
+
+    let response = http::Response::builder()
+        .status(200)
+        .header("etag", "W/\"abc\"")
+        .header("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+        .body(tarball.clone())
+        .unwrap();
+    let validators =
+        match crate::get_package_tarball_conditional_response(response, checksum.as_ref()) {
+            Ok(crate::Conditional::Modified { value, validators }) => {
+                assert_eq!(value, tarball);
+                validators
+            }
+            result => panic!("expected Ok(Conditional::Modified), got {:?}", result),
+        };
+
+    let mut request =
+        crate::get_package_tarball_request(&release_ref("gleam_stdlib", "0.14.0"), None, &config);
+    validators.apply_to(&mut request);
+    assert_eq!(request.headers()["if-none-match"], "W/\"abc\"");
+    assert_eq!(
+        request.headers()["if-modified-since"],
+        "Wed, 21 Oct 2015 07:28:00 GMT"
+    );
+
+    let response = http::Response::builder().status(304).body(vec![]).unwrap();
+    assert_eq!(
+        crate::get_package_tarball_conditional_response(response, checksum.as_ref()).unwrap(),
+        crate::Conditional::NotModified
+    );
+}
+
+#[test]
+fn conditional_docs_fetch() {
+    let config = Config::new();
+    let request =
+        crate::get_docs_tarball_request(&release_ref("gleam_stdlib", "0.14.0"), None, &config)
+            .unwrap();
+    assert_eq!(
+        request.uri().to_string(),
+        "https://repo.hex.pm/docs/gleam_stdlib-0.14.0.tar.gz"
+    );
+    assert!(request.headers().get("if-none-match").is_none());
+
+    let response = http::Response::builder()
+        .status(200)
+        .header("etag", "\"docs\"")
+        .body(b"docs".to_vec())
+        .unwrap();
+    assert_eq!(
+        crate::get_docs_tarball_conditional_response(response).unwrap(),
+        crate::Conditional::Modified {
+            value: b"docs".to_vec(),
+            validators: crate::CacheValidators {
+                etag: Some("\"docs\"".to_string()),
+                last_modified: None,
+            },
+        }
+    );
+
+    let response = http::Response::builder().status(304).body(vec![]).unwrap();
+    assert_eq!(
+        crate::get_docs_tarball_conditional_response(response).unwrap(),
+        crate::Conditional::NotModified
+    );
+
+    let response = http::Response::builder().status(404).body(vec![]).unwrap();
+    assert!(matches!(
+        crate::get_docs_tarball_response(response),
+        Err(ApiError::NotFound)
+    ));
+}
+
+#[test]
+fn release_size() {
+    let config = Config::new();
+    let request =
+        crate::release_size_request(&release_ref("gleam_stdlib", "0.14.0"), None, &config);
+    assert_eq!(request.method(), Method::HEAD);
+    assert_eq!(
+        request.uri().to_string(),
+        "https://repo.hex.pm/tarballs/gleam_stdlib-0.14.0.tar"
+    );
+
+    let response = http::Response::builder()
+        .status(200)
+        .header("content-length", "51200")
+        .body(vec![])
+        .unwrap();
+    assert_eq!(crate::release_size_response(response).unwrap(), Some(51200));
+
+    let response = http::Response::builder().status(200).body(vec![]).unwrap();
+    assert_eq!(crate::release_size_response(response).unwrap(), None);
+
+    let response = http::Response::builder().status(404).body(vec![]).unwrap();
+    assert!(matches!(
+        crate::release_size_response(response),
+        Err(ApiError::NotFound)
+    ));
+}
+
+#[test]
+fn ping_repository() {
+    let request = crate::ping_repository_request(None, &Config::new());
+    assert_eq!(request.method(), Method::HEAD);
+    assert_eq!(request.uri().to_string(), "https://repo.hex.pm/versions");
+
+    let response = http::Response::builder()
+        .status(200)
+        .header("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+        .header("age", "120")
+        .body(vec![])
+        .unwrap();
+    let health = crate::ping_repository_response(response).unwrap();
+    let last_modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1445412480);
+    let RepositoryHealth::Available(freshness) = health else {
+        panic!("{health:?}");
+    };
+    assert_eq!(freshness.last_modified, Some(last_modified));
+    assert_eq!(freshness.age, Some(Duration::from_secs(120)));
+    assert_eq!(
+        freshness.staleness(last_modified + Duration::from_secs(60)),
+        Some(Duration::from_secs(60))
+    );
+
+    let response = http::Response::builder()
+        .status(200)
+        .header("last-modified", "yesterday")
+        .body(vec![])
+        .unwrap();
+    assert_eq!(
+        crate::ping_repository_response(response).unwrap(),
+        RepositoryHealth::Available(Freshness {
+            last_modified: None,
+            age: None
+        })
+    );
+
+    let response = http::Response::builder().status(503).body(vec![]).unwrap();
+    assert_eq!(
+        crate::ping_repository_response(response).unwrap(),
+        RepositoryHealth::Unavailable(StatusCode::SERVICE_UNAVAILABLE)
+    );
+}
+
+#[test]
+fn http_dates() {
+    let date = |s| {
+        crate::parse_http_date(s)
+            .map(|t| t.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs())
+    };
+    assert_eq!(date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+    assert_eq!(date("Tue, 29 Feb 2000 12:00:00 GMT"), Some(951825600));
+    assert_eq!(date("Fri, 31 Dec 2xxx 23:59:59 GMT"), None);
+    assert_eq!(date("Fri, 31 Dec 1999 23:59:59 UTC"), None);
+    assert_eq!(date("Fri, 31 Dec 1999 23:59:59 GMT"), Some(946684799));
+}
+
+#[test]
+fn organization_keys() {
+    let config = Config::new();
+    let permissions = [KeyPermission::repository("acme")];
+    let request =
+        crate::create_organization_key_request("acme", "ci", &permissions, "my-key", &config);
+    assert_eq!(request.method(), Method::POST);
+    assert_eq!(request.uri(), "https://hex.pm/api/orgs/acme/keys");
+    assert_eq!(request.headers()["authorization"], "my-key");
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(request.body()).unwrap(),
+        json!({
+            "name": "ci",
+            "permissions": [{"domain": "repository", "resource": "acme"}],
+        })
+    );
+
+    let response = http::Response::builder()
+        .status(201)
+        .body(
+            json!({
+                "name": "ci",
+                "secret": "0123abcd",
+                "permissions": [{"domain": "repository", "resource": "acme"}],
+                "inserted_at": "2024-01-01T00:00:00Z",
+            })
+            .to_string()
+            .into_bytes(),
+        )
+        .unwrap();
+    assert_eq!(
+        crate::create_organization_key_response(response).unwrap(),
+        OrganizationKey {
+            name: "ci".into(),
+            secret: "0123abcd".into(),
+            permissions: permissions.to_vec(),
+        }
+    );
+
+    let response = http::Response::builder()
+        .status(422)
+        .body(br#"{"errors": {"name": "has already been taken"}}"#.to_vec())
+        .unwrap();
+    assert!(matches!(
+        crate::create_organization_key_response(response),
+        Err(ApiError::KeyNameTaken)
+    ));
+
+    let request = crate::remove_organization_key_request("acme", "ci", "my-key", &config);
+    assert_eq!(request.method(), Method::DELETE);
+    assert_eq!(request.uri(), "https://hex.pm/api/orgs/acme/keys/ci");
+    let response = http::Response::builder().status(204).body(vec![]).unwrap();
+    crate::remove_organization_key_response(response).unwrap();
+    let response = http::Response::builder().status(403).body(vec![]).unwrap();
+    assert!(matches!(
+        crate::remove_organization_key_response(response),
+        Err(ApiError::Forbidden)
+    ));
+}
+
+#[test]
+fn request_bodies_are_byte_stable() {
+    let config = Config::new();
+    let body = |request: http::Request<Vec<u8>>| String::from_utf8(request.into_body()).unwrap();
+    assert_eq!(
+        body(crate::create_api_key_request(
+            "louis", "pass", "laptop", &config
+        )),
+        r#"{"name":"laptop","permissions":[{"domain":"api","resource":"write"}]}"#
+    );
+    assert_eq!(
+        body(crate::create_organization_key_request(
+            "acme",
+            "ci",
+            &[KeyPermission::repository("acme")],
+            "my-key",
+            &config
+        )),
+        r#"{"name":"ci","permissions":[{"domain":"repository","resource":"acme"}]}"#
+    );
+    let release = ReleaseRef::parse("lustre", "1.0.0").unwrap();
+    assert_eq!(
+        body(crate::retire_release_request(
+            &release,
+            RetirementReason::Security,
+            None,
+            "my-key",
+            &config
+        )),
+        r#"{"message":null,"reason":"security"}"#
+    );
+    assert_eq!(
+        body(crate::add_owner_request(
+            "lustre",
+            "louis",
+            OwnerLevel::Maintainer,
+            "my-key",
+            &config
+        )),
+        r#"{"level":"maintainer","transfer":false}"#
+    );
+    assert_eq!(
+        body(crate::transfer_owner_request(
+            "lustre", "louis", "my-key", &config
+        )),
+        r#"{"level":"full","transfer":true}"#
+    );
+}
+
+#[test]
+fn packages_query() {
+    let config = Config::new();
+    assert_eq!(
+        crate::get_packages_page_request(3, None, &config).uri(),
+        "https://hex.pm/api/packages?sort=name&page=3"
+    );
+    assert_eq!(
+        crate::get_packages_request(&Query::new(), None, &config).uri(),
+        "https://hex.pm/api/packages"
+    );
+    let query = Query::new().search("a&page=99?b").per_page(10);
+    assert_eq!(
+        crate::get_packages_request(&query, Some("my-key"), &config).uri(),
+        "https://hex.pm/api/packages?search=a%26page%3D99%3Fb&per_page=10"
+    );
+}
+
+#[test]
+fn release_exists() {
+    let release = ReleaseRef::parse("lustre", "4.0.0").unwrap();
+    let request = crate::release_exists_request(&release, None, &Config::new());
+    assert_eq!(request.method(), Method::HEAD);
+    assert_eq!(
+        request.uri(),
+        "https://hex.pm/api/packages/lustre/releases/4.0.0"
+    );
+
+    let response = |status| {
+        http::Response::builder()
+            .status(status)
+            .body(vec![])
+            .unwrap()
+    };
+    assert!(crate::release_exists_response(response(200)).unwrap());
+    assert!(!crate::release_exists_response(response(404)).unwrap());
+    assert!(matches!(
+        crate::release_exists_response(response(429)),
+        Err(ApiError::RateLimited)
+    ));
+}
+
+/// Encode JSON as Erlang's external term format, as the Hex API does for the
+/// `application/vnd.hex+erlang` content type.
+fn term_to_binary(value: &serde_json::Value) -> Vec<u8> {
+    fn encode(value: &serde_json::Value, out: &mut Vec<u8>) {
+        let binary = |string: &str, out: &mut Vec<u8>| {
+            out.push(109);
+            out.extend_from_slice(&(string.len() as u32).to_be_bytes());
+            out.extend_from_slice(string.as_bytes());
+        };
+        let atom = |atom: &str, out: &mut Vec<u8>| {
+            out.extend_from_slice(&[119, atom.len() as u8]);
+            out.extend_from_slice(atom.as_bytes());
+        };
+        match value {
+            serde_json::Value::Null => atom("nil", out),
+            serde_json::Value::Bool(bool) => atom(&bool.to_string(), out),
+            serde_json::Value::Number(n) => {
+                out.push(98);
+                out.extend_from_slice(&(n.as_i64().unwrap() as i32).to_be_bytes());
+            }
+            serde_json::Value::String(string) => binary(string, out),
+            serde_json::Value::Array(items) if items.is_empty() => out.push(106),
+            serde_json::Value::Array(items) => {
+                out.push(108);
+                out.extend_from_slice(&(items.len() as u32).to_be_bytes());
+                items.iter().for_each(|item| encode(item, out));
+                out.push(106);
+            }
+            serde_json::Value::Object(map) => {
+                out.push(116);
+                out.extend_from_slice(&(map.len() as u32).to_be_bytes());
+                for (key, value) in map {
+                    binary(key, out);
+                    encode(value, out);
+                }
+            }
+        }
+    }
+    let mut out = vec![131];
+    encode(value, &mut out);
+    out
+}
+
+#[test]
+fn erlang_api_format() {
+    let config = Config {
+        api_format: ApiFormat::Erlang,
+        ..Config::new()
+    };
+    let release = release_ref("clint", "0.0.1");
+    let request = crate::get_package_release_request(&release, None, &config);
+    assert_eq!(request.headers()["accept"], "application/vnd.hex+erlang");
+    let request = crate::get_packages_page_request(1, None, &Config::new());
+    assert_eq!(request.headers()["accept"], "application/json");
+
+    let json = json!({
+        "version": "0.0.1",
+        "checksum": "41c6781b5f4b986bce14c3578d39c497bcb8427f1d36d8cde5fcaa6e03cae2b1",
+        "retirement": null,
+        "requirements": {
+            "plug": {"requirement": "~> 0.11.0", "optional": false, "app": "plug"},
+        },
+        "meta": {"app": "clint", "build_tools": ["mix"]},
+    });
+    let response = |content_type: &str, body: Vec<u8>| {
+        http::Response::builder()
+            .status(200)
+            .header("content-type", content_type)
+            .body(body)
+            .unwrap()
+    };
+    let from_erlang = crate::get_package_release_response(response(
+        "application/vnd.hex+erlang; charset=utf-8",
+        term_to_binary(&json),
+    ))
+    .unwrap();
+    let from_json = crate::get_package_release_response(response(
+        "application/json",
+        json.to_string().into_bytes(),
+    ))
+    .unwrap();
+    assert_eq!(from_erlang, from_json);
+    assert_eq!(from_erlang.meta.app, "clint");
+    assert_eq!(from_erlang.outer_checksum[..2], [65, 198]);
+
+    let error = crate::get_package_release_response(response(
+        "application/vnd.hex+erlang",
+        json.to_string().into_bytes(),
+    ))
+    .unwrap_err();
+    assert_eq!(error.code(), "invalid_erlang_term");
+}
+
+#[test]
+fn bare_string_requirements() {
+    let body = json!({
+        "version": "1.0.0",
+        "checksum": "0000000000000000000000000000000000000000000000000000000000000000",
+        "requirements": {
+            "plug": "~> 1.14",
+            "jason": {"requirement": "~> 1.4", "optional": true, "app": "json"},
+        },
+        "retirement_status": null,
+        "meta": {"app": "my_app", "build_tools": ["mix"]},
+    });
+    let response = http::Response::builder()
+        .status(200)
+        .header("content-type", "application/json")
+        .body(serde_json::to_vec(&body).unwrap())
+        .unwrap();
+    let release = crate::get_package_release_response(response).unwrap();
+    assert_eq!(
+        release.requirements["plug"],
+        Dependency {
+            requirement: Range::new("~> 1.14".into()).unwrap(),
+            optional: false,
+            app: None,
+            repository: None,
+        }
+    );
+    assert!(release.requirements["jason"].optional);
+    assert_eq!(release.requirements["jason"].app.as_deref(), Some("json"));
+}
+
+#[test]
+fn suggested_timeouts() {
+    let timeout = |request: &http::Request<Vec<u8>>| {
+        let suggested = request.extensions().get::<SuggestedTimeout>().unwrap();
+        (suggested.kind, suggested.timeout.as_secs())
+    };
+    let mut config = Config::new();
+    let release = release_ref("clint", "0.0.1");
+    assert_eq!(
+        timeout(&crate::get_package_request("clint", None, &config)),
+        (RequestKind::Metadata, 30)
+    );
+    assert_eq!(
+        timeout(&crate::release_exists_request(&release, None, &config)),
+        (RequestKind::Check, 10)
+    );
+    assert_eq!(
+        timeout(&crate::get_package_tarball_request(&release, None, &config)),
+        (RequestKind::Download, 300)
+    );
+    let publish = crate::publish_docs_request(&release, vec![], "key", &config).unwrap();
+    assert_eq!(timeout(&publish), (RequestKind::Upload, 600));
+
+    config
+        .timeouts
+        .insert(RequestKind::Check, Duration::from_secs(2));
+    assert_eq!(
+        timeout(&crate::ping_repository_request(None, &config)),
+        (RequestKind::Check, 2)
+    );
+}
+
+#[test]
+fn bearer_auth_style() {
+    let mut config = Config::new();
+    config.auth_style = crate::AuthStyle::Bearer;
+    let request = crate::get_package_request("gleam_stdlib", Some("key"), &config);
+    assert_eq!(request.headers()["authorization"], "Bearer key");
+    config.auth_style = crate::AuthStyle::ApiKey;
+    let request = crate::get_package_request("gleam_stdlib", Some("key"), &config);
+    assert_eq!(request.headers()["authorization"], "key");
+}
+
+#[test]
+fn config_from_env() {
+    let vars = HashMap::from([
+        ("HEX_API_URL", "https://hex.acme.dev/api"),
+        ("HEX_REPO_URL", "https://repo.acme.dev/"),
+        ("HEX_API_KEY", "secret"),
+        ("HEX_UNSAFE_REGISTRY", "1"),
+        ("HEX_NO_VERIFY_REPO_ORIGIN", "false"),
+    ]);
+    let (config, report) =
+        Config::from_vars(|name| vars.get(name).map(|value| value.to_string())).unwrap();
+    assert_eq!(config.api_base, "https://hex.acme.dev/api/");
+    assert_eq!(config.repository_base, "https://repo.acme.dev/");
+    let profile = config.profile();
+    assert_eq!(profile.api_base, "https://hex.acme.dev/api/");
+    assert_eq!(profile.repository_base, "https://repo.acme.dev/");
+    assert_eq!(profile.public_key_fingerprint, None);
+    assert_eq!(
+        report.used,
+        vec![
+            "HEX_API_URL",
+            "HEX_REPO_URL",
+            "HEX_API_KEY",
+            "HEX_UNSAFE_REGISTRY",
+            "HEX_NO_VERIFY_REPO_ORIGIN"
+        ]
+    );
+    assert_eq!(report.api_key.as_deref(), Some("secret"));
+    assert!(report.unsafe_registry);
+    assert!(!report.no_verify_repo_origin);
+    assert!(!format!("{report:?}").contains("secret"));
+
+    let (config, report) = Config::from_vars(|_| None).unwrap();
+    assert_eq!(config.api_base, "https://hex.pm/api/");
+    assert_eq!(
+        config.profile().public_key_fingerprint.as_deref(),
+        Some(crate::hexpm_defaults::PUBLIC_KEY_FINGERPRINT)
+    );
+    assert_eq!(report, crate::EnvReport::default());
+
+    assert_eq!(
+        Config::from_vars(|name| (name == "HEX_REPO_URL").then(|| "repo.hex.pm".to_string()))
+            .unwrap_err(),
+        crate::EnvError::InvalidUrl {
+            variable: "HEX_REPO_URL",
+            value: "repo.hex.pm".to_string()
+        }
+    );
+}
+
+#[test]
+fn signed_repository_urls() {
+    let mut config = Config::new();
+    config.host_header = true;
+    config.url_signer = Some(crate::UrlSigner::new(|uri: &http::Uri| {
+        format!("https://cdn.example.com{}?sig=abc", uri.path())
+            .parse()
+            .unwrap()
+    }));
+    let release = release_ref("clint", "0.0.1");
+    let requests = [
+        crate::get_repository_versions_request(None, &config),
+        crate::get_package_request("clint", None, &config),
+        crate::get_package_tarball_request(&release, None, &config),
+        crate::get_docs_tarball_request(&release, None, &config).unwrap(),
+    ];
+    for request in &requests {
+        assert_eq!(request.uri().host(), Some("cdn.example.com"));
+        assert_eq!(request.uri().query(), Some("sig=abc"));
+        assert_eq!(request.headers()["host"], "cdn.example.com");
+    }
+    assert_eq!(requests[2].uri().path(), "/tarballs/clint-0.0.1.tar");
+
+    let api = crate::get_current_user_request("key", &config);
+    assert_eq!(api.uri().host(), Some("hex.pm"));
+    assert_eq!(api.uri().query(), None);
+}
+
+#[test]
+fn package_name_rules() {
+    assert_eq!(PackageName::validate("gleam_stdlib"), Ok(()));
+    // Hex allows uppercase letters after the first character.
+    assert_eq!(PackageName::validate("phoenixHTML2"), Ok(()));
+
+    let error = PackageName::validate("1st-pkg!").unwrap_err();
+    assert_eq!(
+        error.violations,
+        vec![
+            NameViolation::InvalidStart('1'),
+            NameViolation::InvalidCharacter {
+                position: 3,
+                character: '-'
+            },
+            NameViolation::InvalidCharacter {
+                position: 7,
+                character: '!'
+            },
+        ]
+    );
+    assert_eq!(
+        error.to_string(),
+        "\"1st-pkg!\" is not a valid package name as it starts with '1' rather than a \
+         lowercase letter, and it contains '-' at position 3, and it contains '!' at position 7"
+    );
+    assert_eq!(
+        PackageName::validate("").unwrap_err().violations,
+        vec![NameViolation::Empty]
+    );
+    let long = "a".repeat(PackageName::MAX_LENGTH + 1);
+    assert_eq!(
+        PackageName::validate(&long).unwrap_err().violations,
+        vec![NameViolation::TooLong(256)]
+    );
+
+    assert_eq!(
+        PackageName::normalize(" Phoenix-Live.View ")
+            .unwrap()
+            .as_str(),
+        "phoenix_live_view"
+    );
+    assert!(PackageName::normalize("école").is_err());
+}
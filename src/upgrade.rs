@@ -0,0 +1,179 @@
+//! Assessing the risk of upgrading a dependency from one release to another,
+//! for tools that show upgrade guidance.
+
+use std::fmt;
+
+use crate::version::{Version, VersionChange};
+use crate::{Package, PackageName, RequirementsDiff};
+
+/// What upgrading a package from one release to another involves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeRisk {
+    pub from: Version,
+    pub to: Version,
+    pub change: VersionChange,
+    pub requirements: RequirementsDiff,
+    /// Retired releases after `from`, up to and including `to`, in version
+    /// order.
+    pub retired: Vec<Version>,
+}
+
+/// A reason to take care when upgrading, see [`UpgradeRisk::warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeWarning {
+    /// The upgrade may contain breaking changes, going by semantic versioning.
+    Breaking(VersionChange),
+    /// The newer release requires packages that the older one did not.
+    NewDependencies(Vec<PackageName>),
+    /// The newer release has been retired.
+    RetiredTarget,
+    /// Releases between the two have been retired, which may point to
+    /// problems in the changes made since.
+    RetiredIntermediate(Vec<Version>),
+    /// The newer release is a pre-release.
+    PreReleaseTarget,
+}
+
+impl UpgradeRisk {
+    /// The reasons to take care when making this upgrade, most serious first.
+    /// An upgrade with no warnings is low risk.
+    pub fn warnings(&self) -> Vec<UpgradeWarning> {
+        let mut warnings = vec![];
+        if self.change.may_break(&self.from) {
+            warnings.push(UpgradeWarning::Breaking(self.change));
+        }
+        if self.retired.last() == Some(&self.to) {
+            warnings.push(UpgradeWarning::RetiredTarget);
+        }
+        let intermediate: Vec<Version> = self
+            .retired
+            .iter()
+            .filter(|version| *version != &self.to)
+            .cloned()
+            .collect();
+        if !intermediate.is_empty() {
+            warnings.push(UpgradeWarning::RetiredIntermediate(intermediate));
+        }
+        let new: Vec<PackageName> = self.requirements.newly_required().cloned().collect();
+        if !new.is_empty() {
+            warnings.push(UpgradeWarning::NewDependencies(new));
+        }
+        if self.to.is_pre() {
+            warnings.push(UpgradeWarning::PreReleaseTarget);
+        }
+        warnings
+    }
+}
+
+impl fmt::Display for UpgradeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpgradeWarning::Breaking(VersionChange::Downgrade) => {
+                write!(f, "this is a downgrade")
+            }
+            UpgradeWarning::Breaking(_) => write!(f, "this upgrade may contain breaking changes"),
+            UpgradeWarning::NewDependencies(names) => {
+                write!(f, "this upgrade adds dependencies on {}", join(names))
+            }
+            UpgradeWarning::RetiredTarget => write!(f, "the new version has been retired"),
+            UpgradeWarning::RetiredIntermediate(versions) => {
+                write!(
+                    f,
+                    "versions retired since the current one: {}",
+                    join(versions)
+                )
+            }
+            UpgradeWarning::PreReleaseTarget => write!(f, "the new version is a pre-release"),
+        }
+    }
+}
+
+fn join(items: &[impl fmt::Display]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Assess upgrading a package from one release to another. Returns `None` if
+/// either version is not in the registry.
+pub fn upgrade_risk(package: &Package, from: &Version, to: &Version) -> Option<UpgradeRisk> {
+    let requirements = package.requirements_diff(from, to)?;
+    let mut retired: Vec<Version> = package
+        .releases
+        .iter()
+        .filter(|release| release.is_retired() && &release.version > from && &release.version <= to)
+        .map(|release| release.version.clone())
+        .collect();
+    retired.sort();
+    Some(UpgradeRisk {
+        from: from.clone(),
+        to: to.clone(),
+        change: from.change_to(to),
+        requirements,
+        retired,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetirementReason;
+    use crate::fixtures::{self, release};
+
+    fn package() -> Package {
+        let stdlib = |version| release(version).requires("gleam_stdlib", "~> 1.0");
+        fixtures::package(
+            "lustre",
+            [
+                stdlib("0.9.0"),
+                stdlib("0.9.1").retired(RetirementReason::Security, ""),
+                stdlib("0.9.2"),
+                stdlib("0.10.0").requires("gleam_json", "~> 1.0"),
+                stdlib("1.0.0-rc1")
+                    .requires("gleam_json", "~> 1.0")
+                    .retired(RetirementReason::Security, ""),
+            ],
+        )
+    }
+
+    fn v(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn patch_upgrade() {
+        let risk = upgrade_risk(&package(), &v("0.9.0"), &v("0.9.2")).unwrap();
+        assert_eq!(risk.change, VersionChange::Patch);
+        assert_eq!(
+            risk.warnings(),
+            vec![UpgradeWarning::RetiredIntermediate(vec![v("0.9.1")])]
+        );
+        assert_eq!(
+            risk.warnings()[0].to_string(),
+            "versions retired since the current one: 0.9.1"
+        );
+    }
+
+    #[test]
+    fn risky_upgrade() {
+        let risk = upgrade_risk(&package(), &v("0.9.2"), &v("1.0.0-rc1")).unwrap();
+        assert_eq!(
+            risk.warnings(),
+            vec![
+                UpgradeWarning::Breaking(VersionChange::Major),
+                UpgradeWarning::RetiredTarget,
+                UpgradeWarning::NewDependencies(vec!["gleam_json".into()]),
+                UpgradeWarning::PreReleaseTarget,
+            ]
+        );
+    }
+
+    #[test]
+    fn low_risk_upgrade() {
+        let risk = upgrade_risk(&package(), &v("0.10.0"), &v("0.10.0")).unwrap();
+        assert!(risk.warnings().is_empty());
+        assert_eq!(upgrade_risk(&package(), &v("0.9.0"), &v("2.0.0")), None);
+    }
+}
@@ -12,9 +12,15 @@ use serde::{
 
 mod lexer;
 mod parser;
+mod requirement;
 #[cfg(test)]
 mod tests;
 
+pub use requirement::{
+    Coverage, PessimisticForm, RequirementError, Tightening, UnlockReason, parse_requirements,
+    parse_requirements_bulk, requirements_table, tighten_requirements, unlocks_for_update,
+};
+
 /// In a nutshell, a version is represented by three numbers:
 ///
 /// MAJOR.MINOR.PATCH
@@ -98,6 +104,16 @@ impl Version {
         Ok(version)
     }
 
+    /// Parse a version that may be written with a leading `v` or `V`, such as
+    /// `v1.2.3` from a git tag or user input, returning whether the prefix
+    /// was present. [`parse`](Self::parse) rejects the prefix, as Hex does.
+    pub fn parse_prefixed(input: &str) -> Result<(Self, bool), parser::Error> {
+        match input.strip_prefix(['v', 'V']) {
+            Some(rest) => Ok((Self::parse(rest)?, true)),
+            None => Ok((Self::parse(input)?, false)),
+        }
+    }
+
     /// Parse a Hex compatible version range. i.e. `> 1 and < 2 or == 4.5.2`.
     fn parse_range(input: &str) -> Result<pubgrub::Range<Version>, parser::Error> {
         let mut parser = Parser::new(input)?;
@@ -131,6 +147,120 @@ impl Version {
     pub fn is_pre(&self) -> bool {
         !self.pre.is_empty()
     }
+
+    /// The release channel this version is published on. Versions with a
+    /// pre-release segment are on the `Pre` channel, all others are `Stable`.
+    pub fn channel(&self) -> Channel {
+        if self.is_pre() {
+            Channel::Pre
+        } else {
+            Channel::Stable
+        }
+    }
+
+    /// The stage of a pre-release version, derived from its first pre-release
+    /// identifier. Returns `None` for stable versions.
+    ///
+    /// "1.0.0-alpha.1" => Some(PreStage::Alpha)
+    /// "1.0.0-rc1"     => Some(PreStage::Rc)
+    ///
+    pub fn pre_stage(&self) -> Option<PreStage> {
+        let identifier = match self.pre.first()? {
+            Identifier::Numeric(_) => return Some(PreStage::Other),
+            Identifier::AlphaNumeric(s) => s.to_ascii_lowercase(),
+        };
+        let stage = if identifier.starts_with("rc") {
+            PreStage::Rc
+        } else if identifier.starts_with("beta") {
+            PreStage::Beta
+        } else if identifier.starts_with("alpha") {
+            PreStage::Alpha
+        } else if identifier.starts_with("dev") {
+            PreStage::Dev
+        } else {
+            PreStage::Other
+        };
+        Some(stage)
+    }
+
+    /// Classify the change from this version to another.
+    ///
+    /// "1.2.3" => "2.0.0"     => VersionChange::Major
+    /// "1.2.3" => "1.2.4-rc1" => VersionChange::Patch
+    /// "1.0.0-rc1" => "1.0.0" => VersionChange::Pre
+    ///
+    pub fn change_to(&self, to: &Version) -> VersionChange {
+        match self.cmp(to) {
+            Ordering::Greater => VersionChange::Downgrade,
+            Ordering::Equal => VersionChange::None,
+            Ordering::Less if self.major != to.major => VersionChange::Major,
+            Ordering::Less if self.minor != to.minor => VersionChange::Minor,
+            Ordering::Less if self.patch != to.patch => VersionChange::Patch,
+            Ordering::Less => VersionChange::Pre,
+        }
+    }
+}
+
+/// The kind of change between two versions, see [`Version::change_to`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VersionChange {
+    /// The versions are the same, ignoring build metadata.
+    None,
+    /// The new version is older.
+    Downgrade,
+    Major,
+    Minor,
+    Patch,
+    /// Only the pre-release segment changed, such as from a release candidate
+    /// to the final release.
+    Pre,
+}
+
+impl VersionChange {
+    /// Returns `true` if the change may contain breaking changes, going by
+    /// semantic versioning. Before 1.0.0 minor releases may be breaking, as
+    /// the `~>` operator assumes.
+    pub fn may_break(&self, from: &Version) -> bool {
+        match self {
+            VersionChange::Major | VersionChange::Downgrade => true,
+            VersionChange::Minor => from.major == 0,
+            VersionChange::Patch | VersionChange::Pre | VersionChange::None => false,
+        }
+    }
+}
+
+/// A release channel, used to select which versions of a package to offer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Only versions without a pre-release segment.
+    Stable,
+    /// Only pre-release versions.
+    Pre,
+    /// Every version.
+    All,
+}
+
+impl Channel {
+    /// Returns `true` if the version is published on this channel.
+    pub fn includes(&self, version: &Version) -> bool {
+        match self {
+            Channel::Stable => !version.is_pre(),
+            Channel::Pre => version.is_pre(),
+            Channel::All => true,
+        }
+    }
+}
+
+/// The stage of a pre-release, ordered from least to most mature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PreStage {
+    /// A pre-release identifier that doesn't follow a known convention.
+    Other,
+    Dev,
+    Alpha,
+    Beta,
+    /// A release candidate.
+    Rc,
 }
 
 pub trait LowestVersion {
@@ -153,8 +283,8 @@ impl<'de> Deserialize<'de> for Version {
     where
         D: Deserializer<'de>,
     {
-        let s: &str = Deserialize::deserialize(deserializer)?;
-        Version::try_from(s).map_err(de::Error::custom)
+        let s: std::borrow::Cow<'de, str> = Deserialize::deserialize(deserializer)?;
+        Version::try_from(s.as_ref()).map_err(de::Error::custom)
     }
 }
 
@@ -189,20 +319,102 @@ impl<'a> TryFrom<&'a str> for Version {
 
 impl fmt::Display for Version {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
-        if !self.pre.is_empty() {
-            write!(f, "-")?;
-            for (i, identifier) in self.pre.iter().enumerate() {
+        self.display().fmt(f)
+    }
+}
+
+impl Version {
+    /// Options for how to display this version. By default it is displayed
+    /// the same as with `to_string`.
+    ///
+    /// ```
+    /// # use hexpm::version::Version;
+    /// let version = Version::parse("1.10.0-rc.2+build.5").unwrap();
+    /// assert_eq!(version.display().without_build().to_string(), "1.10.0-rc.2");
+    /// assert_eq!(version.display().zero_padded(3).to_string(), "001.010.000-rc.002+build.5");
+    /// ```
+    pub fn display(&self) -> VersionDisplay<'_> {
+        VersionDisplay {
+            version: self,
+            build: true,
+            digits: 0,
+        }
+    }
+
+    /// The version left aligned and padded with spaces to at least `width`
+    /// characters, for lining versions up in tables.
+    pub fn fmt_padded(&self, width: usize) -> String {
+        format!("{:<width$}", self.display())
+    }
+
+    /// The number of characters the version takes up when displayed.
+    pub fn display_width(&self) -> usize {
+        self.to_string().len()
+    }
+}
+
+/// A [`Version`] with display options, created with [`Version::display`].
+///
+/// Width, fill and alignment given in the format string are honoured, as
+/// they are when displaying a `Version` directly.
+#[derive(Debug, Clone, Copy)]
+pub struct VersionDisplay<'a> {
+    version: &'a Version,
+    build: bool,
+    digits: usize,
+}
+
+impl VersionDisplay<'_> {
+    /// Leave out the build metadata, which plays no part in ordering or
+    /// matching requirements.
+    pub fn without_build(mut self) -> Self {
+        self.build = false;
+        self
+    }
+
+    /// Pad the major, minor and patch numbers and numeric pre-release
+    /// identifiers with leading zeros to at least `digits` digits.
+    ///
+    /// Stable versions whose numbers fit in `digits` then sort as strings in
+    /// the same order as they do as versions. Pre-releases do not, as a
+    /// pre-release sorts before the stable version it is a prefix of.
+    pub fn zero_padded(mut self, digits: usize) -> Self {
+        self.digits = digits;
+        self
+    }
+}
+
+impl fmt::Display for VersionDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use std::fmt::Write;
+
+        let Self {
+            version,
+            build,
+            digits,
+        } = *self;
+        let mut out = String::new();
+        write!(
+            out,
+            "{:0digits$}.{:0digits$}.{:0digits$}",
+            version.major, version.minor, version.patch
+        )?;
+        if !version.pre.is_empty() {
+            out.push('-');
+            for (i, identifier) in version.pre.iter().enumerate() {
                 if i != 0 {
-                    write!(f, ".")?;
+                    out.push('.');
+                }
+                match identifier {
+                    Identifier::Numeric(n) => write!(out, "{n:0digits$}")?,
+                    Identifier::AlphaNumeric(s) => out.push_str(s),
                 }
-                identifier.fmt(f)?;
             }
         }
-        if let Some(build) = self.build.as_ref() {
-            write!(f, "+{}", build)?;
+        if build && let Some(build) = version.build.as_ref() {
+            write!(out, "+{}", build)?;
         }
-        Ok(())
+        f.pad(&out)
     }
 }
 
@@ -279,8 +491,8 @@ impl<'de> Deserialize<'de> for Range {
     where
         D: Deserializer<'de>,
     {
-        let s: &str = Deserialize::deserialize(deserializer)?;
-        Range::new(s.to_string()).map_err(serde::de::Error::custom)
+        let s: std::borrow::Cow<'de, str> = Deserialize::deserialize(deserializer)?;
+        Range::new(s.into_owned()).map_err(serde::de::Error::custom)
     }
 }
 
@@ -299,18 +511,40 @@ impl fmt::Display for Range {
     }
 }
 
-// A wrapper around Vec where an empty vector is greater than a non-empty one.
-// This is desires as if there is a pre-segment in a version (1.0.0-rc1) it is
-// lower than the same version with no pre-segments (1.0.0).
-#[derive(PartialEq, Eq)]
+/// A wrapper around a list of pre-release identifiers where an empty list is
+/// greater than a non-empty one. This is desired as if there is a pre-segment
+/// in a version (1.0.0-rc1) it is lower than the same version with no
+/// pre-segments (1.0.0).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct PreOrder<'a>(&'a [Identifier]);
 
-impl PreOrder<'_> {
+impl<'a> PreOrder<'a> {
+    pub fn new(pre: &'a [Identifier]) -> Self {
+        Self(pre)
+    }
+
     fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 }
 
+/// Compare the pre-release identifiers of two versions, following the
+/// precedence rules of semantic versioning: an empty list is greater than any
+/// non-empty one, numeric identifiers are lower than alphanumeric ones, and
+/// otherwise identifiers are compared in turn.
+///
+/// ```
+/// use hexpm::version::{Identifier, compare_pre};
+/// use std::cmp::Ordering;
+///
+/// let rc = [Identifier::AlphaNumeric("rc".into()), Identifier::Numeric(1)];
+/// assert_eq!(compare_pre(&rc[..1], &rc), Ordering::Less);
+/// assert_eq!(compare_pre(&rc, &[]), Ordering::Less);
+/// ```
+pub fn compare_pre(a: &[Identifier], b: &[Identifier]) -> Ordering {
+    PreOrder(a).cmp(&PreOrder(b))
+}
+
 impl std::cmp::PartialOrd for PreOrder<'_> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
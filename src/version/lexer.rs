@@ -74,10 +74,7 @@ pub enum Token<'input> {
 impl<'input> Token<'input> {
     /// Check if the current token is a whitespace token.
     pub fn is_whitespace(&self) -> bool {
-        match *self {
-            Whitespace(..) => true,
-            _ => false,
-        }
+        matches!(*self, Whitespace(..))
     }
 }
 
@@ -118,11 +115,14 @@ pub struct Lexer<'input> {
     // lookahead
     c1: Option<(usize, char)>,
     c2: Option<(usize, char)>,
+    // Whether the last token was a `.`, `-` or `+`, after which comes a
+    // pre-release or build identifier rather than a keyword
+    identifier_next: bool,
 }
 
 impl<'input> Lexer<'input> {
     /// Construct a new lexer for the given input.
-    pub fn new(input: &str) -> Lexer {
+    pub fn new(input: &str) -> Lexer<'_> {
         let mut chars = input.char_indices();
         let c1 = chars.next();
         let c2 = chars.next();
@@ -132,6 +132,7 @@ impl<'input> Lexer<'input> {
             chars,
             c1,
             c2,
+            identifier_next: false,
         }
     }
 
@@ -207,18 +208,27 @@ impl<'input> Iterator for Lexer<'input> {
     type Item = Result<Token<'input>, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let token = self.token();
+        self.identifier_next = matches!(token, Some(Ok(Dot | Hyphen | Plus)));
+        token
+    }
+}
+
+impl<'input> Lexer<'input> {
+    fn token(&mut self) -> Option<Result<Token<'input>, Error>> {
         #[allow(clippy::never_loop)]
         loop {
             // two subsequent char tokens.
             if let Some((start, a, b)) = self.two() {
+                let keyword = !self.identifier_next;
                 let two = match (a, b) {
                     ('~', '>') => Some(Pessimistic),
                     ('!', '=') => Some(NotEq),
                     ('<', '=') => Some(LtEq),
                     ('>', '=') => Some(GtEq),
                     ('=', '=') => Some(Eq),
-                    ('o', 'r') => Some(Or),
-                    ('a', 'n') => {
+                    ('o', 'r') if keyword => Some(Or),
+                    ('a', 'n') if keyword => {
                         self.step_n(2);
                         return Some(self.and(start));
                     }
@@ -263,15 +273,17 @@ impl<'input> Iterator for Lexer<'input> {
 mod tests {
     use super::*;
 
-    fn lex(input: &str) -> Vec<Token> {
+    fn lex(input: &str) -> Vec<Token<'_>> {
         Lexer::new(input).map(Result::unwrap).collect::<Vec<_>>()
     }
 
     #[test]
     pub fn simple_tokens() {
         assert_eq!(
-            lex("!===><<=>=~>.-+orand"),
+            lex("orand!===><<=>=~>.-+"),
             vec![
+                Or,
+                And,
                 NotEq,
                 Eq,
                 Gt,
@@ -282,12 +294,27 @@ mod tests {
                 Dot,
                 Hyphen,
                 Plus,
-                Or,
-                And
             ]
         );
     }
 
+    #[test]
+    pub fn identifiers_are_not_keywords() {
+        assert_eq!(
+            lex("0+or.and-orange"),
+            vec![
+                Numeric(0),
+                Plus,
+                AlphaNumeric("or"),
+                Dot,
+                AlphaNumeric("and"),
+                Hyphen,
+                AlphaNumeric("orange"),
+            ]
+        );
+        assert_eq!(lex("0 or"), vec![Numeric(0), Whitespace(1, 2), Or]);
+    }
+
     #[test]
     pub fn whitespace() {
         assert_eq!(
@@ -206,7 +206,8 @@ impl<'input> Parser<'input> {
 
         let mut buffer = String::new();
 
-        loop {
+        // Whitespace ends the metadata of a version within a requirement
+        while !matches!(self.peek(), Some(&Token::Whitespace(_, _))) {
             match self.pop() {
                 Err(UnexpectedEnd) => break,
                 Ok(Token::LeadingZero(s)) => buffer.push_str(s),
@@ -0,0 +1,412 @@
+//! Helpers for rewriting version requirements while preserving the style in
+//! which they were originally written.
+
+use std::collections::{BTreeMap, HashMap};
+
+use thiserror::Error;
+
+use super::{Range, Version, parser};
+use crate::bulk::BulkResult;
+
+/// A requirement in a dependency table that could not be parsed.
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("the requirement {requirement:?} for {name} is not valid: {source}")]
+pub struct RequirementError {
+    /// The key of the offending requirement in the table.
+    pub name: String,
+    pub requirement: String,
+    pub source: parser::Error,
+}
+
+/// Parse a dependency table, such as the `[dependencies]` of a `gleam.toml`
+/// file, into requirements. Entries are returned in the order they are given.
+///
+/// ```
+/// let table = [("gleam_stdlib", ">= 0.34.0 and < 2.0.0"), ("gleeunit", "~> 1.0")];
+/// let requirements = hexpm::version::parse_requirements(table).unwrap();
+/// assert_eq!(requirements[1].0, "gleeunit");
+/// ```
+///
+/// Tables can also contain dependencies that are not from Hex, such as
+/// `{ path = "../thing" }`. These are to be filtered out by the caller.
+pub fn parse_requirements<K, V>(
+    table: impl IntoIterator<Item = (K, V)>,
+) -> Result<Vec<(String, Range)>, RequirementError>
+where
+    K: Into<String>,
+    V: AsRef<str>,
+{
+    parse_requirements_bulk(table).first_error()
+}
+
+/// As [`parse_requirements`], parsing every entry rather than stopping at the
+/// first that is not valid, so that all of the problems in a table can be
+/// reported at once.
+pub fn parse_requirements_bulk<K, V>(
+    table: impl IntoIterator<Item = (K, V)>,
+) -> BulkResult<(String, Range), RequirementError>
+where
+    K: Into<String>,
+    V: AsRef<str>,
+{
+    table
+        .into_iter()
+        .map(|(name, requirement)| {
+            let name = name.into();
+            match Range::new(requirement.as_ref().to_string()) {
+                Ok(range) => Ok((name, range)),
+                Err(source) => Err(RequirementError {
+                    name,
+                    requirement: requirement.as_ref().to_string(),
+                    source,
+                }),
+            }
+        })
+        .collect()
+}
+
+/// Convert requirements into a dependency table of requirement strings, such
+/// as the `[dependencies]` of a `gleam.toml` file, sorted by name.
+pub fn requirements_table<'a, K>(
+    requirements: impl IntoIterator<Item = (K, &'a Range)>,
+) -> BTreeMap<String, String>
+where
+    K: Into<String>,
+{
+    requirements
+        .into_iter()
+        .map(|(name, range)| (name.into(), range.as_str().to_string()))
+        .collect()
+}
+
+/// How tightly a requirement should constrain a locked version, used when
+/// converting locked versions into requirements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tightening {
+    /// Accept later minor and patch versions: `~> 1.4` for 1.4.2.
+    Major,
+    /// Accept later patch versions: `~> 1.4.2` for 1.4.2.
+    Minor,
+    /// Accept only the locked version: `== 1.4.2` for 1.4.2.
+    Exact,
+}
+
+/// Convert the versions locked for a project into requirements that are
+/// tightened around those versions, for users who want to commit a stricter
+/// manifest. This is the inverse of [`Range::widen_to`].
+///
+/// Pre-release versions are always given with their full version, as a
+/// requirement such as `~> 1.0` would not match `1.0.0-rc.1`.
+pub fn tighten_requirements(
+    locked: &HashMap<String, Version>,
+    tightening: Tightening,
+) -> HashMap<String, Range> {
+    locked
+        .iter()
+        .map(|(name, version)| (name.clone(), Range::tightened(version, tightening)))
+        .collect()
+}
+
+/// Why a locked package must be unlocked after its requirements change. See
+/// [`unlocks_for_update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UnlockReason {
+    /// The new requirement shares no versions with the old one, such as a
+    /// move to a new major version.
+    Replaced,
+    /// The new requirement overlaps the old one, or the package was not a
+    /// direct dependency before, but the locked version no longer matches.
+    LockedVersionExcluded,
+    /// The package is no longer a direct dependency. Its locked entry can be
+    /// dropped unless another package still depends on it.
+    Removed,
+}
+
+/// Find the locked packages that must be unlocked for a project whose
+/// requirements change from `old` to `new`, grouped by why. Packages whose
+/// locked version still matches their new requirement stay locked, so only
+/// what changed is updated.
+///
+/// Only direct dependencies are considered. Unlocking a package may in turn
+/// require unlocking packages it depends on, which needs their registry
+/// records to find.
+///
+/// ```
+/// use hexpm::version::{Range, UnlockReason, Version, unlocks_for_update};
+/// use std::collections::HashMap;
+///
+/// let range = |spec: &str| Range::new(spec.into()).unwrap();
+/// let old = HashMap::from([("lustre".into(), range("~> 3.0")), ("gleam_json".into(), range("~> 1.0"))]);
+/// let new = HashMap::from([("lustre".into(), range("~> 4.0")), ("gleam_json".into(), range("~> 1.1"))]);
+/// let locked = HashMap::from([
+///     ("lustre".into(), Version::new(3, 1, 0)),
+///     ("gleam_json".into(), Version::new(1, 2, 0)),
+/// ]);
+/// let unlocks = unlocks_for_update(&old, &new, &locked);
+/// assert_eq!(unlocks[&UnlockReason::Replaced], vec!["lustre".to_string()]);
+/// assert_eq!(unlocks.len(), 1);
+/// ```
+pub fn unlocks_for_update(
+    old: &HashMap<String, Range>,
+    new: &HashMap<String, Range>,
+    locked: &HashMap<String, Version>,
+) -> BTreeMap<UnlockReason, Vec<String>> {
+    let mut unlocks = BTreeMap::<UnlockReason, Vec<String>>::new();
+    for (name, version) in locked {
+        let reason = match (old.get(name), new.get(name)) {
+            (_, Some(new)) if new.range.contains(version) => continue,
+            (Some(old), Some(new)) if old.range.intersection(&new.range).is_empty() => {
+                UnlockReason::Replaced
+            }
+            (_, Some(_)) => UnlockReason::LockedVersionExcluded,
+            (Some(_), None) => UnlockReason::Removed,
+            (None, None) => continue,
+        };
+        unlocks.entry(reason).or_default().push(name.clone());
+    }
+    for names in unlocks.values_mut() {
+        names.sort();
+    }
+    unlocks
+}
+
+impl Range {
+    /// A requirement tightened around the given version. See
+    /// [`tighten_requirements`].
+    pub fn tightened(version: &Version, tightening: Tightening) -> Range {
+        let version = without_build(version);
+        match tightening {
+            Tightening::Major if !version.is_pre() => Range::pessimistic_minor(&version),
+            Tightening::Major | Tightening::Minor => Range::pessimistic_patch(&version),
+            Tightening::Exact => {
+                Range::new(format!("== {}", version)).expect("tightened requirement is valid")
+            }
+        }
+    }
+
+    /// Widen this requirement so that it also matches the given version,
+    /// preserving the style of the original requirement. If the requirement
+    /// already matches the version it is returned unchanged.
+    ///
+    /// ```text
+    /// "~> 1.2"                    + 2.1.0 => "~> 1.2 or ~> 2.1"
+    /// "~> 1.2.3"                  + 1.3.0 => "~> 1.2.3 or ~> 1.3.0"
+    /// ">= 1.0.0 and < 2.0.0"      + 2.3.0 => ">= 1.0.0 and < 3.0.0"
+    /// "== 1.0.0"                  + 1.1.0 => "== 1.0.0 or == 1.1.0"
+    /// "~> 1.2"                    + 3.0.0-rc.1 => "~> 1.2 or ~> 3.0.0-rc.1"
+    /// ```
+    ///
+    /// `~> MAJOR.MINOR` does not match pre-releases, so pre-release versions
+    /// are always added with their full version.
+    ///
+    /// This is intended for tools that automatically update the requirements
+    /// of a project when a new version of a dependency is published.
+    pub fn widen_to(&self, version: &Version) -> Range {
+        if self.range.contains(version) {
+            return self.clone();
+        }
+
+        let alternatives = split_keyword(&self.spec, "or");
+
+        // A single bounded range such as `>= 1.0.0 and < 2.0.0` can be widened
+        // by raising the upper bound rather than adding a new alternative.
+        if let [alternative] = alternatives.as_slice()
+            && let Some(widened) = raise_upper_bound(alternative, version)
+        {
+            return widened;
+        }
+
+        let last = alternatives
+            .last()
+            .and_then(|alternative| split_keyword(alternative, "and").first().copied())
+            .unwrap_or_default();
+        let alternative = match split_operator(last) {
+            ("~>", bound) if bound.matches('.').count() == 1 && !version.is_pre() => {
+                format!("~> {}.{}", version.major, version.minor)
+            }
+            ("~>", _) => format!("~> {}", without_build(version)),
+            ("", _) => without_build(version).to_string(),
+            _ => format!("== {}", without_build(version)),
+        };
+        Range::new(format!("{} or {}", self.spec, alternative))
+            .expect("widened requirement is valid")
+    }
+}
+
+/// The two forms of the pessimistic operator, which differ in how far they
+/// let versions move. See [`Range::pessimistic_form`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PessimisticForm {
+    /// `~> 1.2`, matching `>= 1.2.0 and < 2.0.0`.
+    Minor,
+    /// `~> 1.2.3`, matching `>= 1.2.3 and < 1.3.0`.
+    Patch,
+}
+
+impl Range {
+    /// `~> MAJOR.MINOR` for the given version, accepting later minor and patch
+    /// versions. The patch version, pre-release and build metadata of the
+    /// version are not part of the requirement.
+    pub fn pessimistic_minor(version: &Version) -> Range {
+        Range::new(format!("~> {}.{}", version.major, version.minor))
+            .expect("pessimistic requirement is valid")
+    }
+
+    /// `~> MAJOR.MINOR.PATCH` for the given version, accepting later patch
+    /// versions. Any pre-release is kept, the build metadata is not.
+    pub fn pessimistic_patch(version: &Version) -> Range {
+        Range::new(format!("~> {}", without_build(version)))
+            .expect("pessimistic requirement is valid")
+    }
+
+    /// The form of the pessimistic operator this requirement is written with,
+    /// if it is written only with `~>` constraints of one form, such as
+    /// `~> 1.2` or `~> 1.2 or ~> 2.1`.
+    ///
+    /// ```
+    /// use hexpm::version::{PessimisticForm, Range};
+    ///
+    /// let range = Range::new("~> 1.2.3".into()).unwrap();
+    /// assert_eq!(range.pessimistic_form(), Some(PessimisticForm::Patch));
+    /// assert_eq!(Range::new(">= 1.2.3".into()).unwrap().pessimistic_form(), None);
+    /// ```
+    pub fn pessimistic_form(&self) -> Option<PessimisticForm> {
+        let mut forms = split_keyword(&self.spec, "or")
+            .into_iter()
+            .flat_map(|alternative| split_keyword(alternative, "and"))
+            .map(|clause| match split_operator(clause) {
+                ("~>", bound) => {
+                    let numbers = bound.split(['-', '+']).next().unwrap_or_default();
+                    Some(match numbers.matches('.').count() {
+                        1 => PessimisticForm::Minor,
+                        _ => PessimisticForm::Patch,
+                    })
+                }
+                _ => None,
+            });
+        let first = forms.next()??;
+        forms.all(|form| form == Some(first)).then_some(first)
+    }
+}
+
+/// How a requirement relates to the published versions of a package. See
+/// [`Range::coverage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// The number of published versions matched by the requirement.
+    pub matching: usize,
+    /// The number of published versions.
+    pub total: usize,
+    /// The oldest published version matched by the requirement.
+    pub oldest: Option<Version>,
+    /// The newest published version matched by the requirement.
+    pub newest: Option<Version>,
+    /// The newest published stable version, whether or not it is matched.
+    pub latest_stable: Option<Version>,
+    /// Whether the newest published stable version is matched by the
+    /// requirement.
+    pub matches_latest_stable: bool,
+}
+
+impl Coverage {
+    /// Returns `true` if the newest stable version is not matched by the
+    /// requirement, meaning users are held back from the latest release.
+    pub fn excludes_latest_stable(&self) -> bool {
+        self.latest_stable.is_some() && !self.matches_latest_stable
+    }
+}
+
+impl Range {
+    /// Report how this requirement covers the published versions of a
+    /// package, such as those from the versions index.
+    ///
+    /// ```
+    /// use hexpm::version::{Range, Version};
+    ///
+    /// let versions = ["1.0.0", "1.1.0", "2.0.0"].map(|v| Version::parse(v).unwrap());
+    /// let coverage = Range::new("~> 1.0".into()).unwrap().coverage(&versions);
+    /// assert_eq!(coverage.matching, 2);
+    /// assert!(coverage.excludes_latest_stable());
+    /// ```
+    pub fn coverage(&self, versions: &[Version]) -> Coverage {
+        let range = self.to_pubgrub();
+        let matching: Vec<_> = versions.iter().filter(|v| range.contains(v)).collect();
+        let latest_stable = versions.iter().filter(|v| !v.is_pre()).max().cloned();
+        let matches_latest_stable = latest_stable.as_ref().is_some_and(|v| range.contains(v));
+        Coverage {
+            matching: matching.len(),
+            total: versions.len(),
+            oldest: matching.iter().min().map(|v| (*v).clone()),
+            newest: matching.iter().max().map(|v| (*v).clone()),
+            latest_stable,
+            matches_latest_stable,
+        }
+    }
+}
+
+/// Raise the upper bound of a requirement of the form `>= a and < b` so that it
+/// includes the given version, keeping the precision of the original bound.
+fn raise_upper_bound(requirement: &str, version: &Version) -> Option<Range> {
+    let clauses = split_keyword(requirement, "and");
+    let [lower, upper] = clauses.as_slice() else {
+        return None;
+    };
+    let (lower_operator, lower_bound) = split_operator(lower);
+    if !matches!(lower_operator, ">=" | ">") || Version::parse(lower_bound).ok()? > *version {
+        return None;
+    }
+    let upper = match split_operator(upper) {
+        ("<", bound) => {
+            let bound = Version::parse(bound).ok()?;
+            let raised = if bound.minor == 0 && bound.patch == 0 {
+                version.bump_major()
+            } else if bound.patch == 0 {
+                version.bump_minor()
+            } else {
+                version.bump_patch()
+            };
+            format!("< {}", raised)
+        }
+        ("<=", _) => format!("<= {}", without_build(version)),
+        _ => return None,
+    };
+    Range::new(format!("{} and {}", lower, upper)).ok()
+}
+
+/// Split a requirement on a keyword such as `and` or `or`, trimming the
+/// surrounding whitespace of each part.
+fn split_keyword<'a>(requirement: &'a str, keyword: &str) -> Vec<&'a str> {
+    let mut parts = vec![];
+    let mut rest = requirement.trim();
+    while let Some(index) = find_keyword(rest, keyword) {
+        parts.push(rest[..index].trim());
+        rest = rest[index + keyword.len()..].trim();
+    }
+    parts.push(rest);
+    parts
+}
+
+fn find_keyword(input: &str, keyword: &str) -> Option<usize> {
+    input.match_indices(keyword).map(|(i, _)| i).find(|&i| {
+        let before = input[..i].chars().next_back();
+        let after = input[i + keyword.len()..].chars().next();
+        before.is_some_and(char::is_whitespace) && after.is_some_and(char::is_whitespace)
+    })
+}
+
+/// Split a single requirement clause such as `>= 1.0.0` into its operator and
+/// version. Clauses without an operator have an empty operator.
+fn split_operator(clause: &str) -> (&str, &str) {
+    let clause = clause.trim();
+    let index = clause
+        .find(|c: char| c.is_ascii_alphanumeric())
+        .unwrap_or(clause.len());
+    (clause[..index].trim(), &clause[index..])
+}
+
+fn without_build(version: &Version) -> Version {
+    Version {
+        build: None,
+        ..version.clone()
+    }
+}
@@ -1,5 +1,5 @@
 use std::cmp::Ordering::{Equal, Greater, Less};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use parser::Error;
 
@@ -163,7 +163,7 @@ version_parse_fail_test!(leading_zero_major, "02.3.0");
 
 version_parse_fail_test!(extra_whitespace, "0. 0.0");
 
-version_parse_fail_test!(and_in_version, "0.1.0-andpre");
+version_parse_print!(and_in_version, "0.1.0-andpre");
 
 version_parse_print!(print_triplet, "1.100.1000");
 
@@ -391,6 +391,34 @@ assert_order!(ord_pre_smaller_than_zero_flip, "1.0.0-rc1", Less, "1.0.0");
 
 assert_order!(ord_pre_rc1_2, "1.0.0-rc1", Less, "1.0.0-rc2");
 
+#[test]
+fn keywords_as_identifiers() {
+    let version = v_(1, 0, 0, vec![], Some("or".into()));
+    assert_eq!(Version::parse("1.0.0+or"), Ok(version.clone()));
+    assert!(
+        Range::new("== 1.0.0+or".into())
+            .unwrap()
+            .to_pubgrub()
+            .contains(&version)
+    );
+    assert_eq!(
+        Version::parse("1.0.0-and.or"),
+        Ok(v_(
+            1,
+            0,
+            0,
+            vec![AlphaNumeric("and".into()), AlphaNumeric("or".into())],
+            None
+        ))
+    );
+    assert!(
+        Range::new("== 1.0.0+or or == 2.0.0".into())
+            .unwrap()
+            .to_pubgrub()
+            .contains(&v(2, 0, 0))
+    );
+}
+
 #[test]
 fn manifest_toml() {
     let manifest = toml::to_string(
@@ -429,6 +457,16 @@ thingy = "0.1.0"
     assert!(manifest == expected1 || manifest == expected2);
 }
 
+#[test]
+fn prefixed_version() {
+    assert_eq!(Version::parse_prefixed("v1.2.3"), Ok((v(1, 2, 3), true)));
+    assert_eq!(Version::parse_prefixed("V1.2.3"), Ok((v(1, 2, 3), true)));
+    assert_eq!(Version::parse_prefixed("1.2.3"), Ok((v(1, 2, 3), false)));
+    assert!(Version::parse_prefixed("vv1.2.3").is_err());
+    assert!(Version::parse_prefixed("v").is_err());
+    assert!(Version::parse("v1.2.3").is_err());
+}
+
 #[test]
 fn missing_minor_has_correct_error_type() {
     assert_eq!(Version::parse("1"), Err(Error::MinorVersionMissing(1)))
@@ -438,3 +476,489 @@ fn missing_minor_has_correct_error_type() {
 fn missing_patch_has_correct_error_type() {
     assert_eq!(Version::parse("1.2"), Err(Error::PatchVersionMissing(1, 2)))
 }
+
+#[test]
+fn channel_of_version() {
+    assert_eq!(Version::parse("1.0.0").unwrap().channel(), Channel::Stable);
+    assert_eq!(
+        Version::parse("1.0.0-rc.1").unwrap().channel(),
+        Channel::Pre
+    );
+}
+
+#[test]
+fn channel_includes() {
+    let stable = Version::parse("1.0.0").unwrap();
+    let pre = Version::parse("1.0.0-beta").unwrap();
+    assert!(Channel::Stable.includes(&stable));
+    assert!(!Channel::Stable.includes(&pre));
+    assert!(!Channel::Pre.includes(&stable));
+    assert!(Channel::Pre.includes(&pre));
+    assert!(Channel::All.includes(&stable));
+    assert!(Channel::All.includes(&pre));
+}
+
+#[test]
+fn pre_stage() {
+    let stage = |v: &str| Version::parse(v).unwrap().pre_stage();
+    assert_eq!(stage("1.0.0"), None);
+    assert_eq!(stage("1.0.0-dev"), Some(PreStage::Dev));
+    assert_eq!(stage("1.0.0-alpha.1"), Some(PreStage::Alpha));
+    assert_eq!(stage("1.0.0-beta2"), Some(PreStage::Beta));
+    assert_eq!(stage("1.0.0-RC.1"), Some(PreStage::Rc));
+    assert_eq!(stage("1.0.0-1"), Some(PreStage::Other));
+    assert_eq!(stage("1.0.0-preview"), Some(PreStage::Other));
+    assert!(PreStage::Alpha < PreStage::Beta);
+    assert!(PreStage::Beta < PreStage::Rc);
+}
+
+macro_rules! widen_test {
+    ($name:ident, $requirement:expr, $version:expr, $expected:expr) => {
+        #[test]
+        fn $name() {
+            let requirement = Range::new($requirement.to_string()).unwrap();
+            let version = Version::parse($version).unwrap();
+            let widened = requirement.widen_to(&version);
+            assert_eq!(widened.as_str(), $expected);
+            assert!(widened.to_pubgrub().contains(&version));
+        }
+    };
+}
+
+widen_test!(widen_already_matching, "~> 1.2", "1.5.0", "~> 1.2");
+
+widen_test!(
+    widen_pessimistic_minor,
+    "~> 1.2",
+    "2.1.0",
+    "~> 1.2 or ~> 2.1"
+);
+
+widen_test!(
+    widen_pessimistic_patch,
+    "~> 1.2.3",
+    "1.3.0",
+    "~> 1.2.3 or ~> 1.3.0"
+);
+
+widen_test!(
+    widen_pessimistic_alternatives,
+    "~> 1.2 or ~> 2.0",
+    "3.0.1",
+    "~> 1.2 or ~> 2.0 or ~> 3.0"
+);
+
+widen_test!(
+    widen_bounded_major,
+    ">= 1.0.0 and < 2.0.0",
+    "2.3.0",
+    ">= 1.0.0 and < 3.0.0"
+);
+
+widen_test!(
+    widen_bounded_minor,
+    ">= 1.0.0 and < 1.4.0",
+    "1.5.2",
+    ">= 1.0.0 and < 1.6.0"
+);
+
+widen_test!(
+    widen_bounded_inclusive,
+    ">= 1.0.0 and <= 1.4.0",
+    "1.5.2",
+    ">= 1.0.0 and <= 1.5.2"
+);
+
+widen_test!(widen_exact, "== 1.0.0", "1.1.0", "== 1.0.0 or == 1.1.0");
+
+widen_test!(widen_bare, "1.0.0", "1.1.0", "1.0.0 or 1.1.0");
+
+widen_test!(
+    widen_below_lower_bound,
+    ">= 1.0.0 and < 2.0.0",
+    "0.9.0",
+    ">= 1.0.0 and < 2.0.0 or == 0.9.0"
+);
+
+widen_test!(
+    widen_pessimistic_minor_to_pre_release,
+    "~> 1.2",
+    "3.0.0-rc.1",
+    "~> 1.2 or ~> 3.0.0-rc.1"
+);
+
+mod properties {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn identifier() -> impl Strategy<Value = Identifier> {
+        prop_oneof![
+            any::<u32>().prop_map(Numeric),
+            "[a-z][a-z0-9]{0,6}".prop_map(AlphaNumeric),
+        ]
+    }
+
+    fn version() -> impl Strategy<Value = Version> {
+        (
+            0..20u32,
+            0..20u32,
+            0..20u32,
+            prop::collection::vec(identifier(), 0..3),
+            prop::option::of("[a-z0-9]{1,8}(\\.[a-z0-9]{1,8}){0,2}"),
+        )
+            .prop_map(|(major, minor, patch, pre, build)| Version {
+                major,
+                minor,
+                patch,
+                pre,
+                build,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn parse_display_round_trip(v in version()) {
+            prop_assert_eq!(Version::parse(&v.to_string()), Ok(v));
+        }
+
+        #[test]
+        fn ordering_is_antisymmetric(a in version(), b in version()) {
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+        }
+
+        #[test]
+        fn ordering_is_transitive(a in version(), b in version(), c in version()) {
+            let mut sorted = [a, b, c];
+            sorted.sort();
+            prop_assert!(sorted[0] <= sorted[2]);
+            prop_assert!(sorted[0] <= sorted[1] && sorted[1] <= sorted[2]);
+        }
+
+        #[test]
+        fn bumps_are_monotonic(v in version()) {
+            prop_assert!(v < v.bump_patch());
+            prop_assert!(v.bump_patch() <= v.bump_minor());
+            prop_assert!(v.bump_minor() < v.bump_major());
+        }
+
+        #[test]
+        fn requirements_contain_their_version(v in version()) {
+            for requirement in [format!("== {}", v), format!(">= {}", v), format!("~> {}", v)] {
+                let range = Range::new(requirement).unwrap();
+                prop_assert!(range.to_pubgrub().contains(&v));
+            }
+        }
+
+        #[test]
+        fn widened_requirements_contain_their_version(
+            v in version(),
+            target in version(),
+            form in 0..5usize,
+        ) {
+            let requirement = match form {
+                0 => format!("~> {}.{}", v.major, v.minor),
+                1 => format!("~> {}", v),
+                2 => format!("== {}", v),
+                3 => format!(">= {} and < {}", v, v.bump_major()),
+                _ => v.to_string(),
+            };
+            let widened = Range::new(requirement).unwrap().widen_to(&target);
+            prop_assert!(widened.to_pubgrub().contains(&target), "{}", widened);
+        }
+
+        #[test]
+        fn parse_does_not_panic(input in "\\PC*") {
+            let _ = Version::parse(&input);
+            let _ = Range::new(input);
+        }
+
+        #[test]
+        fn parse_requirement_like_input_does_not_panic(
+            input in "(~>|==|!=|>=|<=|>|<)? ?[0-9]{1,3}(\\.[0-9]{1,3}){0,3}(-[a-z0-9.]{0,5})?( (and|or) )?"
+        ) {
+            let _ = Range::new(input);
+        }
+    }
+}
+
+#[test]
+fn tightened() {
+    let tightened = |version: &str, tightening| {
+        Range::tightened(&Version::parse(version).unwrap(), tightening).to_string()
+    };
+    assert_eq!(tightened("1.4.2", Tightening::Major), "~> 1.4");
+    assert_eq!(tightened("1.4.2", Tightening::Minor), "~> 1.4.2");
+    assert_eq!(tightened("1.4.2", Tightening::Exact), "== 1.4.2");
+    assert_eq!(tightened("1.4.2+build", Tightening::Exact), "== 1.4.2");
+    assert_eq!(tightened("2.0.0-rc.1", Tightening::Major), "~> 2.0.0-rc.1");
+    assert_eq!(tightened("2.0.0-rc.1", Tightening::Minor), "~> 2.0.0-rc.1");
+}
+
+#[test]
+fn unlocks_for_changed_requirements() {
+    let range = |spec: &str| Range::new(spec.into()).unwrap();
+    let old = HashMap::from([
+        ("a".to_string(), range("~> 1.0")),
+        ("b".to_string(), range("~> 1.0")),
+        ("c".to_string(), range("~> 1.0")),
+        ("d".to_string(), range("~> 1.0")),
+        ("removed".to_string(), range("~> 1.0")),
+    ]);
+    let new = HashMap::from([
+        ("a".to_string(), range("~> 1.0")),
+        ("b".to_string(), range("~> 1.3")),
+        ("c".to_string(), range(">= 2.0.0")),
+        ("d".to_string(), range("~> 1.1")),
+        ("transitive".to_string(), range("~> 2.0")),
+        ("new".to_string(), range("~> 1.0")),
+    ]);
+    let locked = HashMap::from([
+        ("a".to_string(), v(1, 0, 0)),
+        ("b".to_string(), v(1, 2, 0)),
+        ("c".to_string(), v(1, 5, 0)),
+        ("d".to_string(), v(1, 5, 0)),
+        ("removed".to_string(), v(1, 0, 0)),
+        ("transitive".to_string(), v(1, 0, 0)),
+        ("untouched".to_string(), v(1, 0, 0)),
+    ]);
+    let unlocks = unlocks_for_update(&old, &new, &locked);
+    assert_eq!(
+        unlocks,
+        BTreeMap::from([
+            (UnlockReason::Replaced, vec!["c".to_string()]),
+            (
+                UnlockReason::LockedVersionExcluded,
+                vec!["b".to_string(), "transitive".to_string()]
+            ),
+            (UnlockReason::Removed, vec!["removed".to_string()]),
+        ])
+    );
+}
+
+#[test]
+fn tighten_locked_requirements() {
+    let locked = [
+        ("gleam_stdlib".to_string(), Version::new(0, 17, 1)),
+        ("thingy".to_string(), Version::new(1, 2, 3)),
+    ]
+    .into();
+    let requirements = tighten_requirements(&locked, Tightening::Major);
+    assert_eq!(requirements.len(), 2);
+    assert_eq!(requirements["gleam_stdlib"].as_str(), "~> 0.17");
+    assert_eq!(requirements["thingy"].as_str(), "~> 1.2");
+    for (name, version) in &locked {
+        assert!(requirements[name].to_pubgrub().contains(version));
+    }
+}
+
+#[test]
+fn coverage() {
+    let versions: Vec<_> = [
+        "0.9.0",
+        "1.0.0",
+        "1.2.0",
+        "1.3.0-rc.1",
+        "2.0.0",
+        "2.1.0-rc.0",
+    ]
+    .into_iter()
+    .map(|v| Version::parse(v).unwrap())
+    .collect();
+    let coverage = |requirement: &str| {
+        Range::new(requirement.to_string())
+            .unwrap()
+            .coverage(&versions)
+    };
+
+    let narrow = coverage("~> 1.0");
+    assert_eq!(narrow.matching, 3);
+    assert_eq!(narrow.total, 6);
+    assert_eq!(narrow.oldest, Some(Version::new(1, 0, 0)));
+    assert_eq!(narrow.newest, Some(Version::parse("1.3.0-rc.1").unwrap()));
+    assert_eq!(narrow.latest_stable, Some(Version::new(2, 0, 0)));
+    assert!(narrow.excludes_latest_stable());
+
+    let wide = coverage(">= 1.0.0");
+    assert_eq!(wide.matching, 5);
+    assert!(!wide.excludes_latest_stable());
+
+    let none = coverage("~> 3.0");
+    assert_eq!(none.matching, 0);
+    assert_eq!(none.newest, None);
+    assert!(none.excludes_latest_stable());
+
+    // The newest match is a pre-release newer than the latest stable version
+    let pre = coverage(">= 2.1.0-rc.0");
+    assert_eq!(pre.newest, Some(Version::parse("2.1.0-rc.0").unwrap()));
+    assert!(!pre.matches_latest_stable);
+    assert!(pre.excludes_latest_stable());
+
+    // The requirement matches versions either side of the latest stable one
+    let disjoint = coverage("== 1.0.0 or == 2.1.0-rc.0");
+    assert_eq!(disjoint.matching, 2);
+    assert!(disjoint.excludes_latest_stable());
+
+    let around = coverage("== 1.0.0 or == 2.0.0");
+    assert!(around.matches_latest_stable);
+    assert!(!around.excludes_latest_stable());
+}
+
+#[test]
+fn gleam_toml_requirements() {
+    let manifest: toml::Table = toml::from_str(
+        r#"
+[dependencies]
+gleam_stdlib = ">= 0.34.0 and < 2.0.0"
+gleeunit = "~> 1.0"
+local_thing = { path = "../local_thing" }
+"#,
+    )
+    .unwrap();
+    let table = manifest["dependencies"]
+        .as_table()
+        .unwrap()
+        .iter()
+        .filter_map(|(name, requirement)| Some((name.as_str(), requirement.as_str()?)));
+    let requirements = parse_requirements(table).unwrap();
+    assert_eq!(requirements.len(), 2);
+
+    let table = requirements_table(
+        requirements
+            .iter()
+            .map(|(name, range)| (name.as_str(), range)),
+    );
+    assert_eq!(
+        toml::to_string(&table).unwrap(),
+        "gleam_stdlib = \">= 0.34.0 and < 2.0.0\"\ngleeunit = \"~> 1.0\"\n"
+    );
+}
+
+#[test]
+fn gleam_toml_invalid_requirement() {
+    let error = parse_requirements([("gleam_stdlib", "~> 0.34"), ("gleeunit", "1.0")]).unwrap_err();
+    assert_eq!(error.name, "gleeunit");
+    assert_eq!(error.requirement, "1.0");
+    assert!(
+        error
+            .to_string()
+            .starts_with("the requirement \"1.0\" for gleeunit is not valid")
+    );
+
+    let results = parse_requirements_bulk([
+        ("gleam_stdlib", "~> 0.34"),
+        ("gleeunit", "1.0"),
+        ("lustre", "~> 4"),
+    ]);
+    assert_eq!(results.summary().to_string(), "1 succeeded, 2 failed");
+    let (parsed, errors) = results.partition();
+    assert_eq!(parsed[0].0, "gleam_stdlib");
+    assert_eq!(
+        errors
+            .iter()
+            .map(|error| error.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["gleeunit", "lustre"]
+    );
+}
+
+#[test]
+fn version_changes() {
+    let v = |version| Version::parse(version).unwrap();
+    assert_eq!(v("1.2.3").change_to(&v("2.0.0")), VersionChange::Major);
+    assert_eq!(v("1.2.3").change_to(&v("1.3.0")), VersionChange::Minor);
+    assert_eq!(v("1.2.3").change_to(&v("1.2.4-rc1")), VersionChange::Patch);
+    assert_eq!(v("1.0.0-rc1").change_to(&v("1.0.0")), VersionChange::Pre);
+    assert_eq!(v("1.0.0").change_to(&v("1.0.0+build")), VersionChange::None);
+    assert_eq!(v("1.0.0").change_to(&v("0.9.0")), VersionChange::Downgrade);
+    assert!(VersionChange::Minor.may_break(&v("0.9.0")));
+    assert!(!VersionChange::Minor.may_break(&v("1.0.0")));
+}
+
+#[test]
+fn pre_release_ordering() {
+    let mut pre = vec![
+        vec![],
+        vec![AlphaNumeric("rc".into()), Numeric(1)],
+        vec![AlphaNumeric("alpha".into())],
+        vec![Numeric(2)],
+        vec![AlphaNumeric("rc".into())],
+    ];
+    pre.sort_by(|a, b| compare_pre(a, b));
+    assert_eq!(
+        pre,
+        vec![
+            vec![Numeric(2)],
+            vec![AlphaNumeric("alpha".into())],
+            vec![AlphaNumeric("rc".into())],
+            vec![AlphaNumeric("rc".into()), Numeric(1)],
+            vec![],
+        ]
+    );
+    assert_eq!(
+        PreOrder::new(&[]).cmp(&PreOrder::new(&[Numeric(1)])),
+        Greater
+    );
+    assert_eq!(compare_pre(&[Numeric(1)], &[Numeric(1)]), Equal);
+}
+
+#[test]
+fn display_options() {
+    let v = |version| Version::parse(version).unwrap();
+    let versions = [v("1.2.3"), v("10.0.0-rc.1+build")];
+    let table: Vec<_> = versions
+        .iter()
+        .map(|version| format!("{}|", version.fmt_padded(8)))
+        .collect();
+    assert_eq!(table, vec!["1.2.3   |", "10.0.0-rc.1+build|"]);
+    assert_eq!(versions[1].display_width(), 17);
+    assert_eq!(format!("{:>7}", versions[0]), "  1.2.3");
+    assert_eq!(
+        versions[1]
+            .display()
+            .without_build()
+            .zero_padded(2)
+            .to_string(),
+        "10.00.00-rc.01"
+    );
+
+    let mut stable = [v("1.10.0"), v("1.9.0"), v("0.10.2"), v("0.9.10")];
+    let mut keys: Vec<_> = stable
+        .iter()
+        .map(|version| version.display().zero_padded(4).to_string())
+        .collect();
+    stable.sort();
+    keys.sort();
+    let sorted: Vec<_> = stable
+        .iter()
+        .map(|version| version.display().zero_padded(4).to_string())
+        .collect();
+    assert_eq!(keys, sorted);
+}
+
+#[test]
+fn pessimistic_forms() {
+    let v = |version| Version::parse(version).unwrap();
+    let r = |spec: &str| Range::new(spec.to_string()).unwrap();
+
+    let minor = Range::pessimistic_minor(&v("1.2.3+build"));
+    assert_eq!(minor.as_str(), "~> 1.2");
+    assert!(minor.to_pubgrub().contains(&v("1.9.0")));
+    assert_eq!(minor.pessimistic_form(), Some(PessimisticForm::Minor));
+
+    let patch = Range::pessimistic_patch(&v("1.2.3-rc.1+build"));
+    assert_eq!(patch.as_str(), "~> 1.2.3-rc.1");
+    assert!(!patch.to_pubgrub().contains(&v("1.3.0")));
+    assert_eq!(patch.pessimistic_form(), Some(PessimisticForm::Patch));
+
+    assert_eq!(
+        r("~> 1.2 or ~> 2.1").pessimistic_form(),
+        Some(PessimisticForm::Minor)
+    );
+    assert_eq!(
+        r("~> 1.2-rc.1").pessimistic_form(),
+        Some(PessimisticForm::Minor)
+    );
+    assert_eq!(r("~> 1.2 or ~> 2.1.0").pessimistic_form(), None);
+    assert_eq!(r("~> 1.2 or >= 3.0.0").pessimistic_form(), None);
+    assert_eq!(r("1.2.3").pessimistic_form(), None);
+}
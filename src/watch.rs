@@ -0,0 +1,212 @@
+//! Watching a repository for newly published and removed releases, the core
+//! of notification bots and other long running services.
+//!
+//! A [`Watch`] polls the versions index at a fixed interval with conditional
+//! requests, so that unchanged indexes are not downloaded again, and reports
+//! the differences between successive fetches to a callback. As with the rest
+//! of this crate the caller sends the requests, and so decides how to wait
+//! until [`Watch::next_poll`].
+//!
+//! ```
+//! use hexpm::{Config, watch::Watch};
+//! use std::time::{Duration, SystemTime};
+//!
+//! let mut watch = Watch::new(Duration::from_secs(300));
+//! let now = SystemTime::now();
+//! assert!(watch.next_poll() <= now);
+//! let request = watch.request(None, &Config::new());
+//! assert_eq!(request.uri().path(), "/versions");
+//! ```
+
+use std::time::{Duration, SystemTime};
+
+use http::StatusCode;
+
+use crate::index::{IndexDiff, VersionsIndex};
+use crate::{ApiError, CacheValidators, Config};
+
+/// The state of a watched repository's versions index.
+#[derive(Debug, Clone)]
+pub struct Watch {
+    interval: Duration,
+    index: Option<VersionsIndex>,
+    validators: CacheValidators,
+    next_poll: SystemTime,
+}
+
+impl Watch {
+    /// Watch a repository, polling every `interval`. The first poll records
+    /// the current index, which later polls are compared with.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            index: None,
+            validators: CacheValidators::default(),
+            next_poll: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    /// Resume watching from a previously fetched index, such as one restored
+    /// with [`decode_versions`](crate::index::decode_versions) after a
+    /// restart, so that releases published in between are reported.
+    pub fn resume(index: VersionsIndex, interval: Duration) -> Self {
+        Self {
+            next_poll: index.fetched_at() + interval,
+            index: Some(index),
+            ..Self::new(interval)
+        }
+    }
+
+    /// When to send the next request.
+    pub fn next_poll(&self) -> SystemTime {
+        self.next_poll
+    }
+
+    /// The index as of the last successful poll.
+    pub fn index(&self) -> Option<&VersionsIndex> {
+        self.index.as_ref()
+    }
+
+    /// Create the next request for the versions index, conditional on it
+    /// having changed since the last poll.
+    pub fn request(&self, api_key: Option<&str>, config: &Config) -> http::Request<Vec<u8>> {
+        let mut request = crate::get_repository_versions_request(api_key, config);
+        self.validators.apply_to(&mut request);
+        request
+    }
+
+    /// Handle the response to a [`request`](Self::request) received at `now`,
+    /// calling `on_change` with the differences from the previous index if
+    /// there are any.
+    ///
+    /// The next poll is scheduled whether or not the response could be
+    /// parsed. When Hex is unavailable and asks for requests to be retried
+    /// later than the interval, the next poll waits until then.
+    pub fn handle_response(
+        &mut self,
+        response: http::Response<Vec<u8>>,
+        public_key: &[u8],
+        now: SystemTime,
+        mut on_change: impl FnMut(&IndexDiff),
+    ) -> Result<(), ApiError> {
+        self.next_poll = now + self.interval;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(());
+        }
+        let validators = CacheValidators::from_headers(response.headers());
+        let versions = match crate::get_repository_versions_response(response, public_key) {
+            Ok(versions) => versions,
+            Err(error) => {
+                if let ApiError::ServiceUnavailable {
+                    retry_after: Some(retry_after),
+                    ..
+                } = &error
+                {
+                    self.next_poll = self.next_poll.max(now + retry_after.delay(now));
+                }
+                return Err(error);
+            }
+        };
+        self.validators = validators;
+        match &mut self.index {
+            None => self.index = Some(VersionsIndex::new(versions, now)),
+            Some(index) => {
+                let diff = index.update(versions, now);
+                if !diff.is_empty() {
+                    on_change(&diff);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_keys::TestKeys;
+    use crate::version::Version;
+    use std::collections::HashMap;
+
+    fn response(keys: &TestKeys, versions: &[(&str, &[Version])]) -> http::Response<Vec<u8>> {
+        let versions: HashMap<_, _> = versions
+            .iter()
+            .map(|(name, versions)| (name.to_string(), versions.to_vec()))
+            .collect();
+        http::Response::builder()
+            .status(200)
+            .header("etag", "\"abc\"")
+            .body(keys.versions_resource("hexpm", &versions))
+            .unwrap()
+    }
+
+    #[test]
+    fn reports_changes() {
+        let keys = TestKeys::new();
+        let interval = Duration::from_secs(60);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let mut watch = Watch::new(interval);
+        let mut diffs = Vec::new();
+
+        let v1 = Version::new(1, 0, 0);
+        let v2 = Version::new(1, 1, 0);
+        watch
+            .handle_response(
+                response(&keys, &[("lustre", std::slice::from_ref(&v1))]),
+                keys.public_key(),
+                now,
+                |diff| diffs.push(diff.clone()),
+            )
+            .unwrap();
+        assert!(diffs.is_empty());
+        assert_eq!(watch.next_poll(), now + interval);
+        let request = watch.request(None, &Config::new());
+        assert_eq!(request.headers()["if-none-match"], "\"abc\"");
+
+        let not_modified = http::Response::builder()
+            .status(304)
+            .body(Vec::new())
+            .unwrap();
+        let later = now + interval;
+        watch
+            .handle_response(not_modified, keys.public_key(), later, |diff| {
+                diffs.push(diff.clone())
+            })
+            .unwrap();
+        assert!(diffs.is_empty());
+        assert_eq!(watch.next_poll(), later + interval);
+
+        watch
+            .handle_response(
+                response(&keys, &[("lustre", &[v1, v2.clone()]), ("wisp", &[])]),
+                keys.public_key(),
+                later,
+                |diff| diffs.push(diff.clone()),
+            )
+            .unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].added_packages, vec!["wisp".to_string()]);
+        assert_eq!(diffs[0].added_versions["lustre"], vec![v2]);
+        assert_eq!(watch.index().unwrap().fetched_at(), later);
+    }
+
+    #[test]
+    fn backs_off_when_unavailable() {
+        let keys = TestKeys::new();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let mut watch = Watch::new(Duration::from_secs(60));
+        let unavailable = http::Response::builder()
+            .status(503)
+            .header("retry-after", "600")
+            .body(Vec::new())
+            .unwrap();
+        let error = watch
+            .handle_response(unavailable, keys.public_key(), now, |_| {
+                panic!("no changes expected")
+            })
+            .unwrap_err();
+        assert!(matches!(error, ApiError::ServiceUnavailable { .. }));
+        assert_eq!(watch.next_poll(), now + Duration::from_secs(600));
+        assert!(watch.index().is_none());
+    }
+}
@@ -0,0 +1,60 @@
+# Generates a corpus for the differential tests in tests/elixir_version.rs by
+# running every version and requirement below through Elixir's Version
+# module, and every pair of them through Version.compare/2 and
+# Version.match?/2.
+#
+#     elixir test/elixir_version_corpus.exs > corpus.tsv
+#     HEXPM_ELIXIR_CORPUS=corpus.tsv cargo test --features elixir-compat --test elixir_version
+
+versions = ~w(
+  0.0.0 0.1.0 1.0.0 1.0.1 1.0.10 1.1.0 1.9.9 2.0.0 2.1.0 10.0.0
+  1.0.0-0 1.0.0-dev 1.0.0-alpha 1.0.0-alpha.1 1.0.0-alpha.beta 1.0.0-beta
+  1.0.0-beta.2 1.0.0-beta.11 1.0.0-rc.1 1.1.0-dev 2.0.0-dev 2.0.0-rc.0
+  1.0.0+build 1.0.0-rc.1+build.7
+  1 1.0 1.0.0. 1.0.0- 1.0.0+ 01.0.0 1.01.0 1.0.0-01 1.0.0.0 v1.0.0 foo
+)
+
+requirements = [
+  "1.0.0", "== 1.0.0", "!= 1.0.0", "> 1.0.0", ">= 1.0.0", "< 2.0.0", "<= 2.0.0",
+  "~> 1.0", "~> 1.0.0", "~> 1.1", "~> 2.0", "~> 1.0.0-rc.1", "~> 2.0.0-dev",
+  ">= 1.0.0 and < 2.0.0", "~> 1.0 or ~> 2.0", "> 1.0.0 and < 2.0.0 or == 10.0.0",
+  "~> 1", "1.0", ">= 1.0.0 and", "or ~> 1.0", ">> 1.0.0", ""
+]
+
+line = fn fields -> IO.puts(Enum.join(fields, "\t")) end
+
+valid_versions =
+  for input <- versions, reduce: [] do
+    valid ->
+      case Version.parse(input) do
+        {:ok, version} ->
+          line.(["parse", input, to_string(version)])
+          valid ++ [input]
+
+        :error ->
+          line.(["parse", input, "error"])
+          valid
+      end
+  end
+
+for a <- valid_versions, b <- valid_versions do
+  line.(["compare", a, b, Version.compare(a, b)])
+end
+
+valid_requirements =
+  for input <- requirements, reduce: [] do
+    valid ->
+      case Version.parse_requirement(input) do
+        {:ok, _} ->
+          line.(["requirement", input, "ok"])
+          valid ++ [input]
+
+        :error ->
+          line.(["requirement", input, "error"])
+          valid
+      end
+  end
+
+for requirement <- valid_requirements, version <- valid_versions do
+  line.(["match", requirement, version, Version.match?(version, requirement)])
+end
@@ -0,0 +1,130 @@
+//! Differential tests comparing version parsing, ordering and requirement
+//! matching with Elixir's `Version` module, which Hex uses and which this
+//! library promises to be compatible with.
+//!
+//! These are only built with the `elixir-compat` feature. The cases are read
+//! from a corpus of results produced by Elixir itself, see
+//! `test/elixir_version_corpus.exs` for how to generate one. A small corpus is
+//! checked in at `test/elixir_version_corpus.tsv`, and `HEXPM_ELIXIR_CORPUS`
+//! sets a larger one to use instead.
+//!
+//! Each line of the corpus is a tab separated case, and lines starting with
+//! `#` are ignored:
+//!
+//! - `parse VERSION RESULT` where the result is the version as Elixir prints
+//!   it, or `error`.
+//! - `compare A B RESULT` where the result is `lt`, `eq` or `gt`.
+//! - `requirement REQUIREMENT RESULT` where the result is `ok` or `error`.
+//! - `match REQUIREMENT VERSION RESULT` where the result is `true` or `false`.
+//!
+//! Every divergence is reported before the test fails, other than those in
+//! `KNOWN_DIVERGENCES`.
+
+#![cfg(feature = "elixir-compat")]
+
+use std::cmp::Ordering;
+
+use hexpm::version::{Range, Version};
+
+/// Cases where this library deliberately or knowingly differs from Elixir,
+/// as the case's line of the corpus with the reason it differs.
+const KNOWN_DIVERGENCES: &[(&str, &str)] = &[
+    (
+        "match\t~> 1.0\t2.0.0-dev\tfalse",
+        "the upper bound of `~>` includes pre-releases of the next version",
+    ),
+    (
+        "match\t~> 1.2.0\t1.3.0-rc.0\tfalse",
+        "the upper bound of `~>` includes pre-releases of the next version",
+    ),
+];
+
+#[derive(Debug)]
+struct Divergence {
+    line: usize,
+    case: String,
+    actual: String,
+}
+
+#[test]
+fn elixir_version_corpus() {
+    let path = std::env::var("HEXPM_ELIXIR_CORPUS").unwrap_or_else(|_| {
+        format!(
+            "{}/test/elixir_version_corpus.tsv",
+            env!("CARGO_MANIFEST_DIR")
+        )
+    });
+    let corpus = std::fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("could not read corpus {path}: {error}"));
+
+    let mut cases = 0;
+    let mut divergences = vec![];
+    for (index, case) in corpus.lines().enumerate() {
+        if case.trim().is_empty() || case.starts_with('#') {
+            continue;
+        }
+        cases += 1;
+        let fields: Vec<&str> = case.split('\t').collect();
+        let (expected, actual) = match fields.as_slice() {
+            ["parse", input, expected] => (*expected, parse(input)),
+            ["compare", a, b, expected] => (*expected, compare(a, b)),
+            ["requirement", input, expected] => (*expected, requirement(input)),
+            ["match", requirement, version, expected] => (*expected, matches(requirement, version)),
+            _ => panic!("{path}:{}: malformed case {case:?}", index + 1),
+        };
+        let known = KNOWN_DIVERGENCES.iter().any(|(known, _)| *known == case);
+        if actual != expected && !known {
+            divergences.push(Divergence {
+                line: index + 1,
+                case: case.replace('\t', " | "),
+                actual,
+            });
+        }
+    }
+
+    for divergence in &divergences {
+        eprintln!(
+            "{path}:{}: {} but got {}",
+            divergence.line, divergence.case, divergence.actual
+        );
+    }
+    assert!(
+        divergences.is_empty(),
+        "{} of {cases} cases diverge from Elixir",
+        divergences.len()
+    );
+}
+
+fn parse(input: &str) -> String {
+    match Version::parse(input) {
+        Ok(version) => version.to_string(),
+        Err(_) => "error".to_string(),
+    }
+}
+
+fn compare(a: &str, b: &str) -> String {
+    let ordering = match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => return "error".to_string(),
+    };
+    match ordering {
+        Ordering::Less => "lt",
+        Ordering::Equal => "eq",
+        Ordering::Greater => "gt",
+    }
+    .to_string()
+}
+
+fn requirement(input: &str) -> String {
+    match Range::new(input.to_string()) {
+        Ok(_) => "ok".to_string(),
+        Err(_) => "error".to_string(),
+    }
+}
+
+fn matches(requirement: &str, version: &str) -> String {
+    match (Range::new(requirement.to_string()), Version::parse(version)) {
+        (Ok(range), Ok(version)) => range.to_pubgrub().contains(&version).to_string(),
+        _ => "error".to_string(),
+    }
+}
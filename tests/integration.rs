@@ -0,0 +1,159 @@
+//! End to end tests against a locally running hexpm server.
+//!
+//! These are only built with the `integration-tests` feature. See
+//! CONTRIBUTING.md for how to start a server to run them against. The server
+//! and account used are read from these environment variables:
+//!
+//! - `HEXPM_API` defaults to `http://localhost:4000/api/`.
+//! - `HEXPM_REPOSITORY` defaults to `http://localhost:4000/repo/`.
+//! - `HEXPM_USERNAME` and `HEXPM_PASSWORD` are the account to publish as.
+//! - `HEXPM_OTHER_USERNAME` is a second account to add as an owner. The owner
+//!   tests are skipped if it is not set.
+//!
+//! The tests publish and then revert `gleam_stdlib` 0.14.0, so the account
+//! must be able to publish that package.
+
+#![cfg(feature = "integration-tests")]
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hexpm::version::Version;
+use hexpm::{ApiError, Config, OwnerLevel, PublishMode, ReleaseRef, RetirementReason};
+
+struct Hexpm {
+    config: Config,
+    username: String,
+    password: String,
+}
+
+impl Hexpm {
+    fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok();
+        let mut config = Config::new();
+        config.api_base = var("HEXPM_API")
+            .unwrap_or_else(|| "http://localhost:4000/api/".to_string())
+            .parse()
+            .expect("HEXPM_API");
+        config.repository_base = var("HEXPM_REPOSITORY")
+            .unwrap_or_else(|| "http://localhost:4000/repo/".to_string())
+            .parse()
+            .expect("HEXPM_REPOSITORY");
+        Self {
+            config,
+            username: var("HEXPM_USERNAME").expect("HEXPM_USERNAME must be set"),
+            password: var("HEXPM_PASSWORD").expect("HEXPM_PASSWORD must be set"),
+        }
+    }
+
+    async fn send(&self, request: http::Request<Vec<u8>>) -> http::Response<Vec<u8>> {
+        let mut response = reqwest::Client::new()
+            .execute(request.try_into().unwrap())
+            .await
+            .expect("hexpm server not reachable");
+        let mut builder = http::Response::builder().status(response.status());
+        std::mem::swap(builder.headers_mut().unwrap(), response.headers_mut());
+        builder
+            .body(response.bytes().await.unwrap().to_vec())
+            .unwrap()
+    }
+
+    /// Create an API key with a name unique to this test run.
+    async fn create_api_key(&self, purpose: &str) -> (String, String) {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let name = format!("hexpm-rust-{purpose}-{nanos}");
+        let request =
+            hexpm::create_api_key_request(&self.username, &self.password, &name, &self.config);
+        let key = hexpm::create_api_key_response(self.send(request).await).unwrap();
+        (name, key)
+    }
+
+    async fn remove_api_key(&self, name: &str, key: &str) {
+        let request = hexpm::remove_api_key_request(name, key, &self.config);
+        hexpm::remove_api_key_response(self.send(request).await).unwrap();
+    }
+}
+
+fn release() -> ReleaseRef {
+    ReleaseRef::new("gleam_stdlib", Version::new(0, 14, 0))
+}
+
+#[tokio::test]
+async fn api_keys() {
+    let hexpm = Hexpm::from_env();
+    let (name, key) = hexpm.create_api_key("keys").await;
+
+    let request =
+        hexpm::create_api_key_request(&hexpm.username, &hexpm.password, &name, &hexpm.config);
+    let error = hexpm::create_api_key_response(hexpm.send(request).await).unwrap_err();
+    assert!(matches!(error, ApiError::KeyNameTaken), "{error:?}");
+
+    hexpm.remove_api_key(&name, &key).await;
+
+    let request = hexpm::create_api_key_request(&hexpm.username, "wrong", &name, &hexpm.config);
+    let error = hexpm::create_api_key_response(hexpm.send(request).await).unwrap_err();
+    assert!(matches!(error, ApiError::InvalidCredentials), "{error:?}");
+}
+
+#[tokio::test]
+async fn release_lifecycle() {
+    let hexpm = Hexpm::from_env();
+    let config = &hexpm.config;
+    let (key_name, key) = hexpm.create_api_key("release").await;
+    let release = release();
+
+    // Replace in case an earlier run failed before reverting the release.
+    let tarball = std::include_bytes!("../test/gleam_stdlib-0.14.0.tar").to_vec();
+    let request = hexpm::publish_package_request(tarball, &key, config, PublishMode::Replace);
+    hexpm::publish_package_response(hexpm.send(request).await).unwrap();
+
+    let request = hexpm::get_package_release_request(&release, Some(&key), config);
+    let published = hexpm::get_package_release_response(hexpm.send(request).await).unwrap();
+    assert_eq!(published.version, release.version);
+    assert_eq!(published.meta.app, "gleam_stdlib");
+
+    let request = hexpm::retire_release_request(
+        &release,
+        RetirementReason::Security,
+        Some("integration test"),
+        &key,
+        config,
+    );
+    hexpm::retire_release_response(hexpm.send(request).await).unwrap();
+    let request = hexpm::get_package_release_request(&release, Some(&key), config);
+    let retired = hexpm::get_package_release_response(hexpm.send(request).await).unwrap();
+    assert!(retired.is_retired());
+
+    let request = hexpm::unretire_release_request(&release, &key, config);
+    hexpm::unretire_release_response(hexpm.send(request).await).unwrap();
+
+    let docs = std::include_bytes!("../test/example.tar.gz").to_vec();
+    let request = hexpm::publish_docs_request(&release, docs, &key, config).unwrap();
+    hexpm::publish_docs_response(hexpm.send(request).await).unwrap();
+
+    if let Ok(other) = std::env::var("HEXPM_OTHER_USERNAME") {
+        let request =
+            hexpm::add_owner_request("gleam_stdlib", &other, OwnerLevel::Maintainer, &key, config);
+        hexpm::add_owner_response(hexpm.send(request).await).unwrap();
+        let request = hexpm::remove_owner_request("gleam_stdlib", &other, &key, config);
+        hexpm::remove_owner_response(hexpm.send(request).await).unwrap();
+    }
+
+    // Removes the docs and then reverts the release.
+    let mut rollback = hexpm::rollback_release(release.clone()).unwrap();
+    let outcome = loop {
+        let request = rollback.next_request(&key, config).unwrap();
+        if let Some(outcome) = rollback.handle_response(hexpm.send(request).await) {
+            break outcome;
+        }
+    };
+    assert!(outcome.is_complete(), "{outcome:?}");
+
+    let request = hexpm::get_package_release_request(&release, Some(&key), config);
+    let error = hexpm::get_package_release_response(hexpm.send(request).await).unwrap_err();
+    assert!(error.is_not_found(), "{error:?}");
+
+    hexpm.remove_api_key(&key_name, &key).await;
+}